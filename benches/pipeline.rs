@@ -0,0 +1,144 @@
+//! Benchmarks for the hot paths in the pipeline. Run with `cargo bench`.
+//!
+//! The dictionary benchmarks need CMU dict (see `download_data.sh`) and are skipped if it's not
+//! in `data/`. The model benchmarks need the tacotron2 ONNX files in `models/tacotron2` so they're
+//! only built with the `model-benches` feature: `cargo bench --features model-benches`.
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::path::Path;
+use xd_tts::phonemes::{find_splits, Unit};
+use xd_tts::tacotron2::INPUT_WINDOW;
+use xd_tts::text_normaliser::*;
+use xd_tts::CmuDictionary;
+
+const CMU_DICT: &str = "data/cmudict-0.7b.txt";
+
+/// A mix of plain words, numbers, currency, dates and punctuation
+const PARAGRAPH: &str = "On June 3rd, 2024 the company reported revenue of £1,250,000.50, up 12% \
+    on the year before. Dr. Smith said: \"We're pleased with the results; our 3 new products sold \
+    over 45,000 units!\" Shares rose 25bps by 5 o'clock. Is this sustainable? Analysts aren't sure \
+    - some expect growth to slow (perhaps to 7.5%) while others point to the café chain's 2nd \
+    quarter numbers.";
+
+fn character_units(text: &str) -> Vec<Unit> {
+    let mut text = normalise_text(text);
+    text.convert_to_units();
+    units(text)
+}
+
+fn units(mut text: NormalisedText) -> Vec<Unit> {
+    let mut units = vec![];
+    for chunk in text.drain_all() {
+        match chunk {
+            NormaliserChunk::Pronunciation(mut x) => units.append(&mut x),
+            NormaliserChunk::Punct(p) => units.push(Unit::Punct(p)),
+            _ => {}
+        }
+    }
+    units
+}
+
+fn normalisation(c: &mut Criterion) {
+    c.bench_function("normalise_text", |b| {
+        b.iter(|| normalise_text(black_box(PARAGRAPH)))
+    });
+    let ssml = format!("<speak><p>{}</p><break time=\"1s\"/></speak>", PARAGRAPH);
+    c.bench_function("normalise_ssml", |b| {
+        b.iter(|| normalise_ssml(black_box(&ssml)).unwrap())
+    });
+}
+
+fn splitting(c: &mut Criterion) {
+    let mut group = c.benchmark_group("find_splits");
+    for repeats in [1, 10, 100] {
+        let units = character_units(&vec![PARAGRAPH; repeats].join(" "));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(units.len()),
+            &units,
+            |b, units| b.iter(|| find_splits(black_box(units), INPUT_WINDOW)),
+        );
+    }
+    group.finish();
+}
+
+fn dictionary(c: &mut Criterion) {
+    if !Path::new(CMU_DICT).exists() {
+        eprintln!("Skipping dictionary benchmarks, {} not found", CMU_DICT);
+        return;
+    }
+    let dict = CmuDictionary::open(CMU_DICT).unwrap();
+    let words = normalise_text(PARAGRAPH)
+        .to_string_unchecked()
+        .split_ascii_whitespace()
+        .map(String::from)
+        .collect::<Vec<_>>();
+    c.bench_function("dictionary_lookup", |b| {
+        b.iter(|| {
+            for word in &words {
+                black_box(dict.get_pronunciations_normalised(word));
+            }
+        })
+    });
+    c.bench_function("words_to_pronunciation", |b| {
+        b.iter(|| {
+            let mut text = normalise_text(black_box(PARAGRAPH));
+            text.words_to_pronunciation(&dict);
+            text
+        })
+    });
+    // Lots of repeated words, this is where the lookup cache helps
+    let repetitive = normalise_text(&vec!["the cat and the dog and the bird"; 200].join(", "));
+    c.bench_function("words_to_pronunciation_repetitive", |b| {
+        b.iter(|| {
+            let mut text = repetitive.clone();
+            text.words_to_pronunciation(black_box(&dict));
+            text
+        })
+    });
+}
+
+#[cfg(feature = "model-benches")]
+fn inference(c: &mut Criterion) {
+    use xd_tts::tacotron2::{create_griffin_lim, Tacotron2};
+    use xd_tts::XdTts;
+
+    let model = Tacotron2::load("models/tacotron2").unwrap();
+    let vocoder = create_griffin_lim(model.config()).unwrap();
+    let units = character_units("Hello there, this is a short sentence to synthesise.");
+    let mel = model.infer(&units).unwrap();
+
+    let mut group = c.benchmark_group("inference");
+    group.sample_size(10);
+    group.bench_function("tacotron2", |b| {
+        b.iter(|| model.infer(black_box(&units)).unwrap())
+    });
+    group.bench_function("griffin_lim", |b| {
+        b.iter(|| vocoder.infer(black_box(&mel)).unwrap())
+    });
+    group.finish();
+
+    // The paragraph with a break after each sentence so it's synthesised in several chunks
+    let ssml = format!(
+        "<speak>{}</speak>",
+        PARAGRAPH
+            .split_inclusive(['.', '!', '?'])
+            .collect::<Vec<_>>()
+            .join("<break time=\"200ms\"/>")
+    );
+    let mut group = c.benchmark_group("vocoder_threads");
+    group.sample_size(10);
+    for threads in [1, 2, 4] {
+        let tts = XdTts::new(Path::new("models/tacotron2"), false, None)
+            .unwrap()
+            .with_vocoder_threads(threads);
+        group.bench_with_input(BenchmarkId::from_parameter(threads), &ssml, |b, ssml| {
+            b.iter(|| tts.synthesise(black_box(ssml), None).unwrap())
+        });
+    }
+    group.finish();
+}
+
+#[cfg(not(feature = "model-benches"))]
+criterion_group!(benches, normalisation, splitting, dictionary);
+#[cfg(feature = "model-benches")]
+criterion_group!(benches, normalisation, splitting, dictionary, inference);
+criterion_main!(benches);
@@ -1,37 +1,207 @@
-use clap::Parser;
-use hound::WavWriter;
-use std::path::PathBuf;
+use clap::{Parser, ValueEnum};
+use hound::SampleFormat;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Instant;
 use tracing::info;
+#[cfg(feature = "opus")]
+use xd_tts::encoding::OpusSettings;
+use xd_tts::encoding::{self, OutputFormat};
 use xd_tts::*;
 
 #[derive(Parser, Debug)]
 pub struct Args {
     /// Text to synthesise speech for
-    #[clap(long, short)]
-    input: String,
-    /// Saves the generated spectrograms for debugging purposes
+    #[clap(
+        long,
+        short,
+        required_unless_present_any = ["self_test", "describe_model", "pronounce"]
+    )]
+    input: Option<String>,
+    /// Saves the spectrogram of the whole utterance for debugging purposes. By default this is a
+    /// `.npy` file, if the extension is `.png` an image is written instead (requires the
+    /// `png-export` feature)
     #[clap(long)]
     output_spectrogram: Option<PathBuf>,
-    /// Location to save the output audio file
+    /// Location to save the output audio file. The format is picked from the extension: `.opus`,
+    /// `.mp3`, `.ogg` (Vorbis) and `.flac` each need the cargo feature of the same name (`vorbis`
+    /// for `.ogg`), anything else is written as WAV
     #[clap(short, long, default_value = "output.wav")]
     output: PathBuf,
+    /// Sample format for WAV output
+    #[clap(long, value_enum, default_value_t = WavFormat::Int16)]
+    wav_format: WavFormat,
+    /// Bitrate in bits per second for Opus output
+    #[cfg(feature = "opus")]
+    #[clap(long, default_value_t = 32000)]
+    bitrate: i32,
     /// If set phonemes and input into tacotron2, by default character inputs are used
     #[clap(long)]
     phoneme_input: bool,
-    /// Directory where the tacotron2 ONNX models can be found
+    /// Directory where the tacotron2 ONNX models can be found, or a zip/tar archive of them
     #[clap(long, default_value = "./models/tacotron2")]
     tacotron2: PathBuf,
+    /// Prints a trace of the input going through each stage of the pipeline instead of saving the
+    /// audio, useful for working out why something sounds wrong
+    #[clap(long)]
+    explain: bool,
+    /// Prints the inputs and outputs of the tacotron2 ONNX graphs and exits, use this to check a
+    /// custom model export
+    #[clap(long)]
+    describe_model: bool,
+    /// Loads the models, synthesises a short sentence and checks the audio isn't empty, silent or
+    /// NaN then prints the timings and real-time factor. Use this to check a deployment works.
+    /// Exits with 0 if the test passes, 3 if the models couldn't be loaded, 4 if synthesis failed
+    /// and 5 if the audio is bad (2 is already used for invalid arguments)
+    #[clap(long)]
+    self_test: bool,
+    /// Prints every dictionary pronunciation of a word in ARPABET and IPA without loading the
+    /// models. Exits with 3 if the word isn't in the dictionary
+    #[clap(long, value_name = "WORD")]
+    pronounce: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum WavFormat {
+    Int16,
+    Int24,
+    Float32,
 }
 
-fn main() -> anyhow::Result<()> {
+/// Text synthesised by `--self-test`
+const SELF_TEST_TEXT: &str = "The quick brown fox jumps over the lazy dog.";
+
+fn main() -> anyhow::Result<ExitCode> {
     xd_tts::setup_logging();
     let args = Args::parse();
 
+    if args.self_test {
+        return Ok(self_test(&args.tacotron2, args.phoneme_input));
+    }
+    if let Some(word) = &args.pronounce {
+        return pronounce(word);
+    }
+
     info!("Loading resources");
 
-    let tts_context = XdTts::new(&args.tacotron2, args.phoneme_input)?;
-    let mut wav_writer = WavWriter::create(&args.output, xd_tts::WAV_SPEC)?;
+    if args.describe_model {
+        print!(
+            "{}",
+            tacotron2::Tacotron2::load(&args.tacotron2)?.describe()
+        );
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let (sample_format, bits) = match args.wav_format {
+        WavFormat::Int16 => (SampleFormat::Int, 16),
+        WavFormat::Int24 => (SampleFormat::Int, 24),
+        WavFormat::Float32 => (SampleFormat::Float, 32),
+    };
+    let tts_context =
+        XdTts::new(&args.tacotron2, args.phoneme_input, None)?.with_wav_format(sample_format, bits);
+    let input = args.input.as_deref().unwrap_or_default();
+
+    if args.explain {
+        print!("{}", tts_context.explain(input)?);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let format = match OutputFormat::from_path(&args.output) {
+        #[cfg(feature = "opus")]
+        OutputFormat::Opus(settings) => OutputFormat::Opus(OpusSettings {
+            bitrate: args.bitrate,
+            ..settings
+        }),
+        OutputFormat::Wav(_) => OutputFormat::Wav(tts_context.wav_spec()),
+        format => format,
+    };
+    // Fail before synthesising if the format isn't available
+    if !format.is_available() {
+        anyhow::bail!(
+            "Can't write '{}', rebuild with the `{}` feature",
+            args.output.display(),
+            format.feature().unwrap_or_default()
+        );
+    }
+    let samples = tts_context.synthesise(input, args.output_spectrogram.clone())?;
+    encoding::encode(&samples, format, &args.output)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Runs the `--self-test` smoke test printing what it did, see `Args::self_test` for the exit
+/// codes.
+fn self_test(tacotron2: &Path, phoneme_input: bool) -> ExitCode {
+    let start = Instant::now();
+    let tts_context = match XdTts::new(tacotron2, phoneme_input, None) {
+        Ok(tts) => tts,
+        Err(e) => {
+            println!(
+                "FAIL: couldn't load models from '{}': {:#}",
+                tacotron2.display(),
+                e
+            );
+            return ExitCode::from(3);
+        }
+    };
+    let load_time = start.elapsed();
+    println!("Loaded models in {:?}", load_time);
+
+    let start = Instant::now();
+    let samples = match tts_context.synthesise(SELF_TEST_TEXT, None) {
+        Ok(samples) => samples,
+        Err(e) => {
+            println!("FAIL: synthesis failed: {:#}", e);
+            return ExitCode::from(4);
+        }
+    };
+    let synthesis_time = start.elapsed();
+    let audio_secs = samples.len() as f32 / xd_tts::WAV_SPEC.sample_rate as f32;
+    println!(
+        "Synthesised {:.2}s of audio in {:?}, real-time factor {:.3}",
+        audio_secs,
+        synthesis_time,
+        synthesis_time.as_secs_f32() / audio_secs.max(f32::EPSILON)
+    );
+
+    let peak = samples.iter().fold(0.0f32, |acc, x| acc.max(x.abs()));
+    let problem = if samples.is_empty() {
+        Some("no audio was generated".to_string())
+    } else if samples.iter().any(|x| !x.is_finite()) {
+        Some("the audio contains NaN or infinite samples".to_string())
+    } else if peak < 1e-3 {
+        Some(format!("the audio is silent, peak amplitude {}", peak))
+    } else {
+        None
+    };
+    match problem {
+        Some(problem) => {
+            println!("FAIL: {}", problem);
+            ExitCode::from(5)
+        }
+        None => {
+            println!("PASS");
+            ExitCode::SUCCESS
+        }
+    }
+}
 
-    tts_context.generate_audio(&args.input, &mut wav_writer, args.output_spectrogram)?;
-    Ok(())
+/// Prints the pronunciations of the word for `--pronounce`, the first is the one used by default.
+fn pronounce(word: &str) -> anyhow::Result<ExitCode> {
+    let dict = load_dictionary()?;
+    let variants = dict.variants(word);
+    if variants.is_empty() {
+        println!("'{}' isn't in the dictionary", word);
+        return Ok(ExitCode::from(3));
+    }
+    for variant in variants {
+        let arpa = variant
+            .pronunciation
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let ipa = phonemes::pronunciation_to_ipa(variant.pronunciation)?;
+        println!("{}: {}  /{}/", variant.index, arpa, ipa);
+    }
+    Ok(ExitCode::SUCCESS)
 }
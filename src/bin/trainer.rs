@@ -1,4 +1,4 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use std::fs::File;
 use std::path::{Path, PathBuf};
 use tracing::{error, info};
@@ -18,9 +18,14 @@ pub enum Commands {
         /// LJ Speech metadata.csv
         #[clap(short, long, default_value = "./data/LJSpeech-1.1/metadata.csv")]
         input: PathBuf,
-        /// Location to save the analysis json
-        #[clap(short, long, default_value = "analysis.json")]
-        output: PathBuf,
+        /// Location to save the analysis, by default this is analysis.json or analysis.csv
+        /// depending on the format
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+        /// Format to save the analysis in. The CSV has a row for each diphone, phoneme, OOV word
+        /// and sentence length with a column for which one it is
+        #[clap(short, long, value_enum, default_value_t = ReportFormat::Json)]
+        format: ReportFormat,
     },
     /// This prepares the data for training, for this I want to normalise the transcripts for LJ
     /// Speech, convert to the phonetic transcription (as per the tacotron2 text processing
@@ -37,6 +42,12 @@ pub enum Commands {
     },
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    Json,
+    Csv,
+}
+
 impl Commands {
     fn input(&self) -> &Path {
         match self {
@@ -71,7 +82,7 @@ fn main() -> anyhow::Result<()> {
     let mut dataset = lj_speech::Dataset::load(args.command.input())?;
 
     match args.command {
-        Commands::Analyse { output, .. } => {
+        Commands::Analyse { output, format, .. } => {
             let mut analytics = AnalyticsGenerator::new(dictionary);
 
             for entry in dataset.entries.iter().map(|x| x.text.as_ref()) {
@@ -83,8 +94,17 @@ fn main() -> anyhow::Result<()> {
             info!("Number of diphones: {}", report.diphones.len());
             info!("Number of phonemes: {}", report.phonemes.len());
 
-            let report = serde_json::to_string_pretty(&report)?;
-            std::fs::write(output, report)?;
+            match format {
+                ReportFormat::Json => {
+                    let output = output.unwrap_or_else(|| PathBuf::from("analysis.json"));
+                    let report = serde_json::to_string_pretty(&report)?;
+                    std::fs::write(output, report)?;
+                }
+                ReportFormat::Csv => {
+                    let output = output.unwrap_or_else(|| PathBuf::from("analysis.csv"));
+                    report.write_csv(File::create(output)?)?;
+                }
+            }
 
             Ok(())
         }
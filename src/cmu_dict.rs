@@ -1,24 +1,291 @@
 //! Dictionary lookup is a common way to bootstrap TTS and ensure reliability. This module is a
-//! fairly simple map from word to list of pronunciations. However at this stage we don't handle
+//! fairly simple map from word to list of pronunciations. However at this stage we only handle
 //! picking the correct pronunciation from words with the same spelling but different
-//! pronunciations. This would require a more involved text processing module - either much more
-//! complicated rules or use of a statistical model. Training such models also needs a grapheme and
+//! pronunciations for a few common words (see `crate::homographs`). Doing this properly would
+//! require a more involved text processing module - either much more complicated rules or use of a
+//! statistical model. Training such models also needs a grapheme and
 //! phoneme level transcription so you can learn the mapping from the word in context to the
 //! correct pronunciation.
 use crate::phonemes::*;
 use crate::text_normaliser::*;
-use std::collections::{btree_map, BTreeMap};
+use std::cmp::Reverse;
+use std::collections::{btree_map, BTreeMap, BTreeSet};
 use std::fs;
 use std::io::{self, prelude::*};
+use std::mem;
 use std::path::Path;
 use std::str::FromStr;
-use tracing::error;
+use tracing::{error, warn};
 
-/// Type that wraps the dictionary, the underlying dictionary store is backed by a `BTreeMap`
+/// How reduced vowels (schwas) in the dictionary should be represented. CMU dict writes the schwa
+/// as an unstressed `AH0`, but it will also use `IH0` and `UH0` for other reduced vowels. Depending
+/// on how the model was trained these may need to be made consistent, otherwise the model will be
+/// given phones it rarely or never saw in training.
+///
+/// The mappings are:
+///
+/// * `Keep` - pronunciations are left as they are in the dictionary.
+/// * `Collapse` - `IH0` and `UH0` become `AH0`, so every reduced vowel is the same schwa.
+/// * `Expand` - `AH0` becomes `AH` without a stress marker, so the full vowel is used instead of
+///   the reduced one.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum SchwaReduction {
+    /// Leave the reduced vowels as they are
+    #[default]
+    Keep,
+    /// Map all reduced vowels to `AH0`
+    Collapse,
+    /// Map `AH0` to an unstressed `AH`
+    Expand,
+}
+
+impl SchwaReduction {
+    /// Applies the mapping to a single phone
+    pub fn apply(&self, unit: PhoneticUnit) -> PhoneticUnit {
+        let reduced = Some(AuxiliarySymbol::NoStress);
+        match self {
+            Self::Keep => unit,
+            Self::Collapse
+                if unit.context == reduced
+                    && matches!(unit.phone, ArpaPhone::Ih | ArpaPhone::Uh) =>
+            {
+                PhoneticUnit {
+                    phone: ArpaPhone::Ah,
+                    context: reduced,
+                }
+            }
+            Self::Expand if unit.context == reduced && unit.phone == ArpaPhone::Ah => {
+                PhoneticUnit {
+                    phone: ArpaPhone::Ah,
+                    context: None,
+                }
+            }
+            _ => unit,
+        }
+    }
+}
+
+/// One of the pronunciations of a word, see `CmuDictionary::variants`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PronunciationVariant<'a> {
+    /// Position of the pronunciation in the dictionary entry, this is what `set_primary` takes.
+    /// Index 0 is the one used by default.
+    pub index: usize,
+    /// The pronunciation
+    pub pronunciation: &'a Pronunciation,
+}
+
+/// Splits a dictionary line with an IPA pronunciation, `word /ipa/`, into the word and the IPA.
+/// Returns `None` if the line isn't in that format.
+fn split_ipa_entry(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim_end();
+    let ipa_start = line.find(" /")?;
+    let (word, ipa) = (line[..ipa_start].trim(), line[ipa_start..].trim_start());
+    let ipa = ipa.strip_prefix('/')?.strip_suffix('/')?;
+    if word.is_empty() || ipa.is_empty() {
+        None
+    } else {
+        Some((word, ipa))
+    }
+}
+
+/// Converts an IPA pronunciation into ARPABET phones the same way they're written in CMU dict. In
+/// IPA the stress marker comes before the stressed syllable but in ARPABET it's on the vowel, so
+/// the stress is moved onto the next vowel and vowels without stress are given `0`. Length marks
+/// and syllable breaks are ignored. Fails if there's anything that isn't an English phone.
+pub fn ipa_pronunciation(ipa: &str) -> anyhow::Result<Pronunciation> {
+    let ipa = ipa.replace(['ː', '.'], "");
+    let mut res = vec![];
+    let mut stress = None;
+    for unit in ipa_string_to_units(ipa.trim()) {
+        let Unit::Phone(mut phone) = unit else {
+            anyhow::bail!("'{}' isn't a single word of English phones", ipa);
+        };
+        if phone.phone.is_vowel() {
+            phone.context = phone
+                .context
+                .or(stress.take())
+                .or(Some(AuxiliarySymbol::NoStress));
+        } else if let Some(context) = phone.context.take() {
+            stress = Some(context);
+        }
+        res.push(phone);
+    }
+    if res.is_empty() {
+        anyhow::bail!("empty pronunciation");
+    }
+    Ok(res)
+}
+
+/// How each letter is said when a word is spelled out, from CMU dict.
+const LETTER_NAMES: [&str; 26] = [
+    "EY1",
+    "B IY1",
+    "S IY1",
+    "D IY1",
+    "IY1",
+    "EH1 F",
+    "JH IY1",
+    "EY1 CH",
+    "AY1",
+    "JH EY1",
+    "K EY1",
+    "EH1 L",
+    "EH1 M",
+    "EH1 N",
+    "OW1",
+    "P IY1",
+    "K Y UW1",
+    "AA1 R",
+    "EH1 S",
+    "T IY1",
+    "Y UW1",
+    "V IY1",
+    "D AH1 B AH0 L Y UW0",
+    "EH1 K S",
+    "W AY1",
+    "Z IY1",
+];
+
+/// Pronounces a word by saying the name of each letter, i.e. "TLS" is "T IY1 EH1 L EH1 S". The
+/// letters are run together the same as acronyms in CMU dict. Returns `None` if the word has
+/// anything other than ASCII letters in it.
+pub fn spell_word(word: &str) -> Option<Pronunciation> {
+    if word.is_empty() || !word.bytes().all(|x| x.is_ascii_alphabetic()) {
+        return None;
+    }
+    let mut res = vec![];
+    for letter in word.bytes() {
+        let name = LETTER_NAMES[(letter.to_ascii_uppercase() - b'A') as usize];
+        for phone in name.split(' ') {
+            res.push(PhoneticUnit::from_str(phone).ok()?);
+        }
+    }
+    Some(res)
+}
+
+/// Checks if a word is made up of letters without any vowels, these are almost always acronyms like
+/// "TLS" or "HTTP" which should be spelled out (see `spell_word`). Y counts as a vowel so words
+/// like "GYM" and "RHYTHM" aren't included.
+pub fn is_vowelless(word: &str) -> bool {
+    !word.is_empty()
+        && word.bytes().all(|x| x.is_ascii_alphabetic())
+        && !word
+            .bytes()
+            .any(|x| b"AEIOUY".contains(&x.to_ascii_uppercase()))
+}
+
+/// A dictionary for another language that's checked when a word isn't in the main dictionary, see
+/// `NormalisedText::words_to_pronunciation_with_dictionaries`.
+#[derive(Debug, Clone)]
+pub struct FallbackDictionary {
+    /// Language of the dictionary, this is used to tag the words found in it i.e. "fr"
+    pub language: String,
+    /// The dictionary itself
+    pub dictionary: CmuDictionary,
+}
+
+/// What was found on one line of a dictionary file.
+enum ParsedLine {
+    Entry(String, Pronunciation),
+    /// Comments and blank lines
+    Ignored,
+    /// A word without any phones
+    Empty(String),
+    /// A line we couldn't parse and why
+    Malformed(String),
+}
+
+/// Parses a line of a dictionary file, see `CmuDictionary::from_reader` for the format.
+fn parse_line(line: &str) -> ParsedLine {
+    if line.starts_with(";;;") || line.trim().is_empty() {
+        return ParsedLine::Ignored;
+    }
+    if let Some((word, ipa)) = split_ipa_entry(line) {
+        let word = dict_normalise(word);
+        return match ipa_pronunciation(ipa) {
+            Ok(pronounce) => ParsedLine::Entry(word, pronounce),
+            Err(e) => ParsedLine::Malformed(format!(
+                "Unable to parse IPA '{}' for word: {}: {}",
+                ipa, word, e
+            )),
+        };
+    }
+    let Some((word, phonemes)) = line.split_once("  ") else {
+        return ParsedLine::Malformed(format!(
+            "No pronunciation for '{}', the word should be followed by two spaces",
+            line.trim()
+        ));
+    };
+    let word = dict_normalise(word);
+    let mut pronounce = vec![];
+    for (i, phone) in phonemes
+        .split(' ')
+        .filter(|x| !x.is_empty())
+        .map(PhoneticUnit::from_str)
+        .enumerate()
+    {
+        match phone {
+            Ok(s) => pronounce.push(s),
+            Err(e) => {
+                return ParsedLine::Malformed(format!(
+                    "Unable to parse phone {}: {} for word: {}",
+                    i, e, word
+                ))
+            }
+        }
+    }
+    if pronounce.is_empty() {
+        ParsedLine::Empty(word)
+    } else {
+        ParsedLine::Entry(word, pronounce)
+    }
+}
+
+/// Most problems `CmuDictionary::validate_file` keeps the details of, any more are only counted.
+pub const MAX_REPORTED_PROBLEMS: usize = 100;
+
+/// The result of checking a dictionary file without loading it, see
+/// `CmuDictionary::validate_file`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// Number of pronunciations that would be loaded. A word with several pronunciations is
+    /// counted once for each.
+    pub entries: usize,
+    /// Number of lines that would be skipped because they can't be parsed or have no phones
+    pub problem_count: usize,
+    /// The first `MAX_REPORTED_PROBLEMS` problems, as the line number (starting from 1) and what
+    /// was wrong with it
+    pub problems: Vec<(usize, String)>,
+    /// Rough upper bound on the memory the loaded dictionary would take in bytes. Words with more
+    /// than one pronunciation are counted once per pronunciation so this overestimates a little.
+    pub estimated_bytes: usize,
+}
+
+impl ValidationReport {
+    /// Returns true if every line could be loaded.
+    pub fn is_valid(&self) -> bool {
+        self.problem_count == 0
+    }
+
+    fn add_problem(&mut self, line: usize, problem: String) {
+        self.problem_count += 1;
+        if self.problems.len() < MAX_REPORTED_PROBLEMS {
+            self.problems.push((line, problem));
+        }
+    }
+}
+
+/// Type that wraps the dictionary, the underlying dictionary store is backed by a `BTreeMap`.
+///
+/// Entries can be multi-word expressions, i.e. `NEW YORK  N UW1 Y AO1 R K`. These are useful for
+/// proper nouns and idioms where the pronunciation differs from saying each word in turn.
 #[derive(Debug, Default, Clone)]
 pub struct CmuDictionary {
     /// One word may have multiple pronunciations
     dictionary: BTreeMap<String, Vec<Pronunciation>>,
+    /// Number of words in the longest entry, so we know how far ahead to look when matching
+    max_words: usize,
 }
 
 impl CmuDictionary {
@@ -29,11 +296,53 @@ impl CmuDictionary {
         Self::from_reader(reader)
     }
 
+    /// Checks a dictionary file can be loaded without keeping any of it in memory, so large or
+    /// untrusted dictionaries can be vetted before calling `open`. Lines `open` would skip are
+    /// reported with their line number, and unlike `open` a line that isn't valid UTF-8 is
+    /// reported and skipped instead of ending the file early.
+    pub fn validate_file(path: impl AsRef<Path>) -> io::Result<ValidationReport> {
+        let file = fs::File::open(path)?;
+        Self::validate_reader(io::BufReader::new(file))
+    }
+
+    /// Like `validate_file` but reads from any reader.
+    pub fn validate_reader(reader: impl BufRead) -> io::Result<ValidationReport> {
+        let mut report = ValidationReport::default();
+        for (i, line) in reader.lines().enumerate() {
+            let line_number = i + 1;
+            let line = match line {
+                Ok(line) => line,
+                Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                    report.add_problem(line_number, "Line isn't valid UTF-8".to_string());
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            match parse_line(&line) {
+                ParsedLine::Entry(word, pronounce) => {
+                    report.entries += 1;
+                    report.estimated_bytes += word.len()
+                        + mem::size_of::<String>()
+                        + mem::size_of::<Vec<Pronunciation>>()
+                        + mem::size_of::<Pronunciation>()
+                        + pronounce.len() * mem::size_of::<PhoneticUnit>();
+                }
+                ParsedLine::Ignored => {}
+                ParsedLine::Empty(word) => {
+                    report.add_problem(line_number, format!("'{}' has no phones", word))
+                }
+                ParsedLine::Malformed(e) => report.add_problem(line_number, e),
+            }
+        }
+        Ok(report)
+    }
+
     /// Merge two dictionaries, we don't want custom words added by the user to be placed in a file
     /// like CMU dict because then we lose the provenance of the pronunciation. In the real world
     /// customers may request _questionable_ pronunciations for inputs, so a merge function is
     /// useful.
     pub fn merge(&mut self, other: CmuDictionary) {
+        self.max_words = self.max_words.max(other.max_words);
         for (k, mut v) in other.dictionary.into_iter() {
             let pronunciations = self.dictionary.entry(k).or_default();
             for pronunc in v.drain(..) {
@@ -44,6 +353,34 @@ impl CmuDictionary {
         }
     }
 
+    /// Rewrites the reduced vowels in every pronunciation to match the conventions the model was
+    /// trained with. This is off by default, see `SchwaReduction` for the mappings. If two
+    /// pronunciations of a word become identical after the mapping only one is kept.
+    pub fn apply_schwa_reduction(&mut self, reduction: SchwaReduction) {
+        if reduction == SchwaReduction::Keep {
+            return;
+        }
+        for pronunciations in self.dictionary.values_mut() {
+            let mut mapped: Vec<Pronunciation> = vec![];
+            for pronunciation in pronunciations.drain(..) {
+                let pronunciation = pronunciation
+                    .into_iter()
+                    .map(|x| reduction.apply(x))
+                    .collect();
+                if !mapped.contains(&pronunciation) {
+                    mapped.push(pronunciation);
+                }
+            }
+            *pronunciations = mapped;
+        }
+    }
+
+    /// The number of words in the longest entry in the dictionary. This will be 1 unless there are
+    /// multi-word entries.
+    pub fn max_entry_words(&self) -> usize {
+        self.max_words.max(1)
+    }
+
     /// Number of words in the dictionary
     pub fn len(&self) -> usize {
         self.dictionary.len()
@@ -57,47 +394,33 @@ impl CmuDictionary {
     /// A function that abstracts away the file and works from a reader. This is generally useful
     /// as it allows you to test your dictionary and the interface using in-memory representations
     /// of the data. This avoids cluttering up your project with test dictionaries.
-    fn from_reader(reader: impl BufRead) -> io::Result<Self> {
+    ///
+    /// Entries are normally in the CMU dict format, the word then two spaces and the ARPABET
+    /// phones. Custom dictionaries can also write the pronunciation in IPA between slashes, like
+    /// `hello /həˈloʊ/`, which is detected per line so the two can be mixed (see
+    /// `ipa_pronunciation`).
+    pub fn from_reader(reader: impl BufRead) -> io::Result<Self> {
         let mut dictionary: BTreeMap<String, Vec<Pronunciation>> = BTreeMap::new();
 
-        'outer: for line in reader
-            .lines()
-            .map_while(Result::ok)
-            .filter(|x| !x.starts_with(";;;"))
-        {
-            let mut data = line.split("  ");
-            let word = match data.next() {
-                Some(s) => dict_normalise(s),
-                None => continue,
-            };
-            let phonemes = match data.next() {
-                Some(s) => s.split(' '),
-                None => continue,
-            };
-            let mut pronounce = vec![];
-            for (i, phone) in phonemes
-                .filter(|x| !x.is_empty())
-                .map(PhoneticUnit::from_str)
-                .enumerate()
-            {
-                match phone {
-                    Ok(s) => {
-                        pronounce.push(s);
-                    }
-                    Err(e) => {
-                        error!("Unable to parse phone {}: {} for word: {}", i, e, word);
-                        continue 'outer;
-                    }
-                }
-            }
-            match dictionary.get_mut(&word) {
-                Some(s) => s.push(pronounce),
-                None => {
-                    dictionary.insert(word, vec![pronounce]);
+        for line in reader.lines().map_while(Result::ok) {
+            match parse_line(&line) {
+                ParsedLine::Entry(word, pronounce) => {
+                    dictionary.entry(word).or_default().push(pronounce)
                 }
+                ParsedLine::Ignored => {}
+                ParsedLine::Empty(word) => warn!("Skipping '{}', it has no phones", word),
+                ParsedLine::Malformed(e) => error!("{}", e),
             }
         }
-        Ok(Self { dictionary })
+        let max_words = dictionary
+            .keys()
+            .map(|x| x.split_ascii_whitespace().count())
+            .max()
+            .unwrap_or_default();
+        Ok(Self {
+            dictionary,
+            max_words,
+        })
     }
 
     /// All the entries in the dictionary should be normalised to simplify lookup. If we know our
@@ -110,7 +433,62 @@ impl CmuDictionary {
 
     /// Pass a word into the dictionary that hasn't yet been normalised.
     pub fn get_pronunciations(&self, word: &str) -> Option<&Vec<Pronunciation>> {
-        self.get_pronunciations_normalised(&normalise_text(word).to_string_unchecked())
+        self.get_pronunciations_normalised(&dict_normalise(word))
+    }
+
+    /// All the pronunciations of a word (which hasn't been normalised) with their index in the
+    /// dictionary entry, in dictionary order.
+    pub fn variants(&self, word: &str) -> Vec<PronunciationVariant<'_>> {
+        self.ranked_variants(word, |_| ())
+    }
+
+    /// Like `variants` but sorted by the key function, lowest first. The sort is stable so variants
+    /// with the same key stay in dictionary order. The indexes are the ones in the dictionary so
+    /// they can still be passed to `set_primary` after ranking. The key could be anything, like
+    /// where the primary stress is or how often the stress pattern appears in a corpus.
+    pub fn ranked_variants<K: Ord>(
+        &self,
+        word: &str,
+        key: impl Fn(&Pronunciation) -> K,
+    ) -> Vec<PronunciationVariant<'_>> {
+        let mut variants = self
+            .get_pronunciations(word)
+            .map(|x| {
+                x.iter()
+                    .enumerate()
+                    .map(|(index, pronunciation)| PronunciationVariant {
+                        index,
+                        pronunciation,
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        variants.sort_by_cached_key(|x| key(x.pronunciation));
+        variants
+    }
+
+    /// The variant of the word (which hasn't been normalised) with the most stress, see
+    /// `phonemes::stress_weight`. This is useful for emphasis, for "the" this picks "DH AH1" over
+    /// the reduced "DH AH0". Ties go to the first in dictionary order.
+    pub fn most_stressed_variant(&self, word: &str) -> Option<PronunciationVariant<'_>> {
+        self.ranked_variants(word, |x| Reverse(stress_weight(x)))
+            .into_iter()
+            .next()
+    }
+
+    /// Makes the pronunciation at `index` the one used by default for the word (which hasn't been
+    /// normalised), the other variants keep their order after it. This changes the indexes of the
+    /// variants before it. Returns false if the word or index isn't in the dictionary.
+    pub fn set_primary(&mut self, word: &str, index: usize) -> bool {
+        let word = dict_normalise(word);
+        match self.dictionary.get_mut(&word) {
+            Some(pronunciations) if index < pronunciations.len() => {
+                let primary = pronunciations.remove(index);
+                pronunciations.insert(0, primary);
+                true
+            }
+            _ => false,
+        }
     }
 
     /// Pretends that words only have one possible pronunciation, and it's the first one in the
@@ -123,6 +501,16 @@ impl CmuDictionary {
             .collect()
     }
 
+    /// Every distinct phone (including stress) used by the pronunciations in the dictionary
+    pub fn phone_inventory(&self) -> BTreeSet<PhoneticUnit> {
+        self.dictionary
+            .values()
+            .flatten()
+            .flatten()
+            .copied()
+            .collect()
+    }
+
     /// Iterator over the elements of the dictionary
     pub fn iter(&self) -> btree_map::Iter<'_, String, Vec<Pronunciation>> {
         self.dictionary.iter()
@@ -158,4 +546,414 @@ mod tests {
         assert_eq!(base.get_pronunciations("RUST").unwrap().len(), 1);
         assert_eq!(base.get_pronunciations("UST").unwrap().len(), 1);
     }
+
+    #[test]
+    fn multi_word_entries() {
+        let cursor = io::Cursor::new(
+            "NEW  N UW1\nYORK  Y AO1 R K\nNEW YORK  N UW2 Y AO1 R K\nI  AY1\nLOVE  L AH1 V",
+        );
+        let dict = CmuDictionary::from_reader(io::BufReader::new(cursor)).unwrap();
+        assert_eq!(dict.max_entry_words(), 2);
+
+        let pronounce = |text: &str| {
+            let mut text = normalise_text(text);
+            text.words_to_pronunciation(&dict);
+            match &text.chunks()[0] {
+                NormaliserChunk::Pronunciation(units) => units
+                    .iter()
+                    .map(|x| x.to_string())
+                    .collect::<String>()
+                    .trim()
+                    .to_string(),
+                e => panic!("Unexpected chunk: {:?}", e),
+            }
+        };
+
+        assert_eq!(pronounce("I love New York"), "AY1 LAH1V NUW2YAO1RK");
+        assert_eq!(pronounce("New love"), "NUW1 LAH1V");
+        assert_eq!(pronounce("York new"), "YAO1RK NUW1");
+    }
+
+    #[test]
+    fn empty_pronunciations() {
+        let cursor = io::Cursor::new("HELLO  HH AH0 L OW1\nEMPTY  \nEMPTY(1)   \n");
+        let dict = CmuDictionary::from_reader(io::BufReader::new(cursor)).unwrap();
+        assert_eq!(dict.len(), 1);
+        assert_eq!(dict.get_pronunciations("EMPTY"), None);
+
+        // Dictionaries built some other way are treated as if the word is missing
+        let mut dictionary = dict.dictionary.clone();
+        dictionary.insert("EMPTY".to_string(), vec![vec![]]);
+        dictionary.insert("NOTHING".to_string(), vec![]);
+        let dict = CmuDictionary {
+            dictionary,
+            max_words: 1,
+        };
+        let mut misses = vec![];
+        let mut text = normalise_text("Hello empty nothing");
+        text.words_to_pronunciation_with_fallback(&dict, |word| {
+            misses.push(word.to_string());
+            None
+        });
+        assert_eq!(misses, ["EMPTY", "NOTHING"]);
+        match &text.chunks()[0] {
+            NormaliserChunk::Pronunciation(units) => {
+                let units = units.iter().map(|x| x.to_string()).collect::<String>();
+                assert_eq!(units.trim(), "HHAH0LOW1");
+            }
+            e => panic!("Unexpected chunk: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn lexicon_miss_callback() {
+        let cursor = io::Cursor::new("HELLO  HH AH0 L OW1");
+        let dict = CmuDictionary::from_reader(io::BufReader::new(cursor)).unwrap();
+
+        let mut misses = vec![];
+        let mut text = normalise_text("Hello xyzzy plugh");
+        text.words_to_pronunciation_with_fallback(&dict, |word| {
+            misses.push(word.to_string());
+            (word == "XYZZY").then(|| {
+                "Z IH1 Z IY0"
+                    .split(' ')
+                    .map(|x| PhoneticUnit::from_str(x).unwrap())
+                    .collect()
+            })
+        });
+        assert_eq!(misses, ["XYZZY", "PLUGH"]);
+
+        let units = match &text.chunks()[0] {
+            NormaliserChunk::Pronunciation(units) => {
+                units.iter().map(|x| x.to_string()).collect::<String>()
+            }
+            e => panic!("Unexpected chunk: {:?}", e),
+        };
+        assert_eq!(units.trim(), "HHAH0LOW1 ZIH1ZIY0");
+    }
+
+    #[test]
+    fn ranked_variants() {
+        let dict = "CONTENT  K AA1 N T EH0 N T\nCONTENT(1)  K AH0 N T EH1 N T\nCONTENT(2)  K AA1 N T EH2 N T";
+        let mut dict =
+            CmuDictionary::from_reader(io::BufReader::new(io::Cursor::new(dict))).unwrap();
+        let indexes = |variants: Vec<PronunciationVariant>| {
+            variants.iter().map(|x| x.index).collect::<Vec<_>>()
+        };
+
+        assert_eq!(indexes(dict.variants("content")), [0, 1, 2]);
+        assert!(dict.variants("missing").is_empty());
+
+        // Stress on the second syllable first, ties stay in dictionary order
+        let ranked = dict.ranked_variants("content", |p| {
+            p.iter()
+                .find(|x| x.phone.is_vowel())
+                .map(|x| x.context == Some(AuxiliarySymbol::PrimaryStress))
+        });
+        assert_eq!(indexes(ranked.clone()), [1, 0, 2]);
+        assert_eq!(
+            ranked[0].pronunciation,
+            &dict.get_pronunciations("CONTENT").unwrap()[1]
+        );
+
+        // CONTENT(2) has secondary stress on the second syllable
+        assert_eq!(dict.most_stressed_variant("content").unwrap().index, 2);
+        assert_eq!(dict.most_stressed_variant("missing"), None);
+        let the = "THE  DH AH0\nTHE(1)  DH AH1\nTHE(2)  DH IY0";
+        let the = CmuDictionary::from_reader(io::BufReader::new(io::Cursor::new(the))).unwrap();
+        let emphasised = the.most_stressed_variant("the").unwrap();
+        assert_eq!(emphasised.index, 1);
+        assert_eq!(
+            emphasise_word(emphasised.pronunciation),
+            *emphasised.pronunciation
+        );
+
+        let expected = ranked[0].pronunciation.clone();
+        assert!(dict.set_primary("content", 1));
+        assert_eq!(dict.get_pronunciations("CONTENT").unwrap()[0], expected);
+        assert_eq!(
+            dict.variants("content")[2].pronunciation[2].phone,
+            ArpaPhone::N
+        );
+        assert!(!dict.set_primary("content", 3));
+        assert!(!dict.set_primary("missing", 0));
+    }
+
+    #[test]
+    fn case_consistent_lookup() {
+        // Entries written in any case end up with the same key as the normaliser output
+        let dict = "McDONALD  M AH0 K D AA1 N AH0 L D\niphone  AY1 F OW2 N\nEBAY  IY1 B EY2";
+        let dict = CmuDictionary::from_reader(io::BufReader::new(io::Cursor::new(dict))).unwrap();
+        for word in [
+            "McDonald", "MCDONALD", "mcdonald", "iPhone", "IPHONE", "eBay", "Ebay",
+        ] {
+            assert!(dict.get_pronunciations(word).is_some(), "{}", word);
+            assert_eq!(dict.variants(word).len(), 1, "{}", word);
+        }
+
+        let pronounce = |mut text: NormalisedText| {
+            let mut misses = vec![];
+            text.words_to_pronunciation_with_fallback(&dict, |word| {
+                misses.push(word.to_string());
+                None
+            });
+            assert!(misses.is_empty(), "{:?}", misses);
+            match &text.chunks()[0] {
+                NormaliserChunk::Pronunciation(units) => units
+                    .iter()
+                    .map(|x| x.to_string())
+                    .collect::<String>()
+                    .trim()
+                    .to_string(),
+                e => panic!("Unexpected chunk: {:?}", e),
+            }
+        };
+        let expected = "MAH0KDAA1NAH0LD AY1FOW2N IY1BEY2";
+        assert_eq!(pronounce(normalise_text("McDonald iPhone eBay")), expected);
+        assert_eq!(pronounce(normalise_text("MCDONALD IPHONE EBAY")), expected);
+        assert_eq!(
+            pronounce(normalise_ssml("<speak>mcDonald IPhone EBay</speak>").unwrap()),
+            expected
+        );
+    }
+
+    #[test]
+    fn word_spans() {
+        let dict = "NEW YORK  N UW1 Y AO1 R K\nIS  IH1 Z\nBIG  B IH1 G";
+        let dict = CmuDictionary::from_reader(io::BufReader::new(io::Cursor::new(dict))).unwrap();
+        let source = "New York is big, xyzzy is big";
+        let mut text = normalise_text(source);
+        let spans = text.words_to_pronunciation_with_spans(
+            source,
+            &dict,
+            &[],
+            &crate::homographs::HomographResolver::default(),
+            |_| None,
+        );
+        let summary = spans
+            .iter()
+            .map(|x| (x.word.as_str(), x.source_chunk, x.chunk, x.units.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                ("NEW YORK", 0, 0, 0..6),
+                ("IS", 0, 0, 7..9),
+                ("BIG", 0, 0, 10..13),
+                ("IS", 2, 2, 0..2),
+                ("BIG", 2, 2, 3..6),
+            ]
+        );
+        for span in &spans {
+            let range = span.source_range.clone().unwrap();
+            assert_eq!(source[range].to_uppercase(), span.word);
+            let NormaliserChunk::Pronunciation(units) = &text.chunks()[span.chunk] else {
+                panic!("{:?} isn't in a pronunciation chunk", span);
+            };
+            let expected = dict.get_pronunciations(&span.word).unwrap()[0]
+                .iter()
+                .map(|x| Unit::Phone(*x))
+                .collect::<Vec<_>>();
+            assert_eq!(units[span.units.clone()], expected);
+        }
+
+        // Words the normaliser rewrote can't be found in the source, the words after them can
+        let dict = "DOCTOR  D AA1 K T ER0\nCAFE  K AH0 F EY1\nTWO  T UW1\nIS  IH1 Z";
+        let dict = CmuDictionary::from_reader(io::BufReader::new(io::Cursor::new(dict))).unwrap();
+        let source = r#"<speak>Dr. <emphasis>Café</emphasis> is 2</speak>"#;
+        let mut text = normalise_ssml(source).unwrap();
+        let spans = text.words_to_pronunciation_with_spans(
+            source,
+            &dict,
+            &[],
+            &crate::homographs::HomographResolver::default(),
+            |_| None,
+        );
+        let ranges = spans
+            .iter()
+            .map(|x| (x.word.as_str(), x.source_range.clone().map(|x| &source[x])))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ranges,
+            [
+                ("DOCTOR", None),
+                ("CAFE", Some("Café")),
+                ("IS", Some("is")),
+                ("TWO", None)
+            ]
+        );
+    }
+
+    #[test]
+    fn fallback_dictionaries() {
+        let load = |x: &str| CmuDictionary::from_reader(io::BufReader::new(io::Cursor::new(x)));
+        let english = load("SAID  S EH1 D\nTHAT  DH AE1 T\nLIFE  L AY1 F\nIS  IH1 Z").unwrap();
+        let french = FallbackDictionary {
+            language: "fr".to_string(),
+            // Approximated with English phones
+            dictionary: load("C'EST  S EH1\nLA  L AA1\nVIE  V IY1\nLIFE  L IY1 F").unwrap(),
+        };
+        let german = FallbackDictionary {
+            language: "de".to_string(),
+            dictionary: load("VIE  V IY1 AH0").unwrap(),
+        };
+
+        let mut misses = vec![];
+        let mut text = normalise_text("That is life, c'est la vie said Jacques");
+        text.words_to_pronunciation_with_dictionaries(
+            &english,
+            &[french, german],
+            &crate::homographs::HomographResolver::default(),
+            |word| {
+                misses.push(word.to_string());
+                None
+            },
+        );
+        assert_eq!(misses, ["JACQUES"]);
+
+        let rendered = text
+            .chunks()
+            .iter()
+            .map(|chunk| match chunk {
+                NormaliserChunk::Pronunciation(units) => units
+                    .iter()
+                    .map(|x| x.to_string())
+                    .collect::<String>()
+                    .trim()
+                    .to_string(),
+                NormaliserChunk::Punct(p) => p.to_string().trim().to_string(),
+                NormaliserChunk::Language(Some(x)) => format!("<{}>", x),
+                NormaliserChunk::Language(None) => "</>".to_string(),
+                e => panic!("Unexpected chunk: {:?}", e),
+            })
+            .collect::<Vec<_>>();
+        // The main dictionary wins and the fallbacks are checked in order
+        assert_eq!(
+            rendered,
+            [
+                "DHAE1T IH1Z LAY1F",
+                ",",
+                "<fr>",
+                "SEH1 LAA1 VIY1",
+                "</>",
+                "SEH1D",
+            ]
+        );
+    }
+
+    #[test]
+    fn ipa_entries() {
+        let load = |x: &str| CmuDictionary::from_reader(io::BufReader::new(io::Cursor::new(x)));
+        let arpa = load("HELLO  HH AH0 L OW1\nABOUT  AH0 B AW1 T\nCHURCH  CH ER1 CH").unwrap();
+        let ipa = load("hello /həˈloʊ/\nAbout  /əˈbaʊt/\nchurch /ˈtʃɝːtʃ/").unwrap();
+        for word in ["hello", "about", "church"] {
+            assert_eq!(
+                ipa.get_pronunciations(word),
+                arpa.get_pronunciations(word),
+                "{}",
+                word
+            );
+        }
+
+        // Formats can be mixed and merging doesn't duplicate the same pronunciation
+        let mut mixed = load("HELLO  HH AH0 L OW1\nhello /hɛˈloʊ/\nslash /slæʃ/").unwrap();
+        assert_eq!(mixed.get_pronunciations("hello").unwrap().len(), 2);
+        assert!(mixed.get_pronunciations("slash").is_some());
+        mixed.merge(ipa);
+        assert_eq!(mixed.get_pronunciations("hello").unwrap().len(), 2);
+
+        // Unknown IPA is skipped
+        let bad = load("bonjour /bɔ̃ʒuʁ/").unwrap();
+        assert!(bad.is_empty());
+        assert!(ipa_pronunciation("").is_err());
+    }
+
+    #[test]
+    fn validation() {
+        let text = ";;; comment\nHELLO  HH AH0 L OW1\n\nHELLO(1)  HH EH0 L OW1\nWORLD W ER1 L D\nbonjour /bɔ̃ʒuʁ/\nEMPTY  \nBAD  HH XX\nchurch /ˈtʃɝːtʃ/";
+        let report =
+            CmuDictionary::validate_reader(io::BufReader::new(io::Cursor::new(text))).unwrap();
+        assert_eq!(report.entries, 3);
+        assert!(!report.is_valid());
+        assert_eq!(report.problem_count, 4);
+        let lines = report.problems.iter().map(|x| x.0).collect::<Vec<_>>();
+        assert_eq!(lines, [5, 6, 7, 8]);
+        assert!(report.estimated_bytes > 0);
+
+        let dict = CmuDictionary::from_reader(io::BufReader::new(io::Cursor::new(text))).unwrap();
+        assert_eq!(dict.get_pronunciations("hello").unwrap().len(), 2);
+        assert!(dict.get_pronunciations("church").is_some());
+        assert!(dict.get_pronunciations("bad").is_none());
+
+        let valid = CmuDictionary::validate_reader(io::BufReader::new(io::Cursor::new(
+            "HELLO  HH AH0 L OW1",
+        )))
+        .unwrap();
+        assert!(valid.is_valid());
+        assert!(valid.problems.is_empty());
+    }
+
+    #[test]
+    fn spelling() {
+        let spelled = |x: &str| {
+            spell_word(x)
+                .unwrap()
+                .iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        assert_eq!(spelled("TLS"), "T IY1 EH1 L EH1 S");
+        assert_eq!(spelled("ssh"), "EH1 S EH1 S EY1 CH");
+        assert_eq!(spelled("HTTP"), "EY1 CH T IY1 T IY1 P IY1");
+        assert_eq!(spelled("W"), "D AH1 B AH0 L Y UW0");
+        assert_eq!(spell_word("R2D2"), None);
+        assert_eq!(spell_word(""), None);
+
+        assert!(is_vowelless("TLS"));
+        assert!(is_vowelless("HTTP"));
+        assert!(!is_vowelless("JSON"));
+        assert!(!is_vowelless("GYM"));
+        assert!(!is_vowelless("MP3"));
+        assert!(!is_vowelless(""));
+    }
+
+    #[test]
+    fn schwa_reduction() {
+        let dict = "ROSES  R OW1 Z IH0 Z\nROSES(1)  R OW1 Z AH0 Z\nSOFA  S OW1 F AH0";
+        let load =
+            || CmuDictionary::from_reader(io::BufReader::new(io::Cursor::new(dict))).unwrap();
+        let pronounce = |dict: &CmuDictionary, word: &str| {
+            dict.get_pronunciations(word)
+                .unwrap()
+                .iter()
+                .map(|x| {
+                    x.iter()
+                        .map(|p| p.to_string())
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut keep = load();
+        keep.apply_schwa_reduction(SchwaReduction::Keep);
+        assert_eq!(
+            pronounce(&keep, "ROSES"),
+            ["R OW1 Z IH0 Z", "R OW1 Z AH0 Z"]
+        );
+
+        let mut collapse = load();
+        collapse.apply_schwa_reduction(SchwaReduction::Collapse);
+        assert_eq!(pronounce(&collapse, "ROSES"), ["R OW1 Z AH0 Z"]);
+        assert_eq!(pronounce(&collapse, "SOFA"), ["S OW1 F AH0"]);
+
+        let mut expand = load();
+        expand.apply_schwa_reduction(SchwaReduction::Expand);
+        assert_eq!(
+            pronounce(&expand, "ROSES"),
+            ["R OW1 Z IH0 Z", "R OW1 Z AH Z"]
+        );
+        assert_eq!(pronounce(&expand, "SOFA"), ["S OW1 F AH"]);
+    }
 }
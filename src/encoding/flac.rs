@@ -0,0 +1,73 @@
+//! Encodes the samples into a FLAC file with [flacenc](https://github.com/yotarok/flacenc-rs).
+//! FLAC is lossless so the audio is exactly the same as the 16-bit WAV, the encoder just predicts
+//! each sample from the previous ones and stores the difference which is small for speech.
+use super::to_i16;
+use flacenc::bitsink::ByteSink;
+use flacenc::component::BitRepr;
+use flacenc::error::Verify;
+use flacenc::source::MemSource;
+use std::io::Write;
+
+/// Encodes the samples as 16-bit FLAC written to `output`. `samples` should be mono and in the
+/// range [-1, 1].
+pub fn encode_flac(
+    samples: &[f32],
+    sample_rate: u32,
+    mut output: impl Write,
+) -> anyhow::Result<()> {
+    let pcm = samples
+        .iter()
+        .map(|x| to_i16(*x) as i32)
+        .collect::<Vec<_>>();
+    let config = flacenc::config::Encoder::default()
+        .into_verified()
+        .map_err(|(_, e)| anyhow::anyhow!("Invalid FLAC encoder config: {:?}", e))?;
+    let source = MemSource::from_samples(&pcm, 1, 16, sample_rate as usize);
+    let stream = flacenc::encode_with_fixed_block_size(&config, source, config.block_size)
+        .map_err(|e| anyhow::anyhow!("FLAC encoding failed: {:?}", e))?;
+    let mut sink = ByteSink::new();
+    stream
+        .write(&mut sink)
+        // Writing to memory can't fail, the error is for sinks that write to files
+        .map_err(|_| anyhow::anyhow!("Writing FLAC stream failed"))?;
+    output.write_all(sink.as_slice())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn flac_round_trip() {
+        let samples = (0..10000)
+            .map(|i| (i as f32 * 0.05).sin() * 0.8)
+            .collect::<Vec<_>>();
+        let mut flac = vec![];
+        encode_flac(&samples, 22050, &mut flac).unwrap();
+
+        let mut reader = claxon::FlacReader::new(Cursor::new(flac)).unwrap();
+        let info = reader.streaminfo();
+        assert_eq!(info.sample_rate, 22050);
+        assert_eq!(info.channels, 1);
+        assert_eq!(info.bits_per_sample, 16);
+
+        let decoded = reader
+            .samples()
+            .map(|x| x.unwrap() as f32 / i16::MAX as f32)
+            .collect::<Vec<_>>();
+        assert_eq!(decoded.len(), samples.len());
+        // Only the conversion to 16 bit loses anything
+        for (decoded, original) in decoded.iter().zip(&samples) {
+            assert!((decoded - original).abs() < 1e-4);
+        }
+
+        // Overshoot is clipped rather than wrapping around
+        let mut flac = vec![];
+        encode_flac(&[1.5, -2.0], 22050, &mut flac).unwrap();
+        let mut reader = claxon::FlacReader::new(Cursor::new(flac)).unwrap();
+        let decoded = reader.samples().map(|x| x.unwrap()).collect::<Vec<_>>();
+        assert_eq!(decoded, [i16::MAX as i32, -i16::MAX as i32]);
+    }
+}
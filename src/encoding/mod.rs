@@ -0,0 +1,218 @@
+//! WAV files are big, for distributing the audio a compressed format is much more practical. This
+//! module takes the raw samples from the TTS (see `XdTts::synthesise`) and writes them in one of
+//! the `OutputFormat`s. WAV is always available, each of the other formats needs a cargo feature
+//! as they pull in an encoder:
+//!
+//! * `opus` - Ogg Opus, the best quality for the size with speech. Needs libopus.
+//! * `mp3` - MP3, it's not as efficient but plays everywhere. LAME is built from source.
+//! * `vorbis` - Ogg Vorbis, libvorbis is built from source.
+//! * `flac` - FLAC, lossless so it's the same audio as the WAV in about half the space. This is
+//!   pure Rust.
+//!
+//! All the formats are written at the sample rate in `WAV_SPEC` apart from Opus, see the `opus`
+//! module for why.
+use crate::{write_wav, WAV_SPEC};
+use hound::{WavSpec, WavWriter};
+use std::path::Path;
+#[cfg(any(
+    feature = "opus",
+    feature = "mp3",
+    feature = "vorbis",
+    feature = "flac"
+))]
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+#[cfg(feature = "flac")]
+mod flac;
+#[cfg(feature = "mp3")]
+mod mp3;
+#[cfg(feature = "opus")]
+mod opus;
+#[cfg(feature = "vorbis")]
+mod vorbis;
+
+#[cfg(feature = "flac")]
+pub use self::flac::encode_flac;
+#[cfg(feature = "mp3")]
+pub use self::mp3::encode_mp3;
+#[cfg(feature = "opus")]
+pub use self::opus::{encode_opus, write_opus};
+#[cfg(feature = "vorbis")]
+pub use self::vorbis::encode_vorbis;
+
+/// Quality options for the Opus encoder.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OpusSettings {
+    /// Target bitrate in bits per second. Opus accepts 500-512000, for mono speech 24-32kbps is
+    /// pretty much transparent and anything under 12kbps starts sounding noticeably compressed.
+    pub bitrate: i32,
+    /// Encoder complexity from 0-10, higher is better quality but slower to encode.
+    pub complexity: u8,
+}
+
+impl Default for OpusSettings {
+    fn default() -> Self {
+        Self {
+            bitrate: 32000,
+            complexity: 10,
+        }
+    }
+}
+
+/// The file formats the audio can be saved as, see the module docs for the features they need.
+/// The default is 16-bit WAV.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// WAV with the given sample format, see `XdTts::with_wav_format`. The sample rate and
+    /// channels have to match `WAV_SPEC`.
+    Wav(WavSpec),
+    /// Ogg Opus
+    Opus(OpusSettings),
+    /// MP3
+    Mp3,
+    /// Ogg Vorbis
+    Vorbis,
+    /// FLAC
+    Flac,
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        Self::Wav(WAV_SPEC)
+    }
+}
+
+impl OutputFormat {
+    /// Picks the format from the file extension ignoring case. `.opus` is Opus and `.ogg` is
+    /// always Vorbis, even though Opus is usually in an Ogg container too, so the same path gives
+    /// the same format whatever features are enabled. Anything not recognised is written as 16-bit
+    /// WAV.
+    pub fn from_path(path: impl AsRef<Path>) -> Self {
+        let extension = path
+            .as_ref()
+            .extension()
+            .and_then(|x| x.to_str())
+            .map(|x| x.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("opus") => Self::Opus(OpusSettings::default()),
+            Some("mp3") => Self::Mp3,
+            Some("ogg" | "oga") => Self::Vorbis,
+            Some("flac") => Self::Flac,
+            _ => Self::default(),
+        }
+    }
+
+    /// The cargo feature needed to write this format, `None` if it's always available.
+    pub fn feature(&self) -> Option<&'static str> {
+        match self {
+            Self::Wav(_) => None,
+            Self::Opus(_) => Some("opus"),
+            Self::Mp3 => Some("mp3"),
+            Self::Vorbis => Some("vorbis"),
+            Self::Flac => Some("flac"),
+        }
+    }
+
+    /// Whether the feature needed for this format is enabled, if not `encode` will fail.
+    pub fn is_available(&self) -> bool {
+        match self {
+            Self::Wav(_) => true,
+            Self::Opus(_) => cfg!(feature = "opus"),
+            Self::Mp3 => cfg!(feature = "mp3"),
+            Self::Vorbis => cfg!(feature = "vorbis"),
+            Self::Flac => cfg!(feature = "flac"),
+        }
+    }
+}
+
+/// Encodes the samples and writes them to the given path in the format. `samples` should be mono,
+/// in the range [-1, 1] and at the sample rate in `WAV_SPEC` which is what `XdTts::synthesise`
+/// returns. If the feature for the format isn't enabled this returns an error.
+pub fn encode(samples: &[f32], format: OutputFormat, path: impl AsRef<Path>) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    match format {
+        OutputFormat::Wav(spec) => {
+            let mut writer = WavWriter::create(path, spec)?;
+            write_wav(samples, &mut writer)?;
+            writer.finalize()?;
+            Ok(())
+        }
+        #[cfg(feature = "opus")]
+        OutputFormat::Opus(settings) => write_opus(path, samples, WAV_SPEC.sample_rate, &settings),
+        #[cfg(feature = "mp3")]
+        OutputFormat::Mp3 => write_file(path, |w| encode_mp3(samples, WAV_SPEC.sample_rate, w)),
+        #[cfg(feature = "vorbis")]
+        OutputFormat::Vorbis => {
+            write_file(path, |w| encode_vorbis(samples, WAV_SPEC.sample_rate, w))
+        }
+        #[cfg(feature = "flac")]
+        OutputFormat::Flac => write_file(path, |w| encode_flac(samples, WAV_SPEC.sample_rate, w)),
+        #[allow(unreachable_patterns)]
+        _ => anyhow::bail!(
+            "Can't write '{}', rebuild with the `{}` feature for {:?} output",
+            path.display(),
+            format.feature().unwrap_or_default(),
+            format
+        ),
+    }
+}
+
+/// Creates the file and runs the encoder on a buffered writer for it.
+#[cfg(any(
+    feature = "opus",
+    feature = "mp3",
+    feature = "vorbis",
+    feature = "flac"
+))]
+fn write_file(
+    path: &Path,
+    encode: impl FnOnce(&mut BufWriter<File>) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    encode(&mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Converts a sample to 16 bit PCM for the encoders that don't take floats, clipping anything
+/// outside [-1, 1].
+#[cfg(any(feature = "mp3", feature = "flac"))]
+fn to_i16(sample: f32) -> i16 {
+    crate::pcm_sample(sample, 16) as i16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_from_path() {
+        assert_eq!(
+            OutputFormat::from_path("out.wav"),
+            OutputFormat::Wav(WAV_SPEC)
+        );
+        assert_eq!(OutputFormat::from_path("out"), OutputFormat::default());
+        assert_eq!(OutputFormat::from_path("out.MP3"), OutputFormat::Mp3);
+        assert_eq!(OutputFormat::from_path("a/b.ogg"), OutputFormat::Vorbis);
+        assert_eq!(OutputFormat::from_path("out.flac"), OutputFormat::Flac);
+        assert_eq!(
+            OutputFormat::from_path("out.opus"),
+            OutputFormat::Opus(OpusSettings::default())
+        );
+        assert_eq!(OutputFormat::Flac.feature(), Some("flac"));
+        assert_eq!(OutputFormat::default().feature(), None);
+        assert!(OutputFormat::default().is_available());
+        assert_eq!(OutputFormat::Flac.is_available(), cfg!(feature = "flac"));
+
+        let path = std::env::temp_dir().join(format!("xd_tts_encode_{}.wav", std::process::id()));
+        let samples = [0.0, 0.5, -0.5, 0.25];
+        encode(&samples, OutputFormat::default(), &path).unwrap();
+        let reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.spec(), WAV_SPEC);
+        assert_eq!(reader.len() as usize, samples.len());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,181 @@
+//! Encodes the raw samples from the TTS into an Ogg Opus file, it's only available with the `opus`
+//! feature enabled as it requires libopus.
+//!
+//! Opus only works on a handful of sample rates (8, 12, 16, 24 and 48kHz) and our audio is at
+//! 22.05kHz, so before encoding it's resampled to 24kHz. This is done with a linear interpolation
+//! which isn't the best resampler, but going up in sample rate with speech that has very little
+//! energy near nyquist it's hard to hear the difference.
+//!
+//! The container format is described in [RFC 7845](https://datatracker.ietf.org/doc/html/rfc7845),
+//! it's two header packets (`OpusHead` and `OpusTags`) and then a packet per 20ms frame of audio.
+use super::OpusSettings;
+use audiopus::coder::Encoder;
+use audiopus::{Application, Bitrate, Channels, SampleRate};
+use ogg::{PacketWriteEndInfo, PacketWriter};
+use std::io::Write;
+use std::path::Path;
+
+/// Sample rate we encode at
+const OPUS_RATE: u32 = 24000;
+/// Granule positions in an Ogg Opus stream are always at 48kHz regardless of the encoder rate
+const GRANULE_RATE: u32 = 48000;
+/// 20ms frames, this is the default in most applications
+const FRAME_SIZE: usize = (OPUS_RATE / 50) as usize;
+/// Maximum size of a packet recommended by the libopus documentation
+const MAX_PACKET_SIZE: usize = 4000;
+/// We only ever write one logical stream so the serial number doesn't matter
+const STREAM_SERIAL: u32 = 0x7864_7474;
+
+/// Encodes the samples and writes the Ogg Opus file to the given path.
+pub fn write_opus(
+    path: impl AsRef<Path>,
+    samples: &[f32],
+    sample_rate: u32,
+    settings: &OpusSettings,
+) -> anyhow::Result<()> {
+    super::write_file(path.as_ref(), |writer| {
+        encode_opus(samples, sample_rate, settings, writer)
+    })
+}
+
+/// Encodes the samples into an Ogg Opus stream written to `output`. `samples` should be mono and
+/// in the range [-1, 1].
+pub fn encode_opus(
+    samples: &[f32],
+    sample_rate: u32,
+    settings: &OpusSettings,
+    output: impl Write,
+) -> anyhow::Result<()> {
+    let mut encoder = Encoder::new(SampleRate::Hz24000, Channels::Mono, Application::Voip)?;
+    encoder.set_bitrate(Bitrate::BitsPerSecond(settings.bitrate))?;
+    encoder.set_complexity(settings.complexity)?;
+
+    let mut audio = resample(samples, sample_rate, OPUS_RATE);
+    audio.iter_mut().for_each(|x| *x = crate::clip_sample(*x));
+    let granule_scale = (GRANULE_RATE / OPUS_RATE) as u64;
+    let pre_skip = encoder.lookahead()? as u64 * granule_scale;
+
+    let mut writer = PacketWriter::new(output);
+    writer.write_packet(
+        opus_head(pre_skip as u16, sample_rate),
+        STREAM_SERIAL,
+        PacketWriteEndInfo::EndPage,
+        0,
+    )?;
+    writer.write_packet(opus_tags(), STREAM_SERIAL, PacketWriteEndInfo::EndPage, 0)?;
+
+    // The final granule position tells the decoder how many samples are real audio so the padding
+    // in the last frame gets trimmed off.
+    let end_granule = pre_skip + audio.len() as u64 * granule_scale;
+    let n_frames = audio.len().div_ceil(FRAME_SIZE).max(1);
+    let mut frame = vec![0.0; FRAME_SIZE];
+    let mut packet = vec![0; MAX_PACKET_SIZE];
+    for i in 0..n_frames {
+        let start = (i * FRAME_SIZE).min(audio.len());
+        let end = ((i + 1) * FRAME_SIZE).min(audio.len());
+        frame.fill(0.0);
+        frame[..(end - start)].copy_from_slice(&audio[start..end]);
+
+        let len = encoder.encode_float(&frame, &mut packet)?;
+        let (end_info, granule) = if i + 1 == n_frames {
+            (PacketWriteEndInfo::EndStream, end_granule)
+        } else {
+            (
+                PacketWriteEndInfo::NormalPacket,
+                pre_skip + ((i + 1) * FRAME_SIZE) as u64 * granule_scale,
+            )
+        };
+        writer.write_packet(
+            packet[..len].to_vec().into_boxed_slice(),
+            STREAM_SERIAL,
+            end_info,
+            granule,
+        )?;
+    }
+    Ok(())
+}
+
+/// Identification header, see RFC 7845 section 5.1
+fn opus_head(pre_skip: u16, input_sample_rate: u32) -> Box<[u8]> {
+    let mut head = Vec::with_capacity(19);
+    head.extend_from_slice(b"OpusHead");
+    head.push(1); // version
+    head.push(1); // channel count
+    head.extend_from_slice(&pre_skip.to_le_bytes());
+    head.extend_from_slice(&input_sample_rate.to_le_bytes());
+    head.extend_from_slice(&0i16.to_le_bytes()); // output gain
+    head.push(0); // channel mapping family, 0 is mono/stereo
+    head.into_boxed_slice()
+}
+
+/// Comment header, see RFC 7845 section 5.2. We don't add any user comments.
+fn opus_tags() -> Box<[u8]> {
+    let vendor = concat!("xd-tts ", env!("CARGO_PKG_VERSION"));
+    let mut tags = vec![];
+    tags.extend_from_slice(b"OpusTags");
+    tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    tags.extend_from_slice(vendor.as_bytes());
+    tags.extend_from_slice(&0u32.to_le_bytes());
+    tags.into_boxed_slice()
+}
+
+/// Linear interpolation resampler.
+fn resample(samples: &[f32], from: u32, to: u32) -> Vec<f32> {
+    if from == to || samples.is_empty() {
+        return samples.to_vec();
+    }
+    let ratio = from as f64 / to as f64;
+    let len = (samples.len() as f64 / ratio).round() as usize;
+    (0..len)
+        .map(|i| {
+            let pos = i as f64 * ratio;
+            let index = pos.floor() as usize;
+            let frac = (pos - index as f64) as f32;
+            let current = samples[index.min(samples.len() - 1)];
+            let next = samples[(index + 1).min(samples.len() - 1)];
+            current + (next - current) * frac
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resampling() {
+        let input = (0..2205).map(|x| x as f32).collect::<Vec<_>>();
+        let output = resample(&input, 22050, 24000);
+        assert_eq!(output.len(), 2400);
+        assert_eq!(output[0], 0.0);
+        // Linear input means the output is still linear just with a different step
+        for (i, sample) in output.iter().enumerate().take(2390) {
+            let expected = i as f32 * 22050.0 / 24000.0;
+            assert!(
+                (sample - expected).abs() < 1e-3,
+                "{} != {}",
+                sample,
+                expected
+            );
+        }
+
+        assert_eq!(resample(&input, 22050, 22050), input);
+    }
+
+    #[test]
+    fn header_packets() {
+        let head = opus_head(312, 22050);
+        assert_eq!(head.len(), 19);
+        assert_eq!(&head[..8], b"OpusHead");
+        assert_eq!(u16::from_le_bytes([head[10], head[11]]), 312);
+        assert_eq!(
+            u32::from_le_bytes([head[12], head[13], head[14], head[15]]),
+            22050
+        );
+
+        let tags = opus_tags();
+        assert_eq!(&tags[..8], b"OpusTags");
+        let vendor_len = u32::from_le_bytes([tags[8], tags[9], tags[10], tags[11]]) as usize;
+        assert_eq!(tags.len(), 8 + 4 + vendor_len + 4);
+    }
+}
@@ -0,0 +1,45 @@
+//! Encodes the samples into an Ogg Vorbis file with
+//! [vorbis_rs](https://github.com/ComunidadAylas/vorbis-rs). Vorbis handles any sample rate so
+//! unlike Opus there's no resampling.
+use anyhow::Context;
+use std::io::Write;
+use std::num::{NonZeroU32, NonZeroU8};
+use vorbis_rs::VorbisEncoderBuilder;
+
+/// Number of samples given to the encoder at a time
+const BLOCK_SIZE: usize = 4096;
+
+/// Encodes the samples as Ogg Vorbis written to `output` using the encoders default quality.
+/// `samples` should be mono and in the range [-1, 1].
+pub fn encode_vorbis(samples: &[f32], sample_rate: u32, output: impl Write) -> anyhow::Result<()> {
+    let sample_rate = NonZeroU32::new(sample_rate).context("Sample rate can't be 0")?;
+    let mut encoder = VorbisEncoderBuilder::new(sample_rate, NonZeroU8::MIN, output)?.build()?;
+    let samples = samples
+        .iter()
+        .map(|x| crate::clip_sample(*x))
+        .collect::<Vec<_>>();
+    for block in samples.chunks(BLOCK_SIZE) {
+        encoder.encode_audio_block([block])?;
+    }
+    encoder.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vorbis_smoke() {
+        let samples = (0..22050)
+            .map(|i| (i as f32 * 0.05).sin() * 0.8)
+            .collect::<Vec<_>>();
+        let mut ogg = vec![];
+        encode_vorbis(&samples, 22050, &mut ogg).unwrap();
+        // An Ogg page with the Vorbis identification header in it
+        assert!(ogg.starts_with(b"OggS"));
+        assert!(ogg.windows(7).any(|x| x == b"\x01vorbis"));
+
+        assert!(encode_vorbis(&samples, 0, Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,204 @@
+//! Traces a single input through every stage of the pipeline, for when somebody reports "it
+//! sounds wrong" and you need to work out which stage is at fault. The trace shows what the text
+//! looked like after each stage, what the model was actually given (after splitting into windows
+//! and mapping to IDs), how long each chunk took and anything that looked suspicious on the way.
+//!
+//! This runs the full synthesis so it takes as long as generating the audio, the audio itself is
+//! thrown away.
+use crate::phonemes::Unit;
+use crate::tacotron2::{ChunkInput, ChunkRetry};
+use crate::text_normaliser::{unsupported_characters, NormaliserChunk};
+use crate::{AcousticModel, Step, SynthesisSteps, XdTts};
+use deunicode::deunicode;
+use once_cell::sync::OnceCell;
+use regex::Regex;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Everything that happened to one input on the way through the pipeline, see
+/// `XdTts::explain`. The `Display` implementation gives a readable report.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PipelineTrace {
+    /// The text as it was given
+    pub text: String,
+    /// The text transliterated to ASCII by deunicode. The normaliser reads symbols like currency
+    /// before transliterating, so this shows what happens to any characters it doesn't read
+    pub transliterated: String,
+    /// Output of the frontend before it's converted to units
+    pub normalised: Vec<NormaliserChunk>,
+    /// Each chunk ran through the model, in order
+    pub chunks: Vec<ChunkTrace>,
+    /// Things that are likely to make the output sound wrong
+    pub diagnostics: Vec<String>,
+}
+
+/// One chunk of inference, this is the text between two breaks or pitch changes. Chunks that are
+/// only punctuation are replaced with a pause like in synthesis, so they aren't traced.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChunkTrace {
+    /// The units given to the model
+    pub units: Vec<Unit>,
+    /// Pitch shift applied to the chunk in cents
+    pub pitch: i32,
+    /// What the model was given for each window the chunk was split into, this is empty if the
+    /// model doesn't map units to IDs
+    pub inputs: Vec<ChunkInput>,
+    /// Number of spectrogram frames generated
+    pub frames: usize,
+    /// Number of audio samples generated
+    pub samples: usize,
+    /// Time spent generating the spectrogram
+    pub inference_time: Duration,
+    /// Time spent in the vocoder
+    pub vocoder_time: Duration,
+}
+
+impl<M: AcousticModel> XdTts<M> {
+    /// Runs the text through every stage of the pipeline recording what each stage did, see the
+    /// `explain` module. Errors from the frontend or model are returned as normal, problems that
+    /// don't stop synthesis are listed in `PipelineTrace::diagnostics`.
+    pub fn explain(&self, text: &str) -> anyhow::Result<PipelineTrace> {
+        let mut trace = PipelineTrace {
+            text: text.to_string(),
+            transliterated: deunicode(text),
+            ..Default::default()
+        };
+        // Don't report the markup in SSML
+        static TAGS: OnceCell<Regex> = OnceCell::new();
+        let tags = TAGS.get_or_init(|| Regex::new(r#"<[^>]*>"#).unwrap());
+        let unsupported = unsupported_characters(&tags.replace_all(text, " "));
+        if !unsupported.is_empty() {
+            trace.diagnostics.push(format!(
+                "Characters that can't be read: {}",
+                unsupported
+                    .iter()
+                    .map(|x| format!("{:?}", x))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+
+        let mut normalised = self.normalise(text)?;
+        trace.normalised = normalised.chunks().to_vec();
+        let mut misses = vec![];
+        self.convert_to_units(&mut normalised, |word| misses.push(word.to_string()));
+        for word in misses {
+            trace
+                .diagnostics
+                .push(format!("'{}' isn't in the dictionary", word));
+        }
+
+        for step in SynthesisSteps::new(normalised.drain_all().map(Ok)) {
+            if let Step::Speech { units, pitch } = step? {
+                self.trace_chunk(units, pitch, &mut trace)?;
+            }
+        }
+        Ok(trace)
+    }
+
+    /// Synthesises the chunk the same way as `XdTts::infer` adding it to the trace.
+    fn trace_chunk(
+        &self,
+        units: Vec<Unit>,
+        pitch: i32,
+        trace: &mut PipelineTrace,
+    ) -> anyhow::Result<()> {
+        let start = Instant::now();
+        let (spectrogram, inputs) = self.model.infer_with_inputs(&units)?;
+        let vocoder_start = Instant::now();
+        let samples = self.vocode(&spectrogram, pitch)?.len();
+        let end = Instant::now();
+
+        let index = trace.chunks.len() + 1;
+        let summary = self.model.unit_summary(&inputs);
+        if !summary.is_empty() {
+            trace.diagnostics.push(format!(
+                "Chunk {}: units not given to the model as they were: {}",
+                index, summary
+            ));
+        }
+        let stalled = inputs.iter().filter(|x| x.stalled).count();
+        if stalled > 0 {
+            trace.diagnostics.push(format!(
+                "Chunk {}: the attention got stuck in {} window(s), the audio was cut short",
+                index, stalled
+            ));
+        }
+        // Same heuristic as the chunk retries, normal speech is around 5-10 frames per input
+        let n_inputs = inputs.iter().map(|x| x.model_ids().len()).sum::<usize>();
+        let limits = ChunkRetry::default();
+        let ratio = spectrogram.ncols() as f32 / n_inputs.max(1) as f32;
+        if n_inputs > 0
+            && (ratio < limits.min_frames_per_unit || ratio > limits.max_frames_per_unit)
+        {
+            trace.diagnostics.push(format!(
+                "Chunk {}: {} frames for {} inputs, the model may have failed",
+                index,
+                spectrogram.ncols(),
+                n_inputs
+            ));
+        }
+        trace.chunks.push(ChunkTrace {
+            units,
+            pitch,
+            inputs,
+            frames: spectrogram.ncols(),
+            samples,
+            inference_time: vocoder_start - start,
+            vocoder_time: end - vocoder_start,
+        });
+        Ok(())
+    }
+}
+
+fn format_units(units: &[Unit]) -> String {
+    units
+        .iter()
+        .map(|x| match x {
+            Unit::Space => "_".to_string(),
+            x => x.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+impl fmt::Display for PipelineTrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Text: {}", self.text)?;
+        writeln!(f, "Transliterated: {}", self.transliterated)?;
+        writeln!(f, "Normalised:")?;
+        for chunk in &self.normalised {
+            writeln!(f, "  {:?}", chunk)?;
+        }
+        for (i, chunk) in self.chunks.iter().enumerate() {
+            writeln!(f, "Chunk {}:", i + 1)?;
+            writeln!(f, "  Units: {}", format_units(&chunk.units))?;
+            if chunk.pitch != 0 {
+                writeln!(f, "  Pitch: {:+} cents", chunk.pitch)?;
+            }
+            for (j, input) in chunk.inputs.iter().enumerate() {
+                writeln!(
+                    f,
+                    "  Window {}: {} -> {:?}",
+                    j + 1,
+                    format_units(&input.units),
+                    input.model_ids()
+                )?;
+            }
+            writeln!(
+                f,
+                "  Frames: {}, samples: {}, inference: {:?}, vocoder: {:?}",
+                chunk.frames, chunk.samples, chunk.inference_time, chunk.vocoder_time
+            )?;
+        }
+        if self.diagnostics.is_empty() {
+            writeln!(f, "No problems found")
+        } else {
+            writeln!(f, "Diagnostics:")?;
+            for diagnostic in &self.diagnostics {
+                writeln!(f, "  - {}", diagnostic)?;
+            }
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,250 @@
+//! Homographs are words with the same spelling but different pronunciations, like "lead" in "lead
+//! the way" and "lead pipe". Getting these right in general needs part of speech tagging or a
+//! model trained on the word in context, but a handful of rules looking at the word before or
+//! after covers the most common cases.
+//!
+//! The rule table is a text file, each line is a word, the pronunciation to use and the words that
+//! cue it:
+//!
+//! ```text
+//! LEAD  L EH1 D | after PIPE BALLOON
+//! LEAD  L IY1 D | before TO WILL
+//! ```
+//!
+//! So "lead" is `L EH1 D` when the next word is "pipe" or "balloon" and `L IY1 D` after "to" or
+//! "will". Rules are checked in the order they're added and the first match wins, if none match
+//! the first pronunciation in the dictionary is used. Lines starting with `#` are comments. The
+//! built-in English table is in `resources/homographs.txt`.
+use crate::phonemes::*;
+use crate::text_normaliser::dict_normalise;
+use anyhow::Context;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{self, prelude::*};
+use std::path::Path;
+use std::str::FromStr;
+use tracing::debug;
+
+/// Which side of the homograph the cue words are on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CuePosition {
+    /// The word before the homograph
+    Before,
+    /// The word after the homograph
+    After,
+}
+
+/// Picks a pronunciation for a homograph when one of the cue words is next to it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HomographRule {
+    /// The pronunciation to use, this has to match one of the dictionary pronunciations exactly
+    pub pronunciation: Pronunciation,
+    /// Where to look for the cues
+    pub position: CuePosition,
+    /// Normalised (uppercase) words that select this pronunciation
+    pub cues: BTreeSet<String>,
+}
+
+/// A table of rules for picking the pronunciation of homographs, see the module docs.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HomographResolver {
+    rules: BTreeMap<String, Vec<HomographRule>>,
+}
+
+impl HomographResolver {
+    /// The built-in rules for common English homographs ("read", "lead", "live", "tear", "bass"
+    /// and "wind").
+    pub fn english() -> Self {
+        Self::from_reader(include_str!("../resources/homographs.txt").as_bytes())
+            .expect("built-in homograph table is valid")
+    }
+
+    /// Loads a rule table from a file
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let file = fs::File::open(path)?;
+        Self::from_reader(io::BufReader::new(file))
+    }
+
+    /// Loads a rule table from a reader
+    pub fn from_reader(reader: impl BufRead) -> anyhow::Result<Self> {
+        let mut res = Self::default();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (word, rule) =
+                parse_rule(line).with_context(|| format!("invalid rule on line {}", i + 1))?;
+            res.add_rule(&word, rule);
+        }
+        Ok(res)
+    }
+
+    /// Adds a rule for the word, it's checked after any existing rules for that word. The word
+    /// is normalised the same way as dictionary entries so it matches however it's written.
+    pub fn add_rule(&mut self, word: &str, rule: HomographRule) {
+        self.rules
+            .entry(dict_normalise(word))
+            .or_default()
+            .push(rule);
+    }
+
+    /// Adds all the rules from another table after the existing ones.
+    pub fn merge(&mut self, other: HomographResolver) {
+        for (word, rules) in other.rules {
+            self.rules.entry(word).or_default().extend(rules);
+        }
+    }
+
+    /// Returns true if there are rules for the word.
+    pub fn contains(&self, word: &str) -> bool {
+        self.rules.contains_key(word)
+    }
+
+    /// Picks which of the candidate pronunciations to use for the word given the normalised words
+    /// either side of it. Returns `None` if there are no rules for the word or none of them
+    /// matched, in which case the first candidate should be used.
+    pub fn resolve(
+        &self,
+        word: &str,
+        previous: Option<&str>,
+        next: Option<&str>,
+        candidates: &[Pronunciation],
+    ) -> Option<usize> {
+        let rules = self.rules.get(word)?;
+        for rule in rules {
+            let cue = match rule.position {
+                CuePosition::Before => previous,
+                CuePosition::After => next,
+            };
+            if !cue.is_some_and(|x| rule.cues.contains(x)) {
+                continue;
+            }
+            match candidates.iter().position(|x| *x == rule.pronunciation) {
+                Some(i) => return Some(i),
+                None => debug!(
+                    "Rule for {} matched but {:?} isn't in the dictionary",
+                    word, rule.pronunciation
+                ),
+            }
+        }
+        None
+    }
+}
+
+fn parse_rule(line: &str) -> anyhow::Result<(String, HomographRule)> {
+    let (entry, cues) = line.split_once('|').context("missing '|'")?;
+    let mut entry = entry.split_ascii_whitespace();
+    let word = entry.next().context("missing word")?.to_string();
+    let pronunciation = entry
+        .map(PhoneticUnit::from_str)
+        .collect::<anyhow::Result<Pronunciation>>()?;
+    if pronunciation.is_empty() {
+        anyhow::bail!("missing pronunciation");
+    }
+    let mut cues = cues.split_ascii_whitespace();
+    let position = match cues.next() {
+        Some("before") => CuePosition::Before,
+        Some("after") => CuePosition::After,
+        Some(x) => anyhow::bail!("expected 'before' or 'after' not '{}'", x),
+        None => anyhow::bail!("missing cue position"),
+    };
+    let cues = cues.map(dict_normalise).collect::<BTreeSet<_>>();
+    if cues.is_empty() {
+        anyhow::bail!("no cue words");
+    }
+    Ok((
+        word,
+        HomographRule {
+            pronunciation,
+            position,
+            cues,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text_normaliser::*;
+    use crate::CmuDictionary;
+
+    fn pronounce(text: &str, dict: &str) -> String {
+        let dict = CmuDictionary::from_reader(io::BufReader::new(io::Cursor::new(dict))).unwrap();
+        let mut text = normalise_text(text);
+        text.words_to_pronunciation(&dict);
+        match &text.chunks()[0] {
+            NormaliserChunk::Pronunciation(units) => units
+                .iter()
+                .map(|x| x.to_string())
+                .collect::<String>()
+                .trim()
+                .to_string(),
+            e => panic!("Unexpected chunk: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn lead_in_context() {
+        let words = "THE  DH AH0\nWAY  W EY1\nPIPE  P AY1 P\n";
+        for dict in [
+            format!("{}LEAD  L EH1 D\nLEAD(1)  L IY1 D", words),
+            format!("{}LEAD  L IY1 D\nLEAD(1)  L EH1 D", words),
+        ] {
+            assert_eq!(pronounce("lead the way", &dict), "LIY1D DHAH0 WEY1");
+            assert_eq!(pronounce("lead pipe", &dict), "LEH1D PAY1P");
+            assert_eq!(pronounce("the lead pipe", &dict), "DHAH0 LEH1D PAY1P");
+            // Repeats are looked up once but still resolved in their own context
+            assert_eq!(
+                pronounce("lead the lead pipe", &dict),
+                "LIY1D DHAH0 LEH1D PAY1P"
+            );
+        }
+        // No cues so it's the first in the dictionary
+        let dict = format!("{}LEAD  L IY1 D\nLEAD(1)  L EH1 D", words);
+        assert_eq!(pronounce("lead", &dict), "LIY1D");
+    }
+
+    #[test]
+    fn custom_rules() {
+        let table = "# comment\n\nMINUTE  M AY0 N UW1 T | after DETAIL DETAILS\n";
+        let mut resolver = HomographResolver::from_reader(table.as_bytes()).unwrap();
+        let candidates = ["M IH1 N AH0 T", "M AY0 N UW1 T"]
+            .iter()
+            .map(|x| {
+                x.split(' ')
+                    .map(|x| PhoneticUnit::from_str(x).unwrap())
+                    .collect::<Pronunciation>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            resolver.resolve("MINUTE", Some("A"), Some("DETAIL"), &candidates),
+            Some(1)
+        );
+        assert_eq!(
+            resolver.resolve("MINUTE", Some("DETAIL"), None, &candidates),
+            None
+        );
+        assert_eq!(
+            resolver.resolve("LEAD", None, Some("PIPE"), &candidates),
+            None
+        );
+
+        // Rules are matched on the normalised word so the case they're written in doesn't matter
+        let mixed_case = "Minute  M AY0 N UW1 T | after Detail\n";
+        let mixed_case = HomographResolver::from_reader(mixed_case.as_bytes()).unwrap();
+        assert_eq!(
+            mixed_case.resolve("MINUTE", None, Some("DETAIL"), &candidates),
+            Some(1)
+        );
+
+        resolver.merge(HomographResolver::english());
+        assert!(resolver.contains("MINUTE"));
+        assert!(resolver.contains("LEAD"));
+
+        assert!(HomographResolver::from_reader("LEAD L EH1 D".as_bytes()).is_err());
+        assert!(HomographResolver::from_reader("LEAD L EH1 D | near PIPE".as_bytes()).is_err());
+        assert!(HomographResolver::from_reader("LEAD | after PIPE".as_bytes()).is_err());
+    }
+}
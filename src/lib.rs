@@ -1,27 +1,52 @@
 #![doc = include_str!("../README.md")]
-use crate::phonemes::Unit;
+use crate::homographs::HomographResolver;
+use crate::phonemes::{
+    collapse_spaces, is_speakable, punctuation_pause, PhoneSubstitution, Pronunciation,
+    SplitStrategy, Unit,
+};
+use crate::streaming::{StreamingOptions, StreamingVocoder};
 use crate::tacotron2::*;
-use crate::text_normaliser::NormaliserChunk;
+use crate::text_normaliser::{
+    BreathPauses, EnglishFrontend, NormalisedText, NormaliserChunk, TextFrontend,
+};
 use griffin_lim::GriffinLim;
 use hound::{SampleFormat, WavSpec, WavWriter};
+use ndarray::{concatenate, Array2, Axis};
+use std::cell::Cell;
+use std::collections::VecDeque;
 use std::env;
 use std::io::prelude::*;
+use std::iter;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
 use std::time::{Duration, Instant};
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 use tracing_subscriber::filter::EnvFilter;
 use tracing_subscriber::{Layer, Registry};
 
 pub mod cmu_dict;
+pub mod encoding;
+pub mod explain;
+pub mod homographs;
+pub mod phone_set;
 pub mod phonemes;
 // This failed for various reasons. Look in the module so see the pains of ML.
 //pub mod speedyspeech;
+pub mod streaming;
 pub mod tacotron2;
+pub mod td_psola;
 pub mod text_normaliser;
 pub mod training;
+#[cfg(feature = "png-export")]
+pub mod visualise;
+pub mod vocoder;
 
-pub use cmu_dict::CmuDictionary;
+use cmu_dict::{is_vowelless, spell_word};
+pub use cmu_dict::{CmuDictionary, FallbackDictionary};
 
+/// Format of the audio the TTS generates, mono at 22.05kHz. WAV files are 16-bit by default, see
+/// `XdTts::with_wav_format` for other sample formats.
 pub const WAV_SPEC: WavSpec = WavSpec {
     channels: 1,
     sample_rate: 22050,
@@ -29,34 +54,479 @@ pub const WAV_SPEC: WavSpec = WavSpec {
     sample_format: SampleFormat::Int,
 };
 
-pub struct XdTts {
+/// The full TTS pipeline, text goes in and audio comes out.
+///
+/// An `XdTts` can be shared between threads (i.e. behind an `Arc`) and used for concurrent
+/// synthesis. Generating audio only needs `&self`, the ORT sessions in `Tacotron2` allow concurrent
+/// calls to `run` and the rest of the state (dictionary, vocoder, frontend) is read-only during
+/// synthesis. Each call will compete for the same CPU cores though, so running many at once won't
+/// be faster than running them one after another if inference is already using all the cores.
+pub struct XdTts<M = Tacotron2> {
+    frontend: Box<dyn TextFrontend>,
     dict: CmuDictionary,
-    model: Tacotron2,
-    vocoder: GriffinLim,
+    model: M,
+    vocoder: Box<dyn Vocoder>,
     phoneme_input: bool,
+    breaths: Option<BreathPauses>,
+    lexicon_miss: Option<Box<LexiconMissHandler>>,
+    homographs: HomographResolver,
+    fallback_dictionaries: Vec<FallbackDictionary>,
+    spell_vowelless: bool,
+    lowercase_characters: bool,
+    max_duration: Option<Duration>,
+    lead_silence: Duration,
+    tail_silence: Duration,
+    vocoder_threads: usize,
+    wav_spec: WavSpec,
 }
 
-impl XdTts {
-    pub fn new(tacotron2: &Path, phoneme_input: bool) -> anyhow::Result<Self> {
+/// Default for `XdTts::with_max_duration`, this is much longer than any reasonable request so it
+/// should only be hit by runaway synthesis.
+pub const DEFAULT_MAX_DURATION: Duration = Duration::from_secs(10 * 60);
+
+/// Default for `XdTts::with_lead_silence` and `XdTts::with_tail_silence`, long enough that players
+/// which clip the start of the audio don't cut off speech.
+pub const DEFAULT_EDGE_SILENCE: Duration = Duration::from_millis(50);
+
+/// Callback to find the pronunciation of a word that isn't in the dictionary, see
+/// `XdTts::with_lexicon_miss_handler`.
+pub type LexiconMissHandler = dyn Fn(&str) -> Option<Pronunciation> + Send + Sync;
+
+/// The model that turns units into a mel spectrogram. `Tacotron2` is the only real one, but this
+/// means the rest of the pipeline can be ran with something else, like a stub model in tests.
+pub trait AcousticModel: Send + Sync {
+    /// Generates a mel spectrogram of shape [80, frames] for the units. Long inputs should be split
+    /// up by the model as needed.
+    fn infer(&self, units: &[Unit]) -> anyhow::Result<Array2<f32>>;
+
+    /// Runs `infer` and also returns what was given to the model for each window it was split
+    /// into, see `ChunkInput`. Models without an ID for each unit return no inputs.
+    fn infer_with_inputs(&self, units: &[Unit]) -> anyhow::Result<(Array2<f32>, Vec<ChunkInput>)> {
+        Ok((self.infer(units)?, vec![]))
+    }
+
+    /// Runs `infer` but once the deadline has passed stops before starting the next window the
+    /// input was split into, returning the spectrogram for the windows that were finished and
+    /// whether it stopped early. Models that don't split the input run it all.
+    fn infer_until(
+        &self,
+        units: &[Unit],
+        deadline: Option<Instant>,
+    ) -> anyhow::Result<(Array2<f32>, bool)> {
+        let _ = deadline;
+        Ok((self.infer(units)?, false))
+    }
+
+    /// Counts the units in the inputs from `infer_with_inputs` that the model couldn't take as they
+    /// were, see `UnitSummary`. By default this only finds dropped units.
+    fn unit_summary(&self, inputs: &[ChunkInput]) -> UnitSummary {
+        UnitSummary::from_inputs(inputs)
+    }
+
+    /// Generates the spectrogram calling `on_mel` with blocks of around `block_frames` frames as
+    /// they're ready, see `streaming`. Models that can't stream output the whole spectrogram as
+    /// one block.
+    fn infer_streaming(
+        &self,
+        units: &[Unit],
+        block_frames: usize,
+        on_mel: &mut dyn FnMut(Array2<f32>) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let _ = block_frames;
+        on_mel(self.infer(units)?)
+    }
+
+    /// Number of audio samples for each frame of the spectrogram.
+    fn hop_length(&self) -> usize {
+        HOP_LENGTH
+    }
+}
+
+/// Turns a mel spectrogram into audio. Griffin-Lim is the default, this means a neural vocoder
+/// like HiFi-GAN can be used instead (see `XdTts::new` and `XdTts::with_vocoder`).
+pub trait Vocoder: Send + Sync {
+    /// Generates the audio for a spectrogram of shape [mels, frames] from the acoustic model. The
+    /// samples should be at the model's sample rate with `AcousticModel::hop_length` samples per
+    /// frame, give or take the window at the edges.
+    fn infer(&self, mel: &Array2<f32>) -> anyhow::Result<Vec<f32>>;
+}
+
+impl Vocoder for GriffinLim {
+    fn infer(&self, mel: &Array2<f32>) -> anyhow::Result<Vec<f32>> {
+        Ok(GriffinLim::infer(self, mel)?.to_vec())
+    }
+}
+
+impl AcousticModel for Tacotron2 {
+    fn infer(&self, units: &[Unit]) -> anyhow::Result<Array2<f32>> {
+        Tacotron2::infer(self, units)
+    }
+
+    fn infer_with_inputs(&self, units: &[Unit]) -> anyhow::Result<(Array2<f32>, Vec<ChunkInput>)> {
+        Tacotron2::infer_with_inputs(self, units)
+    }
+
+    fn infer_until(
+        &self,
+        units: &[Unit],
+        deadline: Option<Instant>,
+    ) -> anyhow::Result<(Array2<f32>, bool)> {
+        Tacotron2::infer_until(self, units, deadline)
+    }
+
+    fn unit_summary(&self, inputs: &[ChunkInput]) -> UnitSummary {
+        Tacotron2::unit_summary(self, inputs)
+    }
+
+    fn infer_streaming(
+        &self,
+        units: &[Unit],
+        block_frames: usize,
+        on_mel: &mut dyn FnMut(Array2<f32>) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        Tacotron2::infer_streaming(self, units, block_frames, on_mel)
+    }
+
+    fn hop_length(&self) -> usize {
+        self.config().hop_length
+    }
+}
+
+/// Audio from a synthesis request that may have been stopped early, see
+/// `XdTts::generate_audio_with_deadline`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PartialAudio {
+    /// Samples in the range [-1, 1] at the sample rate in `WAV_SPEC`
+    pub samples: Vec<f32>,
+    /// True if synthesis stopped before the end of the text
+    pub truncated: bool,
+}
+
+/// Loads the CMU dictionary from `data/cmudict-0.7b.txt` with the entries in
+/// `resources/custom_dict.txt` merged in if it exists. This is the dictionary `XdTts::new` uses.
+pub fn load_dictionary() -> anyhow::Result<CmuDictionary> {
+    let mut dict = CmuDictionary::open("data/cmudict-0.7b.txt")?;
+    if let Ok(custom) = CmuDictionary::open("resources/custom_dict.txt") {
+        dict.merge(custom);
+    }
+    Ok(dict)
+}
+
+impl XdTts<Tacotron2> {
+    /// Loads the tacotron2 model from a folder or archive. If no vocoder is given Griffin-Lim is
+    /// used with the parameters from the model's config.
+    pub fn new(
+        tacotron2: &Path,
+        phoneme_input: bool,
+        vocoder: Option<Box<dyn Vocoder>>,
+    ) -> anyhow::Result<Self> {
+        Self::new_with_providers(tacotron2, phoneme_input, vocoder, &[])
+    }
+
+    /// Like `new` but the model runs on the first of the execution providers that's available,
+    /// falling back to the CPU. See `Tacotron2::load_with_providers`.
+    pub fn new_with_providers(
+        tacotron2: &Path,
+        phoneme_input: bool,
+        vocoder: Option<Box<dyn Vocoder>>,
+        providers: &[ExecutionProvider],
+    ) -> anyhow::Result<Self> {
         let dict = if phoneme_input {
-            let mut dict = CmuDictionary::open("data/cmudict-0.7b.txt")?;
-            if let Ok(custom) = CmuDictionary::open("resources/custom_dict.txt") {
-                dict.merge(custom);
-            }
-            dict
+            load_dictionary()?
         } else {
             CmuDictionary::default()
         };
-        let model = Tacotron2::load(tacotron2)?;
-        let vocoder = create_griffin_lim()?;
-        Ok(Self {
+        let model = Tacotron2::load_with_providers(tacotron2, providers)?;
+        if phoneme_input {
+            let compat = model.check_dictionary(&dict);
+            if !compat.is_exact() {
+                warn!("Dictionary and model phones don't match: {}", compat);
+            }
+        }
+        let config = model.config();
+        anyhow::ensure!(
+            config.sampling_rate == WAV_SPEC.sample_rate && config.hop_length == HOP_LENGTH,
+            "Model is {}Hz with a hop length of {}, only {}Hz with a hop length of {} is supported",
+            config.sampling_rate,
+            config.hop_length,
+            WAV_SPEC.sample_rate,
+            HOP_LENGTH
+        );
+        let vocoder = match vocoder {
+            Some(vocoder) => vocoder,
+            None => Box::new(create_griffin_lim(config)?),
+        };
+        Ok(Self::from_parts(model, dict, phoneme_input, vocoder))
+    }
+
+    /// Sets how NaN/Inf values in the generated spectrogram are handled, by default they're an
+    /// error. See `NonFinitePolicy`.
+    pub fn with_non_finite_policy(mut self, policy: NonFinitePolicy) -> Self {
+        self.model = self.model.with_non_finite_policy(policy);
+        self
+    }
+
+    /// Retries chunks of the utterance that look like they failed to synthesise with a different
+    /// split, see `ChunkRetry` for the retry budget. This is off by default.
+    pub fn with_chunk_retry(mut self, retry: ChunkRetry) -> Self {
+        self.model = self.model.with_chunk_retry(retry);
+        self
+    }
+
+    /// Swaps phones for others before they're given to the model, see `PhoneSubstitution`. This
+    /// only affects phoneme input.
+    pub fn with_phone_substitution(mut self, substitution: PhoneSubstitution) -> Self {
+        self.model = self.model.with_phone_substitution(substitution);
+        self
+    }
+
+    /// Sets what happens to units the model has no ID for, by default they're dropped. See
+    /// `UnknownUnitPolicy`.
+    pub fn with_unknown_unit_policy(mut self, policy: UnknownUnitPolicy) -> Self {
+        self.model = self.model.with_unknown_unit_policy(policy);
+        self
+    }
+
+    /// Adds a short silence between the chunks of a long input that are synthesised separately,
+    /// see `Tacotron2::with_chunk_gap`. By default there's no gap.
+    pub fn with_chunk_gap(mut self, gap: Duration) -> Self {
+        self.model = self.model.with_chunk_gap(gap);
+        self
+    }
+
+    /// Sets how long inputs are split into chunks for the model, see
+    /// `Tacotron2::with_split_strategy`. By default chunks are filled as much as possible.
+    pub fn with_split_strategy(mut self, strategy: SplitStrategy) -> Self {
+        self.model = self.model.with_split_strategy(strategy);
+        self
+    }
+
+    /// Runs the frontend and splits the text into the unit sequences that would be ran through
+    /// the model, without running any inference. Each chunk fits in the models input window
+    /// (`tacotron2::INPUT_WINDOW`) so it can be passed to `Tacotron2::infer` as is, this lets you
+    /// schedule or batch the inference yourself.
+    ///
+    /// Chunks are split at breaks and pitch changes as well as the window boundaries, but the
+    /// breaks themselves aren't included so if you need the pauses look at the `NormalisedText`
+    /// instead. Chunks that are only punctuation are replaced by a pause so they aren't included
+    /// either.
+    ///
+    /// This doesn't take a dictionary, words are looked up in the one `XdTts` was created with
+    /// along with the fallback dictionaries, homographs and lexicon miss handler. Taking a
+    /// separate dictionary would give different units to the ones synthesis uses, to plan with
+    /// another dictionary create the `XdTts` with it (see `XdTts::from_model`).
+    pub fn plan_chunks(&self, text: &str) -> anyhow::Result<Vec<Vec<Unit>>> {
+        let strategy = self.model.split_strategy();
+        let mut text = self.text_to_units(text)?;
+        let mut chunks = vec![];
+        for step in SynthesisSteps::new(text.drain_all().map(Ok)) {
+            if let Step::Speech { units, .. } = step? {
+                chunks.extend(window_chunks_with(&units, strategy));
+            }
+        }
+        chunks.retain(|x| !x.is_empty());
+        Ok(chunks)
+    }
+}
+
+impl<M: AcousticModel> XdTts<M> {
+    /// Creates the pipeline around an already loaded acoustic model using the Griffin-Lim
+    /// vocoder. The dictionary is only used if `phoneme_input` is set.
+    pub fn from_model(model: M, dict: CmuDictionary, phoneme_input: bool) -> anyhow::Result<Self> {
+        let vocoder = create_griffin_lim(&Tacotron2Config::default())?;
+        Ok(Self::from_parts(
+            model,
+            dict,
+            phoneme_input,
+            Box::new(vocoder),
+        ))
+    }
+
+    fn from_parts(
+        model: M,
+        dict: CmuDictionary,
+        phoneme_input: bool,
+        vocoder: Box<dyn Vocoder>,
+    ) -> Self {
+        Self {
+            frontend: Box::new(EnglishFrontend::default()),
             dict,
             model,
             vocoder,
             phoneme_input,
-        })
+            breaths: None,
+            lexicon_miss: None,
+            homographs: HomographResolver::english(),
+            fallback_dictionaries: vec![],
+            spell_vowelless: true,
+            lowercase_characters: true,
+            max_duration: Some(DEFAULT_MAX_DURATION),
+            lead_silence: DEFAULT_EDGE_SILENCE,
+            tail_silence: DEFAULT_EDGE_SILENCE,
+            vocoder_threads: 1,
+            wav_spec: WAV_SPEC,
+        }
+    }
+
+    /// Replaces the vocoder, by default Griffin-Lim is used with the parameters for the model. See
+    /// `Vocoder` for what's expected of the vocoder output.
+    pub fn with_vocoder(mut self, vocoder: impl Vocoder + 'static) -> Self {
+        self.vocoder = Box::new(vocoder);
+        self
     }
 
+    /// Replaces the text frontend, by default the built-in English one is used. See `TextFrontend`
+    /// for what's expected of the frontend output.
+    pub fn with_frontend(mut self, frontend: impl TextFrontend + 'static) -> Self {
+        self.frontend = Box::new(frontend);
+        self
+    }
+
+    /// Sets a callback for words that aren't in the dictionary, this is only used for phoneme
+    /// input. The word is given in its normalised (uppercase) form and if the callback returns
+    /// `None` the word is skipped. The callback is called for every missing word in every request,
+    /// so if it's slow (i.e. it calls out to another service) it should cache the results itself.
+    pub fn with_lexicon_miss_handler(
+        mut self,
+        handler: impl Fn(&str) -> Option<Pronunciation> + Send + Sync + 'static,
+    ) -> Self {
+        self.lexicon_miss = Some(Box::new(handler));
+        self
+    }
+
+    /// Replaces the rules used to pick the pronunciation of homographs like "lead", by default
+    /// `HomographResolver::english` is used. To add your own rules on top of the built-in ones
+    /// merge them into `HomographResolver::english()`. Only used for phoneme input.
+    pub fn with_homographs(mut self, homographs: HomographResolver) -> Self {
+        self.homographs = homographs;
+        self
+    }
+
+    /// Adds a dictionary for another language which is checked when a word isn't in the main
+    /// dictionary, fallbacks are checked in the order they're added. Only used for phoneme input,
+    /// see `NormalisedText::words_to_pronunciation_with_dictionaries`.
+    pub fn with_fallback_dictionary(mut self, language: &str, dictionary: CmuDictionary) -> Self {
+        self.fallback_dictionaries.push(FallbackDictionary {
+            language: language.to_string(),
+            dictionary,
+        });
+        self
+    }
+
+    /// Sets whether words that aren't in the dictionary and have no vowels, like "TLS" or "HTTP",
+    /// are spelled out letter by letter (see `cmu_dict::spell_word`). This is on by default and
+    /// happens before the lexicon miss handler is called. Only used for phoneme input.
+    pub fn with_spell_vowelless_words(mut self, spell: bool) -> Self {
+        self.spell_vowelless = spell;
+        self
+    }
+
+    /// Inserts breaths into long sentences that don't have any sentence ending punctuation. This
+    /// is off by default.
+    pub fn with_breaths(mut self, breaths: BreathPauses) -> Self {
+        self.breaths = Some(breaths);
+        self
+    }
+
+    /// Sets whether text is lowercased before it's given to the model in character input mode, this
+    /// is on by default as the NVIDIA tacotron2 model was only trained on lowercase text. If your
+    /// model is case sensitive turning this off lets capitalised words be stressed. The built-in
+    /// frontend uppercases everything so you'll need a frontend that keeps the case (see
+    /// `with_frontend`). This does nothing with phoneme input.
+    pub fn with_lowercase_characters(mut self, lowercase: bool) -> Self {
+        self.lowercase_characters = lowercase;
+        self
+    }
+
+    /// Caps the total length of audio generated for one request, by default this is
+    /// `DEFAULT_MAX_DURATION` (10 minutes) and `None` disables it. This guards against bad input
+    /// producing minutes of audio on top of the per-chunk decoder step limit. Once the audio
+    /// reaches the cap no more chunks are synthesised and the audio is cut to the cap, a warning is
+    /// logged and `PartialAudio::truncated` is set.
+    pub fn with_max_duration(mut self, max_duration: Option<Duration>) -> Self {
+        self.max_duration = max_duration;
+        self
+    }
+
+    /// Sets the silence added before the start of each utterance, by default this is
+    /// `DEFAULT_EDGE_SILENCE`. Some players clip the very start of the audio and starting on
+    /// speech can sound abrupt. This is separate to breaks in the text and isn't counted towards
+    /// the maximum duration.
+    pub fn with_lead_silence(mut self, silence: Duration) -> Self {
+        self.lead_silence = silence;
+        self
+    }
+
+    /// Sets the silence added after the end of each utterance, by default this is
+    /// `DEFAULT_EDGE_SILENCE`. See `with_lead_silence`.
+    pub fn with_tail_silence(mut self, silence: Duration) -> Self {
+        self.tail_silence = silence;
+        self
+    }
+
+    /// Vocodes the chunks of an utterance (the text between breaks) in parallel on up to
+    /// `threads` threads, 1 (the default) vocodes each chunk straight after it's generated.
+    /// Griffin-Lim is run for 30 iterations per chunk so this is a big part of the time for long
+    /// texts with several chunks, it has no effect on text with only one chunk.
+    ///
+    /// When enabled the spectrograms are all generated first then vocoded together, so the
+    /// deadline in `generate_audio_with_deadline` only stops the spectrogram generation and the
+    /// vocoding can run past it. The maximum duration is checked using the number of frames in the
+    /// spectrograms instead of the audio, so it's approximate until the audio is cut at the end.
+    /// The vocoder is shared between the threads (`Vocoder::infer` only needs `&self`) so there's
+    /// no extra memory per thread beyond the audio being generated. Streaming always vocodes
+    /// sequentially.
+    pub fn with_vocoder_threads(mut self, threads: usize) -> Self {
+        self.vocoder_threads = threads.max(1);
+        self
+    }
+
+    /// Sets the sample format for WAV output, by default it's 16-bit integers. 24-bit integers or
+    /// 32-bit floats avoid the quantisation noise if the audio is going to be processed further.
+    /// Integer samples can be 8, 16, 24 or 32 bits and floats only 32 bits, any other combination
+    /// fails when the audio is written. The `WavWriter` given to `generate_audio` has to be
+    /// created with `wav_spec`, it's an error if it's in a different format.
+    pub fn with_wav_format(mut self, sample_format: SampleFormat, bits_per_sample: u16) -> Self {
+        self.wav_spec = WavSpec {
+            sample_format,
+            bits_per_sample,
+            ..WAV_SPEC
+        };
+        self
+    }
+
+    /// The spec for WAV files, this is `WAV_SPEC` with the sample format from `with_wav_format`.
+    pub fn wav_spec(&self) -> WavSpec {
+        self.wav_spec
+    }
+
+    /// Checks a writer was created with `wav_spec` before any audio is generated for it.
+    fn check_wav_spec(&self, spec: WavSpec) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            spec == self.wav_spec,
+            "WAV writer is {:?} but the TTS is set up for {:?}, create it with `wav_spec`",
+            spec,
+            self.wav_spec
+        );
+        Ok(())
+    }
+
+    /// Runs the full pipeline returning the audio samples in the range [-1, 1] at the sample rate
+    /// in `WAV_SPEC`. Breaks are included as zeroed samples so the buffer can be played, streamed
+    /// or resampled as is, this is the same audio `generate_audio` writes to the WAV file. If
+    /// `output_spectrogram` is given the mel spectrogram for the whole input is also saved there
+    /// as a numpy file, see `--output-spectrogram` in the app.
+    pub fn synthesise(
+        &self,
+        text: &str,
+        output_spectrogram: Option<PathBuf>,
+    ) -> anyhow::Result<Vec<f32>> {
+        let audio = self.run_pipeline(text, output_spectrogram, None)?;
+        Ok(audio.samples)
+    }
+
+    /// Runs the full pipeline and writes the audio to a WAV file. The writer has to be created
+    /// with `wav_spec`, see `with_wav_format`. See `synthesise` if you want the raw audio instead.
     pub fn generate_audio<W>(
         &self,
         text: &str,
@@ -66,115 +536,749 @@ impl XdTts {
     where
         W: Write + Seek,
     {
-        let start = Instant::now();
-        info!("Text normalisation");
-        let mut text = text_normaliser::normalise(text)?;
+        self.check_wav_spec(wav_writer.spec())?;
+        let audio = self.synthesise(text, output_spectrogram)?;
+        write_wav(&audio, wav_writer)
+    }
+
+    /// Skips the text frontend and synthesises the units as is, going straight to the acoustic
+    /// model and vocoder. This is for when you've already got phones from somewhere else like
+    /// another G2P system. The caller is responsible for the units being valid for the model:
+    /// phones when it was loaded with `phoneme_input` otherwise characters, units the model has no
+    /// ID for are dropped. Breaks can't be represented as units so the whole sequence is one
+    /// inference. The only change made to the units is collapsing repeated spaces.
+    pub fn generate_audio_from_units<W>(
+        &self,
+        units: &[Unit],
+        wav_writer: &mut WavWriter<W>,
+    ) -> anyhow::Result<()>
+    where
+        W: Write + Seek,
+    {
+        self.check_wav_spec(wav_writer.spec())?;
+        let mut utterance = Utterance::default();
+        let chunk = NormaliserChunk::Pronunciation(units.to_vec());
+        for step in SynthesisSteps::new(iter::once(Ok(chunk))) {
+            match step? {
+                Step::Speech { units, pitch } => {
+                    self.infer(&units, pitch, &mut utterance, false, None)?;
+                }
+                Step::Pause(duration) => utterance.push_silence(duration),
+            }
+        }
+        let (mut audio, _) = self.vocode_utterance(utterance)?;
+        self.pad_utterance(&mut audio);
+        write_wav(&audio, wav_writer)
+    }
+
+    /// The same as `synthesise`.
+    #[deprecated(note = "Use `XdTts::synthesise`")]
+    pub fn generate_samples(
+        &self,
+        text: &str,
+        output_spectrogram: Option<PathBuf>,
+    ) -> anyhow::Result<Vec<f32>> {
+        self.synthesise(text, output_spectrogram)
+    }
+
+    /// Like `synthesise` but stops once the deadline has passed returning the audio
+    /// generated so far. The deadline is checked before each chunk (the text between two breaks)
+    /// is synthesised and between the windows a long chunk is split into for the model (see
+    /// `AcousticModel::infer_until`), so a window that's running when the deadline passes will
+    /// still be finished and the call can overrun the deadline by up to one window's synthesis
+    /// time. It is never cut mid-window, so you won't get half a word.
+    pub fn generate_audio_with_deadline(
+        &self,
+        text: &str,
+        deadline: Instant,
+    ) -> anyhow::Result<PartialAudio> {
+        self.run_pipeline(text, None, Some(deadline))
+    }
+
+    /// Runs the full pipeline calling `on_audio` with the audio as it's generated instead of
+    /// returning it all at the end. This means playback can start before the whole text has been
+    /// synthesised, see `streaming` for the latency and quality tradeoffs. The samples are the
+    /// same format as `synthesise`. Pitch changes from SSML prosody tags aren't applied when
+    /// streaming. Once the audio reaches the maximum duration (see `with_max_duration`) synthesis
+    /// stops and the tail silence is passed on like the audio was finished.
+    pub fn generate_streaming(
+        &self,
+        text: &str,
+        options: StreamingOptions,
+        on_audio: impl FnMut(&[f32]) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let mut text = self.text_to_units(text)?;
+        self.stream_units(text.drain_all().map(Ok), options, on_audio)
+    }
+
+    /// Like `generate_streaming` but takes chunks that have already been through a text frontend,
+    /// pulling them from the iterator as they're needed. Combined with
+    /// `text_normaliser::SsmlChunks` a large SSML document can be synthesised without normalising
+    /// it all up front.
+    ///
+    /// The chunks are read up to the next break, converted into units and synthesised before the
+    /// next ones are read, so only the text between two breaks is held at once. The audio is in
+    /// the same order as the chunks. Breaths are inserted between breaks so a `<phrase>` with a
+    /// break in it may get a breath after the break.
+    pub fn generate_streaming_chunks(
+        &self,
+        chunks: impl IntoIterator<Item = anyhow::Result<NormaliserChunk>>,
+        options: StreamingOptions,
+        on_audio: impl FnMut(&[f32]) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let mut chunks = chunks.into_iter();
+        let mut converted = VecDeque::new();
+        let units = iter::from_fn(|| loop {
+            if let Some(chunk) = converted.pop_front() {
+                return Some(Ok(chunk));
+            }
+            let mut span = vec![];
+            for chunk in chunks.by_ref() {
+                match chunk {
+                    Ok(chunk) => {
+                        let is_break = matches!(chunk, NormaliserChunk::Break(_));
+                        span.push(chunk);
+                        if is_break {
+                            break;
+                        }
+                    }
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+            if span.is_empty() {
+                return None;
+            }
+            let mut text = NormalisedText::from(span);
+            if let Some(breaths) = &self.breaths {
+                text.insert_breaths(breaths);
+            }
+            self.convert_to_units(&mut text, |_| {});
+            converted.extend(text.drain_all());
+        });
+        self.stream_units(units, options, on_audio)
+    }
+
+    /// Streams the audio for chunks that have been converted into units, see `generate_streaming`.
+    fn stream_units(
+        &self,
+        chunks: impl Iterator<Item = anyhow::Result<NormaliserChunk>>,
+        options: StreamingOptions,
+        mut on_audio: impl FnMut(&[f32]) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let max_samples = self
+            .max_duration
+            .map(|x| silence_samples(x, WAV_SPEC.sample_rate) as usize);
+        let mut generated = 0;
+        let mut warned_pitch = false;
+        // The lead silence is held back until there's some audio so an empty utterance stays empty
+        let started = Cell::new(false);
+        let capped = Cell::new(false);
+        let mut on_speech = |audio: &[f32]| {
+            // Like `run_pipeline` the lead and tail silence don't count towards the maximum
+            let remaining = max_samples.map_or(audio.len(), |x| x.saturating_sub(generated));
+            let cap = audio.len() > remaining;
+            let audio = &audio[..audio.len().min(remaining)];
+            generated += audio.len();
+            if !started.get() && !audio.is_empty() {
+                started.set(true);
+                let mut lead = vec![];
+                append_silence(self.lead_silence, &mut lead);
+                on_audio(&lead)?;
+            }
+            if !(cap && audio.is_empty()) {
+                on_audio(audio)?;
+            }
+            if cap {
+                // Stops the model, this isn't passed on to the caller
+                capped.set(true);
+                anyhow::bail!("Reached the maximum duration");
+            }
+            Ok(())
+        };
+        let res = SynthesisSteps::new(chunks).try_for_each(|step| match step? {
+            Step::Speech { units, pitch } => {
+                if pitch != 0 && !warned_pitch {
+                    warn!("Pitch changes aren't supported when streaming");
+                    warned_pitch = true;
+                }
+                self.stream_chunk(&units, options, &mut on_speech)
+            }
+            Step::Pause(duration) => {
+                let mut silence = vec![];
+                append_silence(duration, &mut silence);
+                on_speech(&silence)
+            }
+        });
+        match res {
+            Err(_) if capped.get() => warn!(
+                "Audio reached the maximum duration of {:?}, stopping synthesis",
+                self.max_duration.unwrap_or_default()
+            ),
+            res => res?,
+        }
+        if started.get() {
+            let mut tail = vec![];
+            append_silence(self.tail_silence, &mut tail);
+            on_audio(&tail)?;
+        }
+        Ok(())
+    }
+
+    fn stream_chunk(
+        &self,
+        units: &[Unit],
+        options: StreamingOptions,
+        on_audio: &mut impl FnMut(&[f32]) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let mut vocoder = StreamingVocoder::new(
+            self.vocoder.as_ref(),
+            options.vocoder_context,
+            self.model.hop_length(),
+        );
+        self.model
+            .infer_streaming(units, options.block_frames, &mut |mel| {
+                let audio = vocoder.push(&mel)?;
+                if audio.is_empty() {
+                    Ok(())
+                } else {
+                    on_audio(&audio)
+                }
+            })?;
+        on_audio(&vocoder.finish())
+    }
+
+    /// Runs the frontend and converts all the text into units for the model.
+    fn text_to_units(&self, text: &str) -> anyhow::Result<NormalisedText> {
+        let mut text = self.normalise(text)?;
+        self.convert_to_units(&mut text, |_| {});
+        Ok(text)
+    }
+
+    /// Runs the frontend, this is the normalised text before it's turned into units.
+    fn normalise(&self, text: &str) -> anyhow::Result<NormalisedText> {
+        let mut text = self.frontend.normalise(text)?;
+        if let Some(breaths) = &self.breaths {
+            text.insert_breaths(breaths);
+        }
+        Ok(text)
+    }
+
+    /// Converts the normalised text into units for the model, `on_miss` is called with any words
+    /// that aren't in the dictionaries.
+    fn convert_to_units(&self, text: &mut NormalisedText, mut on_miss: impl FnMut(&str)) {
         if self.phoneme_input {
             // Sad tacotron2 was trained with ARPA support
-            text.words_to_pronunciation(&self.dict);
+            text.words_to_pronunciation_with_dictionaries(
+                &self.dict,
+                &self.fallback_dictionaries,
+                &self.homographs,
+                |word| {
+                    on_miss(word);
+                    if self.spell_vowelless && is_vowelless(word) {
+                        spell_word(word)
+                    } else {
+                        self.lexicon_miss.as_ref().and_then(|on_miss| on_miss(word))
+                    }
+                },
+            );
         } else {
-            text.convert_to_units();
+            text.convert_to_units_with_case(self.lowercase_characters);
         }
-        let mut inference_chunk = vec![];
+    }
+
+    fn run_pipeline(
+        &self,
+        text: &str,
+        output_spectrogram: Option<PathBuf>,
+        deadline: Option<Instant>,
+    ) -> anyhow::Result<PartialAudio> {
+        let max_samples = self
+            .max_duration
+            .map(|x| silence_samples(x, WAV_SPEC.sample_rate) as usize);
+        let over_limit = |audio: &Utterance| max_samples.is_some_and(|x| audio.len >= x);
+        let past_deadline = || deadline.is_some_and(|x| Instant::now() >= x);
+        let start = Instant::now();
+        info!("Text normalisation");
+        let mut text = self.text_to_units(text)?;
+        let mut utterance = Utterance::default();
+        let mut truncated = false;
 
         let text_end = Instant::now();
         info!("Text processing time: {:?}", text_end - start);
         info!("Generating audio");
-        for chunk in text.drain_all() {
-            debug!("Chunk: {:?}", chunk);
-            match chunk {
-                NormaliserChunk::Pronunciation(mut units) => inference_chunk.append(&mut units),
-                NormaliserChunk::Break(duration) => {
-                    // Infer here.
-                    // Potentially we could use the alignments in the network output and return them
-                    // with the spectrogram to insert this stuff. That might be better - it depends if
-                    // coarticulation sounds more or less natural when a giant pause is inserted.
-                    self.infer(&inference_chunk, wav_writer, output_spectrogram.as_ref())?;
-                    write_silence(duration, wav_writer)?;
-                    inference_chunk.clear();
-                }
-                NormaliserChunk::Text(t) => {
-                    unreachable!("'{}' Should have been converted to pronunciation", t)
-                }
-                NormaliserChunk::Punct(p) => {
-                    inference_chunk.push(Unit::Punct(p));
+        for step in SynthesisSteps::new(text.drain_all().map(Ok)) {
+            if past_deadline() || over_limit(&utterance) {
+                truncated = true;
+                break;
+            }
+            match step? {
+                // Potentially we could use the alignments in the network output and return them
+                // with the spectrogram to insert the pauses. That might be better - it depends if
+                // coarticulation sounds more or less natural when a giant pause is inserted.
+                Step::Speech { units, pitch } => {
+                    let keep_spectrogram = output_spectrogram.is_some();
+                    if self.infer(&units, pitch, &mut utterance, keep_spectrogram, deadline)? {
+                        truncated = true;
+                        break;
+                    }
                 }
+                Step::Pause(duration) => utterance.push_silence(duration),
             }
         }
-        self.infer(&inference_chunk, wav_writer, output_spectrogram.as_ref())?;
+        let spectrograms = std::mem::take(&mut utterance.spectrograms);
+        let (mut audio, starts) = self.vocode_utterance(utterance)?;
         let end = Instant::now();
+        if let Some(max_samples) = max_samples.filter(|x| audio.len() >= *x) {
+            if audio.len() > max_samples || truncated {
+                warn!(
+                    "Audio reached the maximum duration of {:?}, returning the audio generated so far",
+                    self.max_duration.unwrap_or_default()
+                );
+                audio.truncate(max_samples);
+                truncated = true;
+            }
+        } else if truncated {
+            warn!("Deadline passed, returning the audio generated so far");
+        }
+        let unpadded = audio.len();
+        self.pad_utterance(&mut audio);
+        if let Some(path) = output_spectrogram.filter(|_| !spectrograms.is_empty()) {
+            let lead = if audio.len() > unpadded {
+                silence_samples(self.lead_silence, WAV_SPEC.sample_rate) as usize
+            } else {
+                0
+            };
+            let saved = spectrogram_timeline(&spectrograms, &starts, lead, audio.len())
+                .and_then(|x| save_spectrogram(&path, &x));
+            if let Err(e) = saved {
+                error!("Failed to write spectrogram to '{}': {}", path.display(), e);
+            }
+        }
         info!("Finished processing in: {:?}", end - start);
-        Ok(())
+        Ok(PartialAudio {
+            samples: audio,
+            truncated,
+        })
+    }
+
+    /// Adds the lead and tail silence around the audio for a whole utterance, if there's no audio
+    /// it's left empty.
+    fn pad_utterance(&self, audio: &mut Vec<f32>) {
+        if audio.is_empty() {
+            return;
+        }
+        let mut padded = vec![];
+        append_silence(self.lead_silence, &mut padded);
+        padded.append(audio);
+        append_silence(self.tail_silence, &mut padded);
+        *audio = padded;
     }
 
-    fn infer<W>(
+    /// Generates the audio for the units of a `Step::Speech` and appends it to `output`. If
+    /// `pitch` isn't 0 the audio is pitch shifted by that many cents, this is done after vocoding
+    /// so it's only an approximation and may introduce artifacts (see `td_psola`). If vocoding in
+    /// parallel the spectrogram is added to `output` to be vocoded later by `vocode_utterance`. If
+    /// `keep_spectrogram` is set the spectrogram is also kept to be saved, see
+    /// `Utterance::spectrograms`. Returns true if the model stopped early because the deadline
+    /// passed, the audio for what it did generate is still added.
+    fn infer(
         &self,
         input: &[Unit],
-        wav_writer: &mut WavWriter<W>,
-        output_spectrogram: Option<&PathBuf>,
-    ) -> anyhow::Result<()>
-    where
-        W: Write + Seek,
-    {
-        if input.is_empty() {
-            return Ok(());
-        }
+        pitch: i32,
+        output: &mut Utterance,
+        keep_spectrogram: bool,
+        deadline: Option<Instant>,
+    ) -> anyhow::Result<bool> {
         let mel_gen_start = Instant::now();
-        let spectrogram = self.model.infer(input)?;
+        let (spectrogram, stopped) = self.model.infer_until(input, deadline)?;
 
-        if let Some(output_spectrogram) = output_spectrogram {
-            // use wav_writer.duration() to add start_sample
-            let output_spectrogram = if wav_writer.duration() > 0 {
-                todo!()
-            } else {
-                output_spectrogram.clone()
-            };
-            if let Err(e) = ndarray_npy::write_npy(&output_spectrogram, &spectrogram) {
-                error!(
-                    "Failed to write spectrogram to '{}': {}",
-                    output_spectrogram.display(),
-                    e
-                );
-            }
+        if keep_spectrogram {
+            output.keep_spectrogram(&spectrogram);
         }
         let vocoder_start = Instant::now();
-        let audio = self.vocoder.infer(&spectrogram)?;
+        info!("Mel gen time: {:?}", vocoder_start - mel_gen_start);
+        if self.vocoder_threads > 1 {
+            output.len += spectrogram.ncols() * HOP_LENGTH;
+            output
+                .segments
+                .push(Segment::Spectrogram(spectrogram, pitch));
+            return Ok(stopped);
+        }
 
+        let audio = self.vocode(&spectrogram, pitch)?;
         let end = Instant::now();
-
         let audio_length = audio.len() as f32 / 22050.0;
-        info!("Mel gen time: {:?}", vocoder_start - mel_gen_start);
-        info!("Vocoder time: {:?}", end - vocoder_start);
         info!(
             "Real time factor: {}",
             (end - mel_gen_start).as_secs_f32() / audio_length
         );
+        output.len += audio.len();
+        output.segments.push(Segment::Audio(audio));
+        Ok(stopped)
+    }
 
-        let mut i16_writer = wav_writer.get_i16_writer(audio.len() as u32);
-        for sample in &audio {
-            i16_writer.write_sample((*sample * i16::MAX as f32) as i16);
+    /// Turns a spectrogram into audio applying the pitch shift, see `infer`.
+    fn vocode(&self, spectrogram: &Array2<f32>, pitch: i32) -> anyhow::Result<Vec<f32>> {
+        let vocoder_start = Instant::now();
+        let audio = self.vocoder.infer(spectrogram)?;
+        info!("Vocoder time: {:?}", vocoder_start.elapsed());
+
+        if pitch != 0 {
+            let factor = 2.0f32.powf(pitch as f32 / 1200.0);
+            Ok(td_psola::pitch_shift(&audio, WAV_SPEC.sample_rate, factor))
+        } else {
+            Ok(audio)
         }
-        i16_writer.flush()?;
-        Ok(())
     }
+
+    /// Vocodes any spectrograms left in the utterance and joins it into one block of audio. The
+    /// spectrograms are shared out between `vocoder_threads` threads, each taking the next
+    /// spectrogram when it's done with the last so a long chunk doesn't hold the others up. The
+    /// sample each segment of the utterance starts at is returned with the audio.
+    fn vocode_utterance(&self, utterance: Utterance) -> anyhow::Result<(Vec<f32>, Vec<usize>)> {
+        let spectrograms = utterance
+            .segments
+            .iter()
+            .filter_map(|x| match x {
+                Segment::Spectrogram(spectrogram, pitch) => Some((spectrogram, *pitch)),
+                Segment::Audio(_) => None,
+            })
+            .collect::<Vec<_>>();
+        let mut vocoded = Vec::with_capacity(spectrograms.len());
+        if !spectrograms.is_empty() {
+            let start = Instant::now();
+            let next = AtomicUsize::new(0);
+            let threads = self.vocoder_threads.min(spectrograms.len());
+            vocoded = thread::scope(|s| {
+                let workers = (0..threads)
+                    .map(|_| {
+                        s.spawn(|| {
+                            let mut done = vec![];
+                            loop {
+                                let i = next.fetch_add(1, Ordering::Relaxed);
+                                match spectrograms.get(i) {
+                                    Some((spectrogram, pitch)) => {
+                                        done.push((i, self.vocode(spectrogram, *pitch)))
+                                    }
+                                    None => break done,
+                                }
+                            }
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                // Every thread is joined so a panic in one doesn't escape the scope
+                let joined = workers.into_iter().map(|x| x.join()).collect::<Vec<_>>();
+                let mut vocoded = vec![];
+                for done in joined {
+                    match done {
+                        Ok(mut done) => vocoded.append(&mut done),
+                        Err(_) => anyhow::bail!("Vocoder thread panicked"),
+                    }
+                }
+                Ok(vocoded)
+            })?;
+            vocoded.sort_by_key(|(i, _)| *i);
+            info!(
+                "Vocoded {} chunks on {} threads in {:?}",
+                spectrograms.len(),
+                threads,
+                start.elapsed()
+            );
+        }
+
+        let mut vocoded = vocoded.into_iter().map(|(_, audio)| audio);
+        let mut audio = Vec::with_capacity(utterance.len);
+        let mut starts = Vec::with_capacity(utterance.segments.len());
+        for segment in utterance.segments {
+            starts.push(audio.len());
+            match segment {
+                Segment::Audio(mut samples) => audio.append(&mut samples),
+                Segment::Spectrogram(..) => {
+                    let mut samples = vocoded.next().expect("Every spectrogram is vocoded")?;
+                    audio.append(&mut samples);
+                }
+            }
+        }
+        Ok((audio, starts))
+    }
+}
+
+/// What the pipeline does with the next part of the text, see `SynthesisSteps`.
+#[derive(Clone, Debug, PartialEq)]
+enum Step {
+    /// Units to run through the model and vocoder, with the pitch shift in cents to apply to the
+    /// audio. Repeated spaces have been collapsed and there's always something to say.
+    Speech { units: Vec<Unit>, pitch: i32 },
+    /// Silence from a break or in place of a chunk that's only punctuation
+    Pause(Duration),
+}
+
+/// Groups the chunks of converted text into the steps of synthesis. Units are collected up to the
+/// next break or pitch change and then given to the model in one go. Everything that runs the
+/// model walks the text with this so they all split it the same way.
+struct SynthesisSteps<I> {
+    chunks: I,
+    units: Vec<Unit>,
+    pitch: i32,
+    ready: VecDeque<Step>,
+    finished: bool,
 }
 
-fn write_silence<W>(duration: Duration, wav_writer: &mut WavWriter<W>) -> anyhow::Result<()>
+impl<I> SynthesisSteps<I>
 where
-    W: Write + Seek,
+    I: Iterator<Item = anyhow::Result<NormaliserChunk>>,
 {
-    let n_samples = (wav_writer.spec().sample_rate as f32 * duration.as_secs_f32()).round() as u32;
+    fn new(chunks: impl IntoIterator<IntoIter = I>) -> Self {
+        Self {
+            chunks: chunks.into_iter(),
+            units: vec![],
+            pitch: 0,
+            ready: VecDeque::new(),
+            finished: false,
+        }
+    }
 
-    if n_samples > 0 {
-        let mut i16_writer = wav_writer.get_i16_writer(n_samples);
-        for _ in 0..n_samples {
-            i16_writer.write_sample(0);
+    /// Ends the current chunk of units. Repeated spaces are collapsed (see
+    /// `phonemes::collapse_spaces`) and if there's nothing but punctuation left it becomes a pause.
+    fn end_chunk(&mut self) {
+        collapse_spaces(&mut self.units);
+        if self.units.is_empty() {
+            return;
+        }
+        let units = std::mem::take(&mut self.units);
+        if is_speakable(&units) {
+            self.ready.push_back(Step::Speech {
+                units,
+                pitch: self.pitch,
+            });
+        } else {
+            // Nothing to say, so don't give the model a chance to make noise
+            debug!("Replacing punctuation only chunk with silence: {:?}", units);
+            self.ready.push_back(Step::Pause(punctuation_pause(&units)));
         }
-        i16_writer.flush()?;
+    }
+}
+
+impl<I> Iterator for SynthesisSteps<I>
+where
+    I: Iterator<Item = anyhow::Result<NormaliserChunk>>,
+{
+    type Item = anyhow::Result<Step>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(step) = self.ready.pop_front() {
+                return Some(Ok(step));
+            } else if self.finished {
+                return None;
+            }
+            let chunk = match self.chunks.next() {
+                Some(Ok(chunk)) => chunk,
+                Some(Err(e)) => {
+                    self.finished = true;
+                    return Some(Err(e));
+                }
+                None => {
+                    self.finished = true;
+                    self.end_chunk();
+                    continue;
+                }
+            };
+            debug!("Chunk: {:?}", chunk);
+            match chunk {
+                NormaliserChunk::Pronunciation(mut units) => self.units.append(&mut units),
+                NormaliserChunk::Punct(p) => self.units.push(Unit::Punct(p)),
+                NormaliserChunk::Break(duration) => {
+                    self.end_chunk();
+                    self.ready.push_back(Step::Pause(duration));
+                }
+                // The pitch shift is applied to the whole chunk so it's split here
+                NormaliserChunk::Pitch(cents) => {
+                    if cents != self.pitch {
+                        self.end_chunk();
+                        self.pitch = cents;
+                    }
+                }
+                NormaliserChunk::Text(t) => {
+                    unreachable!("'{}' Should have been converted to pronunciation", t)
+                }
+                // There's only one voice so language changes are ignored
+                NormaliserChunk::Language(_) => {}
+                // Breaths have already been inserted
+                NormaliserChunk::NoBreaks(_) => {}
+            }
+        }
+    }
+}
+
+/// The audio for an utterance as it's being generated. When vocoding in parallel (see
+/// `XdTts::with_vocoder_threads`) the spectrograms are kept here to be vocoded all at once at the
+/// end, otherwise it's only audio.
+#[derive(Default)]
+struct Utterance {
+    segments: Vec<Segment>,
+    /// Number of samples in the utterance, for spectrograms that haven't been vocoded yet this is
+    /// estimated from the number of frames.
+    len: usize,
+    /// The spectrogram of each chunk when they're being saved for debugging, with the index of the
+    /// segment that has the chunk's audio. See `spectrogram_timeline`.
+    spectrograms: Vec<(usize, Array2<f32>)>,
+}
+
+enum Segment {
+    Audio(Vec<f32>),
+    /// A spectrogram waiting to be vocoded and the pitch shift to apply afterwards
+    Spectrogram(Array2<f32>, i32),
+}
+
+impl Utterance {
+    fn push_silence(&mut self, duration: Duration) {
+        let mut silence = vec![];
+        append_silence(duration, &mut silence);
+        self.len += silence.len();
+        self.segments.push(Segment::Audio(silence));
+    }
+
+    /// Keeps the spectrogram of the next chunk to be saved, see `Utterance::spectrograms`. This has
+    /// to be called before the chunk's audio is added.
+    fn keep_spectrogram(&mut self, spectrogram: &Array2<f32>) {
+        self.spectrograms
+            .push((self.segments.len(), spectrogram.clone()));
+    }
+}
+
+/// Lays the spectrograms of the chunks out along the audio of the whole utterance, this is what
+/// `--output-spectrogram` saves. `starts` is the sample each segment of the utterance starts at
+/// and `lead` is the silence added before them, see `XdTts::vocode_utterance`. Each chunk starts
+/// at the frame nearest the sample its audio starts at, so it's within half a frame of the audio.
+/// The gaps for breaks and pauses, and for the few extra samples the vocoder adds at the end of
+/// each chunk, are filled with silence. The result is padded or cut to `samples` worth of frames
+/// so it covers the same time as the audio.
+fn spectrogram_timeline(
+    chunks: &[(usize, Array2<f32>)],
+    starts: &[usize],
+    lead: usize,
+    samples: usize,
+) -> anyhow::Result<Array2<f32>> {
+    let frame = |sample: usize| (sample as f64 / HOP_LENGTH as f64).round() as usize;
+    let mels = chunks.first().map(|(_, x)| x.nrows()).unwrap_or_default();
+    let mut timeline = Array2::zeros((mels, 0));
+    for (segment, spectrogram) in chunks {
+        let offset = frame(lead + starts[*segment]);
+        let silence = Array2::from_elem(
+            (mels, offset.saturating_sub(timeline.ncols())),
+            LOG_MEL_FLOOR,
+        );
+        timeline = concatenate(
+            Axis(1),
+            &[timeline.view(), silence.view(), spectrogram.view()],
+        )?;
+    }
+    let frames = frame(samples);
+    if timeline.ncols() < frames {
+        let silence = Array2::from_elem((mels, frames - timeline.ncols()), LOG_MEL_FLOOR);
+        timeline = concatenate(Axis(1), &[timeline.view(), silence.view()])?;
+    }
+    Ok(timeline.slice_move(ndarray::s![.., ..frames]))
+}
+
+/// Writes the samples in the writer's sample format. The sample rate and channels have to match
+/// `WAV_SPEC` as that's the audio the TTS generates.
+pub(crate) fn write_wav<W>(audio: &[f32], wav_writer: &mut WavWriter<W>) -> anyhow::Result<()>
+where
+    W: Write + Seek,
+{
+    let spec = wav_writer.spec();
+    anyhow::ensure!(
+        spec.channels == WAV_SPEC.channels && spec.sample_rate == WAV_SPEC.sample_rate,
+        "WAV output must be {} channel at {}Hz, got {} channels at {}Hz",
+        WAV_SPEC.channels,
+        WAV_SPEC.sample_rate,
+        spec.channels,
+        spec.sample_rate
+    );
+    match (spec.sample_format, spec.bits_per_sample) {
+        (SampleFormat::Int, 16) => {
+            let mut i16_writer = wav_writer.get_i16_writer(audio.len() as u32);
+            for sample in audio {
+                i16_writer.write_sample(pcm_sample(*sample, 16) as i16);
+            }
+            i16_writer.flush()?;
+        }
+        (SampleFormat::Int, bits @ (8 | 24 | 32)) => {
+            for sample in audio {
+                wav_writer.write_sample(pcm_sample(*sample, bits))?;
+            }
+        }
+        (SampleFormat::Float, 32) => {
+            for sample in audio {
+                wav_writer.write_sample(clip_sample(*sample))?;
+            }
+        }
+        (format, bits) => anyhow::bail!("Unsupported WAV sample format: {} bit {:?}", bits, format),
     }
     Ok(())
 }
 
+/// Clips a sample to the range [-1, 1]. Griffin-Lim with a power above 1 can overshoot on loud
+/// parts, anything outside the range would be a loud click once converted to integers (or played
+/// from a float file) so it's clipped to full scale. Every format the audio is written in does
+/// this, the samples returned from `XdTts` are left as they are.
+pub(crate) fn clip_sample(sample: f32) -> f32 {
+    sample.clamp(-1.0, 1.0)
+}
+
+/// Converts a sample in the range [-1, 1] to an integer sample with the given number of bits,
+/// anything outside the range is clipped with `clip_sample`.
+pub(crate) fn pcm_sample(sample: f32, bits: u16) -> i32 {
+    let max = ((1i64 << (bits - 1)) - 1) as f64;
+    (clip_sample(sample) as f64 * max) as i32
+}
+
+fn append_silence(duration: Duration, output: &mut Vec<f32>) {
+    let n_samples = silence_samples(duration, WAV_SPEC.sample_rate) as usize;
+    output.resize(output.len() + n_samples, 0.0);
+}
+
+/// Number of samples of silence used for a break of the given duration, rounded to the nearest
+/// sample. Each break is rounded on it's own so there's at most half a sample of error per break.
+pub fn silence_samples(duration: Duration, sample_rate: u32) -> u32 {
+    (sample_rate as f64 * duration.as_secs_f64()).round() as u32
+}
+
+/// Number of spectrogram frames matching a break of the given duration for a model with the given
+/// sample rate and hop length. This is `silence_samples` divided by the hop length rounded to the
+/// nearest frame, so a spectrogram with breaks inserted lines up with the audio to within half a
+/// frame per break (see `Tacotron2Config`).
+pub fn silence_frames(duration: Duration, sample_rate: u32, hop_length: usize) -> usize {
+    let samples = silence_samples(duration, sample_rate) as f64;
+    (samples / hop_length as f64).round() as usize
+}
+
+/// Saves a spectrogram for debugging. With the `png-export` feature a `.png` path writes an image
+/// (see `visualise`), anything else is written as a numpy `.npy` file.
+fn save_spectrogram(path: &Path, spectrogram: &Array2<f32>) -> anyhow::Result<()> {
+    #[cfg(feature = "png-export")]
+    if path
+        .extension()
+        .is_some_and(|x| x.eq_ignore_ascii_case("png"))
+    {
+        return visualise::write_png(path, spectrogram, visualise::MEL_ROW_HEIGHT);
+    }
+    #[cfg(not(feature = "png-export"))]
+    if path
+        .extension()
+        .is_some_and(|x| x.eq_ignore_ascii_case("png"))
+    {
+        warn!(
+            "Built without `png-export` so '{}' will be a numpy file",
+            path.display()
+        );
+    }
+    ndarray_npy::write_npy(path, spectrogram)?;
+    Ok(())
+}
+
 /// Convenience function to setup logging for any binaries I create. Automatically sets all
 /// binaries and the tts library crate to `info` logging by default.
 pub fn setup_logging() {
@@ -189,3 +1293,788 @@ pub fn setup_logging() {
 
     tracing::subscriber::set_global_default(subscriber).unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::ops::Range;
+    use std::str::FromStr;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    /// Stands in for tacotron2 so the pipeline can be tested without the model files. It outputs a
+    /// quiet spectrogram with a fixed number of frames per unit and records the size of every input
+    /// it's given.
+    #[derive(Default)]
+    struct StubModel {
+        calls: Mutex<Vec<usize>>,
+        inputs: Mutex<Vec<Vec<Unit>>>,
+    }
+
+    const STUB_FRAMES_PER_UNIT: usize = 5;
+
+    impl AcousticModel for StubModel {
+        fn infer(&self, units: &[Unit]) -> anyhow::Result<Array2<f32>> {
+            self.calls.lock().unwrap().push(units.len());
+            self.inputs.lock().unwrap().push(units.to_vec());
+            Ok(Array2::from_elem(
+                (80, units.len() * STUB_FRAMES_PER_UNIT),
+                -5.0,
+            ))
+        }
+
+        fn infer_streaming(
+            &self,
+            units: &[Unit],
+            block_frames: usize,
+            on_mel: &mut dyn FnMut(Array2<f32>) -> anyhow::Result<()>,
+        ) -> anyhow::Result<()> {
+            let mel = self.infer(units)?;
+            for block in mel.axis_chunks_iter(ndarray::Axis(1), block_frames) {
+                on_mel(block.to_owned())?;
+            }
+            Ok(())
+        }
+    }
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn tts_is_send_sync() {
+        assert_send_sync::<XdTts>();
+    }
+
+    #[test]
+    fn end_to_end_stub_model() {
+        let tts = XdTts::from_model(StubModel::default(), CmuDictionary::default(), false).unwrap();
+        let text = r#"<speak>Hello there, 2 worlds.<break time="500ms"/>Goodbye!</speak>"#;
+
+        let mut output = Cursor::new(vec![]);
+        let mut writer = WavWriter::new(&mut output, WAV_SPEC).unwrap();
+        tts.generate_audio(text, &mut writer, None).unwrap();
+        let written = writer.duration() as usize;
+        writer.finalize().unwrap();
+
+        // The break splits the text into two inferences and neither is empty
+        let calls = tts.model.calls.lock().unwrap().clone();
+        assert_eq!(calls.len(), 2);
+        assert!(calls.iter().all(|x| *x > 0));
+
+        let vocoder = create_griffin_lim(&Tacotron2Config::default()).unwrap();
+        let vocoded_len = |units: usize| {
+            let mel = Array2::from_elem((80, units * STUB_FRAMES_PER_UNIT), -5.0);
+            vocoder.infer(&mel).unwrap().len()
+        };
+        let silence = WAV_SPEC.sample_rate as usize / 2;
+        let edges = 2 * silence_samples(DEFAULT_EDGE_SILENCE, WAV_SPEC.sample_rate) as usize;
+        let expected = vocoded_len(calls[0]) + silence + vocoded_len(calls[1]) + edges;
+        assert_eq!(written, expected);
+
+        output.set_position(0);
+        let reader = hound::WavReader::new(output).unwrap();
+        assert_eq!(reader.spec(), WAV_SPEC);
+        assert_eq!(reader.len() as usize, expected);
+    }
+
+    #[test]
+    fn synthesise_duration() {
+        let tts = XdTts::from_model(StubModel::default(), CmuDictionary::default(), false)
+            .unwrap()
+            .with_lead_silence(Duration::ZERO)
+            .with_tail_silence(Duration::ZERO);
+        let text = r#"<speak>Hello there.<break time="1s"/>Goodbye!</speak>"#;
+        let audio = tts.synthesise(text, None).unwrap();
+
+        let calls = tts.model.calls.lock().unwrap().clone();
+        assert_eq!(calls.len(), 2);
+        let frames = calls.iter().sum::<usize>() * STUB_FRAMES_PER_UNIT;
+        let expected = frames * HOP_LENGTH + WAV_SPEC.sample_rate as usize;
+        // Griffin-Lim adds a window length of samples at the end of each chunk, so it's only close
+        let tolerance = silence_samples(Duration::from_millis(100), WAV_SPEC.sample_rate) as usize;
+        assert!(audio.len() >= expected && audio.len() - expected <= tolerance);
+
+        let speech = calls[0] * STUB_FRAMES_PER_UNIT * HOP_LENGTH;
+        let silence = &audio[(speech + HOP_LENGTH)..(speech + WAV_SPEC.sample_rate as usize)];
+        assert!(silence.iter().all(|x| *x == 0.0));
+
+        let mut output = Cursor::new(vec![]);
+        let mut writer = WavWriter::new(&mut output, WAV_SPEC).unwrap();
+        tts.generate_audio(text, &mut writer, None).unwrap();
+        assert_eq!(writer.duration() as usize, audio.len());
+    }
+
+    #[test]
+    fn clipped_samples() {
+        let audio = [1.5, -2.0, 0.5, f32::INFINITY];
+        let write = |spec: WavSpec| {
+            let mut output = Cursor::new(vec![]);
+            let mut writer = WavWriter::new(&mut output, spec).unwrap();
+            write_wav(&audio, &mut writer).unwrap();
+            writer.finalize().unwrap();
+            output.set_position(0);
+            hound::WavReader::new(output).unwrap()
+        };
+
+        let samples = write(WAV_SPEC)
+            .samples::<i16>()
+            .map(|x| x.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(samples, [i16::MAX, -i16::MAX, i16::MAX / 2, i16::MAX]);
+
+        let spec = WavSpec {
+            bits_per_sample: 24,
+            ..WAV_SPEC
+        };
+        let samples = write(spec)
+            .samples::<i32>()
+            .map(|x| x.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(samples, [8388607, -8388607, 4194303, 8388607]);
+
+        let spec = WavSpec {
+            sample_format: SampleFormat::Float,
+            bits_per_sample: 32,
+            ..WAV_SPEC
+        };
+        let samples = write(spec)
+            .samples::<f32>()
+            .map(|x| x.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(samples, [1.0, -1.0, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn wav_sample_formats() {
+        let tts = XdTts::from_model(StubModel::default(), CmuDictionary::default(), false).unwrap();
+        assert_eq!(tts.wav_spec(), WAV_SPEC);
+        let text = "Hello there";
+        let audio = tts.synthesise(text, None).unwrap();
+
+        let tts = tts.with_wav_format(SampleFormat::Float, 32);
+        let spec = tts.wav_spec();
+        assert_eq!(spec.sample_rate, WAV_SPEC.sample_rate);
+        let mut output = Cursor::new(vec![]);
+        let mut writer = WavWriter::new(&mut output, spec).unwrap();
+        tts.generate_audio(text, &mut writer, None).unwrap();
+        writer.finalize().unwrap();
+        output.set_position(0);
+        let mut reader = hound::WavReader::new(output).unwrap();
+        assert_eq!(reader.spec(), spec);
+        let samples = reader
+            .samples::<f32>()
+            .map(|x| x.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(samples, audio);
+
+        let tts = tts.with_wav_format(SampleFormat::Int, 24);
+        let spec = tts.wav_spec();
+        let mut output = Cursor::new(vec![]);
+        let mut writer = WavWriter::new(&mut output, spec).unwrap();
+        tts.generate_audio(text, &mut writer, None).unwrap();
+        writer.finalize().unwrap();
+        output.set_position(0);
+        let mut reader = hound::WavReader::new(output).unwrap();
+        assert_eq!(reader.spec().bits_per_sample, 24);
+        assert_eq!(reader.spec().sample_format, SampleFormat::Int);
+        let samples = reader
+            .samples::<i32>()
+            .map(|x| x.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(samples.len(), audio.len());
+        for (sample, expected) in samples.iter().zip(&audio) {
+            assert_eq!(*sample, pcm_sample(*expected, 24));
+            assert!((*sample as f32 / 8388607.0 - expected).abs() < 1e-6);
+        }
+
+        assert_eq!(pcm_sample(1.0, 16), i16::MAX as i32);
+        assert_eq!(pcm_sample(-1.0, 24), -8388607);
+
+        // The writer has to match the format the TTS was set up for
+        let mut output = Cursor::new(vec![]);
+        let mut writer = WavWriter::new(&mut output, WAV_SPEC).unwrap();
+        let calls = tts.model.calls.lock().unwrap().len();
+        assert!(tts.generate_audio(text, &mut writer, None).is_err());
+        // It fails before synthesising anything
+        assert_eq!(tts.model.calls.lock().unwrap().len(), calls);
+
+        // The TTS only generates audio at one sample rate
+        let mut output = Cursor::new(vec![]);
+        let spec = WavSpec {
+            sample_rate: 44100,
+            ..WAV_SPEC
+        };
+        let mut writer = WavWriter::new(&mut output, spec).unwrap();
+        assert!(tts.generate_audio(text, &mut writer, None).is_err());
+    }
+
+    #[test]
+    fn spectrogram_with_breaks() {
+        let text = r#"<speak>Hello there.<break time="500ms"/>Goodbye!</speak>"#;
+        let path = env::temp_dir().join(format!("xd_tts_spectrogram_{}.npy", std::process::id()));
+        let frame = |sample: usize| (sample as f64 / HOP_LENGTH as f64).round() as usize;
+        let is_silent = |spectrogram: &Array2<f32>, frames: Range<usize>| {
+            spectrogram
+                .slice(ndarray::s![.., frames])
+                .iter()
+                .all(|x| *x == LOG_MEL_FLOOR)
+        };
+
+        for threads in [1, 2] {
+            let tts = XdTts::from_model(StubModel::default(), CmuDictionary::default(), false)
+                .unwrap()
+                .with_vocoder_threads(threads);
+            let audio = tts.synthesise(text, Some(path.clone())).unwrap();
+            let spectrogram: Array2<f32> = ndarray_npy::read_npy(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            let calls = tts.model.calls.lock().unwrap().clone();
+            assert_eq!(calls.len(), 2);
+            assert_eq!(spectrogram.nrows(), 80);
+            // It covers the whole audio including the lead and tail silence
+            assert_eq!(spectrogram.ncols(), frame(audio.len()));
+
+            // Each chunk starts at the frame its audio starts at, whatever the vocoder's length
+            let lead = silence_samples(DEFAULT_EDGE_SILENCE, WAV_SPEC.sample_rate) as usize;
+            let first = Array2::from_elem((80, calls[0] * STUB_FRAMES_PER_UNIT), -5.0);
+            let first_len = tts.vocoder.infer(&first).unwrap().len();
+            let silence = WAV_SPEC.sample_rate as usize / 2;
+            let first_end = frame(lead) + first.ncols();
+            let second_start = frame(lead + first_len + silence);
+            let second_end = second_start + calls[1] * STUB_FRAMES_PER_UNIT;
+            assert!(is_silent(&spectrogram, 0..frame(lead)));
+            assert!(spectrogram
+                .slice(ndarray::s![.., frame(lead)..first_end])
+                .iter()
+                .all(|x| *x == -5.0));
+            assert!(is_silent(&spectrogram, first_end..second_start));
+            assert!(spectrogram
+                .slice(ndarray::s![.., second_start..second_end])
+                .iter()
+                .all(|x| *x == -5.0));
+            assert!(is_silent(&spectrogram, second_end..spectrogram.ncols()));
+        }
+    }
+
+    #[test]
+    fn parallel_vocoding() {
+        let text = r#"<speak>One chunk.<break time="200ms"/>Another, longer chunk.<break time="1s"/>A <prosody pitch="+2st">higher</prosody> chunk,,,<break time="300ms"/>!</speak>"#;
+        let tts = XdTts::from_model(StubModel::default(), CmuDictionary::default(), false).unwrap();
+        let sequential = tts.synthesise(text, None).unwrap();
+        let calls = tts.model.calls.lock().unwrap().clone();
+        assert!(calls.len() > 3);
+        for threads in [2, 3, 16] {
+            let tts = XdTts::from_model(StubModel::default(), CmuDictionary::default(), false)
+                .unwrap()
+                .with_vocoder_threads(threads);
+            let parallel = tts.synthesise(text, None).unwrap();
+            assert_eq!(*tts.model.calls.lock().unwrap(), calls);
+            assert_eq!(parallel.len(), sequential.len());
+            // The breaks are still in the same place
+            let silence = |audio: &[f32]| {
+                audio
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, x)| **x == 0.0)
+                    .map(|(i, _)| i)
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(silence(&parallel), silence(&sequential));
+        }
+    }
+
+    #[test]
+    fn synthesise_units() {
+        let tts = XdTts::from_model(StubModel::default(), CmuDictionary::default(), true).unwrap();
+        // "hello world" from CMU dict
+        let units = "HH AH0 L OW1 _ W ER1 L D ."
+            .split(' ')
+            .map(|x| match x {
+                "_" => Unit::Space,
+                x => Unit::from_str(x).unwrap(),
+            })
+            .collect::<Vec<_>>();
+
+        let mut output = Cursor::new(vec![]);
+        let mut writer = WavWriter::new(&mut output, WAV_SPEC).unwrap();
+        tts.generate_audio_from_units(&units, &mut writer).unwrap();
+        let written = writer.duration() as usize;
+        writer.finalize().unwrap();
+
+        // Goes straight to the model without being normalised or split
+        assert_eq!(*tts.model.calls.lock().unwrap(), [units.len()]);
+        let mel = Array2::from_elem((80, units.len() * STUB_FRAMES_PER_UNIT), -5.0);
+        let edges = 2 * silence_samples(DEFAULT_EDGE_SILENCE, WAV_SPEC.sample_rate) as usize;
+        assert_eq!(written, tts.vocoder.infer(&mel).unwrap().len() + edges);
+
+        let mut output = Cursor::new(vec![]);
+        let mut writer = WavWriter::new(&mut output, WAV_SPEC).unwrap();
+        tts.generate_audio_from_units(&[], &mut writer).unwrap();
+        assert_eq!(writer.duration(), 0);
+        assert_eq!(tts.model.calls.lock().unwrap().len(), 1);
+    }
+
+    /// Vocoder that outputs a constant for each sample of every frame, so it's easy to check the
+    /// audio came from it.
+    struct ConstantVocoder;
+
+    impl Vocoder for ConstantVocoder {
+        fn infer(&self, mel: &Array2<f32>) -> anyhow::Result<Vec<f32>> {
+            Ok(vec![0.5; mel.ncols() * HOP_LENGTH])
+        }
+    }
+
+    #[test]
+    fn custom_vocoder() {
+        let tts = XdTts::from_model(StubModel::default(), CmuDictionary::default(), false)
+            .unwrap()
+            .with_lead_silence(Duration::ZERO)
+            .with_tail_silence(Duration::ZERO)
+            .with_vocoder(ConstantVocoder);
+
+        let mut output = Cursor::new(vec![]);
+        let mut writer = WavWriter::new(&mut output, WAV_SPEC).unwrap();
+        tts.generate_audio("Hello", &mut writer, None).unwrap();
+        writer.finalize().unwrap();
+
+        let units = tts.model.calls.lock().unwrap().iter().sum::<usize>();
+        assert!(units > 0);
+        output.set_position(0);
+        let samples = hound::WavReader::new(output)
+            .unwrap()
+            .into_samples::<i16>()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(samples.len(), units * STUB_FRAMES_PER_UNIT * HOP_LENGTH);
+        assert!(samples.iter().all(|x| *x == samples[0] && *x > 0));
+    }
+
+    /// Vocoder that panics, like a custom vocoder with a bug might.
+    struct PanickingVocoder;
+
+    impl Vocoder for PanickingVocoder {
+        fn infer(&self, _: &Array2<f32>) -> anyhow::Result<Vec<f32>> {
+            panic!("Vocoder bug")
+        }
+    }
+
+    #[test]
+    fn vocoder_thread_panic() {
+        let tts = XdTts::from_model(StubModel::default(), CmuDictionary::default(), false)
+            .unwrap()
+            .with_vocoder(PanickingVocoder)
+            .with_vocoder_threads(2);
+        let text = r#"<speak>Hello<break time="100ms"/>there</speak>"#;
+        assert!(tts.synthesise(text, None).is_err());
+    }
+
+    /// Splits the input into windows of 4 units like tacotron2 splits long inputs, taking `delay`
+    /// to generate each one so the deadline can pass part way through a chunk.
+    struct WindowedModel {
+        delay: Duration,
+    }
+
+    impl AcousticModel for WindowedModel {
+        fn infer(&self, units: &[Unit]) -> anyhow::Result<Array2<f32>> {
+            Ok(self.infer_until(units, None)?.0)
+        }
+
+        fn infer_until(
+            &self,
+            units: &[Unit],
+            deadline: Option<Instant>,
+        ) -> anyhow::Result<(Array2<f32>, bool)> {
+            let mut frames = 0;
+            for (i, window) in units.chunks(4).enumerate() {
+                if i > 0 && deadline.is_some_and(|x| Instant::now() >= x) {
+                    return Ok((Array2::from_elem((80, frames), -5.0), true));
+                }
+                thread::sleep(self.delay);
+                frames += window.len() * STUB_FRAMES_PER_UNIT;
+            }
+            Ok((Array2::from_elem((80, frames), -5.0), false))
+        }
+    }
+
+    #[test]
+    fn deadline_between_windows() {
+        let model = WindowedModel {
+            delay: Duration::from_millis(100),
+        };
+        let tts = XdTts::from_model(model, CmuDictionary::default(), false).unwrap();
+        let text = "Hello there";
+
+        let full = tts
+            .generate_audio_with_deadline(text, Instant::now() + Duration::from_secs(3600))
+            .unwrap();
+        assert!(!full.truncated);
+
+        // The deadline passes during the first window so the rest of the chunk is skipped, but
+        // the window that was running is kept
+        let partial = tts
+            .generate_audio_with_deadline(text, Instant::now() + Duration::from_millis(50))
+            .unwrap();
+        assert!(partial.truncated);
+        assert!(!partial.samples.is_empty());
+        assert!(partial.samples.len() < full.samples.len());
+    }
+
+    /// Frontend that passes the text through untouched so the case is kept.
+    struct PassthroughFrontend;
+
+    impl TextFrontend for PassthroughFrontend {
+        fn normalise(&self, text: &str) -> anyhow::Result<NormalisedText> {
+            Ok(vec![NormaliserChunk::Text(text.to_string())].into())
+        }
+    }
+
+    #[test]
+    fn character_case() {
+        let characters = |lowercase: bool| {
+            let tts = XdTts::from_model(StubModel::default(), CmuDictionary::default(), false)
+                .unwrap()
+                .with_frontend(PassthroughFrontend)
+                .with_lowercase_characters(lowercase);
+            tts.synthesise("I said NO", None).unwrap();
+            let inputs = tts.model.inputs.lock().unwrap();
+            inputs[0]
+                .iter()
+                .map(|x| match x {
+                    Unit::Character(c) => *c,
+                    Unit::Space => ' ',
+                    x => panic!("Unexpected unit: {:?}", x),
+                })
+                .collect::<String>()
+        };
+        assert_eq!(characters(true), "i said no");
+        assert_eq!(characters(false), "I said NO");
+    }
+
+    #[test]
+    fn silence_lengths() {
+        let ms = Duration::from_millis;
+        assert_eq!(silence_samples(ms(0), 22050), 0);
+        assert_eq!(silence_samples(ms(1), 22050), 22);
+        assert_eq!(silence_samples(ms(200), 22050), 4410);
+        assert_eq!(silence_samples(ms(500), 22050), 11025);
+        assert_eq!(silence_samples(ms(1000), 22050), 22050);
+        assert_eq!(silence_samples(Duration::from_secs(5), 22050), 110250);
+        assert_eq!(silence_samples(ms(250), 16000), 4000);
+
+        let frames = |duration| silence_frames(duration, WAV_SPEC.sample_rate, HOP_LENGTH);
+        assert_eq!(frames(ms(0)), 0);
+        assert_eq!(frames(ms(200)), 17);
+        assert_eq!(frames(ms(500)), 43);
+        assert_eq!(frames(ms(1000)), 86);
+        assert_eq!(frames(Duration::from_secs(5)), 431);
+        assert_eq!(silence_frames(ms(1000), 16000, 200), 80);
+
+        // Many breaks don't drift further than the rounding of each one
+        let mut audio = vec![];
+        for _ in 0..100 {
+            append_silence(ms(333), &mut audio);
+        }
+        let exact = 22050.0 * 0.333 * 100.0;
+        assert!((audio.len() as f64 - exact).abs() <= 50.0);
+        assert_eq!(audio.len(), 100 * silence_samples(ms(333), 22050) as usize);
+    }
+
+    #[test]
+    fn explain_trace() {
+        let text = r#"<speak>Hello there, 2 worlds<break time="500ms"/>Good café ©</speak>"#;
+        let tts = XdTts::from_model(StubModel::default(), CmuDictionary::default(), false).unwrap();
+        let trace = tts.explain(text).unwrap();
+
+        assert_eq!(trace.text, text);
+        assert!(trace.transliterated.contains("Good cafe (c)"));
+        assert!(trace
+            .normalised
+            .iter()
+            .any(|x| matches!(x, NormaliserChunk::Break(_))));
+        assert_eq!(trace.chunks.len(), 2);
+        // The units the model was given
+        assert_eq!(
+            trace
+                .chunks
+                .iter()
+                .map(|x| x.units.clone())
+                .collect::<Vec<_>>(),
+            *tts.model.inputs.lock().unwrap()
+        );
+        for chunk in &trace.chunks {
+            assert_eq!(chunk.frames, chunk.units.len() * STUB_FRAMES_PER_UNIT);
+            assert!(chunk.samples > 0);
+            // The stub model doesn't use IDs
+            assert!(chunk.inputs.is_empty());
+        }
+        assert_eq!(trace.diagnostics, ["Characters that can't be read: '©'"]);
+        let report = trace.to_string();
+        assert!(report.contains("Chunk 2:"));
+        assert!(report.contains("Diagnostics:"));
+
+        // Chunks are split like in synthesis, punctuation on its own isn't given to the model
+        let text = r#"<speak>A <prosody pitch="+2st">higher</prosody> chunk<break time="100ms"/>!</speak>"#;
+        let trace = tts.explain(text).unwrap();
+        let tts = XdTts::from_model(StubModel::default(), CmuDictionary::default(), false).unwrap();
+        tts.synthesise(text, None).unwrap();
+        assert_eq!(
+            trace
+                .chunks
+                .iter()
+                .map(|x| (x.units.clone(), x.pitch))
+                .collect::<Vec<_>>(),
+            tts.model
+                .inputs
+                .lock()
+                .unwrap()
+                .iter()
+                .cloned()
+                .zip([0, 200, 0])
+                .collect::<Vec<_>>()
+        );
+
+        let dict = CmuDictionary::from_reader(Cursor::new("HELLO  HH AH0 L OW1")).unwrap();
+        let tts = XdTts::from_model(StubModel::default(), dict, true).unwrap();
+        let trace = tts.explain("Hello xyzzy").unwrap();
+        assert_eq!(trace.diagnostics, ["'XYZZY' isn't in the dictionary"]);
+    }
+
+    #[test]
+    fn punctuation_only_input() {
+        let tts = XdTts::from_model(StubModel::default(), CmuDictionary::default(), false)
+            .unwrap()
+            .with_lead_silence(Duration::ZERO)
+            .with_tail_silence(Duration::ZERO);
+        let sentence_end = silence_samples(Duration::from_millis(400), WAV_SPEC.sample_rate);
+        for text in ["...", "?!", " ? "] {
+            let audio = tts.synthesise(text, None).unwrap();
+            assert_eq!(audio.len(), sentence_end as usize, "{}", text);
+            assert!(audio.iter().all(|x| *x == 0.0));
+        }
+        assert!(tts.model.calls.lock().unwrap().is_empty());
+
+        // Punctuation left on it's own after a break
+        let text = r#"<speak>Hello<break time="100ms"/>!</speak>"#;
+        let audio = tts.synthesise(text, None).unwrap();
+        assert_eq!(tts.model.calls.lock().unwrap().len(), 1);
+        assert!(audio[(audio.len() - sentence_end as usize)..]
+            .iter()
+            .all(|x| *x == 0.0));
+
+        let mut streamed = vec![];
+        tts.generate_streaming("?!", StreamingOptions::default(), |audio| {
+            streamed.extend_from_slice(audio);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(streamed.len(), sentence_end as usize);
+        assert_eq!(tts.model.calls.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn spell_vowelless_words() {
+        let dict = CmuDictionary::from_reader(Cursor::new("USE  Y UW1 Z")).unwrap();
+        let tts = XdTts::from_model(StubModel::default(), dict.clone(), true).unwrap();
+        let text = tts.text_to_units("use TLS SSH HTTP").unwrap();
+        let units = match &text.chunks()[0] {
+            NormaliserChunk::Pronunciation(units) => {
+                units.iter().map(|x| x.to_string()).collect::<String>()
+            }
+            e => panic!("Unexpected chunk: {:?}", e),
+        };
+        assert_eq!(
+            units.trim(),
+            "YUW1Z TIY1EH1LEH1S EH1SEH1SEY1CH EY1CHTIY1TIY1PIY1"
+        );
+
+        let tts = XdTts::from_model(StubModel::default(), dict, true)
+            .unwrap()
+            .with_spell_vowelless_words(false);
+        let text = tts.text_to_units("use TLS").unwrap();
+        assert_eq!(
+            text.chunks(),
+            [NormaliserChunk::Pronunciation(vec![
+                Unit::from_str("Y").unwrap(),
+                Unit::from_str("UW1").unwrap(),
+                Unit::from_str("Z").unwrap(),
+                Unit::Space
+            ])]
+        );
+    }
+
+    #[test]
+    fn edge_silence() {
+        let text = "Hello there";
+        let tts = XdTts::from_model(StubModel::default(), CmuDictionary::default(), false)
+            .unwrap()
+            .with_lead_silence(Duration::ZERO)
+            .with_tail_silence(Duration::ZERO);
+        let speech = tts.synthesise(text, None).unwrap();
+        assert!(!speech.is_empty());
+
+        let tts = tts
+            .with_lead_silence(Duration::from_millis(100))
+            .with_tail_silence(Duration::from_millis(200));
+        let lead = silence_samples(Duration::from_millis(100), WAV_SPEC.sample_rate) as usize;
+        let tail = silence_samples(Duration::from_millis(200), WAV_SPEC.sample_rate) as usize;
+        let padded = tts.synthesise(text, None).unwrap();
+        assert_eq!(padded.len(), lead + speech.len() + tail);
+        assert!(padded[..lead].iter().all(|x| *x == 0.0));
+        assert_eq!(padded[lead..(lead + speech.len())], speech[..]);
+        assert!(padded[(lead + speech.len())..].iter().all(|x| *x == 0.0));
+
+        let mut streamed = vec![];
+        tts.generate_streaming(text, StreamingOptions::default(), |audio| {
+            streamed.extend_from_slice(audio);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(streamed.len(), padded.len());
+
+        // Nothing to say so there's nothing to pad
+        assert!(tts.synthesise("", None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn max_duration_cap() {
+        let text = r#"<speak>Hello<break time="500ms"/>there<break time="500ms"/>world</speak>"#;
+        let tts = XdTts::from_model(StubModel::default(), CmuDictionary::default(), false)
+            .unwrap()
+            .with_lead_silence(Duration::ZERO)
+            .with_tail_silence(Duration::ZERO);
+        let full = tts.synthesise(text, None).unwrap();
+
+        let tts = tts.with_max_duration(Some(Duration::from_millis(600)));
+        let capped = tts
+            .generate_audio_with_deadline(text, Instant::now() + Duration::from_secs(3600))
+            .unwrap();
+        assert!(capped.truncated);
+        assert_eq!(capped.samples.len(), 13230);
+        assert_eq!(capped.samples[..], full[..13230]);
+
+        let tts = tts.with_max_duration(None);
+        let uncapped = tts
+            .generate_audio_with_deadline(text, Instant::now() + Duration::from_secs(3600))
+            .unwrap();
+        assert!(!uncapped.truncated);
+        assert_eq!(uncapped.samples, full);
+
+        // Streaming stops at the same point
+        let tts = tts.with_max_duration(Some(Duration::from_millis(600)));
+        let mut streamed = 0;
+        tts.generate_streaming(text, StreamingOptions::default(), |audio| {
+            streamed += audio.len();
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(streamed, 13230);
+    }
+
+    #[test]
+    fn streaming_synthesis() {
+        let tts = XdTts::from_model(StubModel::default(), CmuDictionary::default(), false).unwrap();
+        let text = r#"<speak>Hello there, 2 worlds.<break time="500ms"/>Goodbye!</speak>"#;
+        let expected = tts.synthesise(text, None).unwrap();
+
+        let mut blocks = vec![];
+        tts.generate_streaming(text, StreamingOptions::default(), |audio| {
+            blocks.push(audio.len());
+            Ok(())
+        })
+        .unwrap();
+        assert!(blocks.len() > 3);
+        assert_eq!(blocks.iter().sum::<usize>(), expected.len());
+        // The break is passed on as it's own block of silence
+        assert!(blocks.contains(&(WAV_SPEC.sample_rate as usize / 2)));
+
+        // Errors from the callback stop synthesis
+        let mut calls = 0;
+        let res = tts.generate_streaming(text, StreamingOptions::default(), |_| {
+            calls += 1;
+            anyhow::bail!("Stop")
+        });
+        assert!(res.is_err());
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn streaming_ssml_chunks() {
+        let tts = XdTts::from_model(StubModel::default(), CmuDictionary::default(), false).unwrap();
+        let text = r#"<speak>Hello there, 2 worlds.<break time="500ms"/>Goodbye!<break time="200ms"/>Bye</speak>"#;
+        let mut expected = vec![];
+        tts.generate_streaming(text, StreamingOptions::default(), |audio| {
+            expected.extend_from_slice(audio);
+            Ok(())
+        })
+        .unwrap();
+        let calls = tts.model.calls.lock().unwrap().clone();
+
+        let tts = XdTts::from_model(StubModel::default(), CmuDictionary::default(), false).unwrap();
+        let options = text_normaliser::NormaliserOptions::default();
+        let ssml = text_normaliser::parse_ssml(text).unwrap();
+        let chunks = text_normaliser::SsmlChunks::new(ssml.event_iter(), &options);
+        let mut streamed = vec![];
+        tts.generate_streaming_chunks(chunks, StreamingOptions::default(), |audio| {
+            streamed.extend_from_slice(audio);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(*tts.model.calls.lock().unwrap(), calls);
+        assert_eq!(streamed, expected);
+
+        // Errors from the frontend stop synthesis
+        let chunks = vec![
+            Ok(NormaliserChunk::Text("HELLO".to_string())),
+            Ok(NormaliserChunk::Break(Duration::from_millis(100))),
+            Err(anyhow::anyhow!("Bad SSML")),
+        ];
+        let mut samples = 0;
+        let res = tts.generate_streaming_chunks(chunks, StreamingOptions::default(), |audio| {
+            samples += audio.len();
+            Ok(())
+        });
+        assert!(res.is_err());
+        assert!(samples > 0);
+    }
+
+    #[test]
+    fn concurrent_synthesis() {
+        let tts = Arc::new(XdTts::new(Path::new("./models/tacotron2"), false, None).unwrap());
+
+        let handles = (0..4)
+            .map(|i| {
+                let tts = Arc::clone(&tts);
+                thread::spawn(move || {
+                    let mut output = Cursor::new(vec![]);
+                    let mut writer = WavWriter::new(&mut output, WAV_SPEC).unwrap();
+                    tts.generate_audio(&format!("This is thread {}", i), &mut writer, None)
+                        .unwrap();
+                    assert!(writer.duration() > 0);
+                    writer.finalize().unwrap();
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn deadline_truncation() {
+        let tts = XdTts::new(Path::new("./models/tacotron2"), false, None).unwrap();
+        let text = "This is the first sentence. This is the second one.";
+
+        let full = tts
+            .generate_audio_with_deadline(text, Instant::now() + Duration::from_secs(3600))
+            .unwrap();
+        assert!(!full.truncated);
+        assert!(!full.samples.is_empty());
+
+        let none = tts
+            .generate_audio_with_deadline(text, Instant::now())
+            .unwrap();
+        assert!(none.truncated);
+        assert!(none.samples.is_empty());
+    }
+}
@@ -0,0 +1,326 @@
+//! A phone set is the inventory of phones a language (or a model) uses and how they're written in
+//! IPA. Originally the mapping from IPA to ARPA and the list of phones the model accepted were
+//! hardcoded match statements and arrays. Here they're kept as data instead, so a different set can
+//! be defined without touching the rest of the pipeline.
+//!
+//! The phones themselves are still `ArpaPhone` values. For languages that are close enough to
+//! English this is fine: a Spanish set can map its IPA onto the nearest ARPA phones and reuse the
+//! dictionary, normaliser and model machinery. Anything needing phones ARPABET doesn't have would
+//! need a new phone type as well.
+use crate::phonemes::*;
+use once_cell::sync::Lazy;
+use std::collections::{BTreeMap, BTreeSet};
+use std::str::FromStr;
+use tracing::{error, trace};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// IPA to ARPA mappings for English, if you read this you'll notice some are commented out. This
+/// is because the mappings present aren't present in CMU dict and seem to be optional/additional
+/// ARPA phones. For simplicity we've omitted them instead of dealing with overlaps.
+const ENGLISH_IPA: &[(&str, ArpaPhone)] = &[
+    ("ɒ", ArpaPhone::Aa),
+    ("ɑ", ArpaPhone::Aa),
+    ("æ", ArpaPhone::Ae),
+    ("ʌ", ArpaPhone::Ah),
+    ("ə", ArpaPhone::Ah), // CMU dict uses AH for ə not AX
+    ("ɔ", ArpaPhone::Ao),
+    ("aʊ", ArpaPhone::Aw),
+    //("ə", ArpaPhone::Ax),
+    ("aɪ", ArpaPhone::Ay),
+    ("ɛ", ArpaPhone::Eh),
+    ("ɝ", ArpaPhone::Er),
+    ("ɚ", ArpaPhone::Er),
+    ("eɪ", ArpaPhone::Ey),
+    ("ɪ", ArpaPhone::Ih),
+    //("ɨ", ArpaPhone::Ix),
+    ("i", ArpaPhone::Iy),
+    ("oʊ", ArpaPhone::Ow),
+    ("ɔɪ", ArpaPhone::Oy),
+    ("ʊ", ArpaPhone::Uh),
+    ("u", ArpaPhone::Uw),
+    //("ʉ", ArpaPhone::Ux),
+    ("b", ArpaPhone::B),
+    ("tʃ", ArpaPhone::Ch),
+    ("t͡ʃ", ArpaPhone::Ch), // unicode is hard man
+    ("d", ArpaPhone::D),
+    ("ð", ArpaPhone::Dh),
+    //("ɾ", ArpaPhone::Dx),
+    ("f", ArpaPhone::F),
+    ("ɡ", ArpaPhone::G),
+    ("h", ArpaPhone::Hh),
+    ("dʒ", ArpaPhone::Jh),
+    ("d͡ʒ", ArpaPhone::Jh),
+    ("k", ArpaPhone::K),
+    ("l", ArpaPhone::L),
+    ("m", ArpaPhone::M),
+    ("n", ArpaPhone::N),
+    ("ŋ", ArpaPhone::Ng),
+    //("ɾ̃", ArpaPhone::Nx),
+    ("p", ArpaPhone::P),
+    //("ʔ", ArpaPhone::Q),
+    ("ɹ", ArpaPhone::R),
+    ("s", ArpaPhone::S),
+    ("ʃ", ArpaPhone::Sh),
+    ("t", ArpaPhone::T),
+    ("θ", ArpaPhone::Th),
+    ("v", ArpaPhone::V),
+    ("w", ArpaPhone::W),
+    //("ʍ", ArpaPhone::Wh),
+    ("j", ArpaPhone::Y),
+    ("z", ArpaPhone::Z),
+    ("ʒ", ArpaPhone::Zh),
+];
+
+/// The English phones with the stress variants CMU dict uses. The order matches the one tacotron2
+/// was trained with so don't sort it!
+const ENGLISH_PHONES: &[&str] = &[
+    "AA", "AA0", "AA1", "AA2", "AE", "AE0", "AE1", "AE2", "AH", "AH0", "AH1", "AH2", "AO", "AO0",
+    "AO1", "AO2", "AW", "AW0", "AW1", "AW2", "AY", "AY0", "AY1", "AY2", "B", "CH", "D", "DH", "EH",
+    "EH0", "EH1", "EH2", "ER", "ER0", "ER1", "ER2", "EY", "EY0", "EY1", "EY2", "F", "G", "HH",
+    "IH", "IH0", "IH1", "IH2", "IY", "IY0", "IY1", "IY2", "JH", "K", "L", "M", "N", "NG", "OW",
+    "OW0", "OW1", "OW2", "OY", "OY0", "OY1", "OY2", "P", "R", "S", "SH", "T", "TH", "UH", "UH0",
+    "UH1", "UH2", "UW", "UW0", "UW1", "UW2", "V", "W", "Y", "Z", "ZH",
+];
+
+static ENGLISH: Lazy<PhoneSet> = Lazy::new(|| {
+    let phones = ENGLISH_PHONES
+        .iter()
+        .map(|x| PhoneticUnit::from_str(x).unwrap())
+        .collect();
+    PhoneSet::new(ENGLISH_IPA.iter().copied(), phones)
+});
+
+/// A phone inventory and its IPA mappings. English is the built-in default, see
+/// `PhoneSet::english`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PhoneSet {
+    /// IPA symbol (which may be several graphemes) to the phone it's represented by
+    ipa: BTreeMap<String, ArpaPhone>,
+    /// First grapheme of each multi-grapheme IPA symbol, these are the points where we need to
+    /// look ahead before deciding on the phone.
+    prefixes: BTreeSet<String>,
+    /// Ordered list of phones (with stress) a model accepts
+    phones: Vec<PhoneticUnit>,
+}
+
+impl Default for PhoneSet {
+    fn default() -> Self {
+        Self::english().clone()
+    }
+}
+
+impl PhoneSet {
+    /// Creates a phone set from a table of IPA to phone mappings and the ordered list of phones.
+    /// Multiple IPA symbols can map to the same phone.
+    pub fn new<S: Into<String>>(
+        ipa: impl IntoIterator<Item = (S, ArpaPhone)>,
+        phones: Vec<PhoneticUnit>,
+    ) -> Self {
+        let ipa = ipa
+            .into_iter()
+            .map(|(k, v)| (k.into(), v))
+            .collect::<BTreeMap<_, _>>();
+        let prefixes = ipa
+            .keys()
+            .filter_map(|k| {
+                let mut graphemes = k.graphemes(true);
+                let first = graphemes.next()?;
+                graphemes.next().map(|_| first.to_string())
+            })
+            .collect();
+        Self {
+            ipa,
+            prefixes,
+            phones,
+        }
+    }
+
+    /// The built-in English (CMU dict ARPABET) phone set. This is built once and shared, clone it
+    /// if you need to own one.
+    pub fn english() -> &'static Self {
+        &ENGLISH
+    }
+
+    /// The ordered phones in this set, this is the order they should appear in a models input IDs
+    pub fn phones(&self) -> &[PhoneticUnit] {
+        &self.phones
+    }
+
+    /// Converts an IPA phoneme into a phonetic unit
+    pub fn ipa_to_unit(&self, ipa: &str, context: Option<AuxiliarySymbol>) -> anyhow::Result<Unit> {
+        match self.ipa.get(ipa) {
+            Some(phone) => Ok(Unit::Phone(PhoneticUnit {
+                phone: *phone,
+                context,
+            })),
+            None => anyhow::bail!("unsupported/invalid IPA Phone {}", ipa),
+        }
+    }
+
+    /// Writes a pronunciation in IPA, the opposite of `ipa_string_to_units`. Where several IPA
+    /// symbols map to the same phone the first in sorted order is used, so English AH is always
+    /// "ə". Stress is marked right before the stressed vowel as that's where `ipa_string_to_units`
+    /// looks for it, tertiary stress is marked as secondary. Errors if a phone has no IPA symbol.
+    pub fn pronunciation_to_ipa(&self, pronunciation: &[PhoneticUnit]) -> anyhow::Result<String> {
+        let mut res = String::new();
+        for unit in pronunciation {
+            let Some((ipa, _)) = self.ipa.iter().find(|(_, phone)| **phone == unit.phone) else {
+                anyhow::bail!("no IPA symbol for {}", unit.phone);
+            };
+            match unit.context {
+                Some(AuxiliarySymbol::PrimaryStress) => res.push('ˈ'),
+                Some(AuxiliarySymbol::SecondaryStress | AuxiliarySymbol::TertiaryStress) => {
+                    res.push('ˌ')
+                }
+                _ => {}
+            }
+            res.push_str(ipa);
+        }
+        Ok(res)
+    }
+
+    /// Here we convert an entire IPA string into a sequence of units, this involves segmenting the
+    /// string into graphemes and identifying where multi-grapheme IPA characters exist.
+    pub fn ipa_string_to_units(&self, ipa: &str) -> Vec<Unit> {
+        let get_unit = |g: &str, stress: Option<AuxiliarySymbol>| {
+            if g.trim().is_empty() {
+                Unit::Space
+            } else {
+                match self.ipa_to_unit(g, stress) {
+                    Ok(s) => s,
+                    Err(e) => {
+                        error!("Failed to map phoneme pushing unk: {}", e);
+                        Unit::Unk
+                    }
+                }
+            }
+        };
+
+        let stress_marker = |g: &str| match g {
+            "'" | "ˈ" => Some(AuxiliarySymbol::PrimaryStress),
+            "ˌ" => Some(AuxiliarySymbol::SecondaryStress),
+            _ => None,
+        };
+
+        let mut res = vec![];
+        let mut graphemes = ipa.graphemes(true).collect::<Vec<&str>>();
+        let mut buffer = String::new();
+        let mut stress = None;
+        for g in graphemes.drain(..) {
+            trace!(
+                "Processing: {:?}. Buffer {:?} Stress {:?}",
+                g,
+                buffer,
+                stress
+            );
+            if buffer.is_empty() {
+                if let Some(marker) = stress_marker(g) {
+                    stress = Some(marker);
+                } else if self.prefixes.contains(g) {
+                    buffer.push_str(g);
+                } else {
+                    res.push(get_unit(g, stress));
+                    stress = None;
+                }
+            } else {
+                let original = buffer.clone();
+                buffer.push_str(g);
+                match self.ipa_to_unit(&buffer, stress) {
+                    Ok(s) => {
+                        res.push(s);
+                        stress = None;
+                        buffer.clear();
+                    }
+                    Err(_) => {
+                        buffer.clear();
+                        res.push(get_unit(&original, stress));
+                        // The stress marker only applies to the phone after it
+                        stress = None;
+                        if let Some(marker) = stress_marker(g) {
+                            stress = Some(marker);
+                        } else if self.prefixes.contains(g) {
+                            buffer.push_str(g);
+                        } else {
+                            res.push(get_unit(g, stress));
+                        }
+                    }
+                }
+            }
+        }
+        if !buffer.is_empty() {
+            res.push(get_unit(&buffer, stress));
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_phone_set() {
+        // A rough Spanish set mapped onto the nearest ARPA phones
+        let ipa = [
+            ("a", ArpaPhone::Aa),
+            ("e", ArpaPhone::Ey),
+            ("o", ArpaPhone::Ow),
+            ("k", ArpaPhone::K),
+            ("s", ArpaPhone::S),
+            ("r", ArpaPhone::R),
+            ("x", ArpaPhone::Hh),
+            ("tʃ", ArpaPhone::Ch),
+        ];
+        let phones = ["AA", "EY", "OW", "K", "S", "R", "HH", "CH"]
+            .iter()
+            .map(|x| PhoneticUnit::from_str(x).unwrap())
+            .collect::<Vec<_>>();
+        let spanish = PhoneSet::new(ipa, phones.clone());
+
+        assert_eq!(spanish.phones(), &phones);
+        let expected = "K AA1 S AA"
+            .split_ascii_whitespace()
+            .map(|x| Unit::from_str(x).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(spanish.ipa_string_to_units("kˈasa"), expected);
+
+        let expected = "CH AA R OW"
+            .split_ascii_whitespace()
+            .map(|x| Unit::from_str(x).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(spanish.ipa_string_to_units("tʃaro"), expected);
+
+        // English has no plain "a" so it can't be mapped
+        assert!(PhoneSet::english().ipa_to_unit("a", None).is_err());
+        assert!(spanish.ipa_to_unit("ɹ", None).is_err());
+    }
+
+    #[test]
+    fn ipa_output() {
+        let pronunciation = |x: &str| {
+            x.split_ascii_whitespace()
+                .map(|x| PhoneticUnit::from_str(x).unwrap())
+                .collect::<Vec<_>>()
+        };
+        let english = PhoneSet::english();
+        let ipa = english
+            .pronunciation_to_ipa(&pronunciation("HH AH0 L OW1"))
+            .unwrap();
+        assert_eq!(ipa, "həlˈoʊ");
+        let ipa = english
+            .pronunciation_to_ipa(&pronunciation("CH ER1 CH Y AA2 R D"))
+            .unwrap();
+        assert_eq!(ipa, "tʃˈɚtʃjˌɑɹd");
+
+        // Reading it back gives the same phones and primary/secondary stresses
+        let phones = pronunciation("K AE1 T AH2 L AO1 G");
+        let ipa = english.pronunciation_to_ipa(&phones).unwrap();
+        let units = phones.into_iter().map(Unit::Phone).collect::<Vec<_>>();
+        assert_eq!(english.ipa_string_to_units(&ipa), units);
+
+        let spanish = PhoneSet::new([("a", ArpaPhone::Aa)], vec![]);
+        assert!(spanish
+            .pronunciation_to_ipa(&pronunciation("K AA1"))
+            .is_err());
+    }
+}
@@ -8,11 +8,13 @@
 //! the phonetic units and get them into a neural network (first speedyspeech, then tacotron2).
 //!
 //! For finding about about phonemes and what ones there are in ARPA or IPA, I rely on Wikipedia.
+pub use crate::phone_set::PhoneSet;
 use anyhow::Error;
+use std::collections::BTreeMap;
 use std::fmt;
 use std::str::FromStr;
-use tracing::{error, warn};
-use unicode_segmentation::UnicodeSegmentation;
+use std::time::Duration;
+use tracing::debug;
 
 /// Type alias for the pronunciation of a word. This is created to work with the CMU dictionary
 pub type Pronunciation = Vec<PhoneticUnit>;
@@ -80,123 +82,66 @@ impl Punctuation {
     }
 }
 
-/// Converts an IPA phoneme into a phonetic unit, if you read the code you'll notice some are
-/// commented out. This is because the mappings present aren't present in CMU dict and seem to be
-/// optional/additional ARPA phones. For simplicity we've omitted them instead of dealing with
-/// overlaps.
+/// Converts an IPA phoneme into a phonetic unit using the English phone set. See `PhoneSet` for
+/// using other phone sets.
 pub fn ipa_to_unit(ipa: &str, context: Option<AuxiliarySymbol>) -> anyhow::Result<Unit> {
-    let phone = match ipa {
-        "ɒ" | "ɑ" => ArpaPhone::Aa,
-        "æ" => ArpaPhone::Ae,
-        "ʌ" | "ə" => ArpaPhone::Ah, // CMU dict uses AH for ə not AX
-        "ɔ" => ArpaPhone::Ao,
-        "aʊ" => ArpaPhone::Aw,
-        //"ə" => ArpaPhone::Ax,
-        "aɪ" => ArpaPhone::Ay,
-        "ɛ" => ArpaPhone::Eh,
-        "ɝ" | "ɚ" => ArpaPhone::Er,
-        "eɪ" => ArpaPhone::Ey,
-        "ɪ" => ArpaPhone::Ih,
-        //"ɨ" => ArpaPhone::Ix,
-        "i" => ArpaPhone::Iy,
-        "oʊ" => ArpaPhone::Ow,
-        "ɔɪ" => ArpaPhone::Oy,
-        "ʊ" => ArpaPhone::Uh,
-        "u" => ArpaPhone::Uw,
-        //"ʉ" => ArpaPhone::Ux,
-        "b" => ArpaPhone::B,
-        "tʃ" | "t͡ʃ" => ArpaPhone::Ch, // unicode is hard man
-        "d" => ArpaPhone::D,
-        "ð" => ArpaPhone::Dh,
-        //"ɾ" => ArpaPhone::Dx,
-        "f" => ArpaPhone::F,
-        "ɡ" => ArpaPhone::G,
-        "h" => ArpaPhone::Hh,
-        "dʒ" | "d͡ʒ" => ArpaPhone::Jh,
-        "k" => ArpaPhone::K,
-        "l" => ArpaPhone::L,
-        "m" => ArpaPhone::M,
-        "n" => ArpaPhone::N,
-        "ŋ" => ArpaPhone::Ng,
-        //"ɾ̃" => ArpaPhone::Nx,
-        "p" => ArpaPhone::P,
-        //"ʔ" => ArpaPhone::Q,
-        "ɹ" => ArpaPhone::R,
-        "s" => ArpaPhone::S,
-        "ʃ" => ArpaPhone::Sh,
-        "t" => ArpaPhone::T,
-        "θ" => ArpaPhone::Th,
-        "v" => ArpaPhone::V,
-        "w" => ArpaPhone::W,
-        //"ʍ" => ArpaPhone::Wh,
-        "j" => ArpaPhone::Y,
-        "z" => ArpaPhone::Z,
-        "ʒ" => ArpaPhone::Zh,
-        _ => anyhow::bail!("unsupported/invalid IPA Phone {}", ipa),
-    };
-    Ok(Unit::Phone(PhoneticUnit { phone, context }))
+    PhoneSet::english().ipa_to_unit(ipa, context)
 }
 
-/// Here we convert an entire IPA string into a sequence of units, this involves segmenting the
-/// string into graphemes and identifying where 2-grapheme IPA characters exist.
+/// Here we convert an entire IPA string into a sequence of units using the English phone set, this
+/// involves segmenting the string into graphemes and identifying where 2-grapheme IPA characters
+/// exist.
 pub fn ipa_string_to_units(ipa: &str) -> Vec<Unit> {
-    let get_unit = |g: &str, stress: Option<AuxiliarySymbol>| {
-        if g.trim().is_empty() {
-            Unit::Space
-        } else {
-            match ipa_to_unit(g, stress) {
-                Ok(s) => s,
-                Err(e) => {
-                    error!("Failed to map phoneme pushing unk: {}", e);
-                    Unit::Unk
-                }
-            }
-        }
-    };
+    PhoneSet::english().ipa_string_to_units(ipa)
+}
 
-    let mut res = vec![];
-    let mut graphemes = ipa.graphemes(true).collect::<Vec<&str>>();
-    let mut buffer = String::new();
-    let mut stress = None;
-    for g in graphemes.drain(..) {
-        println!(
-            "Processing: {:?}. Buffer {:?} Stress {:?}",
-            g, buffer, stress
-        );
-        if buffer.is_empty() {
-            if matches!(g, "'" | "ˈ") {
-                stress = Some(AuxiliarySymbol::PrimaryStress);
-            } else if g == "ˌ" {
-                stress = Some(AuxiliarySymbol::SecondaryStress);
-            } else if matches!(g, "t" | "a" | "d" | "o") {
-                buffer.push_str(g);
-            } else {
-                res.push(get_unit(g, stress));
-                stress = None;
+/// Writes a pronunciation in IPA using the English phone set, see
+/// `PhoneSet::pronunciation_to_ipa`.
+pub fn pronunciation_to_ipa(pronunciation: &[PhoneticUnit]) -> anyhow::Result<String> {
+    PhoneSet::english().pronunciation_to_ipa(pronunciation)
+}
+
+/// Writes the units as a space separated string for sending between services, i.e. a frontend on
+/// one machine and the model on another. "Hello." as phones is `HH AH0 L OW1 .` and spaces between
+/// words are `_`. Phones, punctuation, `<PAD>` and `<UNK>` are written the same as their `Display`
+/// form, characters are prefixed with `#` so they don't clash with single letter phones, i.e.
+/// `#B` is the character and `B` the phone. Whitespace characters are written as their code point,
+/// i.e. `#U+0009` for a tab. Use `compact_string_to_units` to read it back.
+pub fn units_to_compact_string(units: &[Unit]) -> String {
+    units
+        .iter()
+        .map(|unit| match unit {
+            Unit::Space => "_".to_string(),
+            Unit::Character(c) if c.is_whitespace() => format!("#U+{:04X}", *c as u32),
+            Unit::Character(c) => format!("#{}", c),
+            unit => unit.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Reads units written by `units_to_compact_string`.
+pub fn compact_string_to_units(s: &str) -> anyhow::Result<Vec<Unit>> {
+    s.split_ascii_whitespace()
+        .map(|token| {
+            if token == "_" {
+                return Ok(Unit::Space);
             }
-        } else {
-            let original = buffer.clone();
-            buffer.push_str(g);
-            match ipa_to_unit(&buffer, stress) {
-                Ok(s) => {
-                    res.push(s);
-                    stress = None;
-                    buffer.clear();
-                }
-                Err(_) => {
-                    buffer.clear();
-                    res.push(get_unit(&original, stress));
-                    if matches!(g, "t" | "a" | "d" | "o") {
-                        buffer.push_str(g);
-                    } else {
-                        res.push(get_unit(g, stress));
-                        stress = None;
-                    }
-                }
+            let Some(character) = token.strip_prefix('#') else {
+                return Unit::from_str(token);
+            };
+            let mut chars = character.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Ok(Unit::Character(c)),
+                _ => character
+                    .strip_prefix("U+")
+                    .and_then(|x| u32::from_str_radix(x, 16).ok())
+                    .and_then(char::from_u32)
+                    .map(Unit::Character)
+                    .ok_or_else(|| anyhow::anyhow!("Invalid character unit: '{}'", token)),
             }
-        }
-    }
-    res
+        })
+        .collect()
 }
 
 impl fmt::Display for Unit {
@@ -341,6 +286,31 @@ pub enum ArpaPhone {
     Zh,
 }
 
+impl ArpaPhone {
+    /// Returns true if the phone is a vowel (including diphthongs and the rhotic vowel). In CMU
+    /// dict these are the phones that carry stress markers.
+    pub fn is_vowel(&self) -> bool {
+        matches!(
+            self,
+            Self::Aa
+                | Self::Ae
+                | Self::Ah
+                | Self::Ao
+                | Self::Aw
+                | Self::Ay
+                | Self::Eh
+                | Self::Er
+                | Self::Ey
+                | Self::Ih
+                | Self::Iy
+                | Self::Ow
+                | Self::Oy
+                | Self::Uh
+                | Self::Uw
+        )
+    }
+}
+
 impl fmt::Display for ArpaPhone {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -623,33 +593,102 @@ impl FromStr for AuxiliarySymbol {
     }
 }
 
-/// When provided with a unit and a list of units a model accepts this finds th
+/// Phones to swap for others before they're given to the model. This can retune the pronunciation
+/// globally without editing the dictionary, i.e. moving an American accent towards a British one
+/// or avoiding a phone the model says badly.
+///
+/// A phone without stress matches that phone with any stress, and if the replacement has no
+/// stress the original stress is kept. So `AO -> AA` turns `AO1` into `AA1`. A rule for an exact
+/// stress takes priority over one without.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PhoneSubstitution {
+    rules: BTreeMap<PhoneticUnit, PhoneticUnit>,
+}
+
+impl PhoneSubstitution {
+    /// Adds a rule replacing `from` with `to`, this replaces any existing rule for `from`.
+    pub fn insert(&mut self, from: PhoneticUnit, to: PhoneticUnit) {
+        self.rules.insert(from, to);
+    }
+
+    /// Returns true if there are no rules.
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Applies the substitution to the unit, anything that isn't a phone with a rule is returned
+    /// as is.
+    pub fn apply(&self, unit: &Unit) -> Unit {
+        let Unit::Phone(phone) = unit else {
+            return *unit;
+        };
+        let any_stress = PhoneticUnit {
+            phone: phone.phone,
+            context: None,
+        };
+        match self
+            .rules
+            .get(phone)
+            .or_else(|| self.rules.get(&any_stress))
+        {
+            Some(to) => Unit::Phone(PhoneticUnit {
+                phone: to.phone,
+                context: to.context.or(phone.context),
+            }),
+            None => *unit,
+        }
+    }
+}
+
+/// When provided with a unit and a list of units a model accepts this finds the ID of the best
+/// match for it. For non-phones this has to be an exact match. For phones it's the same phone and
+/// if there's more than one candidate the tie-break is:
+///
+/// 1. The candidate with exactly the same stress/auxiliary symbol
+/// 2. The candidate with the stress given by `collapse_stress`, so tertiary stress uses the
+///    secondary stress phone
+/// 3. Otherwise the lowest stress: unmarked, then `0`, `1`, `2`, tertiary and finally any other
+///    auxiliary symbol
+/// 4. If that's still tied the first one in `unit_list`
+///
+/// This means the result doesn't depend on where the stress variants of a phone are in the list.
 pub fn best_match_for_unit(unit: &Unit, unit_list: &[Unit]) -> Option<i64> {
     if let Unit::Phone(unit) = unit {
-        let mut best = None;
-        for (i, potential) in unit_list
+        let stress_rank = |context: Option<AuxiliarySymbol>| match context {
+            None => 0,
+            Some(AuxiliarySymbol::NoStress) => 1,
+            Some(AuxiliarySymbol::PrimaryStress) => 2,
+            Some(AuxiliarySymbol::SecondaryStress) => 3,
+            Some(AuxiliarySymbol::TertiaryStress) => 4,
+            Some(_) => 5,
+        };
+        let best = unit_list
             .iter()
             .enumerate()
-            .filter(|(_, x)| matches!(x, Unit::Phone(v) if v.phone == unit.phone))
-        {
-            if best.is_none() {
-                best = Some(i as i64);
-            }
-            if let Unit::Phone(v) = potential {
-                if unit.context.is_none() && v.context.is_some() {
-                    warn!("Unstressed phone when stressed expected: {:?}", v.phone);
-                    best = Some(i as i64);
-                    break;
-                } else if v == unit {
-                    best = Some(i as i64);
-                    break;
+            .filter_map(|(i, x)| match x {
+                Unit::Phone(v) if v.phone == unit.phone => Some((i, v)),
+                _ => None,
+            })
+            .min_by_key(|(i, v)| {
+                (
+                    *v != unit,
+                    v.context != collapse_stress(unit.context),
+                    stress_rank(v.context),
+                    *i,
+                )
+            });
+        match best {
+            Some((i, v)) => {
+                if v != unit {
+                    debug!("No exact match for {:?}, using {:?}", unit, v);
                 }
+                Some(i as i64)
+            }
+            None => {
+                debug!("No ID found for {:?}", unit);
+                None
             }
         }
-        if best.is_none() {
-            warn!("No ID found for {:?}", unit);
-        }
-        best
     } else {
         unit_list
             .iter()
@@ -659,8 +698,251 @@ pub fn best_match_for_unit(unit: &Unit, unit_list: &[Unit]) -> Option<i64> {
     }
 }
 
-/// Scores how good this location is for splitting the transcript if it's too long
-fn split_score(unit: &Unit) -> usize {
+/// The stress to use for a phone when a model doesn't have the exact stress. CMU dict only uses
+/// `0`, `1` and `2` so models trained on it won't have tertiary stress, this is collapsed to
+/// secondary stress as the closest weak stress. Everything else is left as is.
+pub fn collapse_stress(context: Option<AuxiliarySymbol>) -> Option<AuxiliarySymbol> {
+    match context {
+        Some(AuxiliarySymbol::TertiaryStress) => Some(AuxiliarySymbol::SecondaryStress),
+        context => context,
+    }
+}
+
+/// Applies a stress pattern to a pronunciation, each character in the pattern is the stress for
+/// the next vowel (syllable nucleus) using the CMU dict markers: `0` unstressed, `1` primary and
+/// `2` secondary. So `"10"` makes a two syllable word a trochee and `"01"` an iamb. Consonants are
+/// left untouched. The pattern must have one marker per vowel.
+pub fn restress(pron: &[PhoneticUnit], pattern: &str) -> anyhow::Result<Pronunciation> {
+    let syllables = pron.iter().filter(|x| x.phone.is_vowel()).count();
+    let stresses = pattern
+        .chars()
+        .map(|c| match c {
+            '0' => Ok(AuxiliarySymbol::NoStress),
+            '1' => Ok(AuxiliarySymbol::PrimaryStress),
+            '2' => Ok(AuxiliarySymbol::SecondaryStress),
+            c => Err(Error::msg(format!("invalid stress marker '{}'", c))),
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    if stresses.len() != syllables {
+        anyhow::bail!(
+            "Stress pattern '{}' has {} syllables but the pronunciation has {}",
+            pattern,
+            stresses.len(),
+            syllables
+        );
+    }
+    let mut stresses = stresses.into_iter();
+    Ok(pron
+        .iter()
+        .map(|unit| {
+            if unit.phone.is_vowel() {
+                PhoneticUnit {
+                    phone: unit.phone,
+                    context: stresses.next(),
+                }
+            } else {
+                *unit
+            }
+        })
+        .collect())
+}
+
+/// How strongly stressed a pronunciation is, each vowel adds 2 for primary stress and 1 for
+/// secondary or tertiary stress. This is used to compare variants of the same word, the absolute
+/// value doesn't mean much.
+pub fn stress_weight(pron: &[PhoneticUnit]) -> usize {
+    pron.iter()
+        .filter(|x| x.phone.is_vowel())
+        .map(|x| match x.context {
+            Some(AuxiliarySymbol::PrimaryStress) => 2,
+            Some(AuxiliarySymbol::SecondaryStress | AuxiliarySymbol::TertiaryStress) => 1,
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Adds emphasis to a pronunciation by strengthening the stress around its primary stress. The
+/// unstressed vowels either side of the primary stressed vowel are given secondary stress, so
+/// "HH AH0 L OW1" becomes "HH AH2 L OW1". If there's no primary stress, like
+/// the reduced "DH AH0" for "the", the most stressed vowel (the first if they're all the same) is
+/// made the primary stress. Consonants are left untouched and a pronunciation without vowels is
+/// returned as is.
+///
+/// To emphasise a word from the dictionary it's worth picking its most stressed variant first,
+/// see `CmuDictionary::most_stressed_variant`.
+pub fn emphasise_word(pron: &[PhoneticUnit]) -> Pronunciation {
+    let mut res = pron.to_vec();
+    let vowels = res
+        .iter()
+        .enumerate()
+        .filter(|(_, x)| x.phone.is_vowel())
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    let strength = |unit: &PhoneticUnit| match unit.context {
+        Some(AuxiliarySymbol::PrimaryStress) => 3,
+        Some(AuxiliarySymbol::SecondaryStress) => 2,
+        Some(AuxiliarySymbol::TertiaryStress) => 1,
+        _ => 0,
+    };
+    // max_by_key returns the last maximum so reverse to get the first
+    let primary = match vowels.iter().rev().max_by_key(|i| strength(&res[**i])) {
+        Some(i) => vowels.iter().position(|x| x == i).unwrap_or_default(),
+        None => return res,
+    };
+    res[vowels[primary]].context = Some(AuxiliarySymbol::PrimaryStress);
+    let neighbours = [primary.checked_sub(1), Some(primary + 1)];
+    for i in neighbours
+        .into_iter()
+        .flatten()
+        .filter_map(|x| vowels.get(x))
+    {
+        if strength(&res[*i]) == 0 {
+            res[*i].context = Some(AuxiliarySymbol::SecondaryStress);
+        }
+    }
+    res
+}
+
+/// Edit distance between two pronunciations counted in phones, so an insertion, deletion or
+/// substitution of a single phone each cost 1. Units are compared with `PhoneticUnit` equality so
+/// a vowel with a different stress counts as a substitution, see
+/// [`pronunciation_distance_ignoring_stress`] to compare just the phones.
+pub fn pronunciation_distance(a: &[PhoneticUnit], b: &[PhoneticUnit]) -> usize {
+    edit_distance(a, b, |x, y| x == y)
+}
+
+/// Like [`pronunciation_distance`] but only looks at the phones, so two pronunciations that only
+/// differ in stress have a distance of 0.
+pub fn pronunciation_distance_ignoring_stress(a: &[PhoneticUnit], b: &[PhoneticUnit]) -> usize {
+    edit_distance(a, b, |x, y| x.phone == y.phone)
+}
+
+fn edit_distance<T>(a: &[T], b: &[T], eq: impl Fn(&T, &T) -> bool) -> usize {
+    // Only need the previous row of the matrix to compute the next one
+    let mut prev = (0..=b.len()).collect::<Vec<usize>>();
+    let mut current = vec![0; b.len() + 1];
+    for (i, x) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, y) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(!eq(x, y));
+            current[j + 1] = substitution.min(prev[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut current);
+    }
+    prev[b.len()]
+}
+
+/// Summary of how a set of phones (typically every phone used in a dictionary) will be mapped onto
+/// the inputs a model accepts.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PhoneCompatibility {
+    /// Phones the model doesn't have an exact ID for, and the phone that will be used instead
+    pub substitutions: Vec<(PhoneticUnit, Unit)>,
+    /// Phones with no ID at all, these are dropped during inference
+    pub unmappable: Vec<PhoneticUnit>,
+}
+
+impl PhoneCompatibility {
+    /// Returns true if every phone has an exact match in the model
+    pub fn is_exact(&self) -> bool {
+        self.substitutions.is_empty() && self.unmappable.is_empty()
+    }
+}
+
+impl fmt::Display for PhoneCompatibility {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_exact() {
+            return write!(f, "all phones supported");
+        }
+        let substitutions = self
+            .substitutions
+            .iter()
+            .map(|(from, to)| format!("{}->{}", from, to))
+            .collect::<Vec<_>>();
+        let unmappable = self
+            .unmappable
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>();
+        write!(
+            f,
+            "{} substituted [{}], {} unmappable [{}]",
+            substitutions.len(),
+            substitutions.join(", "),
+            unmappable.len(),
+            unmappable.join(", ")
+        )
+    }
+}
+
+/// Does a dry-run of `best_match_for_unit` over the phones to find which ones won't go into the
+/// model as they are. If a custom model is missing stress variants or phones the dictionary uses
+/// the output quality will degrade and this is a lot easier to spot than listening to the audio.
+pub fn check_phone_compatibility(
+    phones: impl IntoIterator<Item = PhoneticUnit>,
+    unit_list: &[Unit],
+) -> PhoneCompatibility {
+    let mut result = PhoneCompatibility::default();
+    for phone in phones {
+        let unit = Unit::Phone(phone);
+        match best_match_for_unit(&unit, unit_list) {
+            Some(id) if unit_list[id as usize] == unit => {}
+            Some(id) => result.substitutions.push((phone, unit_list[id as usize])),
+            None => result.unmappable.push(phone),
+        }
+    }
+    result
+}
+
+/// Tidies up the gaps between words before the units are given to the model. The frontend adds a
+/// space after every word and more can appear around punctuation and chunk boundaries, repeated
+/// spaces can throw off the models timing. Runs of `Unit::Space` and `Unit::Padding` are collapsed
+/// into one unit, which is padding if there was any padding in the run, and gaps at the start and
+/// end are removed.
+pub fn collapse_spaces(units: &mut Vec<Unit>) {
+    let is_gap = |x: &Unit| matches!(x, Unit::Space | Unit::Padding);
+    let mut result = Vec::with_capacity(units.len());
+    for unit in units.drain(..) {
+        match result.last_mut() {
+            Some(last) if is_gap(last) && is_gap(&unit) => {
+                if unit == Unit::Padding {
+                    *last = Unit::Padding;
+                }
+            }
+            None if is_gap(&unit) => {}
+            _ => result.push(unit),
+        }
+    }
+    if result.last().is_some_and(is_gap) {
+        result.pop();
+    }
+    *units = result;
+}
+
+/// Returns true if there's anything to say in the units. Chunks of only punctuation and spaces,
+/// i.e. from an input of "...", can make the model produce noise so they're replaced with silence
+/// (see `punctuation_pause`).
+pub fn is_speakable(units: &[Unit]) -> bool {
+    units
+        .iter()
+        .any(|x| matches!(x, Unit::Phone(_) | Unit::Character(_) | Unit::Unk))
+}
+
+/// The pause used in place of a chunk with nothing to say, a sentence end gets a longer pause than
+/// a comma or semi-colon and anything else is no pause at all.
+pub fn punctuation_pause(units: &[Unit]) -> Duration {
+    let pause = |unit: &Unit| match unit {
+        Unit::Punct(p) if p.is_sentence_end() => 400,
+        Unit::Punct(p) if p.is_pause() => 200,
+        _ => 0,
+    };
+    Duration::from_millis(units.iter().map(pause).max().unwrap_or_default())
+}
+
+/// Scores how good this location is for splitting the transcript if it's too long. This is the
+/// default scoring used by `find_splits`, 0 means never split here, 1 is only if we have to (a
+/// space), 2 is a good place (a pause) and 3 is the best place (a sentence end).
+pub fn split_score(unit: &Unit) -> usize {
     match unit {
         Unit::Punct(p) if p.is_sentence_end() => 3,
         Unit::Padding => 3,
@@ -670,19 +952,59 @@ fn split_score(unit: &Unit) -> usize {
     }
 }
 
+/// How a transcript that's too long for the model is split into chunks that are inferred
+/// separately.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum SplitStrategy {
+    /// Fits as much as possible in each chunk, splitting at the best places near the size limit.
+    /// This means the fewest inferences, see `find_splits`. This is the default.
+    #[default]
+    Greedy,
+    /// One sentence per chunk, even if they're short, only splitting sentences that don't fit.
+    /// The model gets the whole sentence so the prosody within it is intact, at the cost of more
+    /// inferences. See `sentence_splits`.
+    PerSentence,
+}
+
+impl SplitStrategy {
+    /// Finds the end of each chunk, in the same format as `find_splits`.
+    pub fn splits(self, units: &[Unit], max_size: usize) -> Vec<usize> {
+        match self {
+            Self::Greedy => find_splits(units, max_size),
+            Self::PerSentence => sentence_splits(units, max_size),
+        }
+    }
+}
+
 /// Given a length constraint uses some heuristics to attempt to split up the transcript into
 /// smaller chunks we can process. This function probably does a bit too much sorting and too many
 /// vectors. But given how long the mel generation takes it's a drop in the pond!
 ///
-/// A smarter approach may be just to split on every sentence and make use of something like rayon
-/// to run all the sentences in parallel. Or some more complicated inference passing in multiple
-/// batched inputs. But I'm working on an assumption that we do a single inference in one call to
-/// the network, and inference will be roughly similar time due to fixed window length.
+/// A smarter approach may be just to split on every sentence (see `sentence_splits`) and make use
+/// of something like rayon to run all the sentences in parallel. Or some more complicated inference
+/// passing in multiple batched inputs. But I'm working on an assumption that we do a single
+/// inference in one call to the network, and inference will be roughly similar time due to fixed
+/// window length.
+///
+/// The returned indexes are where each chunk ends, the end of the units isn't included. Every
+/// chunk is at most `max_size` long, if there's nowhere good to split the units are cut at
+/// `max_size` even if it's in the middle of a word.
 pub fn find_splits(units: &[Unit], max_size: usize) -> Vec<usize> {
+    find_splits_with(units, max_size, split_score)
+}
+
+/// The same as `find_splits` but with a custom scoring function for the split points, so you can
+/// prefer or avoid splitting at certain units. Scores work the same as in `split_score`, anything
+/// over 3 is treated as 3.
+pub fn find_splits_with(
+    units: &[Unit],
+    max_size: usize,
+    score: impl Fn(&Unit) -> usize,
+) -> Vec<usize> {
     let punct_and_spaces = units
         .iter()
         .enumerate()
-        .map(|(i, x)| (i, split_score(x)))
+        .map(|(i, x)| (i, score(x).min(3)))
         .filter(|(_, score)| *score > 0)
         .collect::<Vec<_>>();
 
@@ -726,30 +1048,77 @@ pub fn find_splits(units: &[Unit], max_size: usize) -> Vec<usize> {
             scan = false;
         }
     }
-    // Now we should merge things we broke up too small
-
+    // Now we should merge things we broke up too small, taking the furthest split point that
+    // keeps each chunk within `max_size`. If there isn't one in the window (i.e. a long run of
+    // letters with no spaces) we have to cut it at `max_size` regardless.
     let mut merged_results = vec![];
+    let mut start = 0;
+    let mut last_candidate = None;
 
-    let mut running_total = 0;
-    let mut last_insert = 0;
-
-    for i in &results {
-        if (i - last_insert) + running_total > max_size {
-            merged_results.push(last_insert);
-            running_total = i - last_insert;
-        } else {
-            running_total += i - last_insert;
+    for i in results
+        .iter()
+        .copied()
+        .filter(|i| *i > 0)
+        .chain(std::iter::once(units.len()))
+    {
+        while i - start > max_size {
+            let split = match last_candidate.take() {
+                Some(candidate) if candidate > start => candidate,
+                _ => start + max_size,
+            };
+            merged_results.push(split);
+            start = split;
         }
-        last_insert = *i;
+        last_candidate = Some(i);
     }
-    if running_total + (units.len() - last_insert) > max_size {
-        if let Some(x) = results.last() {
-            merged_results.push(*x);
+
+    merged_results
+}
+
+/// Splits the transcript after every sentence end, with any punctuation and spaces straight after
+/// it (like the quote in `"Hello." she said`) staying with the sentence. The units don't say
+/// whether a word was capitalised, so this relies on the normaliser removing the full stops that
+/// don't end a sentence. It does this after abbreviations it expands like "Dr. Smith", and after
+/// ones it keeps like "p.m.", "U.S.", "e.g." or "fig." unless the next word is capitalised.
+/// Sentences longer than `max_size` are split further with `find_splits`.
+///
+/// The indexes are where each chunk ends, the same as `find_splits` the end of the units isn't
+/// included.
+pub fn sentence_splits(units: &[Unit], max_size: usize) -> Vec<usize> {
+    let mut ends = vec![];
+    let mut i = 0;
+    while i < units.len() {
+        let is_end = matches!(units[i], Unit::Punct(p) if p.is_sentence_end());
+        i += 1;
+        if is_end {
+            while i < units.len()
+                && matches!(units[i], Unit::Punct(_) | Unit::Space | Unit::Padding)
+            {
+                i += 1;
+            }
+            ends.push(i);
         }
     }
-    merged_results.dedup();
+    if ends.last() != Some(&units.len()) {
+        ends.push(units.len());
+    }
 
-    merged_results
+    let mut splits = vec![];
+    let mut start = 0;
+    for end in ends {
+        if end - start > max_size {
+            splits.extend(
+                find_splits(&units[start..end], max_size)
+                    .into_iter()
+                    .map(|x| x + start),
+            );
+        }
+        if end < units.len() {
+            splits.push(end);
+        }
+        start = end;
+    }
+    splits
 }
 
 #[cfg(test)]
@@ -770,6 +1139,15 @@ mod test {
 
         assert_eq!(ipa_converted, arpa_parsed);
 
+        // A trailing grapheme that could start a diphthong/affricate shouldn't be lost
+        let ipa_converted = ipa_string_to_units("kæt");
+        let arpa_parsed = "K AE T"
+            .split_ascii_whitespace()
+            .map(|x| Unit::from_str(x).unwrap())
+            .collect::<Vec<Unit>>();
+
+        assert_eq!(ipa_converted, arpa_parsed);
+
         let ipa_str = "ˈoʊnfˈɔɹθ";
         let ipa_converted = ipa_string_to_units(ipa_str);
 
@@ -781,6 +1159,117 @@ mod test {
         assert_eq!(ipa_converted, arpa_parsed);
     }
 
+    #[test]
+    fn pronunciation_distances() {
+        let parse = |s: &str| {
+            s.split_ascii_whitespace()
+                .map(|x| PhoneticUnit::from_str(x).unwrap())
+                .collect::<Vec<_>>()
+        };
+        let record_noun = parse("R EH1 K ER0 D");
+        let record_verb = parse("R IH0 K AO1 R D");
+        let restressed = parse("R EH0 K ER1 D");
+
+        assert_eq!(pronunciation_distance(&record_noun, &record_noun), 0);
+        assert_eq!(pronunciation_distance(&record_noun, &[]), 5);
+        assert_eq!(pronunciation_distance(&[], &record_verb), 6);
+        // Stress only differences
+        assert_eq!(pronunciation_distance(&record_noun, &restressed), 2);
+        assert_eq!(
+            pronunciation_distance_ignoring_stress(&record_noun, &restressed),
+            0
+        );
+        // EH1->IH0, ER0->AO1 and an inserted R
+        assert_eq!(pronunciation_distance(&record_noun, &record_verb), 3);
+        assert_eq!(
+            pronunciation_distance(&record_noun, &record_verb),
+            pronunciation_distance(&record_verb, &record_noun)
+        );
+        assert_eq!(
+            pronunciation_distance_ignoring_stress(&record_noun, &record_verb),
+            3
+        );
+    }
+
+    #[test]
+    fn apply_stress_pattern() {
+        let parse = |s: &str| {
+            s.split_ascii_whitespace()
+                .map(|x| PhoneticUnit::from_str(x).unwrap())
+                .collect::<Vec<_>>()
+        };
+        // Record as a noun vs a verb
+        let record = parse("R EH1 K ER0 D");
+        assert_eq!(restress(&record, "01").unwrap(), parse("R EH0 K ER1 D"));
+        assert_eq!(restress(&record, "10").unwrap(), record);
+
+        let poetry = parse("P OW1 AH0 T R IY0");
+        assert_eq!(
+            restress(&poetry, "102").unwrap(),
+            parse("P OW1 AH0 T R IY2")
+        );
+
+        assert!(restress(&record, "1").is_err());
+        assert!(restress(&record, "100").is_err());
+        assert!(restress(&record, "1x").is_err());
+    }
+
+    #[test]
+    fn emphasis() {
+        let parse = |s: &str| {
+            s.split_ascii_whitespace()
+                .map(|x| PhoneticUnit::from_str(x).unwrap())
+                .collect::<Vec<_>>()
+        };
+        let emphasise = |s: &str| emphasise_word(&parse(s));
+        assert_eq!(emphasise("HH AH0 L OW1"), parse("HH AH2 L OW1"));
+        assert_eq!(emphasise("B AH0 N AE1 N AH0"), parse("B AH2 N AE1 N AH2"));
+        // Only the direct neighbours and existing stress is kept
+        assert_eq!(emphasise("P OW1 AH0 T R IY0"), parse("P OW1 AH2 T R IY0"));
+        assert_eq!(emphasise("K AA1 N T EH2 N T"), parse("K AA1 N T EH2 N T"));
+        // No primary stress so the strongest vowel gets it
+        assert_eq!(emphasise("DH AH0"), parse("DH AH1"));
+        assert_eq!(emphasise("AH0 B AH2 T"), parse("AH2 B AH1 T"));
+        assert_eq!(emphasise("HH M"), parse("HH M"));
+
+        assert!(stress_weight(&emphasise("HH AH0 L OW1")) > stress_weight(&parse("HH AH0 L OW1")));
+        assert_eq!(stress_weight(&parse("B AH0 N AE1 N AH2")), 3);
+    }
+
+    #[test]
+    fn phone_compatibility() {
+        let unit_list = ["AH", "AH0", "AH1", "T", "K"]
+            .iter()
+            .map(|x| Unit::from_str(x).unwrap())
+            .collect::<Vec<_>>();
+        let phones = ["AH0", "AH2", "T", "K", "S"]
+            .iter()
+            .map(|x| PhoneticUnit::from_str(x).unwrap())
+            .collect::<Vec<_>>();
+
+        let compat = check_phone_compatibility(phones, &unit_list);
+        assert!(!compat.is_exact());
+        assert_eq!(
+            compat.substitutions,
+            vec![(
+                PhoneticUnit::from_str("AH2").unwrap(),
+                Unit::from_str("AH").unwrap()
+            )]
+        );
+        assert_eq!(
+            compat.unmappable,
+            vec![PhoneticUnit::from_str("S").unwrap()]
+        );
+        assert_eq!(
+            compat.to_string(),
+            "1 substituted [AH2->AH], 1 unmappable [S]"
+        );
+
+        let compat =
+            check_phone_compatibility([PhoneticUnit::from_str("AH1").unwrap()], &unit_list);
+        assert!(compat.is_exact());
+    }
+
     #[test]
     fn split_units() {
         let text = "a b c d. e f g h. i j k l m n o p";
@@ -809,4 +1298,256 @@ mod test {
         assert!(splits[2] > splits[1] && splits[2] < splits[1] + 11);
         assert_eq!(units[splits[2]], Unit::Space);
     }
+
+    #[test]
+    fn best_match_tie_break() {
+        let units = |x: &str| {
+            x.split_ascii_whitespace()
+                .map(|x| Unit::from_str(x).unwrap())
+                .collect::<Vec<_>>()
+        };
+        let unit = |x: &str| Unit::from_str(x).unwrap();
+
+        let list = units("AA1 B AA2 AA0 AA");
+        assert_eq!(best_match_for_unit(&unit("AA2"), &list), Some(2));
+        assert_eq!(best_match_for_unit(&unit("AA"), &list), Some(4));
+        assert_eq!(best_match_for_unit(&unit("B"), &list), Some(1));
+
+        // No unmarked variant so the unstressed one is used
+        let list = units("AA2 AA1 AA0");
+        assert_eq!(best_match_for_unit(&unit("AA"), &list), Some(2));
+        // Reordering the candidates picks the same phone
+        let list = units("AA0 AA2 AA1");
+        assert_eq!(best_match_for_unit(&unit("AA"), &list), Some(0));
+
+        // Stressed phone without a stressed variant prefers the unmarked one
+        let list = units("AA0 AA");
+        assert_eq!(best_match_for_unit(&unit("AA1"), &list), Some(1));
+
+        // Tertiary stress goes to secondary stress wherever it is in the list
+        let list = units("AA0 AA2 AA1");
+        assert_eq!(best_match_for_unit(&unit("AA3"), &list), Some(1));
+        let list = units("AA0 AA1");
+        assert_eq!(best_match_for_unit(&unit("AA3"), &list), Some(0));
+
+        // Duplicates go to the first one
+        let list = units("AA1 AA1");
+        assert_eq!(best_match_for_unit(&unit("AA1"), &list), Some(0));
+        assert_eq!(best_match_for_unit(&unit("B"), &list), None);
+    }
+
+    #[test]
+    fn compact_string_round_trip() {
+        let units = vec![
+            Unit::Padding,
+            Unit::from_str("HH").unwrap(),
+            Unit::from_str("AH0").unwrap(),
+            Unit::from_str("B").unwrap(),
+            Unit::Space,
+            Unit::Character('B'),
+            Unit::Character('a'),
+            Unit::Character('#'),
+            Unit::Character('_'),
+            Unit::Character('\t'),
+            Unit::Character('é'),
+            Unit::Space,
+            Unit::Unk,
+            Unit::Punct(Punctuation::Comma),
+            Unit::Punct(Punctuation::Apostrophe),
+            Unit::Punct(Punctuation::FullStop),
+            Unit::Padding,
+        ];
+        let compact = units_to_compact_string(&units);
+        assert_eq!(
+            compact,
+            "<PAD> HH AH0 B _ #B #a ## #_ #U+0009 #é _ <UNK> , ' . <PAD>"
+        );
+        assert_eq!(compact_string_to_units(&compact).unwrap(), units);
+
+        assert_eq!(compact_string_to_units("").unwrap(), vec![]);
+        assert!(compact_string_to_units("#ab").is_err());
+        assert!(compact_string_to_units("#U+D800").is_err());
+        assert!(compact_string_to_units("XYZ").is_err());
+    }
+
+    #[test]
+    fn space_collapsing() {
+        let parse = |s: &str| {
+            s.split(' ')
+                .map(|x| match x {
+                    "_" => Unit::Space,
+                    "#" => Unit::Padding,
+                    x => Unit::from_str(x).unwrap(),
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut units = parse("_ _ HH AH0 L OW1 _ _ _ , _ W ER1 L D _ # _ B AY1 _ _");
+        collapse_spaces(&mut units);
+        assert_eq!(units, parse("HH AH0 L OW1 _ , _ W ER1 L D # B AY1"));
+
+        let mut units = parse("_ # _");
+        collapse_spaces(&mut units);
+        assert!(units.is_empty());
+
+        let mut units = parse("HH AY1 _ W ER1 L D");
+        let expected = units.clone();
+        collapse_spaces(&mut units);
+        assert_eq!(units, expected);
+    }
+
+    #[test]
+    fn punctuation_only_chunks() {
+        let units = |s: &str| {
+            s.split(' ')
+                .map(|x| match x {
+                    "_" => Unit::Space,
+                    x => Unit::from_str(x).unwrap(),
+                })
+                .collect::<Vec<_>>()
+        };
+        assert!(is_speakable(&units("HH AY1 !")));
+        assert!(is_speakable(&[
+            Unit::Character('a'),
+            Unit::Punct(Punctuation::FullStop)
+        ]));
+        assert!(!is_speakable(&units(". . .")));
+        assert!(!is_speakable(&units("? _ !")));
+        assert!(!is_speakable(&[]));
+
+        assert_eq!(
+            punctuation_pause(&units(". . .")),
+            Duration::from_millis(400)
+        );
+        assert_eq!(
+            punctuation_pause(&units(", _ ?")),
+            Duration::from_millis(400)
+        );
+        assert_eq!(punctuation_pause(&units(",")), Duration::from_millis(200));
+        assert_eq!(punctuation_pause(&units("_ -")), Duration::ZERO);
+    }
+
+    #[test]
+    fn custom_split_scores() {
+        let units = "aaaa bbbb; cccc dddd"
+            .chars()
+            .map(|x| Unit::from_str(&x.to_string()).unwrap())
+            .collect::<Vec<_>>();
+
+        let splits = find_splits(&units, 12);
+        assert_eq!(splits, vec![9]);
+
+        // Never split on a semicolon
+        let no_semicolons = |unit: &Unit| match unit {
+            Unit::Punct(Punctuation::SemiColon) => 0,
+            x => split_score(x),
+        };
+        let splits = find_splits_with(&units, 12, no_semicolons);
+        assert!(!splits.is_empty());
+        assert!(splits.iter().all(|x| units[*x] == Unit::Space));
+    }
+
+    #[test]
+    fn per_sentence_splits() {
+        let to_units = |text: &str| {
+            let mut normalised = normalise(text).unwrap();
+            normalised.convert_to_units();
+            let mut units = vec![];
+            for chunk in normalised.drain_all() {
+                match chunk {
+                    NormaliserChunk::Pronunciation(mut u) => units.append(&mut u),
+                    NormaliserChunk::Punct(p) => units.push(Unit::Punct(p)),
+                    _ => {}
+                }
+            }
+            collapse_spaces(&mut units);
+            units
+        };
+        let to_chunks = |units: &[Unit], splits: Vec<usize>| {
+            let mut start = 0;
+            splits
+                .into_iter()
+                .chain(std::iter::once(units.len()))
+                .map(|end| {
+                    let chunk = units[start..end]
+                        .iter()
+                        .map(|x| x.to_string())
+                        .collect::<String>();
+                    start = end;
+                    chunk
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Full stops after abbreviations only end the sentence before a capital
+        let units = to_units(
+            "It ends at 5 p.m. tomorrow. See fig. 3 for the U.S. data, e.g. this. \
+             It ends at 5 p.m. Then we go.",
+        );
+        assert_eq!(
+            to_chunks(&units, sentence_splits(&units, 200)),
+            [
+                "it ends at five pm tomorrow. ",
+                "see fig three for the us data, eg this. ",
+                "it ends at five pm. ",
+                "then we go."
+            ]
+        );
+
+        let units = to_units("Hi there. How are you?! \"Fine,\" she said. Dr. Smith is here.");
+        let chunks = |splits: Vec<usize>| to_chunks(&units, splits);
+
+        // Everything fits in one window so greedy doesn't split it at all
+        assert!(find_splits(&units, 100).is_empty());
+        assert_eq!(
+            SplitStrategy::Greedy.splits(&units, 100),
+            find_splits(&units, 100)
+        );
+
+        let sentences = chunks(SplitStrategy::PerSentence.splits(&units, 100));
+        assert_eq!(
+            sentences,
+            [
+                "hi there. ",
+                "how are you? ",
+                "fine, she said. ",
+                "doctor smith is here."
+            ]
+        );
+
+        // With a small window greedy packs as much as it can and splits before the full stops,
+        // per sentence keeps the sentence ends but still fits the window
+        let greedy = chunks(find_splits(&units, 30));
+        let sentences = chunks(sentence_splits(&units, 30));
+        assert!(greedy.len() < sentences.len());
+        assert!(greedy.iter().skip(1).any(|x| x.starts_with('.')));
+        assert!(sentences.iter().all(|x| x.chars().count() <= 30));
+        assert!(sentences[0].ends_with(". "));
+        assert_eq!(greedy.concat(), sentences.concat());
+
+        assert!(sentence_splits(&[], 30).is_empty());
+    }
+
+    #[test]
+    fn splits_without_break_points() {
+        let units = vec![Unit::from_str("a").unwrap(); 250];
+
+        let splits = find_splits(&units, 100);
+        assert_eq!(splits, vec![100, 200]);
+
+        let mut start = 0;
+        for end in splits.iter().copied().chain(std::iter::once(units.len())) {
+            assert!(end > start);
+            assert!(end - start <= 100);
+            start = end;
+        }
+
+        // A long word after a good split point still gets cut
+        let mut units = "aaaa "
+            .chars()
+            .map(|x| Unit::from_str(&x.to_string()).unwrap())
+            .collect::<Vec<_>>();
+        units.extend(vec![Unit::from_str("b").unwrap(); 30]);
+        let splits = find_splits(&units, 12);
+        assert_eq!(splits, vec![4, 16, 28]);
+    }
 }
@@ -0,0 +1,139 @@
+//! Streaming synthesis, so audio can be played while the rest of the utterance is still being
+//! generated. Without streaming the time to first audio is the time to synthesise the whole chunk
+//! of text between two breaks, which for a long sentence can be several seconds.
+//!
+//! Tacotron2 decodes one spectrogram frame at a time, so the frames are passed on in blocks as
+//! soon as they're decoded (see `Tacotron2::infer_streaming`). Each block is then vocoded and the
+//! audio passed to the caller. The tradeoffs are:
+//!
+//! * Latency: the first audio is available after `block_frames` plus `POSTNET_CONTEXT` decoder
+//!   steps. Each frame is ~11.6ms of audio so the default of 16 frames is ~190ms of audio per
+//!   block. Smaller blocks get audio out sooner but run the postnet and vocoder more times.
+//! * Quality: the spectrogram is identical to the non-streaming one, but Griffin-Lim estimates the
+//!   phase from the whole spectrogram it's given. Vocoding blocks separately means the phase won't
+//!   line up perfectly at the block boundaries. To reduce this each block is vocoded with the last
+//!   `vocoder_context` frames of the previous block in front of it and the overlapping audio is
+//!   crossfaded. More context gives smoother joins at the cost of more vocoder work.
+//!
+//! The audio ends up the same length as the non-streaming output.
+use crate::Vocoder;
+use ndarray::{concatenate, s, Array2, Axis};
+
+/// Settings for streaming synthesis, see the module docs for the tradeoffs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StreamingOptions {
+    /// Number of spectrogram frames in each block passed to the vocoder
+    pub block_frames: usize,
+    /// Number of frames from the previous block vocoded with each block to smooth the joins. This
+    /// is at least 1.
+    pub vocoder_context: usize,
+}
+
+impl Default for StreamingOptions {
+    fn default() -> Self {
+        Self {
+            block_frames: 16,
+            vocoder_context: 8,
+        }
+    }
+}
+
+/// Vocodes a spectrogram a block at a time, crossfading the audio between blocks. Audio for the
+/// end of each block is held back until the next block (or `finish`) so it can be crossfaded.
+pub struct StreamingVocoder<'a> {
+    vocoder: &'a dyn Vocoder,
+    context: usize,
+    hop_length: usize,
+    /// The last frames of the previous block
+    history: Array2<f32>,
+    /// Audio from the previous block which hasn't been returned yet
+    pending: Vec<f32>,
+}
+
+impl<'a> StreamingVocoder<'a> {
+    /// Creates a streaming vocoder keeping `context` frames between blocks. `hop_length` is the
+    /// number of samples the vocoder generates for each frame, for tacotron2 this is
+    /// `Tacotron2Config::hop_length`.
+    pub fn new(vocoder: &'a dyn Vocoder, context: usize, hop_length: usize) -> Self {
+        Self {
+            vocoder,
+            context: context.max(1),
+            hop_length,
+            history: Array2::zeros((0, 0)),
+            pending: vec![],
+        }
+    }
+
+    /// Vocodes the next block of the spectrogram, with shape [80, frames], returning the audio
+    /// that's ready.
+    pub fn push(&mut self, mel: &Array2<f32>) -> anyhow::Result<Vec<f32>> {
+        if mel.is_empty() {
+            return Ok(vec![]);
+        }
+        let input = if self.history.is_empty() {
+            mel.clone()
+        } else {
+            concatenate(Axis(1), &[self.history.view(), mel.view()])?
+        };
+        let audio = self.vocoder.infer(&input)?;
+        let history_len = input.ncols() - mel.ncols();
+
+        // The pending audio starts `fade` samples before the start of this block
+        let block_start = (history_len * self.hop_length).min(audio.len());
+        let fade = self
+            .pending
+            .len()
+            .min(self.hop_length)
+            .min(block_start)
+            .min(audio.len() - block_start);
+        let mut output = Vec::with_capacity(mel.ncols() * self.hop_length);
+        for i in 0..fade {
+            let weight = (i as f32 + 0.5) / fade as f32;
+            let new = audio[block_start - fade + i];
+            output.push(self.pending[i] * (1.0 - weight) + new * weight);
+        }
+        // Hold back the end of the block so it can be crossfaded with the next one
+        let emit_end = (input.ncols() * self.hop_length)
+            .saturating_sub(self.hop_length)
+            .clamp(block_start, audio.len());
+        output.extend_from_slice(&audio[block_start..emit_end]);
+        self.pending = audio[emit_end..].to_vec();
+
+        let keep = self.context.min(input.ncols());
+        self.history = input.slice(s![.., (input.ncols() - keep)..]).to_owned();
+        Ok(output)
+    }
+
+    /// Returns the rest of the audio once the last block has been pushed.
+    pub fn finish(self) -> Vec<f32> {
+        self.pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tacotron2::{create_griffin_lim, Tacotron2Config};
+
+    #[test]
+    fn streaming_length_matches() {
+        let config = Tacotron2Config::default();
+        let vocoder = create_griffin_lim(&config).unwrap();
+        let mel = Array2::from_shape_fn((80, 37), |(i, j)| -5.0 + (i + j) as f32 * 0.01);
+        let expected = vocoder.infer(&mel).unwrap().len();
+
+        for (block, context) in [(16, 8), (1, 1), (5, 20), (37, 8), (50, 0)] {
+            let mut streaming = StreamingVocoder::new(&vocoder, context, config.hop_length);
+            let mut audio = vec![];
+            let mut start = 0;
+            while start < mel.ncols() {
+                let end = (start + block).min(mel.ncols());
+                let chunk = mel.slice(s![.., start..end]).to_owned();
+                audio.extend(streaming.push(&chunk).unwrap());
+                start = end;
+            }
+            audio.extend(streaming.finish());
+            assert_eq!(audio.len(), expected, "block {} context {}", block, context);
+        }
+    }
+}
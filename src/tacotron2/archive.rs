@@ -0,0 +1,195 @@
+//! Loading a tacotron2 model from a single archive instead of a folder of ONNX files. This makes
+//! it easier to version and distribute a model as one file.
+//!
+//! The archive can be a zip, tar or gzipped tar (`.zip`, `.tar`, `.tar.gz` or `.tgz`) and should
+//! contain:
+//!
+//! * encoder.onnx
+//! * decoder_iter.onnx
+//! * postnet.onnx
+//! * config.json (optional)
+//!
+//! Files are matched on their name so they can either be at the root of the archive or inside a
+//! folder, i.e. `tacotron2/encoder.onnx` from running `tar -czf tacotron2.tar.gz tacotron2`. Any
+//! other files are ignored. Everything is extracted into memory, nothing is written to disk.
+use anyhow::Context;
+use flate2::read::GzDecoder;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek};
+use std::path::Path;
+
+/// The files that make up a tacotron2 model, see `Tacotron2::load_from_files`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ModelFiles {
+    /// Contents of encoder.onnx
+    pub encoder: Vec<u8>,
+    /// Contents of decoder_iter.onnx
+    pub decoder: Vec<u8>,
+    /// Contents of postnet.onnx
+    pub postnet: Vec<u8>,
+    /// Contents of config.json if it was present
+    pub config: Option<Vec<u8>>,
+}
+
+impl ModelFiles {
+    /// Opens an archive picking the format from the file extension. See the module docs for the
+    /// layout.
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let name = path
+            .file_name()
+            .map(|x| x.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+        let file = BufReader::new(
+            File::open(path).with_context(|| format!("opening '{}'", path.display()))?,
+        );
+        if name.ends_with(".zip") {
+            Self::from_zip(file)
+        } else if name.ends_with(".tar") {
+            Self::from_tar(file)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Self::from_tar(GzDecoder::new(file))
+        } else {
+            anyhow::bail!(
+                "Unsupported model archive '{}', expected a zip or tar file",
+                path.display()
+            )
+        }
+    }
+
+    /// Reads the model from a zip archive.
+    pub fn from_zip(reader: impl Read + Seek) -> anyhow::Result<Self> {
+        let mut archive = zip::ZipArchive::new(reader)?;
+        let mut files = FoundFiles::default();
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i)?;
+            if file.is_dir() {
+                continue;
+            }
+            let name = file.name().to_string();
+            if let Some(slot) = files.slot(&name) {
+                let mut data = vec![];
+                file.read_to_end(&mut data)
+                    .with_context(|| format!("reading '{}' from zip", name))?;
+                *slot = Some(data);
+            }
+        }
+        files.finish()
+    }
+
+    /// Reads the model from a tar archive, wrap the reader in a decoder for compressed archives.
+    pub fn from_tar(reader: impl Read) -> anyhow::Result<Self> {
+        let mut archive = tar::Archive::new(reader);
+        let mut files = FoundFiles::default();
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let name = entry.path()?.to_string_lossy().to_string();
+            if let Some(slot) = files.slot(&name) {
+                let mut data = vec![];
+                entry
+                    .read_to_end(&mut data)
+                    .with_context(|| format!("reading '{}' from tar", name))?;
+                *slot = Some(data);
+            }
+        }
+        files.finish()
+    }
+}
+
+#[derive(Default)]
+struct FoundFiles {
+    encoder: Option<Vec<u8>>,
+    decoder: Option<Vec<u8>>,
+    postnet: Option<Vec<u8>>,
+    config: Option<Vec<u8>>,
+}
+
+impl FoundFiles {
+    /// Where to put the file with the given path in the archive, if it's one we want
+    fn slot(&mut self, path: &str) -> Option<&mut Option<Vec<u8>>> {
+        match path.rsplit('/').next()? {
+            "encoder.onnx" => Some(&mut self.encoder),
+            "decoder_iter.onnx" => Some(&mut self.decoder),
+            "postnet.onnx" => Some(&mut self.postnet),
+            "config.json" => Some(&mut self.config),
+            _ => None,
+        }
+    }
+
+    fn finish(self) -> anyhow::Result<ModelFiles> {
+        Ok(ModelFiles {
+            encoder: self.encoder.context("archive has no encoder.onnx")?,
+            decoder: self.decoder.context("archive has no decoder_iter.onnx")?,
+            postnet: self.postnet.context("archive has no postnet.onnx")?,
+            config: self.config,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+
+    #[test]
+    fn read_zip() {
+        let mut writer = zip::ZipWriter::new(Cursor::new(vec![]));
+        let options = zip::write::FileOptions::default();
+        for (name, data) in [
+            ("tacotron2/encoder.onnx", "encoder"),
+            ("tacotron2/decoder_iter.onnx", "decoder"),
+            ("tacotron2/postnet.onnx", "postnet"),
+            ("tacotron2/README.md", "ignored"),
+        ] {
+            writer.start_file(name, options).unwrap();
+            writer.write_all(data.as_bytes()).unwrap();
+        }
+        let archive = writer.finish().unwrap().into_inner();
+
+        let files = ModelFiles::from_zip(Cursor::new(&archive)).unwrap();
+        assert_eq!(
+            files,
+            ModelFiles {
+                encoder: b"encoder".to_vec(),
+                decoder: b"decoder".to_vec(),
+                postnet: b"postnet".to_vec(),
+                config: None,
+            }
+        );
+
+        let mut writer = zip::ZipWriter::new(Cursor::new(vec![]));
+        writer.start_file("encoder.onnx", options).unwrap();
+        writer.write_all(b"encoder").unwrap();
+        let archive = writer.finish().unwrap().into_inner();
+        assert!(ModelFiles::from_zip(Cursor::new(&archive)).is_err());
+    }
+
+    #[test]
+    fn read_tar() {
+        let mut builder = tar::Builder::new(vec![]);
+        for (name, data) in [
+            ("encoder.onnx", "encoder"),
+            ("decoder_iter.onnx", "decoder"),
+            ("postnet.onnx", "postnet"),
+            ("config.json", "{}"),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, data.as_bytes())
+                .unwrap();
+        }
+        let archive = builder.into_inner().unwrap();
+
+        let files = ModelFiles::from_tar(archive.as_slice()).unwrap();
+        assert_eq!(files.encoder, b"encoder");
+        assert_eq!(files.decoder, b"decoder");
+        assert_eq!(files.postnet, b"postnet");
+        assert_eq!(files.config.as_deref(), Some(b"{}".as_slice()));
+    }
+}
@@ -0,0 +1,180 @@
+//! The parameters of a tacotron2 model that aren't in the ONNX graphs. These are read from a
+//! `config.json` next to the ONNX files (or in the archive) so a retrained model can be swapped in
+//! without recompiling, anything that's missing keeps the value for the nvidia pretrained model.
+//!
+//! The field names match the hyperparameters in the nvidia tacotron2 repo so their values can be
+//! copied across, any other fields in the file are ignored. A config for a model trained on phones
+//! only with a lower top of the mel filter bank could look like:
+//!
+//! ```json
+//! {
+//!     "sampling_rate": 22050,
+//!     "mel_fmax": 7600.0,
+//!     "symbols": ["_", " ", ".", ",", "@AA0", "@AA1", "@AA2"]
+//! }
+//! ```
+//!
+//! `Tacotron2` on its own can load models at other sample rates, but `XdTts` only supports 22050Hz
+//! with a hop length of 256 (see `WAV_SPEC` and `HOP_LENGTH`) as the audio output and the timings
+//! are worked out at that rate. `XdTts::new` returns an error for any other model.
+use super::HOP_LENGTH;
+use crate::phonemes::*;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Mel spectrogram, network and input parameters of a tacotron2 model, see the module docs. Only
+/// a `sampling_rate` of 22050 and a `hop_length` of 256 can be used with `XdTts`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Tacotron2Config {
+    /// Sample rate of the audio the model was trained on, `XdTts` needs this to be 22050
+    pub sampling_rate: u32,
+    /// Number of FFT bins used to make the spectrogram, this is also the window length
+    pub filter_length: usize,
+    /// Number of audio samples between each frame of the spectrogram, `XdTts` needs this to be 256
+    pub hop_length: usize,
+    /// Number of mel channels in the spectrogram
+    pub n_mel_channels: usize,
+    /// Lowest frequency in the mel filter bank
+    pub mel_fmin: f32,
+    /// Highest frequency in the mel filter bank, if not set this is half the sample rate
+    pub mel_fmax: Option<f32>,
+    /// Size of the attention LSTM state
+    pub attention_rnn_dim: usize,
+    /// Size of the decoder LSTM state
+    pub decoder_rnn_dim: usize,
+    /// Size of the encoder output for each input unit
+    pub encoder_embedding_dim: usize,
+    /// Input symbols in ID order. `_` is padding, phones are prefixed with `@` (i.e. `@AA1`) to
+    /// tell them apart from characters and anything else is a single character or punctuation. If
+    /// not set the IDs are generated from the phone set the model is loaded with.
+    pub symbols: Option<Vec<String>>,
+}
+
+impl Default for Tacotron2Config {
+    fn default() -> Self {
+        Self {
+            sampling_rate: 22050,
+            filter_length: 1024,
+            hop_length: HOP_LENGTH,
+            n_mel_channels: 80,
+            mel_fmin: 0.0,
+            mel_fmax: Some(8000.0),
+            attention_rnn_dim: 1024,
+            decoder_rnn_dim: 1024,
+            encoder_embedding_dim: 512,
+            symbols: None,
+        }
+    }
+}
+
+impl Tacotron2Config {
+    /// Reads the config from a json file.
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read(path).with_context(|| format!("reading '{}'", path.display()))?;
+        Self::from_json(&json).with_context(|| format!("parsing '{}'", path.display()))
+    }
+
+    /// Parses the config from json and checks the values make sense.
+    pub fn from_json(json: &[u8]) -> anyhow::Result<Self> {
+        let config: Self = serde_json::from_slice(json)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        anyhow::ensure!(self.sampling_rate > 0, "sampling_rate must be above 0");
+        anyhow::ensure!(self.n_mel_channels > 0, "n_mel_channels must be above 0");
+        anyhow::ensure!(
+            self.hop_length > 0 && self.hop_length < self.filter_length,
+            "hop_length must be above 0 and less than filter_length"
+        );
+        if let Some(symbols) = self.symbols.as_ref() {
+            for symbol in symbols {
+                parse_symbol(symbol)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The units in the order of their IDs, from `symbols` if it's set otherwise generated from
+    /// the phone set.
+    pub fn phoneme_ids(&self, phone_set: &PhoneSet) -> anyhow::Result<Vec<Unit>> {
+        match self.symbols.as_ref() {
+            Some(symbols) => symbols.iter().map(|x| parse_symbol(x)).collect(),
+            None => Ok(super::generate_id_list(phone_set)),
+        }
+    }
+}
+
+/// Parses one of the `symbols` in the config, see `Tacotron2Config::symbols` for the format.
+fn parse_symbol(symbol: &str) -> anyhow::Result<Unit> {
+    if symbol == "_" {
+        return Ok(Unit::Padding);
+    }
+    if symbol == " " {
+        return Ok(Unit::Space);
+    }
+    if let Some(phone) = symbol.strip_prefix('@') {
+        return PhoneticUnit::from_str(phone)
+            .map(Unit::Phone)
+            .with_context(|| format!("invalid phone symbol '{}'", symbol));
+    }
+    if let Ok(punct) = Punctuation::from_str(symbol) {
+        return Ok(Unit::Punct(punct));
+    }
+    let mut chars = symbol.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(Unit::Character(c)),
+        _ => anyhow::bail!(
+            "invalid symbol '{}', expected a single character or a phone starting with @",
+            symbol
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_ids() {
+        let config = Tacotron2Config::from_json(b"{}").unwrap();
+        assert_eq!(config, Tacotron2Config::default());
+        let phone_set = PhoneSet::english();
+        assert_eq!(
+            config.phoneme_ids(phone_set).unwrap(),
+            super::super::generate_id_list(phone_set)
+        );
+    }
+
+    #[test]
+    fn symbols() {
+        let config = Tacotron2Config::from_json(
+            br#"{"symbols": ["_", " ", ".", "'", "a", "B", "@B", "@AA1"], "unknown": 1}"#,
+        )
+        .unwrap();
+        let ids = config.phoneme_ids(PhoneSet::english()).unwrap();
+        assert_eq!(
+            ids,
+            [
+                Unit::Padding,
+                Unit::Space,
+                Unit::Punct(Punctuation::FullStop),
+                Unit::Punct(Punctuation::Apostrophe),
+                Unit::Character('a'),
+                Unit::Character('B'),
+                Unit::from_str("B").unwrap(),
+                Unit::from_str("AA1").unwrap(),
+            ]
+        );
+
+        assert!(Tacotron2Config::from_json(br#"{"symbols": ["ab"]}"#).is_err());
+        assert!(Tacotron2Config::from_json(br#"{"symbols": ["@XX"]}"#).is_err());
+        assert!(Tacotron2Config::from_json(br#"{"hop_length": 2048}"#).is_err());
+        assert!(Tacotron2Config::from_json(br#"{"sampling_rate": "fast"}"#).is_err());
+    }
+}
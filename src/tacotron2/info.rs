@@ -0,0 +1,139 @@
+//! Describes the inputs and outputs of the loaded ONNX graphs. A model exported with different
+//! tensor names, types or shapes to the ones the inference code expects is the main reason a
+//! custom model fails to load or run, so this is the first thing to check when that happens.
+//!
+//! Dynamic dimensions are reported as -1 the same as ONNX runtime does.
+use super::Tacotron2;
+use ort::{Session, ValueType};
+use std::fmt;
+
+/// Inputs and outputs of each of the tacotron2 graphs, see `Tacotron2::describe`. The `Display`
+/// implementation gives a readable summary.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ModelInfo {
+    /// The encoder graph (encoder.onnx)
+    pub encoder: GraphInfo,
+    /// The decoder step graph (decoder_iter.onnx)
+    pub decoder: GraphInfo,
+    /// The postnet graph (postnet.onnx)
+    pub postnet: GraphInfo,
+}
+
+/// The inputs and outputs of one ONNX graph in the order the graph declares them.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GraphInfo {
+    pub inputs: Vec<TensorInfo>,
+    pub outputs: Vec<TensorInfo>,
+}
+
+/// Name, type and shape of a graph input or output.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TensorInfo {
+    /// Name of the input/output, the inference code looks these up by name
+    pub name: String,
+    /// Element type, i.e. `Float32`. For anything that isn't a tensor this is the debug output of
+    /// the value type
+    pub element_type: String,
+    /// Shape of the tensor with -1 for dynamic dimensions, empty for values that aren't tensors
+    pub shape: Vec<i64>,
+}
+
+impl TensorInfo {
+    fn new(name: &str, value_type: &ValueType) -> Self {
+        let (element_type, shape) = match value_type {
+            ValueType::Tensor { ty, dimensions } => (format!("{:?}", ty), dimensions.clone()),
+            other => (format!("{:?}", other), vec![]),
+        };
+        Self {
+            name: name.to_string(),
+            element_type,
+            shape,
+        }
+    }
+}
+
+impl GraphInfo {
+    fn from_session(session: &Session) -> Self {
+        Self {
+            inputs: session
+                .inputs
+                .iter()
+                .map(|x| TensorInfo::new(&x.name, &x.input_type))
+                .collect(),
+            outputs: session
+                .outputs
+                .iter()
+                .map(|x| TensorInfo::new(&x.name, &x.output_type))
+                .collect(),
+        }
+    }
+}
+
+impl Tacotron2 {
+    /// Lists the inputs and outputs of the encoder, decoder and postnet graphs. Use this to check
+    /// a custom export matches what the inference code expects.
+    pub fn describe(&self) -> ModelInfo {
+        ModelInfo {
+            encoder: GraphInfo::from_session(&self.encoder),
+            decoder: GraphInfo::from_session(&self.decoder),
+            postnet: GraphInfo::from_session(&self.postnet),
+        }
+    }
+}
+
+impl fmt::Display for TensorInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {} {:?}", self.name, self.element_type, self.shape)
+    }
+}
+
+impl fmt::Display for ModelInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, graph) in [
+            ("Encoder", &self.encoder),
+            ("Decoder", &self.decoder),
+            ("Postnet", &self.postnet),
+        ] {
+            writeln!(f, "{}:", name)?;
+            writeln!(f, "  Inputs:")?;
+            for input in &graph.inputs {
+                writeln!(f, "    {}", input)?;
+            }
+            writeln!(f, "  Outputs:")?;
+            for output in &graph.outputs {
+                writeln!(f, "    {}", output)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ort::TensorElementType;
+
+    #[test]
+    fn tensor_info() {
+        let mel = TensorInfo::new(
+            "mel_outputs_postnet",
+            &ValueType::Tensor {
+                ty: TensorElementType::Float32,
+                dimensions: vec![1, 80, -1],
+            },
+        );
+        assert_eq!(mel.element_type, "Float32");
+        assert_eq!(mel.shape, vec![1, 80, -1]);
+        assert_eq!(mel.to_string(), "mel_outputs_postnet: Float32 [1, 80, -1]");
+
+        let sequence = TensorInfo::new(
+            "sequence",
+            &ValueType::Sequence(Box::new(ValueType::Tensor {
+                ty: TensorElementType::Int64,
+                dimensions: vec![-1],
+            })),
+        );
+        assert!(sequence.element_type.starts_with("Sequence"));
+        assert!(sequence.shape.is_empty());
+    }
+}
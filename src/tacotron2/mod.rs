@@ -28,12 +28,12 @@
 //! the model with the correct parameters to generate audio. If these parameters are wrong then the
 //! audio will sound pitch shifted, like random noise or demonic nasal demons.
 //!
-//! Some things have been done quickly or within the code to try and keep things constrained to the
-//! same file. In general I'd recommend every neural network come with some sort of config file
-//! (json etc) which details things like these parameters, the IDs for the input/output and
-//! relevant tensor names. This allows faster swapping of models if researchers are playing around
-//! with different training or model setups. For keeping to one known architecture the returns can
-//! diminish especially if you're not training your own model.
+//! In general I'd recommend every neural network come with some sort of config file (json etc)
+//! which details things like these parameters, the IDs for the input/output and relevant tensor
+//! names. This allows faster swapping of models if researchers are playing around with different
+//! training or model setups. Here the parameters are read from a `config.json` alongside the model,
+//! see `Tacotron2Config`, and if there isn't one the values for the nvidia pretrained model are
+//! used.
 //!
 //! # What's is an Encoder-Decoder Model?
 //!
@@ -62,41 +62,38 @@
 //! LSTMs. You can see these architectures appear in other audio related tasks such as
 //! transcription and also in machine translation. These areas as well as TTS will refer to a lot
 //! of related foundational knowledge.
+pub use self::archive::ModelFiles;
+pub use self::config::Tacotron2Config;
+pub use self::info::{GraphInfo, ModelInfo, TensorInfo};
 use crate::phonemes::*;
+use crate::CmuDictionary;
 use anyhow::Context;
 use griffin_lim::mel::create_mel_filter_bank;
 use griffin_lim::GriffinLim;
 use ndarray::{concatenate, prelude::*};
-use ort::{inputs, GraphOptimizationLevel, Session};
+use ort::{
+    inputs, CPUExecutionProvider, CUDAExecutionProvider, DirectMLExecutionProvider,
+    ExecutionProvider as _, ExecutionProviderDispatch, GraphOptimizationLevel, Session,
+    SessionBuilder, TensorRTExecutionProvider,
+};
+use std::collections::BTreeMap;
+use std::fmt;
 use std::path::Path;
-use std::str::FromStr;
-use tracing::debug;
-
-// Mel parameters:
-// fmin 0
-// fmax 7000
-// win-length 1024
-// hop-length 256
-// filter length 1024 (number of FFTs)
-// sample rate 22050
-// number of mels 80
-//
-// How many FFT bins?
-//
-// So the paper says fmin-fmax are 125Hz to 7.6kHz
+use std::time::Instant;
+use tracing::{debug, warn};
 
-/// Function to generate the ordered unit ID list for tacotron2. Any character/punctuation/phoneme
-/// can be searched in this list and it's index will correspond to the model input.
-fn generate_id_list() -> Vec<Unit> {
-    let phones = [
-        "AA", "AA0", "AA1", "AA2", "AE", "AE0", "AE1", "AE2", "AH", "AH0", "AH1", "AH2", "AO",
-        "AO0", "AO1", "AO2", "AW", "AW0", "AW1", "AW2", "AY", "AY0", "AY1", "AY2", "B", "CH", "D",
-        "DH", "EH", "EH0", "EH1", "EH2", "ER", "ER0", "ER1", "ER2", "EY", "EY0", "EY1", "EY2", "F",
-        "G", "HH", "IH", "IH0", "IH1", "IH2", "IY", "IY0", "IY1", "IY2", "JH", "K", "L", "M", "N",
-        "NG", "OW", "OW0", "OW1", "OW2", "OY", "OY0", "OY1", "OY2", "P", "R", "S", "SH", "T", "TH",
-        "UH", "UH0", "UH1", "UH2", "UW", "UW0", "UW1", "UW2", "V", "W", "Y", "Z", "ZH",
-    ];
+mod archive;
+mod config;
+mod info;
+
+// Mel parameters are in `Tacotron2Config`, the paper says fmin-fmax are 125Hz to 7.6kHz but the
+// nvidia model uses 0Hz to 8kHz.
 
+/// Function to generate the ordered unit ID list for tacotron2. Any character/punctuation/phoneme
+/// can be searched in this list and it's index will correspond to the model input. The phones come
+/// last and are taken from the phone set in order. This is used when the model config doesn't
+/// list the symbols.
+fn generate_id_list(phone_set: &PhoneSet) -> Vec<Unit> {
     let mut res = vec![
         Unit::Padding,
         Unit::Punct(Punctuation::Dash),
@@ -116,11 +113,20 @@ fn generate_id_list() -> Vec<Unit> {
         .map(Unit::Character);
 
     res.extend(characters);
-    res.extend(phones.iter().map(|x| Unit::from_str(x).unwrap()));
+    res.extend(phone_set.phones().iter().copied().map(Unit::Phone));
 
     res
 }
 
+/// Finds the ID of the padding unit, models trained with a different symbol order may not have it
+/// at 0.
+fn padding_id(ids: &[Unit]) -> anyhow::Result<i64> {
+    ids.iter()
+        .position(|x| *x == Unit::Padding)
+        .map(|x| x as i64)
+        .context("model ID list has no padding unit")
+}
+
 /// Sigmoid function, would have been done by the network but the ONNX split meant it was no
 /// longer part of the graph.
 fn sigmoid(x: f32) -> f32 {
@@ -132,6 +138,372 @@ fn sigmoid(x: f32) -> f32 {
     }
 }
 
+/// The value tacotron2 uses for silence in the log-mel spectrogram, `ln(1e-5)`
+pub const LOG_MEL_FLOOR: f32 = -11.512925;
+
+/// What to do if the network outputs NaN or infinite values in the spectrogram. This shouldn't
+/// happen with a good model, but numerical issues or a badly exported/trained model can cause it
+/// and passing them to the vocoder results in loud, ear-splitting noise.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum NonFinitePolicy {
+    /// Return an error from inference, this is the default as a non-finite output usually means
+    /// something is wrong with the model.
+    #[default]
+    Error,
+    /// Log a warning and replace the values so synthesis can continue. NaNs are replaced with
+    /// silence and infinities are clamped to the largest/smallest finite value in the spectrogram.
+    Clamp,
+}
+
+/// Hardware ONNX Runtime can run the model on, see `Tacotron2::load_with_providers`. Anything
+/// other than the CPU needs an ONNX Runtime build with that provider, and for the GPU providers
+/// the matching crate feature (`cuda`, `tensorrt` or `directml`).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ExecutionProvider {
+    #[default]
+    Cpu,
+    Cuda,
+    TensorRt,
+    DirectMl,
+}
+
+impl ExecutionProvider {
+    /// Returns the ORT execution provider, or `None` if it isn't available in this build.
+    fn dispatch(self) -> Option<ExecutionProviderDispatch> {
+        let (available, dispatch) = match self {
+            Self::Cpu => (
+                CPUExecutionProvider::default().is_available(),
+                CPUExecutionProvider::default().build(),
+            ),
+            Self::Cuda => (
+                CUDAExecutionProvider::default().is_available(),
+                CUDAExecutionProvider::default().build(),
+            ),
+            Self::TensorRt => (
+                TensorRTExecutionProvider::default().is_available(),
+                TensorRTExecutionProvider::default().build(),
+            ),
+            Self::DirectMl => (
+                DirectMLExecutionProvider::default().is_available(),
+                DirectMLExecutionProvider::default().build(),
+            ),
+        };
+        match available {
+            Ok(true) => Some(dispatch),
+            Ok(false) => {
+                warn!(
+                    "{:?} execution provider isn't available, falling back to CPU",
+                    self
+                );
+                None
+            }
+            Err(e) => {
+                warn!(
+                    "Couldn't check if the {:?} execution provider is available, falling back to CPU: {}",
+                    self, e
+                );
+                None
+            }
+        }
+    }
+}
+
+/// Gets the ORT execution providers for the requested ones in order of preference, leaving out
+/// any that aren't available. ORT always falls back to the CPU so an empty list is fine.
+pub(crate) fn execution_providers(
+    providers: &[ExecutionProvider],
+) -> Vec<ExecutionProviderDispatch> {
+    providers.iter().filter_map(|x| x.dispatch()).collect()
+}
+
+/// Creates the builder all the networks are loaded with.
+pub(crate) fn session_builder(
+    providers: &[ExecutionProviderDispatch],
+) -> anyhow::Result<SessionBuilder> {
+    let builder = Session::builder()?
+        .with_optimization_level(GraphOptimizationLevel::Level3)?
+        .with_execution_providers(providers.to_vec())?;
+    Ok(builder)
+}
+
+/// What to do with units the model has no ID for. Tacotron2 has no input for an unknown unit, so
+/// by default they're dropped which closes up the gap and the sounds either side run together.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum UnknownUnitPolicy {
+    /// Leave the unit out of the model input, this is the default.
+    #[default]
+    Drop,
+    /// Give the model this unit instead, i.e. a schwa (`AH0`) or a space. The sound is only an
+    /// approximation but the timing is kept. If the model has no ID for this unit either the unit
+    /// is dropped.
+    Substitute(Unit),
+}
+
+/// Scans the spectrogram for NaN/Inf values and applies the policy to them.
+fn guard_non_finite(mel: &mut Array2<f32>, policy: NonFinitePolicy) -> anyhow::Result<()> {
+    let non_finite = mel.iter().filter(|x| !x.is_finite()).count();
+    if non_finite == 0 {
+        return Ok(());
+    }
+    match policy {
+        NonFinitePolicy::Error => {
+            anyhow::bail!(
+                "Spectrogram contains {} non-finite values out of {}",
+                non_finite,
+                mel.len()
+            );
+        }
+        NonFinitePolicy::Clamp => {
+            warn!(
+                "Clamping {} non-finite values in spectrogram of size {}",
+                non_finite,
+                mel.len()
+            );
+            let (min, max) = mel
+                .iter()
+                .filter(|x| x.is_finite())
+                .fold((LOG_MEL_FLOOR, LOG_MEL_FLOOR), |(min, max), x| {
+                    (min.min(*x), max.max(*x))
+                });
+            mel.mapv_inplace(|x| {
+                if x.is_nan() {
+                    LOG_MEL_FLOOR
+                } else if x == f32::INFINITY {
+                    max
+                } else if x == f32::NEG_INFINITY {
+                    min
+                } else {
+                    x
+                }
+            });
+            Ok(())
+        }
+    }
+}
+
+/// Size of the encoder input window, the ONNX export has a fixed input size so every inference is
+/// padded up to this many IDs and longer inputs have to be split.
+pub const INPUT_WINDOW: usize = 100;
+
+/// The postnet is 5 convolutions with a kernel size of 5, so each output frame depends on the 10
+/// decoder frames either side of it.
+pub const POSTNET_CONTEXT: usize = 10;
+
+/// Default maximum number of decoder steps before we give up waiting for the gate to say we're
+/// done, see `Tacotron2::with_max_decoder_steps`. Constant taken from the python implementation.
+pub const DEFAULT_MAX_DECODER_STEPS: usize = 1000;
+
+/// Default probability the gate has to go over to stop decoding, see
+/// `Tacotron2::with_gate_threshold`. Constant taken from the python implementation.
+pub const DEFAULT_GATE_THRESHOLD: f32 = 0.6;
+
+/// Settings for re-synthesising chunks which look like they've failed. Tacotron2 sometimes fails
+/// on a specific input, the attention gets stuck and it babbles until it hits the decoder step
+/// limit, or the gate fires straight away and we get almost no audio. Often splitting the input
+/// differently is enough to get a good output. Rather than redoing the whole utterance only the
+/// chunk which failed is re-split and retried.
+///
+/// The quality check is a heuristic on the number of spectrogram frames generated per input unit,
+/// normal speech is around 5-10 frames per character or phone.
+///
+/// The retry budget is `max_retries`, each retry splits the failing span in two and retries both
+/// halves. So a chunk will be split at most `max_retries` times deep, and in the worst case a
+/// budget of 2 results in 1 + 2 + 4 = 7 inferences for that chunk. If the chunk still fails once
+/// the budget is used up the last output is kept.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChunkRetry {
+    /// How many times a failing chunk can be re-split
+    pub max_retries: usize,
+    /// Below this many frames per input unit the chunk is treated as a failure
+    pub min_frames_per_unit: f32,
+    /// Above this many frames per input unit the chunk is treated as a failure
+    pub max_frames_per_unit: f32,
+}
+
+impl Default for ChunkRetry {
+    fn default() -> Self {
+        Self {
+            max_retries: 2,
+            min_frames_per_unit: 1.0,
+            max_frames_per_unit: 25.0,
+        }
+    }
+}
+
+impl ChunkRetry {
+    /// Checks if the number of output frames looks reasonable for the number of inputs, hitting
+    /// the model's decoder step limit (see `Tacotron2::with_max_decoder_steps`) counts as a
+    /// failure.
+    pub fn is_acceptable(
+        &self,
+        n_inputs: usize,
+        n_frames: usize,
+        max_decoder_steps: usize,
+    ) -> bool {
+        if n_inputs == 0 {
+            return true;
+        }
+        if n_frames >= max_decoder_steps {
+            return false;
+        }
+        let ratio = n_frames as f32 / n_inputs as f32;
+        ratio >= self.min_frames_per_unit && ratio <= self.max_frames_per_unit
+    }
+}
+
+/// Watches the attention while decoding to spot when it's stuck, see
+/// `Tacotron2::with_stall_steps`.
+#[derive(Clone, Copy, Debug)]
+struct StallDetector {
+    /// Steps the peak can stay put before it counts as stuck
+    limit: usize,
+    /// Furthest input position the attention peak has reached
+    furthest: Option<usize>,
+    /// Steps since the peak last moved past `furthest`
+    steps: usize,
+}
+
+impl StallDetector {
+    fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            furthest: None,
+            steps: 0,
+        }
+    }
+
+    /// Takes the attention weights for a decoder step, returning true once the peak hasn't
+    /// advanced for `limit` steps.
+    fn update(&mut self, attention: &[f32]) -> bool {
+        let peak = attention
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(i, _)| i)
+            .unwrap_or_default();
+        if self.furthest < Some(peak) {
+            self.furthest = Some(peak);
+            self.steps = 0;
+        } else {
+            self.steps += 1;
+        }
+        self.steps >= self.limit
+    }
+}
+
+/// Finds where to split a failing span of units for a retry. This prefers the best split point
+/// (by `split_score`) and then the one closest to the middle, if there's nothing better it splits
+/// in the middle of a word. Returns `None` if the span is too small to split.
+fn retry_split_point(units: &[Unit]) -> Option<usize> {
+    if units.len() < 2 {
+        return None;
+    }
+    let middle = units.len() / 2;
+    (1..units.len()).max_by_key(|i| {
+        (
+            split_score(&units[*i]),
+            std::cmp::Reverse(i.abs_diff(middle)),
+        )
+    })
+}
+
+/// The input given to the model for one chunk of inference. This is what the model "heard" so is
+/// useful for debugging when the output doesn't sound like what was typed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChunkInput {
+    /// The units in the chunk
+    pub units: Vec<Unit>,
+    /// The ID each unit resolved to, `None` if the model has no ID for the unit and it was dropped
+    pub ids: Vec<Option<i64>>,
+    /// Whether decoding was stopped early because the attention got stuck, see
+    /// `Tacotron2::with_stall_steps`. The audio for the chunk is cut short where it stopped.
+    pub stalled: bool,
+}
+
+impl ChunkInput {
+    /// The IDs given to the model. Before inference these are padded with the padding ID up to the
+    /// models input length.
+    pub fn model_ids(&self) -> Vec<i64> {
+        self.ids.iter().filter_map(|x| *x).collect()
+    }
+
+    /// Units the model has no ID for, these were dropped
+    pub fn dropped(&self) -> Vec<Unit> {
+        self.units
+            .iter()
+            .zip(self.ids.iter())
+            .filter(|(_, id)| id.is_none())
+            .map(|(unit, _)| *unit)
+            .collect()
+    }
+}
+
+/// How many times each unit couldn't be given to the model as it was over one or more chunks,
+/// see `Tacotron2::unit_summary`. This gives a concise picture of how well the model covers the
+/// pronunciations without logging every unit.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UnitSummary {
+    /// Units the model has no ID for and how many times each was dropped
+    pub dropped: BTreeMap<Unit, usize>,
+    /// Units the model has no exact match for, the unit that was used instead and how many times
+    pub substituted: BTreeMap<(Unit, Unit), usize>,
+}
+
+impl UnitSummary {
+    /// Counts the dropped units in the inputs, this doesn't know what the IDs map to so it can't
+    /// find substitutions.
+    pub fn from_inputs(inputs: &[ChunkInput]) -> Self {
+        let mut summary = Self::default();
+        for unit in inputs.iter().flat_map(|x| x.dropped()) {
+            *summary.dropped.entry(unit).or_default() += 1;
+        }
+        summary
+    }
+
+    /// Total number of units that were dropped
+    pub fn dropped_count(&self) -> usize {
+        self.dropped.values().sum()
+    }
+
+    /// Total number of units that were substituted
+    pub fn substituted_count(&self) -> usize {
+        self.substituted.values().sum()
+    }
+
+    /// Returns true if every unit went into the model as it was
+    pub fn is_empty(&self) -> bool {
+        self.dropped.is_empty() && self.substituted.is_empty()
+    }
+}
+
+impl fmt::Display for UnitSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // A space on its own is hard to read
+        let name = |unit: &Unit| match unit {
+            Unit::Space => "_".to_string(),
+            unit => unit.to_string(),
+        };
+        let dropped = self
+            .dropped
+            .iter()
+            .map(|(unit, count)| format!("{} x{}", name(unit), count))
+            .collect::<Vec<_>>();
+        let substituted = self
+            .substituted
+            .iter()
+            .map(|((from, to), count)| format!("{} -> {} x{}", name(from), name(to), count))
+            .collect::<Vec<_>>();
+        write!(
+            f,
+            "{} dropped [{}], {} substituted [{}]",
+            self.dropped_count(),
+            dropped.join(", "),
+            self.substituted_count(),
+            substituted.join(", ")
+        )
+    }
+}
+
 /// Handle to the tacotron2 ONNX graphs.
 ///
 /// These were initially downloaded from `https://developer.nvidia.com/joc-tacotron2-fp32-pyt-20190306` and used
@@ -145,6 +517,55 @@ pub struct Tacotron2 {
     postnet: Session,
     /// IDs of the input tokens
     phoneme_ids: Vec<Unit>,
+    /// ID used to pad the input up to the window size, the index of `Unit::Padding` in
+    /// `phoneme_ids`
+    padding_id: i64,
+    /// How to handle NaN/Inf in the network output
+    non_finite: NonFinitePolicy,
+    /// Whether to retry chunks that look like they failed
+    retry: Option<ChunkRetry>,
+    /// Frames of silence between the outputs of chunks that were inferred separately
+    chunk_gap: usize,
+    /// How inputs too long for the window are split into chunks
+    split_strategy: SplitStrategy,
+    /// Phones swapped for others before they're mapped to IDs
+    substitution: PhoneSubstitution,
+    /// What to do with units that have no ID
+    unknown: UnknownUnitPolicy,
+    /// Probability the gate has to go over to stop decoding
+    gate_threshold: f32,
+    /// Maximum number of frames to decode for one chunk
+    max_decoder_steps: usize,
+    /// Steps the attention can stay in one place before decoding is stopped
+    stall_steps: Option<usize>,
+    /// Mel and network parameters
+    config: Tacotron2Config,
+    /// Whether to log a `UnitSummary` after each inference
+    log_unit_summary: bool,
+}
+
+/// What the decoder generated for a chunk, see `Tacotron2::run_decoder`.
+struct DecoderOutput {
+    /// The spectrogram frames with shape [frames, 80], once the postnet has been ran it's
+    /// [80, frames]
+    mel: Array2<f32>,
+    /// Attention weights for each step with shape [frames, encoder_len]
+    alignment: Array2<f32>,
+    /// Whether decoding was stopped because the attention got stuck, see
+    /// `Tacotron2::with_stall_steps`
+    stalled: bool,
+}
+
+/// Everything generated for an input that was split into windows, see `Tacotron2::infer_all`.
+struct Inference {
+    /// Spectrogram with shape [80, frames]
+    mel: Array2<f32>,
+    /// Attention weights with shape [frames, units]
+    alignment: Array2<f32>,
+    /// What was given to the model for each window
+    inputs: Vec<ChunkInput>,
+    /// Whether the windows after the first were skipped because the deadline passed
+    stopped: bool,
 }
 
 /// We don't want to trigger clippy warnings about too many parameters so the decoder state ran
@@ -199,13 +620,13 @@ struct DecoderState {
 impl DecoderState {
     /// Creates a new decoder state given the output of the encoder network and the length of the
     /// sequence before padding.
-    fn new(memory: &ArrayViewD<f32>, unpadded_len: usize) -> Self {
+    fn new(memory: &ArrayViewD<f32>, unpadded_len: usize, config: &Tacotron2Config) -> Self {
         let bs = memory.shape()[0];
         let seq_len = memory.shape()[1];
-        let attention_rnn_dim = 1024;
-        let decoder_rnn_dim = 1024;
-        let encoder_embedding_dim = 512;
-        let n_mel_channels = 80;
+        let attention_rnn_dim = config.attention_rnn_dim;
+        let decoder_rnn_dim = config.decoder_rnn_dim;
+        let encoder_embedding_dim = config.encoder_embedding_dim;
+        let n_mel_channels = config.n_mel_channels;
 
         let attention_hidden = Array2::zeros((bs, attention_rnn_dim));
         let attention_cell = Array2::zeros((bs, attention_rnn_dim));
@@ -239,46 +660,263 @@ impl Tacotron2 {
     /// 1. encoder.onnx
     /// 2. decoder_iter.onnx
     /// 3. postnet.onnx
+    ///
+    /// It can also contain a config.json with the model parameters, see `Tacotron2Config`.
+    ///
+    /// The path can also be a zip or tar archive containing the same files, see `ModelFiles` for
+    /// the layout.
     pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Self::load_with_phone_set(path, PhoneSet::english())
+    }
+
+    /// Load a tacotron2 model trained on a phone set other than the English ARPABET one. The model
+    /// input IDs are generated from the phone set so the phones must be in the same order they
+    /// were in training.
+    pub fn load_with_phone_set(
+        path: impl AsRef<Path>,
+        phone_set: &PhoneSet,
+    ) -> anyhow::Result<Self> {
+        Self::load_with_options(path, phone_set, &[])
+    }
+
+    /// Load a tacotron2 model to run on the first of the execution providers that's available,
+    /// i.e. `[ExecutionProvider::Cuda]` to use the GPU. If none of them are available a warning is
+    /// logged and the model runs on the CPU.
+    pub fn load_with_providers(
+        path: impl AsRef<Path>,
+        providers: &[ExecutionProvider],
+    ) -> anyhow::Result<Self> {
+        Self::load_with_options(path, PhoneSet::english(), providers)
+    }
+
+    /// Load a tacotron2 model with a custom phone set (see `load_with_phone_set`) and execution
+    /// providers (see `load_with_providers`).
+    pub fn load_with_options(
+        path: impl AsRef<Path>,
+        phone_set: &PhoneSet,
+        providers: &[ExecutionProvider],
+    ) -> anyhow::Result<Self> {
+        let providers = execution_providers(providers);
+        if path.as_ref().is_file() {
+            let files = ModelFiles::open(path)?;
+            return Self::load_from_files_with_providers(&files, phone_set, &providers);
+        }
         // Load all the networks. Context is added to the error so we can tell easily which network
         // messes things up
 
-        let encoder = Session::builder()?
-            .with_optimization_level(GraphOptimizationLevel::Level3)?
+        let encoder = session_builder(&providers)?
             .commit_from_file(path.as_ref().join("encoder.onnx"))
             .context("converting encoder to runnable model")?;
 
-        let decoder = Session::builder()?
-            .with_optimization_level(GraphOptimizationLevel::Level3)?
+        let decoder = session_builder(&providers)?
             .commit_from_file(path.as_ref().join("decoder_iter.onnx"))
             .context("converting decoder_iter to runnable model")?;
 
-        let postnet = Session::builder()?
-            .with_optimization_level(GraphOptimizationLevel::Level3)?
+        let postnet = session_builder(&providers)?
             .commit_from_file(path.as_ref().join("postnet.onnx"))
             .context("converting postnet to runnable model")?;
 
+        let config_path = path.as_ref().join("config.json");
+        let config = if config_path.exists() {
+            Tacotron2Config::open(config_path)?
+        } else {
+            Tacotron2Config::default()
+        };
+
+        Self::from_sessions(encoder, decoder, postnet, phone_set, config)
+    }
+
+    /// Load a tacotron2 model from the ONNX files already in memory, i.e. read from an archive
+    /// with `ModelFiles::open`.
+    pub fn load_from_files(files: &ModelFiles, phone_set: &PhoneSet) -> anyhow::Result<Self> {
+        Self::load_from_files_with_providers(files, phone_set, &[])
+    }
+
+    fn load_from_files_with_providers(
+        files: &ModelFiles,
+        phone_set: &PhoneSet,
+        providers: &[ExecutionProviderDispatch],
+    ) -> anyhow::Result<Self> {
+        let encoder = session_builder(providers)?
+            .commit_from_memory(&files.encoder)
+            .context("converting encoder to runnable model")?;
+
+        let decoder = session_builder(providers)?
+            .commit_from_memory(&files.decoder)
+            .context("converting decoder_iter to runnable model")?;
+
+        let postnet = session_builder(providers)?
+            .commit_from_memory(&files.postnet)
+            .context("converting postnet to runnable model")?;
+
+        let config = match files.config.as_ref() {
+            Some(json) => Tacotron2Config::from_json(json).context("parsing config.json")?,
+            None => Tacotron2Config::default(),
+        };
+
+        Self::from_sessions(encoder, decoder, postnet, phone_set, config)
+    }
+
+    fn from_sessions(
+        encoder: Session,
+        decoder: Session,
+        postnet: Session,
+        phone_set: &PhoneSet,
+        config: Tacotron2Config,
+    ) -> anyhow::Result<Self> {
+        let phoneme_ids = config.phoneme_ids(phone_set)?;
+        let padding_id = padding_id(&phoneme_ids)?;
         Ok(Self {
             encoder,
             decoder,
             postnet,
-            phoneme_ids: generate_id_list(),
+            phoneme_ids,
+            padding_id,
+            non_finite: NonFinitePolicy::default(),
+            retry: None,
+            chunk_gap: 0,
+            split_strategy: SplitStrategy::default(),
+            substitution: PhoneSubstitution::default(),
+            unknown: UnknownUnitPolicy::default(),
+            gate_threshold: DEFAULT_GATE_THRESHOLD,
+            max_decoder_steps: DEFAULT_MAX_DECODER_STEPS,
+            stall_steps: None,
+            config,
+            log_unit_summary: true,
         })
     }
 
+    /// The parameters the model was loaded with, use these to create a matching vocoder with
+    /// `create_griffin_lim`.
+    pub fn config(&self) -> &Tacotron2Config {
+        &self.config
+    }
+
+    /// Enables re-splitting and retrying chunks which look like they failed, see `ChunkRetry`.
+    /// This is off by default.
+    pub fn with_chunk_retry(mut self, retry: ChunkRetry) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Adds a short silence between the spectrograms of chunks that are inferred separately, either
+    /// because the input didn't fit in the `INPUT_WINDOW` or a chunk was retried. Each chunk is
+    /// synthesised on its own so joining them directly can make the words at the boundary run
+    /// together, a few frames of silence gives a micro-pause instead. The duration is rounded to
+    /// the nearest frame and by default there's no gap.
+    pub fn with_chunk_gap(mut self, gap: std::time::Duration) -> Self {
+        self.chunk_gap =
+            crate::silence_frames(gap, self.config.sampling_rate, self.config.hop_length);
+        self
+    }
+
+    /// Sets how the input is split into chunks that are inferred separately, by default as much is
+    /// put in each chunk as fits in the `INPUT_WINDOW`. With `SplitStrategy::PerSentence` every
+    /// sentence is inferred on its own which keeps the prosody of each sentence intact, but means
+    /// more inferences for text with a lot of short sentences.
+    pub fn with_split_strategy(mut self, strategy: SplitStrategy) -> Self {
+        self.split_strategy = strategy;
+        self
+    }
+
+    /// How the input is split into chunks, see `with_split_strategy`.
+    pub fn split_strategy(&self) -> SplitStrategy {
+        self.split_strategy
+    }
+
+    /// Swaps phones for others before they're given to the model, see `PhoneSubstitution`. The
+    /// `ChunkInput`s from `infer_with_inputs` have the original units with the IDs of the
+    /// substituted ones.
+    pub fn with_phone_substitution(mut self, substitution: PhoneSubstitution) -> Self {
+        self.substitution = substitution;
+        self
+    }
+
+    /// Sets what happens to units the model has no ID for, see `UnknownUnitPolicy`. The
+    /// `ChunkInput`s from `infer_with_inputs` have the original units with the IDs of their
+    /// replacements.
+    pub fn with_unknown_unit_policy(mut self, policy: UnknownUnitPolicy) -> Self {
+        self.unknown = policy;
+        self
+    }
+
+    /// Sets the probability the gate prediction has to go over for decoding to stop. A model
+    /// that's quieter or less confident at the end of an utterance may need this lowered to stop
+    /// it running on, by default it's `DEFAULT_GATE_THRESHOLD`.
+    pub fn with_gate_threshold(mut self, threshold: f32) -> Self {
+        self.gate_threshold = threshold;
+        self
+    }
+
+    /// Sets the maximum number of frames decoded for each chunk, if the gate hasn't fired by then
+    /// the chunk is cut off. Long chunks with slow speech may need more than the default of
+    /// `DEFAULT_MAX_DECODER_STEPS`. At least one step is always ran.
+    pub fn with_max_decoder_steps(mut self, steps: usize) -> Self {
+        self.max_decoder_steps = steps.max(1);
+        self
+    }
+
+    /// Stops decoding a chunk once the attention hasn't moved forward through the input for this
+    /// many steps. When tacotron2 fails the attention often gets stuck on one unit and the decoder
+    /// babbles until `max_decoder_steps`, this catches it early instead of wasting the time. A
+    /// normal long vowel or pause holds the attention for 20-30 steps so the limit should be above
+    /// that. A stalled chunk is treated as a failure for `ChunkRetry` so this works best with
+    /// retries enabled, without them the output up to where it stopped is kept and the chunk is
+    /// marked with `ChunkInput::stalled`. This is off by default.
+    pub fn with_stall_steps(mut self, steps: usize) -> Self {
+        self.stall_steps = Some(steps.max(1));
+        self
+    }
+
+    /// Sets what happens when the network outputs NaN or infinite values, see `NonFinitePolicy`.
+    pub fn with_non_finite_policy(mut self, policy: NonFinitePolicy) -> Self {
+        self.non_finite = policy;
+        self
+    }
+
+    /// Sets whether a summary of the units that were dropped or substituted is logged once per
+    /// call to `infer`, see `UnitSummary`. This is on by default, each unit is only logged at debug
+    /// level so long documents don't spam the logs.
+    pub fn with_unit_summary(mut self, log: bool) -> Self {
+        self.log_unit_summary = log;
+        self
+    }
+
+    /// Counts the units in the inputs that were dropped or given to the model as a different unit.
+    pub fn unit_summary(&self, inputs: &[ChunkInput]) -> UnitSummary {
+        unit_summary(inputs, &self.substitution, &self.phoneme_ids)
+    }
+
+    /// Logs the `UnitSummary` for the inputs if there's anything in it and it's enabled.
+    fn log_unit_summary(&self, inputs: &[ChunkInput]) {
+        if !self.log_unit_summary {
+            return;
+        }
+        let summary = self.unit_summary(inputs);
+        if !summary.is_empty() {
+            warn!("Units not given to the model as they were: {}", summary);
+        }
+    }
+
+    /// Checks how the phones used in the dictionary map onto the model inputs. Any substituted or
+    /// unmappable phones mean the dictionary and model don't share the same conventions.
+    pub fn check_dictionary(&self, dict: &CmuDictionary) -> PhoneCompatibility {
+        check_phone_compatibility(dict.phone_inventory(), &self.phoneme_ids)
+    }
+
     /// Run the decoder stage of the network. This function would be fairly small if not for the
     /// amount of state that needs to be extracted from the model and fed into it, however it is
     /// relatively low complexity.
+    ///
+    /// This returns the decoder output before the postnet, see `DecoderOutput`. After every step
+    /// `on_step` is called with all the frames decoded so far, which lets the output be streamed.
     fn run_decoder(
         &self,
         memory: &Array<f32, IxDyn>,
         processed_memory: &Array<f32, IxDyn>,
         state: &mut DecoderState,
-    ) -> anyhow::Result<Array2<f32>> {
-        // Constants taken from the python implementation
-        let gate_threshold = 0.6;
-        let max_decoder_steps = 1000;
-
+        mut on_step: impl FnMut(&Array2<f32>) -> anyhow::Result<()>,
+    ) -> anyhow::Result<DecoderOutput> {
         // An example of why setting inputs based on names is much more readable to someone
         // approaching ML code.
         let mut inputs = inputs![
@@ -297,15 +935,22 @@ impl Tacotron2 {
         // Concat the spectrogram etc
 
         let mut mel_spec = Array2::zeros((0, 0));
+        let seq_len = state.attention_weights.ncols();
+        let mut alignment = Vec::new();
+        let mut stall = self.stall_steps.map(StallDetector::new);
+        let mut stalled = false;
 
         // Because we always break out of this we could use `loop`.
-        for i in 0..max_decoder_steps {
+        for i in 0..self.max_decoder_steps {
             // init decoder inputs
             let mut infer = self.decoder.run(inputs)?;
 
             let gate_prediction = &infer["gate_prediction"].try_extract_tensor::<f32>()?;
             let mel_output = &infer["decoder_output"].try_extract_tensor::<f32>()?;
             let mel_output = mel_output.view().clone().into_dimensionality()?;
+            let attention = &infer["out_attention_weights"].try_extract_tensor::<f32>()?;
+            let step_start = alignment.len();
+            alignment.extend(attention.view().iter().copied());
 
             debug!("Gate: {}", gate_prediction.view()[[0, 0]]);
 
@@ -315,13 +960,25 @@ impl Tacotron2 {
                 mel_spec = concatenate(Axis(0), &[mel_spec.view(), mel_output.view()])
                     .context("Joining decoder iter output")?;
             }
+            on_step(&mel_spec)?;
 
-            if sigmoid(gate_prediction.view()[[0, 0]]) > gate_threshold
-                || i + 1 == max_decoder_steps
+            if sigmoid(gate_prediction.view()[[0, 0]]) > self.gate_threshold
+                || i + 1 == self.max_decoder_steps
             {
                 debug!("Stopping after {} steps", i);
                 break;
             }
+            if let Some(stall) = stall.as_mut() {
+                if stall.update(&alignment[step_start..]) {
+                    warn!(
+                        "Attention stuck for {} steps, stopping after {} steps",
+                        stall.limit,
+                        i + 1
+                    );
+                    stalled = true;
+                    break;
+                }
+            }
             // Prepare the inputs for the next run. We could put this in a condition, but as it's
             // moved on inference it's hard to do this and keep the borrow checker happy. So I
             // moved the condition up to above with the break.
@@ -341,6 +998,18 @@ impl Tacotron2 {
             ]?;
         }
 
+        let alignment = Array2::from_shape_vec((mel_spec.nrows(), seq_len), alignment)
+            .context("invalid attention weights dimensions")?;
+        Ok(DecoderOutput {
+            mel: mel_spec,
+            alignment,
+            stalled,
+        })
+    }
+
+    /// Runs the postnet over decoder output of shape [frames, 80] returning the final spectrogram
+    /// of shape [80, frames].
+    fn run_postnet(&self, mel_spec: ArrayView2<f32>) -> anyhow::Result<Array2<f32>> {
         // We have to transpose it and add in a batch dimension for it to be the right shape.
         let mel_spec = mel_spec.t().insert_axis(Axis(0));
 
@@ -357,17 +1026,29 @@ impl Tacotron2 {
         Ok(post)
     }
 
-    /// Given a chunk of phonemes run inference
-    fn infer_chunk(&self, mut phonemes: Vec<i64>) -> anyhow::Result<Array2<f32>> {
+    /// Given a chunk of phonemes run inference, returning the spectrogram and alignment
+    fn infer_chunk(&self, phonemes: Vec<i64>) -> anyhow::Result<DecoderOutput> {
+        let mut output = self.decode_chunk(phonemes, |_| Ok(()))?;
+        output.mel = self.run_postnet(output.mel.view())?;
+        Ok(output)
+    }
+
+    /// Runs the encoder and decoder for a chunk of phonemes, see `run_decoder` for the output and
+    /// `on_step`.
+    fn decode_chunk(
+        &self,
+        mut phonemes: Vec<i64>,
+        on_step: impl FnMut(&Array2<f32>) -> anyhow::Result<()>,
+    ) -> anyhow::Result<DecoderOutput> {
         let units_len = phonemes.len();
-        assert!(units_len <= 100);
+        assert!(units_len <= INPUT_WINDOW);
 
         // So it's not documented or shown in the inference functions but if your tensor is a lower
         // sequence length than the LSTM node in the encoder it will fail. This length is 50 (seen
         // via netron) so here I just pad it to 50 if it's below. This is likely due to torch JIT
         // replacing some dynamic values with constant ones!
-        if phonemes.len() < 100 {
-            phonemes.resize(100, 0);
+        if phonemes.len() < INPUT_WINDOW {
+            phonemes.resize(INPUT_WINDOW, self.padding_id);
         }
 
         // Run encoder
@@ -384,62 +1065,399 @@ impl Tacotron2 {
         let memory = encoder_outputs[0].try_extract_tensor()?;
         let processed_memory = encoder_outputs[1].try_extract_tensor()?;
 
-        let mut decoder_state = DecoderState::new(&memory.view(), units_len);
+        let mut decoder_state = DecoderState::new(&memory.view(), units_len, &self.config);
 
         let memory = memory.view().to_owned();
         let processed_memory = processed_memory.view().to_owned();
 
-        self.run_decoder(&memory, &processed_memory, &mut decoder_state)
+        self.run_decoder(&memory, &processed_memory, &mut decoder_state, on_step)
+    }
+
+    /// Runs inference like `infer` but calls `on_mel` with blocks of `block_frames` spectrogram
+    /// frames as soon as they're decoded, instead of waiting for the whole spectrogram. The blocks
+    /// joined together are the same spectrogram `infer` would return. This is the basis for
+    /// low latency synthesis, see `crate::streaming`.
+    ///
+    /// The postnet needs `POSTNET_CONTEXT` frames either side of a frame to compute it, so a block
+    /// is emitted once that many frames after it have been decoded. The postnet is ran on each
+    /// block with that context so the output doesn't lose any quality, but it does mean the
+    /// postnet is ran over some frames more than once. Chunk retries aren't supported as the
+    /// output has already been emitted by the time we'd know the chunk failed.
+    pub fn infer_streaming(
+        &self,
+        units: &[Unit],
+        block_frames: usize,
+        mut on_mel: impl FnMut(Array2<f32>) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let block_frames = block_frames.max(1);
+        let mut inputs = vec![];
+        for (i, chunk) in window_chunks_with(units, self.split_strategy)
+            .iter()
+            .filter(|x| !x.is_empty())
+            .enumerate()
+        {
+            if i > 0 && self.chunk_gap > 0 {
+                on_mel(Array2::from_elem(
+                    (self.config.n_mel_channels, self.chunk_gap),
+                    LOG_MEL_FLOOR,
+                ))?;
+            }
+            if let Some(silence) = punctuation_only_mel(chunk, &self.config) {
+                if !silence.is_empty() {
+                    on_mel(silence)?;
+                }
+                continue;
+            }
+            let mut input = ChunkInput {
+                units: chunk.clone(),
+                ids: unit_ids(chunk, &self.substitution, self.unknown, &self.phoneme_ids),
+                stalled: false,
+            };
+            let phonemes = input.model_ids();
+            let mut emitted = 0;
+            let output = self.decode_chunk(phonemes, |decoded| {
+                while decoded.nrows() >= emitted + block_frames + POSTNET_CONTEXT {
+                    on_mel(self.postnet_block(decoded, emitted, emitted + block_frames)?)?;
+                    emitted += block_frames;
+                }
+                Ok(())
+            })?;
+            input.stalled = output.stalled;
+            inputs.push(input);
+            let decoded = output.mel;
+            while emitted < decoded.nrows() {
+                let end = (emitted + block_frames).min(decoded.nrows());
+                on_mel(self.postnet_block(&decoded, emitted, end)?)?;
+                emitted = end;
+            }
+        }
+        self.log_unit_summary(&inputs);
+        Ok(())
+    }
+
+    /// Runs the postnet for the decoded frames in `start..end` using the frames around them as
+    /// context.
+    fn postnet_block(
+        &self,
+        decoded: &Array2<f32>,
+        start: usize,
+        end: usize,
+    ) -> anyhow::Result<Array2<f32>> {
+        let context_start = start.saturating_sub(POSTNET_CONTEXT);
+        let context_end = (end + POSTNET_CONTEXT).min(decoded.nrows());
+        let post = self.run_postnet(decoded.slice(s![context_start..context_end, ..]))?;
+        let mut mel_spec = post
+            .slice(s![.., (start - context_start)..(end - context_start)])
+            .to_owned();
+        guard_non_finite(&mut mel_spec, self.non_finite)?;
+        Ok(mel_spec)
     }
 
     /// Runs inference on the units returning a mel-spectrogram. This will split the inference into
     /// smaller chunks that fit into the models fixed size input window and run as many inferences
     /// as necessary.
     pub fn infer(&self, units: &[Unit]) -> anyhow::Result<Array2<f32>> {
-        let mut splits = find_splits(units, 100);
+        self.infer_with_inputs(units).map(|(mel_spec, _)| mel_spec)
+    }
 
-        // There's no UNK input to tacotron2, so we're just going to silently throw away failing
-        // units (do not do this in a real system)
-        let mut phonemes = units
-            .iter()
-            .filter_map(|x| best_match_for_unit(x, &self.phoneme_ids))
-            .collect::<Vec<_>>();
-        debug!("Phonemes: {:?}", phonemes);
+    /// Runs inference the same as `infer` but also returns the inputs for each chunk that was ran
+    /// through the model. This is intended for debugging mispronunciations, it shows exactly which
+    /// IDs the model saw and which units they came from after splitting and any retries.
+    pub fn infer_with_inputs(
+        &self,
+        units: &[Unit],
+    ) -> anyhow::Result<(Array2<f32>, Vec<ChunkInput>)> {
+        self.infer_all(units, None).map(|x| (x.mel, x.inputs))
+    }
 
-        let mut mel_spec = Array2::zeros((0, 0));
+    /// Runs inference the same as `infer` but once the deadline has passed it stops before
+    /// starting the next window of the input, returning the spectrogram for the windows that were
+    /// finished and whether it stopped early. The first window is always ran and a window that's
+    /// running when the deadline passes is finished, so the output never ends mid-window.
+    pub fn infer_until(
+        &self,
+        units: &[Unit],
+        deadline: Option<Instant>,
+    ) -> anyhow::Result<(Array2<f32>, bool)> {
+        self.infer_all(units, deadline).map(|x| (x.mel, x.stopped))
+    }
 
-        // Make sure we have at least one because of the lazy split implementation.
-        if !splits.contains(&units.len()) {
-            splits.push(units.len());
-        }
-        debug!("Splits: {:?}", splits);
+    /// Runs inference the same as `infer` but also returns the attention alignment, the weights
+    /// the decoder gave each unit for every frame it generated. This has the shape [frames,
+    /// units] with a row for each column of the spectrogram and a column for each unit passed in.
+    ///
+    /// Taking the argmax of each row gives the unit the model was saying in that frame, so
+    /// counting the frames each unit is the argmax for gives its duration (multiply by
+    /// `hop_length / sampling_rate` in `Tacotron2Config` for seconds) and the first and last frame
+    /// of a word's units gives its timing. Dropped units have a column of zeros, as do the rows for
+    /// silence the model didn't generate, like the gap between chunks or a chunk of only
+    /// punctuation, so check the row isn't all zeros before using its argmax.
+    pub fn infer_with_alignment(
+        &self,
+        units: &[Unit],
+    ) -> anyhow::Result<(Array2<f32>, Array2<f32>)> {
+        self.infer_all(units, None).map(|x| (x.mel, x.alignment))
+    }
+
+    /// Runs inference returning everything the other `infer` methods need, see
+    /// `infer_with_inputs`, `infer_with_alignment` and `infer_until`.
+    fn infer_all(&self, units: &[Unit], deadline: Option<Instant>) -> anyhow::Result<Inference> {
+        let mut inputs = vec![];
+        let mut stopped = false;
+        let splits = window_splits(units, self.split_strategy);
 
-        let mut offset = 0;
+        let mut mel_spec = Array2::zeros((0, 0));
+        let mut alignment = Array2::zeros((0, 0));
+
+        let retries = self.retry.map(|x| x.max_retries).unwrap_or_default();
+        let mut start = 0;
         // So interestingly if we exceed the input length we end up getting silence back. Instead
         // of spending too much time debugging this I'm going to ensure we stick to the fixed
         // length as our ONNX has a fixed input size and we're not going to be giving dynamic sized
         // inputs to a fixed size tensor.
-        for split in splits.iter() {
-            let remaining = phonemes.split_off(*split - offset);
-            offset += phonemes.len();
-            let array = self.infer_chunk(phonemes)?;
+        for (i, split) in splits.iter().enumerate() {
+            if i > 0 && deadline.is_some_and(|x| Instant::now() >= x) {
+                stopped = true;
+                break;
+            }
+            let (array, chunk_alignment) =
+                self.infer_span(&units[start..*split], retries, &mut inputs)?;
+            start = *split;
 
             if mel_spec.is_empty() {
                 mel_spec = array;
+                alignment = join_alignments(alignment.view(), chunk_alignment.view(), 0);
             } else {
-                mel_spec = concatenate(Axis(1), &[mel_spec.view(), array.view()])
+                mel_spec = join_chunks(mel_spec.view(), array.view(), self.chunk_gap)
                     .context("Joining inference chunk output")?;
+                alignment =
+                    join_alignments(alignment.view(), chunk_alignment.view(), self.chunk_gap);
             }
-            phonemes = remaining;
         }
 
-        Ok(mel_spec)
+        guard_non_finite(&mut mel_spec, self.non_finite)?;
+        self.log_unit_summary(&inputs);
+        Ok(Inference {
+            mel: mel_spec,
+            alignment,
+            inputs,
+            stopped,
+        })
+    }
+
+    /// Runs inference on a span of units that fits in the input window. If chunk retries are
+    /// enabled and the output looks bad the span is split in two and each half is retried. The
+    /// alignment returned has a column for each of the units.
+    fn infer_span(
+        &self,
+        units: &[Unit],
+        retries: usize,
+        inputs: &mut Vec<ChunkInput>,
+    ) -> anyhow::Result<(Array2<f32>, Array2<f32>)> {
+        if let Some(silence) = punctuation_only_mel(units, &self.config) {
+            inputs.push(ChunkInput {
+                units: units.to_vec(),
+                ids: unit_ids(units, &self.substitution, self.unknown, &self.phoneme_ids),
+                stalled: false,
+            });
+            let alignment = Array2::zeros((silence.ncols(), units.len()));
+            return Ok((silence, alignment));
+        }
+        let mut input = ChunkInput {
+            units: units.to_vec(),
+            ids: unit_ids(units, &self.substitution, self.unknown, &self.phoneme_ids),
+            stalled: false,
+        };
+        // There's no UNK input to tacotron2, so failing units are thrown away unless there's a
+        // substitute for them (see `UnknownUnitPolicy`)
+        let phonemes = input.model_ids();
+        debug!("Phonemes: {:?}", phonemes);
+        let n_inputs = phonemes.len();
+        let output = self.infer_chunk(phonemes)?;
+        input.stalled = output.stalled;
+        let mel_spec = output.mel;
+        let alignment = unit_alignment(output.alignment.view(), &input);
+
+        let retry = match self.retry {
+            Some(retry) if retries > 0 => retry,
+            _ => {
+                inputs.push(input);
+                return Ok((mel_spec, alignment));
+            }
+        };
+        let split = match retry_split_point(units) {
+            Some(split)
+                if !retry.is_acceptable(n_inputs, mel_spec.ncols(), self.max_decoder_steps)
+                    || output.stalled =>
+            {
+                split
+            }
+            _ => {
+                inputs.push(input);
+                return Ok((mel_spec, alignment));
+            }
+        };
+        warn!(
+            "Chunk of {} inputs produced {} frames, retrying as two chunks",
+            n_inputs,
+            mel_spec.ncols()
+        );
+        let (left, left_alignment) = self.infer_span(&units[..split], retries - 1, inputs)?;
+        let (right, right_alignment) = self.infer_span(&units[split..], retries - 1, inputs)?;
+        let joined = join_chunks(left.view(), right.view(), self.chunk_gap)
+            .context("Joining retried chunk output")?;
+        let alignment = join_alignments(
+            left_alignment.view(),
+            right_alignment.view(),
+            self.chunk_gap,
+        );
+        Ok((joined, alignment))
     }
 }
 
-/// Creates a griffin-lim vocoder for the tacotron2 model
-pub fn create_griffin_lim() -> anyhow::Result<GriffinLim> {
-    // So these parameters we get from the config.json in the tacotron2 repo that lets us know
+/// Finds the model ID for each unit after applying the phone substitution, `None` if the model has
+/// no ID for it and the policy is to drop it.
+fn unit_ids(
+    units: &[Unit],
+    substitution: &PhoneSubstitution,
+    unknown: UnknownUnitPolicy,
+    phoneme_ids: &[Unit],
+) -> Vec<Option<i64>> {
+    units
+        .iter()
+        .map(|x| {
+            best_match_for_unit(&substitution.apply(x), phoneme_ids).or_else(|| match unknown {
+                UnknownUnitPolicy::Drop => None,
+                UnknownUnitPolicy::Substitute(unit) => best_match_for_unit(&unit, phoneme_ids),
+            })
+        })
+        .collect()
+}
+
+/// Counts the dropped units in the inputs, and the ones whose ID is for a different unit to the
+/// one asked for after applying the phone substitution. Substitutions the user asked for aren't
+/// counted.
+fn unit_summary(
+    inputs: &[ChunkInput],
+    substitution: &PhoneSubstitution,
+    phoneme_ids: &[Unit],
+) -> UnitSummary {
+    let mut summary = UnitSummary::from_inputs(inputs);
+    for input in inputs {
+        for (unit, id) in input.units.iter().zip(input.ids.iter()) {
+            let wanted = substitution.apply(unit);
+            if let Some(used) = id.and_then(|x| phoneme_ids.get(x as usize)) {
+                if *used != wanted {
+                    *summary.substituted.entry((wanted, *used)).or_default() += 1;
+                }
+            }
+        }
+    }
+    summary
+}
+
+/// Joins the spectrograms of two chunks that were inferred separately with `gap` frames of silence
+/// between them.
+fn join_chunks(
+    left: ArrayView2<f32>,
+    right: ArrayView2<f32>,
+    gap: usize,
+) -> anyhow::Result<Array2<f32>> {
+    let silence = Array2::from_elem((left.nrows(), gap), LOG_MEL_FLOOR);
+    Ok(concatenate(
+        Axis(1),
+        &[left.view(), silence.view(), right.view()],
+    )?)
+}
+
+/// Joins the alignments of two chunks that were inferred separately, the frames and units of the
+/// right one come after the left with `gap` rows of zeros for the silence between them.
+fn join_alignments(left: ArrayView2<f32>, right: ArrayView2<f32>, gap: usize) -> Array2<f32> {
+    let mut joined = Array2::zeros((
+        left.nrows() + gap + right.nrows(),
+        left.ncols() + right.ncols(),
+    ));
+    joined
+        .slice_mut(s![..left.nrows(), ..left.ncols()])
+        .assign(&left);
+    joined
+        .slice_mut(s![left.nrows() + gap.., left.ncols()..])
+        .assign(&right);
+    joined
+}
+
+/// Maps the columns of the attention weights from the model inputs back to the units they came
+/// from, leaving dropped units with a column of zeros. The padding columns are removed.
+fn unit_alignment(attention: ArrayView2<f32>, input: &ChunkInput) -> Array2<f32> {
+    let mut alignment = Array2::zeros((attention.nrows(), input.units.len()));
+    let model_units = input
+        .ids
+        .iter()
+        .enumerate()
+        .filter_map(|(i, id)| id.map(|_| i));
+    for (column, unit) in model_units.enumerate() {
+        alignment.column_mut(unit).assign(&attention.column(column));
+    }
+    alignment
+}
+
+/// A window can end up with nothing but punctuation in it after splitting, i.e. the "..." at the
+/// end of a long sentence. Rather than let the model make noise from it this returns a silent
+/// spectrogram for the pause, or `None` if there's something to say.
+fn punctuation_only_mel(units: &[Unit], config: &Tacotron2Config) -> Option<Array2<f32>> {
+    if is_speakable(units) {
+        return None;
+    }
+    let frames = crate::silence_frames(
+        punctuation_pause(units),
+        config.sampling_rate,
+        config.hop_length,
+    );
+    Some(Array2::from_elem(
+        (config.n_mel_channels, frames),
+        LOG_MEL_FLOOR,
+    ))
+}
+
+/// Finds the end index of each span of units that will be ran through the model in one go.
+fn window_splits(units: &[Unit], strategy: SplitStrategy) -> Vec<usize> {
+    let mut splits = strategy.splits(units, INPUT_WINDOW);
+    // The splits don't include the end of the units, so make sure the last chunk is there.
+    if !splits.contains(&units.len()) {
+        splits.push(units.len());
+    }
+    debug!("Splits: {:?}", splits);
+    splits
+}
+
+/// Splits the units into the chunks `Tacotron2::infer` would run through the model, each one fits
+/// in the `INPUT_WINDOW`. Running `infer` on each chunk won't split them any further (unless chunk
+/// retries are enabled and the output looks bad).
+pub fn window_chunks(units: &[Unit]) -> Vec<Vec<Unit>> {
+    window_chunks_with(units, SplitStrategy::Greedy)
+}
+
+/// The same as `window_chunks` but for a model using a different `SplitStrategy`, see
+/// `Tacotron2::with_split_strategy`.
+pub fn window_chunks_with(units: &[Unit], strategy: SplitStrategy) -> Vec<Vec<Unit>> {
+    let mut start = 0;
+    window_splits(units, strategy)
+        .into_iter()
+        .map(|end| {
+            let chunk = units[start..end].to_vec();
+            start = end;
+            chunk
+        })
+        .collect()
+}
+
+/// Number of audio samples between each frame of the spectrogram for the nvidia pretrained model.
+pub const HOP_LENGTH: usize = 256;
+
+/// Creates a griffin-lim vocoder for a tacotron2 model with the given config, see
+/// `Tacotron2::config`.
+pub fn create_griffin_lim(config: &Tacotron2Config) -> anyhow::Result<GriffinLim> {
+    // So the default parameters we get from the config.json in the tacotron2 repo that lets us know
     // what parameters they're using for their vocoder. They're also available here:
     // https://catalog.ngc.nvidia.com/orgs/nvidia/resources/tacotron_2_and_waveglow_for_pytorch/advanced
     //
@@ -450,22 +1468,163 @@ pub fn create_griffin_lim() -> anyhow::Result<GriffinLim> {
     //
     // For iterations there wasn't any perceivable increase in quality after 10 iterations, but as
     // it's fast I kept it at 20 just in case there's some trickier/noisier samples.
-    let mel_basis = create_mel_filter_bank(22050.0, 1024, 80, 0.0, Some(8000.0));
-    // So the hop length is 256, this means the overlap is the window_size - hop_length. Getting
-    // this value wrong will result in noisier time stretched versions of the audio.
-    let vocoder = GriffinLim::new(mel_basis, 1024 - 256, 1.7, 30, 0.99)?;
+    let mel_basis = create_mel_filter_bank(
+        config.sampling_rate as f32,
+        config.filter_length,
+        config.n_mel_channels,
+        config.mel_fmin,
+        config.mel_fmax,
+    );
+    // The overlap is the window_size - hop_length. Getting this value wrong will result in noisier
+    // time stretched versions of the audio.
+    let vocoder = GriffinLim::new(
+        mel_basis,
+        config.filter_length - config.hop_length,
+        1.7,
+        30,
+        0.99,
+    )?;
     Ok(vocoder)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn padding_id_lookup() {
+        assert_eq!(
+            padding_id(&generate_id_list(PhoneSet::english())).unwrap(),
+            0
+        );
+
+        let ids = [
+            Unit::Space,
+            Unit::Punct(Punctuation::FullStop),
+            Unit::Padding,
+            Unit::Character('a'),
+        ];
+        assert_eq!(padding_id(&ids).unwrap(), 2);
+        assert!(padding_id(&ids[..2]).is_err());
+    }
+
+    #[test]
+    fn phone_substitution_ids() {
+        let phoneme_ids = generate_id_list(PhoneSet::english());
+        let units = ["HH", "AO1", "T", "AO"]
+            .iter()
+            .map(|x| Unit::from_str(x).unwrap())
+            .collect::<Vec<_>>();
+        let original = unit_ids(
+            &units,
+            &PhoneSubstitution::default(),
+            UnknownUnitPolicy::Drop,
+            &phoneme_ids,
+        );
+
+        let phone = |x: &str| PhoneticUnit::from_str(x).unwrap();
+        let mut substitution = PhoneSubstitution::default();
+        substitution.insert(phone("AO"), phone("AA"));
+        let substituted = unit_ids(&units, &substitution, UnknownUnitPolicy::Drop, &phoneme_ids);
+        let expected = ["HH", "AA1", "T", "AA"]
+            .iter()
+            .map(|x| best_match_for_unit(&Unit::from_str(x).unwrap(), &phoneme_ids))
+            .collect::<Vec<_>>();
+        assert_eq!(substituted, expected);
+        assert_ne!(substituted, original);
+        assert_eq!(substituted[0], original[0]);
+        assert_eq!(substituted[2], original[2]);
+
+        // An exact stress wins over a rule for any stress
+        substitution.insert(phone("AO1"), phone("OW1"));
+        let substituted = unit_ids(&units, &substitution, UnknownUnitPolicy::Drop, &phoneme_ids);
+        assert_eq!(
+            substituted[1],
+            best_match_for_unit(&Unit::from_str("OW1").unwrap(), &phoneme_ids)
+        );
+        assert_eq!(substituted[3], expected[3]);
+    }
+
+    #[test]
+    fn chunk_gaps() {
+        let left = Array2::from_elem((80, 4), 1.0);
+        let right = Array2::from_elem((80, 3), 2.0);
+
+        let joined = join_chunks(left.view(), right.view(), 0).unwrap();
+        assert_eq!(joined.dim(), (80, 7));
+        assert_eq!(joined.slice(s![.., ..4]), left);
+        assert_eq!(joined.slice(s![.., 4..]), right);
+
+        let gap = crate::silence_frames(
+            std::time::Duration::from_millis(35),
+            crate::WAV_SPEC.sample_rate,
+            HOP_LENGTH,
+        );
+        assert_eq!(gap, 3);
+        let joined = join_chunks(left.view(), right.view(), gap).unwrap();
+        assert_eq!(joined.dim(), (80, 10));
+        // The chunks are untouched and the gap only goes between them
+        assert_eq!(joined.slice(s![.., ..4]), left);
+        assert!(joined
+            .slice(s![.., 4..7])
+            .iter()
+            .all(|x| *x == LOG_MEL_FLOOR));
+        assert_eq!(joined.slice(s![.., 7..]), right);
+    }
+
+    #[test]
+    fn alignment_columns() {
+        let input = ChunkInput {
+            units: vec![Unit::Character('a'), Unit::Unk, Unit::Character('b')],
+            ids: vec![Some(1), None, Some(2)],
+            stalled: false,
+        };
+        // Two frames attending to the first then second model input, with padding after
+        let attention = array![[0.9, 0.1, 0.0, 0.0], [0.2, 0.8, 0.0, 0.0]];
+        let alignment = unit_alignment(attention.view(), &input);
+        assert_eq!(alignment, array![[0.9, 0.0, 0.1], [0.2, 0.0, 0.8]]);
+
+        let right = array![[1.0]];
+        let joined = join_alignments(alignment.view(), right.view(), 2);
+        assert_eq!(joined.dim(), (5, 4));
+        assert_eq!(joined.slice(s![..2, ..3]), alignment);
+        assert!(joined.slice(s![2..4, ..]).iter().all(|x| *x == 0.0));
+        assert_eq!(joined.slice(s![4.., 3..]), right);
+        assert!(joined.slice(s![4.., ..3]).iter().all(|x| *x == 0.0));
+    }
+
+    #[test]
+    fn punctuation_only_windows() {
+        // One very long word so the only place to split is before the full stops
+        let mut units = vec![Unit::Character('a'); 98];
+        units.extend([Unit::Punct(Punctuation::FullStop); 5]);
+        let windows = window_chunks(&units);
+        assert!(windows.len() > 1);
+        let mel = windows
+            .iter()
+            .filter_map(|x| punctuation_only_mel(x, &Tacotron2Config::default()))
+            .collect::<Vec<_>>();
+        assert_eq!(mel.len(), 1);
+        assert_eq!(mel[0].nrows(), 80);
+        assert_eq!(
+            mel[0].ncols(),
+            crate::silence_frames(
+                std::time::Duration::from_millis(400),
+                crate::WAV_SPEC.sample_rate,
+                HOP_LENGTH
+            )
+        );
+        assert!(mel[0].iter().all(|x| *x == LOG_MEL_FLOOR));
+
+        assert!(punctuation_only_mel(&units[..5], &Tacotron2Config::default()).is_none());
+    }
 
     #[test]
     fn correct_phoneme_id_output() {
         // These mappings were independently generated via tacotron2's python scripts.
 
-        let phoneme_ids = generate_id_list();
+        let phoneme_ids = generate_id_list(PhoneSet::english());
         #[rustfmt::skip]
         let units = ["IH0", "N", " ", 
                      "B", "IY1", "IH0", "NG", " ", 
@@ -490,9 +1649,36 @@ mod tests {
         assert_eq!(phonemes, expected);
     }
 
+    #[test]
+    fn tertiary_stress_collapse() {
+        // Tacotron2 was trained on CMU dict which only has 0, 1 and 2 stress markers
+        let phoneme_ids = generate_id_list(PhoneSet::english());
+        let phone = |x: &str| Unit::from_str(x).unwrap();
+        for vowel in PhoneSet::english()
+            .phones()
+            .iter()
+            .filter(|x| x.phone.is_vowel() && x.context.is_some())
+        {
+            let tertiary = Unit::Phone(PhoneticUnit {
+                phone: vowel.phone,
+                context: Some(AuxiliarySymbol::TertiaryStress),
+            });
+            assert!(!phoneme_ids.contains(&tertiary));
+            let secondary = Unit::Phone(PhoneticUnit {
+                phone: vowel.phone,
+                context: Some(AuxiliarySymbol::SecondaryStress),
+            });
+            assert_eq!(
+                best_match_for_unit(&tertiary, &phoneme_ids),
+                best_match_for_unit(&secondary, &phoneme_ids)
+            );
+        }
+        assert_eq!(best_match_for_unit(&phone("IY3"), &phoneme_ids), Some(114));
+    }
+
     #[test]
     fn correct_char_id_output() {
-        let phoneme_ids = generate_id_list();
+        let phoneme_ids = generate_id_list(PhoneSet::english());
         let mut units = "hello".chars().map(Unit::Character).collect::<Vec<_>>();
         units.push(Unit::Space);
         units.extend("world".chars().map(Unit::Character));
@@ -507,6 +1693,215 @@ mod tests {
         assert_eq!(phonemes, expected);
     }
 
+    #[test]
+    fn chunk_input_ids() {
+        let input = ChunkInput {
+            units: vec![
+                Unit::Character('a'),
+                Unit::Unk,
+                Unit::Space,
+                Unit::Character('b'),
+            ],
+            ids: vec![Some(38), None, Some(11), Some(39)],
+            stalled: false,
+        };
+        assert_eq!(input.model_ids(), vec![38, 11, 39]);
+        assert_eq!(input.dropped(), vec![Unit::Unk]);
+    }
+
+    #[test]
+    fn unit_summaries() {
+        let phoneme_ids = generate_id_list(PhoneSet::english());
+        let units = ["HH", "AH3", "L", "OW1", "AH3"]
+            .iter()
+            .map(|x| Unit::from_str(x).unwrap())
+            .chain([Unit::Unk, Unit::Space, Unit::Unk])
+            .collect::<Vec<_>>();
+        let input = |units: &[Unit], substitution: &PhoneSubstitution| ChunkInput {
+            units: units.to_vec(),
+            ids: unit_ids(units, substitution, UnknownUnitPolicy::Drop, &phoneme_ids),
+            stalled: false,
+        };
+        let no_substitution = PhoneSubstitution::default();
+        let inputs = [
+            input(&units[..4], &no_substitution),
+            input(&units[4..], &no_substitution),
+        ];
+
+        let summary = UnitSummary::from_inputs(&inputs);
+        assert_eq!(summary.dropped_count(), 2);
+        assert_eq!(
+            summary.dropped_count(),
+            inputs.iter().map(|x| x.dropped().len()).sum::<usize>()
+        );
+        assert_eq!(summary.dropped, BTreeMap::from([(Unit::Unk, 2)]));
+        assert!(summary.substituted.is_empty());
+
+        let summary = unit_summary(&inputs, &no_substitution, &phoneme_ids);
+        assert_eq!(summary.dropped_count(), 2);
+        assert_eq!(
+            summary.substituted,
+            BTreeMap::from([(
+                (
+                    Unit::from_str("AH3").unwrap(),
+                    Unit::from_str("AH2").unwrap()
+                ),
+                2
+            )])
+        );
+        assert_eq!(
+            summary.to_string(),
+            "2 dropped [<UNK> x2], 2 substituted [AH3 -> AH2 x2]"
+        );
+
+        // Substitutions that were asked for aren't counted
+        let phone = |x: &str| PhoneticUnit::from_str(x).unwrap();
+        let mut substitution = PhoneSubstitution::default();
+        substitution.insert(phone("AH3"), phone("AA1"));
+        let inputs = [input(&units, &substitution)];
+        let summary = unit_summary(&inputs, &substitution, &phoneme_ids);
+        assert_eq!(summary.dropped_count(), 2);
+        assert_eq!(summary.substituted_count(), 0);
+
+        let everything = input(&units[..1], &no_substitution);
+        assert!(unit_summary(&[everything], &no_substitution, &phoneme_ids).is_empty());
+    }
+
+    #[test]
+    fn unknown_unit_substitution() {
+        let phoneme_ids = generate_id_list(PhoneSet::english());
+        let schwa = Unit::from_str("AH0").unwrap();
+        let units = [Unit::from_str("HH").unwrap(), Unit::Unk, Unit::Space];
+        let no_substitution = PhoneSubstitution::default();
+
+        let dropped = unit_ids(
+            &units,
+            &no_substitution,
+            UnknownUnitPolicy::Drop,
+            &phoneme_ids,
+        );
+        assert_eq!(dropped[1], None);
+
+        let input = ChunkInput {
+            units: units.to_vec(),
+            ids: unit_ids(
+                &units,
+                &no_substitution,
+                UnknownUnitPolicy::Substitute(schwa),
+                &phoneme_ids,
+            ),
+            stalled: false,
+        };
+        // The unknown unit takes up a place in the input instead of leaving a gap
+        assert_eq!(input.ids[0], dropped[0]);
+        assert_eq!(input.ids[1], best_match_for_unit(&schwa, &phoneme_ids));
+        assert_eq!(input.ids[2], dropped[2]);
+        assert_eq!(input.model_ids().len(), units.len());
+        assert!(input.dropped().is_empty());
+
+        // It still shows up in the summary, as a substitution
+        let summary = unit_summary(&[input], &no_substitution, &phoneme_ids);
+        assert_eq!(
+            summary.to_string(),
+            "0 dropped [], 1 substituted [<UNK> -> AH0 x1]"
+        );
+
+        // A substitute the model doesn't have is no help
+        let ids = unit_ids(
+            &units,
+            &no_substitution,
+            UnknownUnitPolicy::Substitute(Unit::Unk),
+            &phoneme_ids,
+        );
+        assert_eq!(ids, dropped);
+    }
+
+    #[test]
+    fn window_chunking() {
+        let sentence = "this is a short sentence. ";
+        let text = sentence.repeat(10);
+        let units = text
+            .trim()
+            .chars()
+            .map(|x| Unit::from_str(&x.to_string()).unwrap())
+            .collect::<Vec<_>>();
+
+        let chunks = window_chunks(&units);
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|x| x.len() <= INPUT_WINDOW));
+        assert_eq!(chunks.concat(), units);
+
+        assert_eq!(window_chunks(&units[..10]), vec![units[..10].to_vec()]);
+
+        let sentences = window_chunks_with(&units, SplitStrategy::PerSentence);
+        assert_eq!(sentences.len(), 10);
+        assert_eq!(sentences[0], units[..sentence.len()]);
+        assert_eq!(sentences.concat(), units);
+    }
+
+    #[test]
+    fn chunk_retry_checks() {
+        let retry = ChunkRetry::default();
+        let steps = DEFAULT_MAX_DECODER_STEPS;
+        assert!(retry.is_acceptable(20, 150, steps));
+        assert!(!retry.is_acceptable(20, 5, steps));
+        assert!(!retry.is_acceptable(5, steps, steps));
+        assert!(!retry.is_acceptable(10, 900, steps));
+        assert!(retry.is_acceptable(0, 0, steps));
+        // A lower step limit on the model is a failure even at a normal rate
+        assert!(retry.is_acceptable(20, 150, 200));
+        assert!(!retry.is_acceptable(20, 150, 150));
+
+        let units = "abc de, fgh ijk"
+            .chars()
+            .map(|x| Unit::from_str(&x.to_string()).unwrap())
+            .collect::<Vec<_>>();
+        // The comma beats the spaces even though it's not the middle
+        assert_eq!(retry_split_point(&units), Some(6));
+
+        let units = "abcdef"
+            .chars()
+            .map(|x| Unit::from_str(&x.to_string()).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(retry_split_point(&units), Some(3));
+        assert_eq!(retry_split_point(&units[..1]), None);
+    }
+
+    #[test]
+    fn attention_stall() {
+        let mut stall = StallDetector::new(3);
+        assert!(!stall.update(&[0.9, 0.1, 0.0]));
+        assert!(!stall.update(&[0.2, 0.7, 0.1]));
+        // Holding on one unit for a bit is fine as long as it moves on in time
+        assert!(!stall.update(&[0.1, 0.8, 0.1]));
+        assert!(!stall.update(&[0.1, 0.6, 0.3]));
+        assert!(!stall.update(&[0.0, 0.3, 0.7]));
+        // Jumping backwards doesn't count as progress
+        assert!(!stall.update(&[0.8, 0.1, 0.1]));
+        assert!(!stall.update(&[0.1, 0.8, 0.1]));
+        assert!(stall.update(&[0.2, 0.2, 0.6]));
+    }
+
+    #[test]
+    fn non_finite_guard() {
+        let mut mel = arr2(&[
+            [1.0, f32::NAN],
+            [f32::INFINITY, -2.0],
+            [f32::NEG_INFINITY, 0.5],
+        ]);
+        assert!(guard_non_finite(&mut mel.clone(), NonFinitePolicy::Error).is_err());
+
+        guard_non_finite(&mut mel, NonFinitePolicy::Clamp).unwrap();
+        assert_eq!(
+            mel,
+            arr2(&[[1.0, LOG_MEL_FLOOR], [1.0, -2.0], [LOG_MEL_FLOOR, 0.5]])
+        );
+
+        let mut fine = arr2(&[[1.0, 2.0]]);
+        guard_non_finite(&mut fine, NonFinitePolicy::Error).unwrap();
+        assert_eq!(fine, arr2(&[[1.0, 2.0]]));
+    }
+
     #[test]
     fn tacotron_sanity_test() {
         // Loading the graph and performing inference on it are fallible operations. If we've
@@ -520,4 +1915,100 @@ mod tests {
         assert_eq!(spec.nrows(), 80);
         assert!(spec.ncols() > 0);
     }
+
+    #[test]
+    fn available_execution_providers() {
+        // The CPU is always there and asking for a provider we don't have doesn't panic
+        assert_eq!(execution_providers(&[ExecutionProvider::Cpu]).len(), 1);
+        let providers = execution_providers(&[ExecutionProvider::Cuda, ExecutionProvider::Cpu]);
+        assert!(!providers.is_empty());
+        assert!(session_builder(&providers).is_ok());
+    }
+
+    #[test]
+    fn tacotron_execution_providers() {
+        let model =
+            Tacotron2::load_with_providers("./models/tacotron2", &[ExecutionProvider::Cuda])
+                .unwrap();
+        let spec = model.infer(&[Unit::Character('a')]).unwrap();
+        assert_eq!(spec.nrows(), 80);
+        assert!(spec.ncols() > 0);
+    }
+
+    #[test]
+    fn config_propagates() {
+        let config = Tacotron2Config::from_json(
+            br#"{"n_mel_channels": 40, "attention_rnn_dim": 8, "decoder_rnn_dim": 6, "encoder_embedding_dim": 4}"#,
+        )
+        .unwrap();
+        let memory = ArrayD::<f32>::zeros(IxDyn(&[1, 5, 4]));
+        let state = DecoderState::new(&memory.view(), 3, &config);
+        assert_eq!(state.attention_hidden.dim(), (1, 8));
+        assert_eq!(state.decoder_hidden.dim(), (1, 6));
+        assert_eq!(state.attention_context.dim(), (1, 4));
+        assert_eq!(state.decoder_input.dim(), (1, 40));
+        assert_eq!(state.attention_weights.dim(), (1, 5));
+
+        let mel = punctuation_only_mel(&[Unit::Punct(Punctuation::FullStop)], &config).unwrap();
+        assert_eq!(mel.nrows(), 40);
+        assert!(create_griffin_lim(&config).is_ok());
+    }
+
+    #[test]
+    fn tacotron_config() {
+        let read = |name: &str| std::fs::read(Path::new("./models/tacotron2").join(name)).unwrap();
+        let mut symbols = generate_id_list(PhoneSet::english())
+            .iter()
+            .map(|x| match x {
+                Unit::Padding => "_".to_string(),
+                Unit::Phone(p) => format!("@{}", p),
+                x => x.to_string(),
+            })
+            .collect::<Vec<_>>();
+        symbols.swap(1, 2);
+        let json = serde_json::json!({ "decoder_rnn_dim": 1024, "symbols": symbols });
+        let files = ModelFiles {
+            encoder: read("encoder.onnx"),
+            decoder: read("decoder_iter.onnx"),
+            postnet: read("postnet.onnx"),
+            config: Some(json.to_string().into_bytes()),
+        };
+        let model = Tacotron2::load_from_files(&files, PhoneSet::english()).unwrap();
+        assert_eq!(model.config().symbols.as_ref(), Some(&symbols));
+        assert_eq!(
+            model.phoneme_ids[1],
+            Unit::Punct(Punctuation::ExclamationMark)
+        );
+        assert_eq!(model.phoneme_ids[2], Unit::Punct(Punctuation::Dash));
+        let spec = model.infer(&[Unit::Character('a')]).unwrap();
+        assert_eq!(spec.nrows(), model.config().n_mel_channels);
+    }
+
+    #[test]
+    fn tacotron_alignment() {
+        let units = "hello world."
+            .chars()
+            .map(|x| Unit::from_str(&x.to_string()).unwrap())
+            .collect::<Vec<_>>();
+        let model = Tacotron2::load("./models/tacotron2").unwrap();
+        let (spec, alignment) = model.infer_with_alignment(&units).unwrap();
+
+        assert_eq!(alignment.nrows(), spec.ncols());
+        assert_eq!(alignment.ncols(), units.len());
+    }
+
+    #[test]
+    fn tacotron_decoder_step_limit() {
+        let units = "this sentence takes a lot more than ten frames to say"
+            .chars()
+            .map(|x| Unit::from_str(&x.to_string()).unwrap())
+            .collect::<Vec<_>>();
+        let model = Tacotron2::load("./models/tacotron2")
+            .unwrap()
+            .with_max_decoder_steps(10);
+        let spec = model.infer(&units).unwrap();
+
+        assert_eq!(spec.nrows(), 80);
+        assert!(spec.ncols() > 0 && spec.ncols() <= 10);
+    }
 }
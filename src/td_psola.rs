@@ -1,3 +1,165 @@
 //! The reference implementation for this module is taken from
 //! [here](https://speech.zone/td-psola-the-hard-way/) and it aims to be a relatively simple
-//! implementation similar to the video. 
+//! implementation similar to the video.
+//!
+//! TD-PSOLA (Time Domain Pitch Synchronous Overlap Add) changes the pitch of speech without
+//! changing the duration. The audio is cut into grains two pitch periods long centred on pitch
+//! marks, then the grains are added back together with the marks closer together (higher pitch)
+//! or further apart (lower pitch). Grains are repeated or skipped so the output is the same
+//! length as the input.
+//!
+//! Because Tacotron2 can't be conditioned on pitch this is applied to the vocoder output as a
+//! post-processing step. It's an approximation: the pitch marks come from a simple
+//! autocorrelation pitch tracker and aren't aligned to the glottal closures so expect some
+//! roughness or echo, especially for large shifts. Unvoiced audio is copied across unchanged.
+
+/// Lowest pitch we look for, below most male voices
+const MIN_PITCH: f32 = 60.0;
+/// Highest pitch we look for, above most female voices
+const MAX_PITCH: f32 = 400.0;
+/// Size of the analysis window for the pitch tracker
+const FRAME_LEN: usize = 1024;
+/// Hop between pitch tracker frames
+const HOP_LEN: usize = 256;
+/// Normalised autocorrelation peak needed for a frame to count as voiced
+const VOICING_THRESHOLD: f32 = 0.3;
+
+/// Shifts the pitch of the audio by `factor` keeping the same duration, 2.0 raises it an octave
+/// and 0.5 lowers it an octave. See the module docs for the limitations.
+pub fn pitch_shift(samples: &[f32], sample_rate: u32, factor: f32) -> Vec<f32> {
+    if samples.is_empty() || !factor.is_finite() || factor <= 0.0 || factor == 1.0 {
+        return samples.to_vec();
+    }
+    let periods = track_periods(samples, sample_rate);
+    // Used for unvoiced frames so there's still grains to copy across
+    let default_period = (sample_rate as f32 / 100.0) as usize;
+    let period_at = |i: usize| periods[(i / HOP_LEN).min(periods.len() - 1)];
+
+    let mut marks = vec![];
+    let mut t = 0;
+    while t < samples.len() {
+        let period = period_at(t);
+        marks.push((t, period));
+        t += period.unwrap_or(default_period);
+    }
+
+    let mut output = vec![0.0; samples.len()];
+    let mut weights = vec![0.0; samples.len()];
+    let mut synthesis_mark = 0.0f32;
+    let mut mark_index = 0;
+    while (synthesis_mark as usize) < samples.len() {
+        let target = synthesis_mark as usize;
+        // Use whichever analysis mark is closest to where we are in the output
+        while mark_index + 1 < marks.len()
+            && marks[mark_index + 1].0.abs_diff(target) <= marks[mark_index].0.abs_diff(target)
+        {
+            mark_index += 1;
+        }
+        let (mark, period) = marks[mark_index];
+        let (grain_period, spacing) = match period {
+            Some(p) => (p, p as f32 / factor),
+            None => (default_period, default_period as f32),
+        };
+        for offset in 0..(2 * grain_period) {
+            let source = (mark + offset).checked_sub(grain_period);
+            let dest = (target + offset).checked_sub(grain_period);
+            if let (Some(source), Some(dest)) = (source, dest) {
+                if source >= samples.len() || dest >= output.len() {
+                    break;
+                }
+                let window = hann(offset, 2 * grain_period);
+                output[dest] += samples[source] * window;
+                weights[dest] += window;
+            }
+        }
+        synthesis_mark += spacing.max(1.0);
+    }
+    // Raising the pitch puts more grains on top of each other, so scale them back down
+    for (sample, weight) in output.iter_mut().zip(weights.iter()) {
+        if *weight > 1.0 {
+            *sample /= weight;
+        }
+    }
+    output
+}
+
+fn hann(i: usize, len: usize) -> f32 {
+    0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / len as f32).cos()
+}
+
+/// Estimates the pitch period in samples for each hop of the audio using autocorrelation, `None`
+/// means the frame is unvoiced.
+fn track_periods(samples: &[f32], sample_rate: u32) -> Vec<Option<usize>> {
+    let min_lag = (sample_rate as f32 / MAX_PITCH) as usize;
+    let max_lag = (sample_rate as f32 / MIN_PITCH) as usize;
+    (0..samples.len())
+        .step_by(HOP_LEN)
+        .map(|start| {
+            let frame = &samples[start..(start + FRAME_LEN).min(samples.len())];
+            let energy = frame.iter().map(|x| x * x).sum::<f32>();
+            if energy < 1e-6 || frame.len() <= max_lag {
+                return None;
+            }
+            let (lag, correlation) = (min_lag..=max_lag)
+                .map(|lag| {
+                    let c = frame
+                        .iter()
+                        .zip(&frame[lag..])
+                        .map(|(a, b)| a * b)
+                        .sum::<f32>();
+                    (lag, c)
+                })
+                .max_by(|a, b| a.1.total_cmp(&b.1))?;
+            (correlation / energy > VOICING_THRESHOLD).then_some(lag)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(freq: f32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| (2.0 * std::f32::consts::PI * freq * i as f32 / 22050.0).sin() * 0.5)
+            .collect()
+    }
+
+    fn zero_crossing_pitch(samples: &[f32]) -> f32 {
+        let crossings = samples
+            .windows(2)
+            .filter(|x| x[0] < 0.0 && x[1] >= 0.0)
+            .count();
+        crossings as f32 * 22050.0 / samples.len() as f32
+    }
+
+    #[test]
+    fn shift_keeps_duration() {
+        let input = sine(150.0, 22050);
+        for factor in [0.75, 1.0, 1.5] {
+            let output = pitch_shift(&input, 22050, factor);
+            assert_eq!(output.len(), input.len());
+            assert!(output.iter().all(|x| x.is_finite()));
+        }
+        assert_eq!(pitch_shift(&input, 22050, 1.0), input);
+        assert!(pitch_shift(&[], 22050, 2.0).is_empty());
+    }
+
+    #[test]
+    fn shift_changes_pitch() {
+        let input = sine(150.0, 22050);
+        // Ignore the ends where the grains don't fully overlap
+        let middle = 2205..(22050 - 2205);
+
+        let higher = pitch_shift(&input, 22050, 1.5);
+        let pitch = zero_crossing_pitch(&higher[middle.clone()]);
+        assert!((pitch - 225.0).abs() < 15.0, "{}", pitch);
+
+        let lower = pitch_shift(&input, 22050, 0.75);
+        let pitch = zero_crossing_pitch(&lower[middle]);
+        assert!((pitch - 112.5).abs() < 15.0, "{}", pitch);
+
+        let silence = vec![0.0; 4096];
+        assert_eq!(pitch_shift(&silence, 22050, 1.5), silence);
+    }
+}
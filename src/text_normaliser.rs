@@ -34,9 +34,9 @@
 //! hybrid system utilising statistical models in some places and rules in others to try and create
 //! the best system given time/data/complexity of the given linguistics.
 //!
-//! For this project we won't be dealing with homographs and selecting the right pronunciation.
-//! We'll instead make a simple rules based engine and try to handle numbers and unicode somewhat
-//! correctly.
+//! For this project we won't be dealing with homographs in general. There's a small rule table
+//! for the most common ones (see `crate::homographs`), otherwise we'll make a simple rules based
+//! engine and try to handle numbers and unicode somewhat correctly.
 //!
 //! ## SSML
 //!
@@ -58,6 +58,22 @@
 //! The SSML spec is much wider than the features supported here, but generally it says a
 //! synthesiser can quietly ignore features it doesn't support.
 //!
+//! ## Number Hints
+//!
+//! Writing SSML to fix one misread number is a lot of effort, so in plain text a number can be
+//! followed by a hint in braces saying how to read it:
+//!
+//! * `{cardinal}` - "the 1980{cardinal} entries" is "THE ONE THOUSAND NINE HUNDRED AND EIGHTY
+//!   ENTRIES"
+//! * `{ordinal}` - "finished 2{ordinal}" is "FINISHED SECOND"
+//! * `{year}` - "in 1980{year}" is "IN NINETEEN EIGHTY"
+//! * `{digits}` - "room 101{digits}" is "ROOM ONE ZERO ONE"
+//!
+//! The hint has to come straight after the number with no space and only works on whole numbers,
+//! on a decimal, negative number or currency amount the hint is dropped. Anything else in braces
+//! is left alone. Hints can be turned off with
+//! `NormaliserOptions::number_hints`.
+//!
 //! ## A Note on Other Languages
 //!
 //! These docs and this example are largely only applicable to English for other languages there
@@ -88,14 +104,22 @@
 //!
 //! There are undoubtedly many more examples spanning all languages, but these are examples from
 //! two languages I've had experience with in my personal and professional life!
+use crate::cmu_dict::{CmuDictionary, FallbackDictionary};
+use crate::homographs::HomographResolver;
 use crate::phonemes::Unit as TtsUnit;
 use crate::phonemes::*;
-use crate::CmuDictionary;
 use deunicode::deunicode;
 use num2words::Num2Words;
 use once_cell::sync::OnceCell;
 use regex::Regex;
-use ssml_parser::{elements::*, parser::SsmlParserBuilder, ParserEvent};
+use ssml_parser::{
+    elements::*,
+    parser::{Ssml, SsmlParserBuilder},
+    ParserEvent,
+};
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+use std::ops::Range;
 use std::str::FromStr;
 use std::time::Duration;
 use tracing::{debug, error, warn};
@@ -113,6 +137,348 @@ pub enum NormaliserChunk {
     /// Punctuation to be applied. This is separate so we can map it to pauses (if not handled by
     /// the model).
     Punct(Punctuation),
+    /// Changes the pitch of everything after it, in cents (hundredths of a semitone) relative to
+    /// the model's normal voice. 0 goes back to the normal pitch. This comes from SSML
+    /// `<prosody pitch="...">` and is applied to the audio after vocoding, see `td_psola`.
+    Pitch(i32),
+    /// Marks the language of the pronunciations after it, `None` goes back to the main language.
+    /// Words found in a fallback dictionary are wrapped in these (see
+    /// `NormalisedText::words_to_pronunciation_with_dictionaries`) so a multi-voice setup could
+    /// switch voices. The built-in pipeline doesn't do anything with them yet.
+    Language(Option<String>),
+    /// Starts (`true`) or ends (`false`) a span that should be read without any pauses, this
+    /// comes from the SSML `<phrase>` tag. Punctuation inside the span is already removed by the
+    /// normaliser and `NormalisedText::insert_breaths` won't add breaths inside it.
+    NoBreaks(bool),
+}
+
+/// The kind of a sentence going by how it ends, this can be used to pick an intonation for it
+/// i.e. a rising pitch at the end of a question.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SentenceType {
+    /// Ends in a full stop, or has no terminal punctuation at all.
+    Statement,
+    /// Ends in a question mark.
+    Question,
+    /// Ends in an exclamation mark.
+    Exclamation,
+}
+
+impl SentenceType {
+    /// Gets the sentence type for a piece of punctuation, returns `None` if it doesn't end a
+    /// sentence.
+    pub fn from_punctuation(punct: Punctuation) -> Option<Self> {
+        match punct {
+            Punctuation::FullStop => Some(Self::Statement),
+            Punctuation::QuestionMark => Some(Self::Question),
+            Punctuation::ExclamationMark => Some(Self::Exclamation),
+            _ => None,
+        }
+    }
+}
+
+/// Settings for inserting breaths into long runs of speech. A person reading a long sentence
+/// without any full stops will have to stop and take a breath at some point, typically at a comma
+/// or before a conjunction. Without this long run-on sentences sound very unnatural.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BreathPauses {
+    /// Number of units (characters or phones) spoken since the last pause before we look for a
+    /// place to breathe.
+    pub threshold: usize,
+    /// How long the breath should be.
+    pub duration: Duration,
+}
+
+impl Default for BreathPauses {
+    fn default() -> Self {
+        // 160 is what the dataset analytics flags as a very long sentence.
+        Self {
+            threshold: 160,
+            duration: Duration::from_millis(250),
+        }
+    }
+}
+
+/// Options for the built-in English normaliser. The defaults are what `normalise` uses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NormaliserOptions {
+    /// Runs of digits longer than this are read one digit at a time, so account numbers and codes
+    /// like "1234567890" are read as "ONE TWO THREE..." instead of "ONE BILLION...". Numbers with
+    /// thousands separators or a decimal point are always read as quantities. This is a heuristic,
+    /// so set it to `None` to always read numbers as quantities.
+    pub read_digits_over: Option<usize>,
+    /// How tokens mixing several groups of letters and digits, like "R2D2", are read.
+    pub alphanumeric_ids: AlphanumericIds,
+    /// Reads numbers followed by a hint like "1980{year}" the way the hint says, see the module
+    /// docs. This is on by default.
+    pub number_hints: bool,
+    /// Which units after a number are expanded into words.
+    pub units: UnitExpansion,
+    /// Splits code identifiers into words, so "get_pronunciations" is "GET PRONUNCIATIONS" and
+    /// "camelCase" is "CAMEL CASE". This is off by default as it changes word boundaries, brand
+    /// names like "McDonald" or "YouTube" will be split too. See `split_identifiers`.
+    pub split_identifiers: bool,
+    /// Reads repeated digits in phone numbers as "DOUBLE" or "TRIPLE", so "000" is "TRIPLE OH"
+    /// instead of "OH OH OH". This only applies to SSML `<say-as interpret-as="telephone">`.
+    pub repeated_digits: bool,
+    /// Abbreviations that are expanded into words, the abbreviation is matched ignoring case with
+    /// or without a full stop after it. By default this is `DEFAULT_ABBREVIATIONS`, replace it to
+    /// add your own or use an empty slice to turn it off.
+    pub abbreviations: &'static [Abbreviation],
+    /// Reads words that are valid Roman numerals as numbers, so "World War II" is "WORLD WAR TWO".
+    /// Only uppercase words are read and "I" is always left alone, but this is still off by
+    /// default as words like "MIX" and "CD" are valid numerals. SSML `<say-as
+    /// interpret-as="roman">` always reads the numeral. See `roman_to_int`.
+    pub roman_numerals: bool,
+    /// How the markers at the start of each item in a list, like "1." or "•", are read.
+    pub list_markers: ListMarkers,
+    /// Names a Roman numeral after is read as a regnal number, so "Henry VIII" is "HENRY THE
+    /// EIGHTH". The name has to be capitalised and matches ignoring case. This doesn't need
+    /// `roman_numerals` to be on. By default this is `DEFAULT_REGNAL_NAMES`, replace it to add
+    /// your own or use an empty slice to turn it off.
+    pub regnal_names: &'static [&'static str],
+}
+
+/// Reading options for list markers at the start of a line, i.e. from a list pasted into the text.
+/// Numbered markers are up to three digits or a single letter followed by "." or ")" (a capital
+/// letter needs the ")" so initials aren't mistaken for markers), bullets are "•", "◦", "▪", "-"
+/// or "*". Each item is read with a pause before it instead of running on from the last one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ListMarkers {
+    /// No special handling, so "1." is read like any other number at the end of a sentence.
+    #[default]
+    Off,
+    /// Numbered markers are read with a pause after them so "1. Eggs" is "ONE, EGGS", bullets are
+    /// replaced with a pause.
+    Read,
+    /// All markers are replaced with a pause so "1. Eggs" is "EGGS".
+    Skip,
+}
+
+/// Units that can be read out after a number. These are niche enough that you may want them left
+/// alone, if they're disabled the symbol is handled like any other character (so `‰` is dropped).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UnitExpansion {
+    /// Reads "5‰" as "FIVE PER MILLE".
+    pub per_mille: bool,
+    /// Reads "25bps" or "25 bps" as "TWENTY FIVE BASIS POINTS".
+    pub basis_points: bool,
+    /// Reads metric, data size and frequency units, "5kg" or "5 kg" is "FIVE KILOGRAMS" and "2.5GB"
+    /// is "TWO POINT FIVE GIGABYTES".
+    pub measurements: bool,
+}
+
+impl Default for UnitExpansion {
+    fn default() -> Self {
+        Self {
+            per_mille: true,
+            basis_points: true,
+            measurements: true,
+        }
+    }
+}
+
+/// Reading options for alphanumeric IDs such as "R2D2", "C3PO" or "A1B2C3". These only apply to
+/// tokens with more than one group of letters or digits, simple tokens with one number in like
+/// "A1" or "k8s" are always read as letters and a number ("A ONE").
+///
+/// Letter groups of up to 3 letters are spelled out, longer groups are treated as a word. So
+/// "C3PO" is "C THREE P O" but "w3schools" is "W THREE SCHOOLS".
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AlphanumericIds {
+    /// Digit groups are read as numbers, "A12B" becomes "A TWELVE B".
+    #[default]
+    ReadDigitGroups,
+    /// Each digit is read on it's own, "A12B" becomes "A ONE TWO B".
+    SpellDigits,
+    /// No special handling, the token is split at the first number and each part normalised
+    /// separately. "C3PO" becomes "C THREE PO".
+    Off,
+}
+
+impl Default for NormaliserOptions {
+    fn default() -> Self {
+        // Anything over 7 digits is in the tens of millions, it's much more likely to be a phone
+        // number or ID than a quantity written without commas.
+        Self {
+            read_digits_over: Some(7),
+            alphanumeric_ids: AlphanumericIds::default(),
+            number_hints: true,
+            units: UnitExpansion::default(),
+            split_identifiers: false,
+            repeated_digits: true,
+            abbreviations: DEFAULT_ABBREVIATIONS,
+            roman_numerals: false,
+            list_markers: ListMarkers::default(),
+            regnal_names: DEFAULT_REGNAL_NAMES,
+        }
+    }
+}
+
+/// An abbreviation that's expanded into words, see `NormaliserOptions::abbreviations`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Abbreviation {
+    /// The abbreviation without the full stop
+    pub short: &'static str,
+    /// The words it's read as
+    pub expansion: &'static str,
+    /// Titles like "Dr." come before a name, so the full stop after them is never the end of a
+    /// sentence. For other abbreviations like "etc." it's kept if the next word is capitalised.
+    pub title: bool,
+}
+
+impl Abbreviation {
+    /// An abbreviation that can end a sentence, see `Abbreviation::title`.
+    pub const fn new(short: &'static str, expansion: &'static str) -> Self {
+        Self {
+            short,
+            expansion,
+            title: false,
+        }
+    }
+
+    /// An abbreviation that's always followed by a name, see `Abbreviation::title`.
+    pub const fn title(short: &'static str, expansion: &'static str) -> Self {
+        Self {
+            short,
+            expansion,
+            title: true,
+        }
+    }
+}
+
+/// Abbreviations expanded by default, see `NormaliserOptions::abbreviations`. "St" can be "SAINT"
+/// or "STREET" and working out which needs more context than we look at, so it's always
+/// "SAINT". Use your own table if your text is mostly addresses.
+pub const DEFAULT_ABBREVIATIONS: &[Abbreviation] = &[
+    Abbreviation::title("DR", "DOCTOR"),
+    Abbreviation::title("MR", "MISTER"),
+    Abbreviation::title("MRS", "MISSUS"),
+    Abbreviation::title("PROF", "PROFESSOR"),
+    Abbreviation::title("ST", "SAINT"),
+    Abbreviation::new("ETC", "ET CETERA"),
+    Abbreviation::new("VS", "VERSUS"),
+];
+
+/// Names of monarchs and popes a Roman numeral is read after as a regnal number, see
+/// `NormaliserOptions::regnal_names`. Names that are also common words, like "Victor" or "Urban",
+/// are left out as "Urban II" is more likely a sequel than a pope.
+pub const DEFAULT_REGNAL_NAMES: &[&str] = &[
+    "Alexander",
+    "Alfonso",
+    "Anne",
+    "Benedict",
+    "Boniface",
+    "Catherine",
+    "Charles",
+    "Christian",
+    "Clement",
+    "Constantine",
+    "Edward",
+    "Elizabeth",
+    "Ferdinand",
+    "Francis",
+    "Frederick",
+    "George",
+    "Gregory",
+    "Gustav",
+    "Haakon",
+    "Harald",
+    "Henry",
+    "Innocent",
+    "Ivan",
+    "James",
+    "John",
+    "Leo",
+    "Louis",
+    "Ludwig",
+    "Mary",
+    "Nicholas",
+    "Paul",
+    "Peter",
+    "Philip",
+    "Pius",
+    "Ramesses",
+    "Richard",
+    "Robert",
+    "Wilhelm",
+    "William",
+];
+
+/// Pause between groups of digits in a phone number.
+const TELEPHONE_GROUP_PAUSE: Duration = Duration::from_millis(200);
+
+/// Words we're happy to take a breath before.
+const CONJUNCTIONS: &[&str] = &[
+    "AND", "BUT", "OR", "SO", "YET", "BECAUSE", "WHILE", "ALTHOUGH",
+];
+
+/// Where a word's pronunciation came from, see `NormalisedText::words_to_pronunciation_with_spans`.
+/// This lets a read-along UI highlight the word being spoken when combined with the timings of the
+/// units. Words are found in the text that was given to the normaliser by matching them against
+/// its transliterated words in order, so words the normaliser rewrote like "Dr." or "2" have no
+/// source range.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WordSpan {
+    /// The word as it was looked up, entries with multiple words like "NEW YORK" are one span
+    pub word: String,
+    /// Index of the text chunk the word came from, this is the index in `NormalisedText::chunks`
+    /// before the text was converted
+    pub source_chunk: usize,
+    /// Byte range of the word in the text given to the normaliser, this is `None` if the word
+    /// isn't written there as it's pronounced
+    pub source_range: Option<Range<usize>>,
+    /// Index of the pronunciation chunk in `NormalisedText::chunks` after the text was converted
+    pub chunk: usize,
+    /// Range of the word's units in the pronunciation chunk, this doesn't include the space after
+    /// the word
+    pub units: Range<usize>,
+}
+
+/// Finds each span's word in the text that was normalised. The words in `source` are transliterated
+/// and uppercased like the normaliser does, then each span takes the next run of words that match
+/// it exactly. SSML tags are skipped so their names and attributes aren't matched.
+fn locate_words(source: &str, spans: &mut [WordSpan]) {
+    let is_ssml = source.trim_start().starts_with('<');
+    let mut words: Vec<(Range<usize>, String)> = vec![];
+    let mut start = None;
+    let mut in_tag = false;
+    for (i, c) in source
+        .char_indices()
+        .chain(std::iter::once((source.len(), ' ')))
+    {
+        let is_word = !in_tag && (c.is_alphanumeric() || c == '\'');
+        match start {
+            Some(s) if !is_word => {
+                let word = deunicode(&source[s..i]).to_uppercase();
+                words.push((s..i, word));
+                start = None;
+            }
+            None if is_word => start = Some(i),
+            _ => {}
+        }
+        if is_ssml && c == '<' {
+            in_tag = true;
+        } else if is_ssml && c == '>' {
+            in_tag = false;
+        }
+    }
+    let mut next = 0;
+    for span in spans.iter_mut() {
+        let parts = span.word.split_ascii_whitespace().collect::<Vec<_>>();
+        let found = (next..words.len()).find(|i| {
+            words.len() - i >= parts.len()
+                && parts
+                    .iter()
+                    .zip(&words[*i..])
+                    .all(|(part, (_, word))| part == word)
+        });
+        if let Some(i) = found {
+            let end = i + parts.len() - 1;
+            span.source_range = Some(words[i].0.start..words[end].0.end);
+            next = end + 1;
+        }
+    }
 }
 
 /// Output from the text normaliser, this contains a sequence of chunks to be processed. We return
@@ -127,41 +493,283 @@ pub struct NormalisedText {
 }
 
 impl NormalisedText {
+    /// Merges each run of adjacent text and punctuation chunks into a single text chunk, with the
+    /// punctuation written after the word it follows. This is meant to be ran before
+    /// `words_to_pronunciation`, which splits the punctuation back out into its own chunks, so
+    /// the pauses are kept. But multi-word dictionary entries can then match words that came from
+    /// different chunks i.e. separate SSML elements, and homographs can use the words either side
+    /// of a punctuation mark to pick a pronunciation.
+    ///
+    /// Punctuation before the first word of a run is left as it is.
+    pub fn merge_text_chunks(&mut self) {
+        let mut chunks = Vec::with_capacity(self.chunks.len());
+        for chunk in self.chunks.drain(..) {
+            let merged = match (chunks.last_mut(), &chunk) {
+                (Some(NormaliserChunk::Text(merged)), NormaliserChunk::Text(text)) => {
+                    let text = text.trim();
+                    if !text.is_empty() {
+                        if !merged.is_empty() && !merged.ends_with(' ') {
+                            merged.push(' ');
+                        }
+                        merged.push_str(text);
+                    }
+                    true
+                }
+                (Some(NormaliserChunk::Text(merged)), NormaliserChunk::Punct(punct)) => {
+                    merged.truncate(merged.trim_end().len());
+                    merged.push_str(&punct.to_string());
+                    true
+                }
+                _ => false,
+            };
+            if !merged {
+                match chunk {
+                    NormaliserChunk::Text(text) => {
+                        chunks.push(NormaliserChunk::Text(text.trim().to_string()))
+                    }
+                    chunk => chunks.push(chunk),
+                }
+            }
+        }
+        self.chunks = chunks;
+    }
+
     /// Takes the normaliser and a dictionary and converts all the text to an exact pronunciation.
-    /// This does not handle picking the right pronunciation when there are multiple candidate
-    /// ones, it will just select the first in the dictionary. Unsupported words will be skipped
-    /// (traditionally there would be a G2P model to estimate a pronunciation for them).
+    /// When there are multiple candidate pronunciations the first in the dictionary is used,
+    /// unless it's one of the common homographs in `HomographResolver::english` which are picked
+    /// based on the words around them. Unsupported words will be skipped (traditionally there
+    /// would be a G2P model to estimate a pronunciation for them).
+    ///
+    /// If the dictionary has multi-word entries the longest matching sequence of words is used,
+    /// falling back to looking up the words one at a time. Matches don't run over punctuation or
+    /// the end of a text chunk, see `merge_text_chunks` for looking at more than one chunk.
     pub fn words_to_pronunciation(&mut self, dict: &CmuDictionary) {
-        for x in self
-            .chunks
-            .iter_mut()
-            .filter(|x| matches!(x, NormaliserChunk::Text(_)))
-        {
-            let units = match x {
-                NormaliserChunk::Text(s) => {
-                    let mut units = vec![];
-                    for word in s.split_ascii_whitespace() {
-                        if let Some(pronunciation) = dict.get_pronunciations(word) {
-                            assert!(!pronunciation.is_empty());
-                            debug!("{} is pronounced: {:?}", word, pronunciation);
-                            units.extend(pronunciation[0].iter().map(|x| TtsUnit::Phone(*x)));
-                            units.push(TtsUnit::Space);
-                        } else {
-                            warn!("Unsupported word: '{}'", word);
+        self.words_to_pronunciation_with_fallback(dict, |_| None);
+    }
+
+    /// The same as `words_to_pronunciation` but words which aren't in the dictionary are passed
+    /// to `on_miss`. This is where you'd plug in a G2P model or an external lexicon service, if it
+    /// returns `None` the word is skipped. The callback can cache what it finds itself so each word
+    /// is only looked up once.
+    pub fn words_to_pronunciation_with_fallback(
+        &mut self,
+        dict: &CmuDictionary,
+        on_miss: impl FnMut(&str) -> Option<Pronunciation>,
+    ) {
+        static ENGLISH: OnceCell<HomographResolver> = OnceCell::new();
+        let homographs = ENGLISH.get_or_init(HomographResolver::english);
+        self.words_to_pronunciation_with_homographs(dict, homographs, on_miss);
+    }
+
+    /// The same as `words_to_pronunciation_with_fallback` but with your own homograph rules
+    /// instead of the built-in English ones.
+    pub fn words_to_pronunciation_with_homographs(
+        &mut self,
+        dict: &CmuDictionary,
+        homographs: &HomographResolver,
+        on_miss: impl FnMut(&str) -> Option<Pronunciation>,
+    ) {
+        self.words_to_pronunciation_with_dictionaries(dict, &[], homographs, on_miss);
+    }
+
+    /// The same as `words_to_pronunciation_with_homographs` but words missing from the main
+    /// dictionary are looked up in each of the fallback dictionaries in turn before calling
+    /// `on_miss`. This is a first step towards code-switching, i.e. pronouncing the odd French
+    /// phrase in English text. Runs of words found in a fallback are surrounded by
+    /// `NormaliserChunk::Language` chunks with the fallback's language. The fallback
+    /// pronunciations have to use phones the model knows, so they'll be an English speaker's
+    /// approximation of the foreign words.
+    ///
+    /// Lookups are cached for the duration of the call so repeated words like "the" are only
+    /// normalised and looked up once. `on_miss` isn't cached, it's called for every missing word.
+    pub fn words_to_pronunciation_with_dictionaries(
+        &mut self,
+        dict: &CmuDictionary,
+        fallbacks: &[FallbackDictionary],
+        homographs: &HomographResolver,
+        on_miss: impl FnMut(&str) -> Option<Pronunciation>,
+    ) {
+        self.pronounce_words(dict, fallbacks, homographs, on_miss, None);
+    }
+
+    /// The same as `words_to_pronunciation_with_dictionaries` but also returns where each word's
+    /// pronunciation is and which word it came from. `source` is the text or SSML that was
+    /// normalised, the spans' `source_range` index into it. Words that were skipped because
+    /// there's no pronunciation for them don't have a span.
+    pub fn words_to_pronunciation_with_spans(
+        &mut self,
+        source: &str,
+        dict: &CmuDictionary,
+        fallbacks: &[FallbackDictionary],
+        homographs: &HomographResolver,
+        on_miss: impl FnMut(&str) -> Option<Pronunciation>,
+    ) -> Vec<WordSpan> {
+        let mut spans = vec![];
+        self.pronounce_words(dict, fallbacks, homographs, on_miss, Some(&mut spans));
+        locate_words(source, &mut spans);
+        spans
+    }
+
+    fn pronounce_words(
+        &mut self,
+        dict: &CmuDictionary,
+        fallbacks: &[FallbackDictionary],
+        homographs: &HomographResolver,
+        mut on_miss: impl FnMut(&str) -> Option<Pronunciation>,
+        mut spans: Option<&mut Vec<WordSpan>>,
+    ) {
+        let mut chunks = Vec::with_capacity(self.chunks.len());
+        let mut cache = HashMap::new();
+        for (source_chunk, chunk) in self.chunks.drain(..).enumerate() {
+            let s = match chunk {
+                NormaliserChunk::Text(s) => s,
+                chunk => {
+                    chunks.push(chunk);
+                    continue;
+                }
+            };
+            let mut units = vec![];
+            let mut language: Option<&str> = None;
+            let start = chunks.len();
+            // Start of the current run of words between punctuation, and whether it has any words
+            let mut segment_start = start;
+            let mut pending = false;
+            let mut offset = 0;
+            let mut words = vec![];
+            let mut word_ranges = vec![];
+            let mut puncts: Vec<Option<Punctuation>> = vec![];
+            // Punctuation left in the text i.e. by `merge_text_chunks` is split off the words so it
+            // can still be turned into a pause.
+            for word in s.split_ascii_whitespace() {
+                let start = offset + s[offset..].find(word).unwrap_or_default();
+                offset = start + word.len();
+                let bare = word.trim_end_matches(|c: char| c.is_ascii_punctuation());
+                let punct = collapse_punctuation(&word[bare.len()..]);
+                if bare.is_empty() {
+                    match (puncts.last_mut(), punct) {
+                        (_, None) => {}
+                        (Some(Some(prev)), Some(punct)) => *prev = merge_punctuation(*prev, punct),
+                        (Some(prev), punct) => *prev = punct,
+                        (None, _) => debug!("Dropping leading punctuation: '{}'", word),
+                    }
+                    continue;
+                }
+                words.push(bare);
+                word_ranges.push(start..(start + bare.len()));
+                puncts.push(punct);
+            }
+            let mut i = 0;
+            while i < words.len() {
+                // Phrases can't run over punctuation
+                let segment_end = (i..words.len())
+                    .find(|j| puncts[*j].is_some())
+                    .map(|j| j + 1)
+                    .unwrap_or(words.len());
+                let segment = &words[i..segment_end];
+                let found = longest_match(dict, 0, segment, &mut cache)
+                    .map(|x| (x, None))
+                    .or_else(|| {
+                        fallbacks.iter().enumerate().find_map(|(j, fallback)| {
+                            longest_match(&fallback.dictionary, j + 1, segment, &mut cache)
+                                .map(|x| (x, Some(fallback.language.as_str())))
+                        })
+                    })
+                    .filter(|((_, phrase, pronunciation), _)| {
+                        // Only possible with a dictionary that wasn't loaded by `from_reader`
+                        let valid = !pronunciation.is_empty()
+                            && pronunciation.iter().all(|x| !x.is_empty());
+                        if !valid {
+                            warn!("Ignoring empty pronunciation for '{}'", phrase);
+                        }
+                        valid
+                    });
+                let pronunciation = if let Some(((n, phrase, pronunciation), found_in)) = found {
+                    let index = if n == 1 && pronunciation.len() > 1 && found_in.is_none() {
+                        let previous = i.checked_sub(1).map(|x| words[x]);
+                        let next = words.get(i + 1).copied();
+                        homographs
+                            .resolve(&phrase, previous, next, pronunciation)
+                            .unwrap_or_default()
+                    } else {
+                        0
+                    };
+                    if found_in != language {
+                        if !units.is_empty() {
+                            chunks.push(NormaliserChunk::Pronunciation(std::mem::take(&mut units)));
+                        }
+                        chunks.push(NormaliserChunk::Language(found_in.map(String::from)));
+                        language = found_in;
+                    }
+                    debug!("{} is pronounced: {:?}", phrase, pronunciation[index]);
+                    Some((n, pronunciation[index].clone()))
+                } else {
+                    let pronunciation = on_miss(words[i]);
+                    match &pronunciation {
+                        Some(pronunciation) => {
+                            debug!("{} is pronounced: {:?}", words[i], pronunciation)
+                        }
+                        None => warn!("Unsupported word: '{}'", words[i]),
+                    }
+                    pronunciation.map(|x| (1, x))
+                };
+                let n = match pronunciation {
+                    Some((n, pronunciation)) => {
+                        if let Some(spans) = spans.as_mut() {
+                            let range = word_ranges[i].start..word_ranges[i + n - 1].end;
+                            spans.push(WordSpan {
+                                word: s[range].to_string(),
+                                source_chunk,
+                                source_range: None,
+                                chunk: chunks.len(),
+                                units: units.len()..(units.len() + pronunciation.len()),
+                            });
                         }
+                        units.extend(pronunciation.iter().map(|x| TtsUnit::Phone(*x)));
+                        units.push(TtsUnit::Space);
+                        n
+                    }
+                    None => 1,
+                };
+                i += n;
+                pending = true;
+                if let Some(punct) = puncts[i - 1] {
+                    if !units.is_empty() || chunks.len() == segment_start {
+                        chunks.push(NormaliserChunk::Pronunciation(std::mem::take(&mut units)));
                     }
-                    units
+                    chunks.push(NormaliserChunk::Punct(punct));
+                    segment_start = chunks.len();
+                    pending = false;
                 }
-                _ => unreachable!(),
-            };
-            *x = NormaliserChunk::Pronunciation(units);
+            }
+            // Every text chunk, and every run of words in it between punctuation, becomes at least
+            // one pronunciation even if it's empty
+            if (pending || chunks.len() == start)
+                && (!units.is_empty() || chunks.len() == segment_start)
+            {
+                chunks.push(NormaliserChunk::Pronunciation(units));
+            }
+            if language.is_some() {
+                chunks.push(NormaliserChunk::Language(None));
+            }
         }
+        self.chunks = chunks;
     }
 
     /// Converts the existing representation to be all in terms of `crate::phonemes::Unit`. This
     /// will turn words into a sequence of `Unit::Character` not convert to a pronunciation. If you
     /// want phonemes out use `NormalisedText::words_to_pronunciation`.
+    ///
+    /// The characters are lowercased as that's what the NVIDIA tacotron2 model was trained on, use
+    /// `convert_to_units_with_case` to keep the case.
     pub fn convert_to_units(&mut self) {
+        self.convert_to_units_with_case(true);
+    }
+
+    /// The same as `convert_to_units` but the characters are only lowercased if `lowercase` is
+    /// set. A model trained on mixed case text can use capitals to stress words. Be aware the
+    /// built-in normaliser uppercases everything, so keeping the case is only useful with a
+    /// frontend that preserves it.
+    pub fn convert_to_units_with_case(&mut self, lowercase: bool) {
         for x in self
             .chunks
             .iter_mut()
@@ -169,7 +777,11 @@ impl NormalisedText {
         {
             let units = match x {
                 NormaliserChunk::Text(x) => {
-                    let x = x.to_ascii_lowercase();
+                    let x = if lowercase {
+                        x.to_ascii_lowercase()
+                    } else {
+                        x.clone()
+                    };
                     let mut chunk = vec![];
                     for c in x.chars() {
                         if c.is_whitespace() {
@@ -189,6 +801,69 @@ impl NormalisedText {
         }
     }
 
+    /// Inserts breaths into long runs of speech which lack a sentence end. Once we've gone over
+    /// the threshold a break is inserted at the next pause punctuation (commas, semi-colons) or
+    /// before the next conjunction. This should be ran before the text is converted into units so
+    /// we can still see the words.
+    pub fn insert_breaths(&mut self, breaths: &BreathPauses) {
+        let mut chunks = Vec::with_capacity(self.chunks.len());
+        let mut run_length = 0;
+        let mut no_breaks = false;
+        for chunk in self.chunks.drain(..) {
+            match chunk {
+                NormaliserChunk::Text(text) => {
+                    let mut current = String::new();
+                    for word in text.split_inclusive(' ') {
+                        if !no_breaks
+                            && run_length > breaths.threshold
+                            && CONJUNCTIONS.contains(&word.trim())
+                            && !current.trim().is_empty()
+                        {
+                            chunks.push(NormaliserChunk::Text(current.clone()));
+                            chunks.push(NormaliserChunk::Break(breaths.duration));
+                            current.clear();
+                            run_length = 0;
+                        }
+                        current.push_str(word);
+                        run_length += word.len();
+                    }
+                    if !current.is_empty() {
+                        chunks.push(NormaliserChunk::Text(current));
+                    }
+                }
+                NormaliserChunk::Pronunciation(units) => {
+                    run_length += units.len();
+                    chunks.push(NormaliserChunk::Pronunciation(units));
+                }
+                NormaliserChunk::Punct(p) => {
+                    run_length += 1;
+                    chunks.push(NormaliserChunk::Punct(p));
+                    match split_score(&TtsUnit::Punct(p)) {
+                        0 | 1 => {}
+                        2 if run_length > breaths.threshold && !no_breaks => {
+                            chunks.push(NormaliserChunk::Break(breaths.duration));
+                            run_length = 0;
+                        }
+                        2 => {}
+                        _ => run_length = 0,
+                    }
+                }
+                NormaliserChunk::NoBreaks(start) => {
+                    no_breaks = start;
+                    chunks.push(NormaliserChunk::NoBreaks(start));
+                }
+                NormaliserChunk::Break(duration) => {
+                    run_length = 0;
+                    chunks.push(NormaliserChunk::Break(duration));
+                }
+                chunk @ (NormaliserChunk::Pitch(_) | NormaliserChunk::Language(_)) => {
+                    chunks.push(chunk);
+                }
+            }
+        }
+        self.chunks = chunks;
+    }
+
     /// Draining iterator, takes all the chunks out
     pub fn drain_all(&mut self) -> impl Iterator<Item = NormaliserChunk> + '_ {
         self.chunks.drain(..)
@@ -209,7 +884,10 @@ impl NormalisedText {
                 NormaliserChunk::Pronunciation(_) => {
                     anyhow::bail!("Can't turn pronunciation chunk into text")
                 }
-                NormaliserChunk::Break(_) => {}
+                NormaliserChunk::Break(_)
+                | NormaliserChunk::Pitch(_)
+                | NormaliserChunk::Language(_)
+                | NormaliserChunk::NoBreaks(_) => {}
             }
         }
         Ok(res)
@@ -221,24 +899,142 @@ impl NormalisedText {
         self.to_string().unwrap()
     }
 
-    /// Appends another set of normalised text chunks to this one.
-    fn append(&mut self, mut other: NormalisedText) {
-        self.chunks.append(&mut other.chunks);
+    /// Adds a chunk to the end of the normalised text. This is mainly intended for custom
+    /// frontends building up their own output.
+    pub fn push(&mut self, chunk: NormaliserChunk) {
+        self.chunks.push(chunk);
+    }
+
+    /// Returns the chunks currently in the normalised text.
+    pub fn chunks(&self) -> &[NormaliserChunk] {
+        &self.chunks
+    }
+
+    /// Gets the type of each sentence in the text in order, based on the punctuation ending it.
+    /// Any spoken text after the last sentence end is treated as a statement, and repeated
+    /// punctuation like "?!" only ends a sentence once.
+    pub fn sentence_types(&self) -> Vec<SentenceType> {
+        let mut res = vec![];
+        let mut has_content = false;
+        let mut end_sentence = |punct: Punctuation, has_content: &mut bool| {
+            if let Some(ty) = SentenceType::from_punctuation(punct) {
+                if *has_content {
+                    res.push(ty);
+                    *has_content = false;
+                }
+            }
+        };
+        for chunk in &self.chunks {
+            match chunk {
+                NormaliserChunk::Text(t) => {
+                    has_content |= t.chars().any(|c| c.is_alphanumeric());
+                }
+                NormaliserChunk::Pronunciation(units) => {
+                    for unit in units {
+                        match unit {
+                            TtsUnit::Punct(p) => end_sentence(*p, &mut has_content),
+                            TtsUnit::Phone(_) | TtsUnit::Character(_) => has_content = true,
+                            _ => {}
+                        }
+                    }
+                }
+                NormaliserChunk::Punct(p) => end_sentence(*p, &mut has_content),
+                _ => {}
+            }
+        }
+        if has_content {
+            res.push(SentenceType::Statement);
+        }
+        res
+    }
+}
+
+impl From<Vec<NormaliserChunk>> for NormalisedText {
+    fn from(chunks: Vec<NormaliserChunk>) -> Self {
+        Self { chunks }
+    }
+}
+
+/// A text frontend is the part of the pipeline that takes the users input and turns it into
+/// `NormalisedText`. The built-in frontend only handles English, so if you want to support another
+/// language or your own normalisation rules you can implement this and give it to `XdTts`.
+///
+/// The contract for the output is that it should be ready to be turned into units. That means any
+/// `NormaliserChunk::Text` should be normalised words separated by whitespace that can be looked
+/// up in a dictionary (or turned into characters), and anything with a known pronunciation should
+/// already be a `NormaliserChunk::Pronunciation`. Breaks and punctuation are passed through as is.
+///
+/// Frontends need to be `Send + Sync` so `XdTts` can be shared between threads.
+pub trait TextFrontend: Send + Sync {
+    /// Normalise the input text, this may be plain text or SSML depending on what the frontend
+    /// supports.
+    fn normalise(&self, text: &str) -> anyhow::Result<NormalisedText>;
+}
+
+/// The built-in English frontend, this just calls `normalise_with_options`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EnglishFrontend {
+    options: NormaliserOptions,
+}
+
+impl EnglishFrontend {
+    /// Creates an English frontend with non-default normaliser options.
+    pub fn new(options: NormaliserOptions) -> Self {
+        Self { options }
+    }
+}
+
+impl TextFrontend for EnglishFrontend {
+    fn normalise(&self, text: &str) -> anyhow::Result<NormalisedText> {
+        normalise_with_options(text, &self.options)
     }
 }
 
 /// Runs text normalisation. Attempts to detect if the given transcript is SSML or just text and
 /// pick an appropriate normaliser.
+///
+/// This is safe to call with untrusted input, it shouldn't panic on anything. Invalid SSML results
+/// in an error and text we can't make sense of is either read out as best we can or dropped.
 pub fn normalise(x: &str) -> anyhow::Result<NormalisedText> {
+    normalise_with_options(x, &NormaliserOptions::default())
+}
+
+/// Runs text normalisation with the given options, see `normalise`.
+pub fn normalise_with_options(
+    x: &str,
+    options: &NormaliserOptions,
+) -> anyhow::Result<NormalisedText> {
     // We're doing this really simply and assuming the start of a speak tag won't appear in
     // non-SSML.
     if x.contains("<speak") {
-        normalise_ssml(x)
+        normalise_ssml_with_options(x, options)
     } else {
-        Ok(normalise_text(x))
+        Ok(normalise_text_with_options(x, options))
     }
 }
 
+/// Dictionary lookups keyed on the index of the dictionary and the phrase, see `longest_match`.
+type LookupCache<'a> = HashMap<(usize, String), Option<&'a Vec<Pronunciation>>>;
+
+/// Finds the longest run of words at the start of `words` that's in the dictionary, returning the
+/// number of words matched, the phrase and its pronunciations. `dict_index` identifies the
+/// dictionary in the cache.
+fn longest_match<'a>(
+    dict: &'a CmuDictionary,
+    dict_index: usize,
+    words: &[&str],
+    cache: &mut LookupCache<'a>,
+) -> Option<(usize, String, &'a Vec<Pronunciation>)> {
+    let longest = dict.max_entry_words().min(words.len());
+    (1..=longest).rev().find_map(|n| {
+        let phrase = words[..n].join(" ");
+        let found = *cache
+            .entry((dict_index, phrase.clone()))
+            .or_insert_with(|| dict.get_pronunciations(&phrase));
+        found.map(|x| (n, phrase, x))
+    })
+}
+
 /// This is a normalisation just for CMU dictionary entries. These are typically words some
 /// containing numbers - hence needing a mild normalisation. But also for words with multiple
 /// entries they will add `(N)` after the word where N is the index of the pronunciation. This
@@ -250,14 +1046,23 @@ pub fn dict_normalise(x: &str) -> String {
 
     let version_strip = version_regex.replace_all(x, "");
 
-    normalise_text(&version_strip).to_string_unchecked()
+    // Abbreviations are entries in their own right, "ST" is pronounced "STREET" in CMU dict
+    let options = NormaliserOptions {
+        abbreviations: &[],
+        ..Default::default()
+    };
+    normalise_text_with_options(&version_strip, &options).to_string_unchecked()
 }
 
 /// Handles an SSML `<say-as>` tag. This tag is used to help disambiguate numbers, make acronyms a
 /// bit better to handle among other things. I've kept say-as support minimal but you could add as
 /// many or little as you desire. There's also minimal validation that the input is correct instead
 /// trying to do a best effort guess of what the user wants.
-fn handle_say_as(say_as: &SayAsAttributes, text: &str) -> anyhow::Result<NormaliserChunk> {
+fn handle_say_as(
+    say_as: &SayAsAttributes,
+    text: &str,
+    options: &NormaliserOptions,
+) -> anyhow::Result<Vec<NormaliserChunk>> {
     match say_as.interpret_as.as_str() {
         "ordinal" => {
             let num = text.trim().parse::<i64>()?;
@@ -267,7 +1072,7 @@ fn handle_say_as(say_as: &SayAsAttributes, text: &str) -> anyhow::Result<Normali
                 .map_err(|e| anyhow::anyhow!(e))?
                 .replace('-', " ")
                 .to_ascii_uppercase();
-            Ok(NormaliserChunk::Text(text))
+            Ok(vec![NormaliserChunk::Text(text)])
         }
         "cardinal" => {
             let num = text.trim().parse::<i64>()?;
@@ -277,7 +1082,31 @@ fn handle_say_as(say_as: &SayAsAttributes, text: &str) -> anyhow::Result<Normali
                 .map_err(|e| anyhow::anyhow!(e))?
                 .replace('-', " ")
                 .to_ascii_uppercase();
-            Ok(NormaliserChunk::Text(text))
+            Ok(vec![NormaliserChunk::Text(text)])
+        }
+        "year" => {
+            let year = text.trim().parse::<i64>()?;
+            Ok(vec![NormaliserChunk::Text(year_words(year)?)])
+        }
+        "date" => Ok(vec![NormaliserChunk::Text(process_date(
+            text,
+            say_as.format.as_deref(),
+        )?)]),
+        "telephone" => process_telephone(text, options),
+        "roman" => {
+            let num = roman_to_int(&text.trim().to_ascii_uppercase())
+                .ok_or_else(|| anyhow::anyhow!("Invalid roman numeral: '{}'", text.trim()))?;
+            let text = if say_as.format.as_deref() == Some("ordinal") {
+                Num2Words::new(num)
+                    .ordinal()
+                    .to_words()
+                    .map_err(|e| anyhow::anyhow!(e))?
+                    .replace('-', " ")
+                    .to_ascii_uppercase()
+            } else {
+                cardinal_words(num.into())?
+            };
+            Ok(vec![NormaliserChunk::Text(text)])
         }
         "characters" => {
             let characters = text.graphemes(true).collect::<Vec<&str>>().join(" ");
@@ -286,9 +1115,9 @@ fn handle_say_as(say_as: &SayAsAttributes, text: &str) -> anyhow::Result<Normali
                 .chunks
                 .retain(|x| matches!(x, NormaliserChunk::Text(t) if !t.is_empty()));
             if chunk.chunks.len() == 1 {
-                Ok(chunk.chunks.remove(0))
+                Ok(vec![chunk.chunks.remove(0)])
             } else {
-                Ok(NormaliserChunk::Text(chunk.to_string_unchecked()))
+                Ok(vec![NormaliserChunk::Text(chunk.to_string()?)])
             }
         }
         s => {
@@ -302,28 +1131,112 @@ fn handle_say_as(say_as: &SayAsAttributes, text: &str) -> anyhow::Result<Normali
 /// simple as possible. The SSML parser crate should remove non-synthesisable text by default and
 /// this helps simplify some of our usage code!
 pub fn normalise_ssml(x: &str) -> anyhow::Result<NormalisedText> {
+    normalise_ssml_with_options(x, &NormaliserOptions::default())
+}
+
+/// Normalise SSML with the given options, see `normalise_ssml`.
+pub fn normalise_ssml_with_options(
+    x: &str,
+    options: &NormaliserOptions,
+) -> anyhow::Result<NormalisedText> {
+    let ssml = parse_ssml(x)?;
+    let chunks = SsmlChunks::new(ssml.event_iter(), options).collect::<anyhow::Result<Vec<_>>>()?;
+    Ok(chunks.into())
+}
+
+/// Parses an SSML document with the parser settings the normaliser expects, to be passed to
+/// `SsmlChunks`.
+pub fn parse_ssml(x: &str) -> anyhow::Result<Ssml> {
     let parser = SsmlParserBuilder::default().expand_sub(true).build()?;
+    parser.parse(x)
+}
+
+/// Normalises SSML one parser event at a time, yielding the chunks as they're made instead of
+/// collecting the whole document into a `NormalisedText`. Paired with
+/// `XdTts::generate_streaming_chunks` this means a large document never has to be normalised all
+/// at once.
+///
+/// The chunks come out in document order and are the same as `normalise_ssml_with_options` would
+/// give. Most chunks are yielded as soon as the event that made them is seen, the exceptions are
+/// the contents of a `<w>` or `<token>` which are normalised together when the tag closes, and
+/// everything inside a `<phrase>` which is held back until the phrase closes so the punctuation
+/// inside it can be removed. After an error the iterator ends.
+///
+/// ```ignore
+/// let ssml = parse_ssml(text)?;
+/// for chunk in SsmlChunks::new(ssml.event_iter(), &options) {
+///     println!("{:?}", chunk?);
+/// }
+/// ```
+pub struct SsmlChunks<'a, I> {
+    events: I,
+    options: &'a NormaliserOptions,
+    /// Chunks that have been made but not yielded yet
+    chunks: VecDeque<NormaliserChunk>,
+    stack: Vec<ParsedElement>,
+    /// Some of the tags we support mean we ignore the text inside the tag and instead use XML
+    /// attributes to work out pronunciation. Hence the need to track push_text
+    push_text: bool,
+    /// Whether the open `<phoneme>` tag has already given us a pronunciation
+    pushed_phonemes: bool,
+    /// Text inside a `<w>` or `<token>` tag, this has to be normalised all at once when the tag
+    /// closes.
+    token: String,
+    /// Current pitch in cents and the pitch to go back to when each open prosody tag closes
+    pitch: i32,
+    pitch_stack: Vec<i32>,
+    /// Where the outermost open `<phrase>` tag started in `chunks` and how many are open
+    phrase_start: usize,
+    phrase_depth: usize,
+    failed: bool,
+}
+
+impl<'a, I> SsmlChunks<'a, I>
+where
+    I: Iterator<Item = ParserEvent>,
+{
+    /// Creates the iterator from the events of a parsed document, see `parse_ssml`.
+    pub fn new(events: impl IntoIterator<IntoIter = I>, options: &'a NormaliserOptions) -> Self {
+        Self {
+            events: events.into_iter(),
+            options,
+            chunks: VecDeque::new(),
+            stack: vec![],
+            push_text: true,
+            pushed_phonemes: false,
+            token: String::new(),
+            pitch: 0,
+            pitch_stack: vec![],
+            phrase_start: 0,
+            phrase_depth: 0,
+            failed: false,
+        }
+    }
 
-    let mut res = NormalisedText::default();
-    let mut stack = vec![];
-    // Some of the tags we support mean we ignore the text inside the tag and instead use XML
-    // attributes to work out pronunciation. Hence the need to track push_text
-    let mut push_text = true;
-    for event in parser.parse(x)?.event_iter() {
+    fn append(&mut self, mut text: NormalisedText) {
+        self.chunks.extend(text.drain_all());
+    }
+
+    /// Handles one event from the parser, adding any chunks it makes to `self.chunks`.
+    fn handle_event(&mut self, event: ParserEvent) -> anyhow::Result<()> {
+        let options = self.options;
         match event {
             ParserEvent::Text(t) => {
-                if push_text {
-                    res.append(normalise_text(&t));
-                } else if let Some(tag) = stack.last() {
+                if self.push_text {
+                    self.append(normalise_text_with_options(&t, options));
+                } else if let Some(tag) = self.stack.last() {
                     // We should look at the stack to see if there's something we're meant to be
                     // doing
                     match tag {
                         ParsedElement::SayAs(sa) => {
-                            res.chunks.push(handle_say_as(sa, &t)?);
+                            let chunks = handle_say_as(sa, &t, options)?;
+                            self.chunks.extend(chunks);
+                        }
+                        ParsedElement::Token(_) | ParsedElement::Word(_) => {
+                            self.token.push_str(&t);
                         }
                         ParsedElement::Phoneme(ph) => {
-                            if matches!(res.chunks.last(), Some(NormaliserChunk::Pronunciation(_)))
-                            {
+                            if self.pushed_phonemes {
                                 debug!(
                                     "Skipping: {} because we already pushed phonemes {:?}",
                                     t, ph
@@ -332,7 +1245,12 @@ pub fn normalise_ssml(x: &str) -> anyhow::Result<NormalisedText> {
                                 warn!("Couldn't handle phoneme tag, trying to just normalise!");
                             }
                         }
-                        _ => unreachable!(),
+                        e => {
+                            // Something nested inside one of the above tags, we don't support this
+                            // so just treat it as normal text.
+                            warn!("Unexpected text inside {:?}, normalising as text", e);
+                            self.append(normalise_text_with_options(&t, options));
+                        }
                     }
                 } else {
                     warn!("I don't know what to do with myself");
@@ -341,29 +1259,70 @@ pub fn normalise_ssml(x: &str) -> anyhow::Result<NormalisedText> {
             ParserEvent::Open(open) => {
                 match &open {
                     ParsedElement::SayAs(_) => {
-                        push_text = false;
+                        self.push_text = false;
+                    }
+                    ParsedElement::Token(_) | ParsedElement::Word(_) => {
+                        self.push_text = false;
+                        self.token.clear();
                     }
                     ParsedElement::Phoneme(ph) => {
-                        push_text = false;
-                        if matches!(ph.alphabet, None | Some(PhonemeAlphabet::Ipa)) {
+                        self.push_text = false;
+                        self.pushed_phonemes =
+                            matches!(ph.alphabet, None | Some(PhonemeAlphabet::Ipa));
+                        if self.pushed_phonemes {
                             let pronunciation = ipa_string_to_units(&ph.ph);
-                            res.chunks
-                                .push(NormaliserChunk::Pronunciation(pronunciation));
+                            self.chunks
+                                .push_back(NormaliserChunk::Pronunciation(pronunciation));
+                        }
+                    }
+                    ParsedElement::Prosody(prosody) => {
+                        self.pitch_stack.push(self.pitch);
+                        if let Some(change) = prosody.pitch.as_ref().and_then(pitch_change) {
+                            // Nested prosody tags are relative to the outer one
+                            self.pitch += change;
+                            self.chunks.push_back(NormaliserChunk::Pitch(self.pitch));
+                        }
+                    }
+                    ParsedElement::Custom((name, _)) if name == "phrase" => {
+                        if self.phrase_depth == 0 {
+                            self.phrase_start = self.chunks.len();
+                            self.chunks.push_back(NormaliserChunk::NoBreaks(true));
                         }
+                        self.phrase_depth += 1;
                     }
                     ParsedElement::Speak(_) => {}
                     e => {
                         error!("Unhandled open tag: {:?}", e);
                     }
                 }
-                stack.push(open);
+                self.stack.push(open);
             }
             ParserEvent::Close(_close) => {
-                if let Some(_end) = stack.pop() {
+                if let Some(end) = self.stack.pop() {
+                    match end {
+                        ParsedElement::Token(_) | ParsedElement::Word(_) => {
+                            self.append(normalise_token(&self.token, options));
+                        }
+                        ParsedElement::Prosody(_) => {
+                            let previous = self.pitch_stack.pop().unwrap_or_default();
+                            if previous != self.pitch {
+                                self.pitch = previous;
+                                self.chunks.push_back(NormaliserChunk::Pitch(self.pitch));
+                            }
+                        }
+                        ParsedElement::Custom((name, _)) if name == "phrase" => {
+                            self.phrase_depth -= 1;
+                            if self.phrase_depth == 0 {
+                                remove_internal_punctuation(&mut self.chunks, self.phrase_start);
+                                self.chunks.push_back(NormaliserChunk::NoBreaks(false));
+                            }
+                        }
+                        _ => {}
+                    }
                     // Assume we only go one deep
-                    push_text = true;
+                    self.push_text = true;
                 } else {
-                    unreachable!();
+                    anyhow::bail!("Closing tag without a matching open tag");
                 }
             }
             ParserEvent::Empty(tag) => match &tag {
@@ -371,7 +1330,7 @@ pub fn normalise_ssml(x: &str) -> anyhow::Result<NormalisedText> {
                     let duration = match (b.time.map(|x| x.duration()), b.strength) {
                         (Some(duration), _) => duration,
                         (_, Some(strength)) => match strength {
-                            Strength::No => continue,
+                            Strength::No => return Ok(()),
                             Strength::ExtraWeak => Duration::from_secs_f32(0.2),
                             Strength::Weak => Duration::from_secs_f32(0.5),
                             Strength::Medium => Duration::from_secs(1),
@@ -380,105 +1339,1049 @@ pub fn normalise_ssml(x: &str) -> anyhow::Result<NormalisedText> {
                         },
                         _ => Duration::from_secs(1),
                     };
-                    res.chunks.push(NormaliserChunk::Break(duration));
+                    self.chunks.push_back(NormaliserChunk::Break(duration));
                 }
                 _ => {
                     error!("Unhandled tag: {:?}", tag);
                 }
             },
         }
+        Ok(())
     }
-    Ok(res)
 }
 
-/// Numbers are quite complicated. Here we have basic handling for ordinals, cardinals and numbers
-/// with letters or symbols after them. Currency, years, phone numbers all add extra complexity and
-/// have been ignored. So if you input a phone number like 0800001066 it will read it as a number -
-/// not an intuitive way to receive a phone number!
-fn process_number(x: &str) -> anyhow::Result<String> {
-    static IS_ORDINAL: OnceCell<Regex> = OnceCell::new();
-    static JUST_NUMBER: OnceCell<Regex> = OnceCell::new();
-    static NUM_SPLITTER: OnceCell<Regex> = OnceCell::new();
-
-    let is_ordinal = IS_ORDINAL.get_or_init(|| Regex::new("^[[:digit:]](st|nd|th|rd)$").unwrap());
-    let just_number = JUST_NUMBER.get_or_init(|| Regex::new(r#"^[\d\.,]+$"#).unwrap());
-    let num_splitter = NUM_SPLITTER
-        .get_or_init(|| Regex::new(r#"(?<head>\D*)(?<digit>[[:digit:]]+)(?<tail>\D*)"#).unwrap());
+impl<I> Iterator for SsmlChunks<'_, I>
+where
+    I: Iterator<Item = ParserEvent>,
+{
+    type Item = anyhow::Result<NormaliserChunk>;
 
-    if is_ordinal.is_match(x) {
-        let text = Num2Words::parse(x)
-            .and_then(|x| x.ordinal().to_words().ok())
-            .ok_or_else(|| anyhow::anyhow!("Invalid ordinal: '{}'", x))?
-            .replace('-', " ")
-            .to_ascii_uppercase();
-        Ok(text)
-    } else if just_number.is_match(x) {
-        let text = Num2Words::parse(x)
-            .and_then(|x| x.to_words().ok())
-            .ok_or_else(|| anyhow::anyhow!("Invalid number '{}'", x))?
-            .replace('-', " ")
-            .to_ascii_uppercase();
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        loop {
+            // Chunks in an open phrase might still have their punctuation removed
+            if self.phrase_depth == 0 {
+                if let Some(chunk) = self.chunks.pop_front() {
+                    return Some(Ok(chunk));
+                }
+            }
+            match self.events.next() {
+                Some(event) => {
+                    if let Err(e) = self.handle_event(event) {
+                        self.failed = true;
+                        return Some(Err(e));
+                    }
+                }
+                // An unclosed phrase is still returned
+                None => return self.chunks.pop_front().map(Ok),
+            }
+        }
+    }
+}
 
-        Ok(text)
-    } else if let Some(cap) = num_splitter.captures(x) {
-        // We can to_string the normalise text stuff here because we know that this is isolated to
-        // a single word and punctuation has already been stripped.
-        let head = normalise_text(&cap["head"]).to_string_unchecked();
+/// Removes the punctuation inside a `<phrase>` so it's read without pauses. Punctuation at the
+/// end of the phrase is kept as that's a boundary not a pause inside the phrase.
+fn remove_internal_punctuation(chunks: &mut VecDeque<NormaliserChunk>, start: usize) {
+    let last_word = chunks.iter().rposition(|x| {
+        matches!(
+            x,
+            NormaliserChunk::Text(_) | NormaliserChunk::Pronunciation(_)
+        )
+    });
+    let last_word = match last_word {
+        Some(x) if x >= start => x,
+        _ => return,
+    };
+    let mut i = 0;
+    chunks.retain(|x| {
+        let keep = i < start || i > last_word || !matches!(x, NormaliserChunk::Punct(_));
+        i += 1;
+        keep
+    });
+}
 
-        let digit = Num2Words::parse(&cap["digit"])
-            .and_then(|x| x.to_words().ok())
-            .ok_or_else(|| anyhow::anyhow!("Invalid number: '{}'", &cap["digit"]))?
-            .replace('-', " ")
-            .to_ascii_uppercase();
+/// Converts an SSML prosody pitch into a change in cents. Absolute frequencies and changes in Hz
+/// can't be supported because we don't know the pitch of the voice, so they're ignored.
+fn pitch_change(pitch: &PitchRange) -> Option<i32> {
+    let cents = match pitch {
+        PitchRange::Strength(strength) => match strength {
+            PitchStrength::XLow => -600.0,
+            PitchStrength::Low => -300.0,
+            PitchStrength::Medium | PitchStrength::Default => 0.0,
+            PitchStrength::High => 300.0,
+            PitchStrength::XHigh => 600.0,
+        },
+        PitchRange::Percentage((percent, sign)) => {
+            let ratio = match sign {
+                Sign::Plus => 1.0 + percent / 100.0,
+                Sign::Minus => 1.0 - percent / 100.0,
+            };
+            if ratio <= 0.0 {
+                warn!("Can't lower pitch by {}%", percent);
+                return None;
+            }
+            1200.0 * ratio.log2()
+        }
+        PitchRange::RelativeChange((semitones, sign, ssml_parser::elements::Unit::St)) => {
+            match sign {
+                Sign::Plus => semitones * 100.0,
+                Sign::Minus => -semitones * 100.0,
+            }
+        }
+        p => {
+            warn!("Unsupported prosody pitch: {:?}", p);
+            return None;
+        }
+    };
+    Some(cents.round() as i32)
+}
 
-        let tail = normalise_text(&cap["tail"]).to_string_unchecked();
+/// Normalises the contents of an SSML `<w>` or `<token>` as a single word. The characters that
+/// would normally split a word up (hyphens, brackets and colons) are removed so "Wi-Fi" is looked
+/// up as "WIFI" instead of "WI FI". Spaces are kept so "New York" is still two words, and
+/// punctuation at the end is still treated as punctuation.
+fn normalise_token(x: &str, options: &NormaliserOptions) -> NormalisedText {
+    let word = x
+        .split_whitespace()
+        .map(|word| word.replace(['[', ']', '(', ')', '-', ':'], ""))
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    normalise_text_with_options(&word, options)
+}
 
-        let mut res = String::new();
+/// Converts a number into its cardinal form in words, in the same uppercase form as the rest of the
+/// normaliser output.
+fn cardinal_words(num: i64) -> anyhow::Result<String> {
+    let text = Num2Words::new(num)
+        .cardinal()
+        .to_words()
+        .map_err(|e| anyhow::anyhow!(e))?
+        .replace('-', " ")
+        .to_ascii_uppercase();
+    Ok(text)
+}
 
-        let head_t = head.trim();
-        let tail_t = tail.trim();
+/// Reads a fraction written with a slash like "3/4". Halves, thirds and quarters are read the way
+/// people say them, "THREE QUARTERS", anything else is read as "THREE OVER EIGHT".
+fn fraction_words(numerator: i64, denominator: i64) -> anyhow::Result<String> {
+    let name = match denominator {
+        2 => Some(("HALF", "HALVES")),
+        3 => Some(("THIRD", "THIRDS")),
+        4 => Some(("QUARTER", "QUARTERS")),
+        _ => None,
+    };
+    let numerator_words = cardinal_words(numerator)?;
+    match name {
+        Some((single, _)) if numerator == 1 => Ok(format!("{} {}", numerator_words, single)),
+        Some((_, plural)) => Ok(format!("{} {}", numerator_words, plural)),
+        None => Ok(format!(
+            "{} OVER {}",
+            numerator_words,
+            cardinal_words(denominator)?
+        )),
+    }
+}
 
-        if !head_t.is_empty() {
-            res.push_str(head_t);
-            res.push(' ');
+/// Reads a year the way people say them, as two pairs of digits. So 1970 is "NINETEEN SEVENTY",
+/// 1905 is "NINETEEN OH FIVE" and 1900 is "NINETEEN HUNDRED". The first decade of a millennium is
+/// read as a number instead, 2000 is "TWO THOUSAND" and 2005 is "TWO THOUSAND AND FIVE". Anything
+/// outside of 1100-2099 isn't commonly read like this so it's read as a normal number.
+fn year_words(year: i64) -> anyhow::Result<String> {
+    if !(1100..=2099).contains(&year) {
+        return cardinal_words(year);
+    }
+    let (high, low) = (year / 100, year % 100);
+    let text = if high % 10 == 0 && low < 10 {
+        let thousands = cardinal_words(high * 100)?;
+        if low == 0 {
+            thousands
+        } else {
+            format!("{} AND {}", thousands, cardinal_words(low)?)
         }
+    } else if low == 0 {
+        format!("{} HUNDRED", cardinal_words(high)?)
+    } else if low < 10 {
+        format!("{} OH {}", cardinal_words(high)?, cardinal_words(low)?)
+    } else {
+        format!("{} {}", cardinal_words(high)?, cardinal_words(low)?)
+    };
+    Ok(text)
+}
 
-        res.push_str(&digit);
+/// Reads a numeric date from `<say-as interpret-as="date">`. The fields can be separated by `-`,
+/// `/` or `.` and `format` gives their order with the SSML letters d, m and y. So "2024-01-05"
+/// with the format "ymd" is "THE FIFTH OF JANUARY TWENTY TWENTY FOUR". Fields can be left out,
+/// "my" is just a month and year.
+///
+/// Without a format a single field is a year, a 4 digit first field is year first and anything
+/// else is day first (or month first if there's no day). Two digit years are taken to be between
+/// 1950 and 2049. Months can be written as names as well as numbers.
+fn process_date(text: &str, format: Option<&str>) -> anyhow::Result<String> {
+    let fields = text
+        .trim()
+        .split(['-', '/', '.'])
+        .map(str::trim)
+        .collect::<Vec<_>>();
+    let format = match format {
+        Some(format) => format.to_ascii_lowercase(),
+        None => match fields.len() {
+            1 => "y",
+            2 if fields[0].len() == 4 => "ym",
+            2 => "my",
+            _ if fields[0].len() == 4 => "ymd",
+            _ => "dmy",
+        }
+        .to_string(),
+    };
+    anyhow::ensure!(
+        format.len() == fields.len() && format.chars().all(|c| "dmy".contains(c)),
+        "Date '{}' doesn't match the format '{}'",
+        text,
+        format
+    );
 
-        if !tail_t.is_empty() {
-            res.push(' ');
-            res.push_str(tail_t);
+    let (mut day, mut month, mut year) = (None, None, None);
+    for (field, c) in fields.iter().zip(format.chars()) {
+        match c {
+            'd' => {
+                day = Some(
+                    day_of_month(field)
+                        .ok_or_else(|| anyhow::anyhow!("Invalid day in date: '{}'", field))?,
+                );
+            }
+            'm' => {
+                let name = match field.parse::<usize>() {
+                    Ok(n @ 1..=12) => Some(MONTHS[n - 1]),
+                    Ok(_) => None,
+                    Err(_) => month_name(field),
+                };
+                month = Some(
+                    name.ok_or_else(|| anyhow::anyhow!("Invalid month in date: '{}'", field))?,
+                );
+            }
+            _ => {
+                let value = field.parse::<i64>()?;
+                year = Some(match (field.len(), value) {
+                    (1 | 2, 0..=49) => 2000 + value,
+                    (1 | 2, _) => 1900 + value,
+                    _ => value,
+                });
+            }
         }
+    }
 
-        Ok(res)
-    } else {
-        unreachable!()
+    let mut words = vec![];
+    if let Some(day) = day {
+        words.push("THE".to_string());
+        words.push(
+            Num2Words::new(day)
+                .ordinal()
+                .to_words()
+                .map_err(|e| anyhow::anyhow!(e))?
+                .replace('-', " ")
+                .to_ascii_uppercase(),
+        );
+        if month.is_some() {
+            words.push("OF".to_string());
+        }
+    }
+    if let Some(month) = month {
+        words.push(month.to_ascii_uppercase());
+    }
+    if let Some(year) = year {
+        words.push(year_words(year)?);
     }
+    Ok(words.join(" "))
+}
+
+/// Reads a phone number one digit at a time with a short pause between each group of digits.
+/// Groups are separated by whitespace or the usual separators (`-./()`), a leading `+` is read as
+/// "PLUS" and letters like in "1-800-FLOWERS" are spelled out. Zero is read as "OH".
+fn process_telephone(x: &str, options: &NormaliserOptions) -> anyhow::Result<Vec<NormaliserChunk>> {
+    let mut chunks = vec![];
+    let groups = x
+        .split(|c: char| c.is_whitespace() || "-./()".contains(c))
+        .filter(|x| !x.is_empty());
+    for group in groups {
+        let mut words = vec![];
+        let mut chars = group.chars().peekable();
+        while let Some(c) = chars.next() {
+            let word = match c {
+                '+' => "PLUS".to_string(),
+                '0' => "OH".to_string(),
+                c if c.is_ascii_digit() => cardinal_words(c as i64 - '0' as i64)?,
+                c if c.is_ascii_alphabetic() => c.to_ascii_uppercase().to_string(),
+                c => anyhow::bail!("Unexpected character in phone number: {:?}", c),
+            };
+            let mut run = 1;
+            while options.repeated_digits && c.is_ascii_digit() && chars.peek() == Some(&c) {
+                chars.next();
+                run += 1;
+            }
+            // Long runs are split up how people say them, 4 is "DOUBLE DOUBLE" not
+            // "TRIPLE ONE"
+            while run > 0 {
+                let n = match run {
+                    1 => 1,
+                    2 | 4 => 2,
+                    _ => 3,
+                };
+                match n {
+                    2 => words.push("DOUBLE".to_string()),
+                    3 => words.push("TRIPLE".to_string()),
+                    _ => {}
+                }
+                words.push(word.clone());
+                run -= n;
+            }
+        }
+        if !chunks.is_empty() {
+            chunks.push(NormaliserChunk::Break(TELEPHONE_GROUP_PAUSE));
+        }
+        chunks.push(NormaliserChunk::Text(words.join(" ")));
+    }
+    Ok(chunks)
+}
+
+/// Gets the names of the major and minor units for a currency symbol, as singular and plural
+/// pairs. Currencies without a commonly spoken minor unit return `None` for it.
+#[allow(clippy::type_complexity)]
+fn currency_names(
+    symbol: &str,
+) -> Option<(
+    (&'static str, &'static str),
+    Option<(&'static str, &'static str)>,
+)> {
+    match symbol {
+        "$" => Some((("DOLLAR", "DOLLARS"), Some(("CENT", "CENTS")))),
+        "£" => Some((("POUND", "POUNDS"), Some(("PENNY", "PENCE")))),
+        "€" => Some((("EURO", "EUROS"), Some(("CENT", "CENTS")))),
+        "¥" => Some((("YEN", "YEN"), None)),
+        _ => None,
+    }
+}
+
+/// Reads an amount of money. The amount can contain thousands separators and a decimal part, the
+/// decimal part is read as the minor unit of the currency (i.e. cents). So "$1,234.50" becomes
+/// "ONE THOUSAND TWO HUNDRED THIRTY FOUR DOLLARS AND FIFTY CENTS". If either the major or minor
+/// amount is zero it's omitted unless the whole amount is zero.
+fn process_currency(symbol: &str, amount: &str) -> anyhow::Result<String> {
+    let (major_name, minor_name) = currency_names(symbol)
+        .ok_or_else(|| anyhow::anyhow!("Unsupported currency: '{}'", symbol))?;
+
+    let amount = amount.replace(',', "");
+    let (major, minor) = match amount.split_once('.') {
+        Some((major, minor)) => (major, Some(minor)),
+        None => (amount.as_str(), None),
+    };
+    let major = if major.is_empty() {
+        0
+    } else {
+        major.parse::<i64>()?
+    };
+    // We only read two decimal places, so "$3.5" is fifty cents and anything after the cents is
+    // dropped.
+    let minor = match minor.filter(|x| !x.is_empty()) {
+        Some(minor) => {
+            let mut digits = minor.chars().take(2).collect::<String>();
+            if digits.len() == 1 {
+                digits.push('0');
+            }
+            digits.parse::<i64>()?
+        }
+        None => 0,
+    };
+
+    let plural = |n: i64, (singular, plural): (&'static str, &'static str)| {
+        if n == 1 {
+            singular
+        } else {
+            plural
+        }
+    };
+
+    let mut res = String::new();
+    if major > 0 || minor == 0 || minor_name.is_none() {
+        res.push_str(&cardinal_words(major)?);
+        res.push(' ');
+        res.push_str(plural(major, major_name));
+    }
+    if let (Some(minor_name), true) = (minor_name, minor > 0) {
+        if !res.is_empty() {
+            res.push_str(" AND ");
+        }
+        res.push_str(&cardinal_words(minor)?);
+        res.push(' ');
+        res.push_str(plural(minor, minor_name));
+    }
+    Ok(res)
+}
+
+/// Numbers are quite complicated. Here we have basic handling for cardinals, fractions (see
+/// `fraction_words`) and numbers with letters or symbols glued to them like ordinals and times
+/// (see `process_glued`). Currency and units are mostly handled by `expand_currency` and
+/// `expand_units` before we get here. Years and phone numbers add extra complexity and have been
+/// ignored (SSML `<say-as interpret-as="year">` and `"telephone"` can be used to read them
+/// properly), the only concession is long runs of digits (see
+/// `NormaliserOptions::read_digits_over`) are read one digit at a time. So a phone number like
+/// 0800001066 is read digit by digit, but a short one like 1066 is still read as a number - not
+/// an intuitive way to receive a phone number!
+fn process_number(x: &str, options: &NormaliserOptions) -> anyhow::Result<String> {
+    static JUST_NUMBER: OnceCell<Regex> = OnceCell::new();
+    static DECIMAL: OnceCell<Regex> = OnceCell::new();
+    static FRACTION: OnceCell<Regex> = OnceCell::new();
+
+    let just_number = JUST_NUMBER.get_or_init(|| Regex::new(r#"^[\d\.,]+$"#).unwrap());
+    let decimal = DECIMAL.get_or_init(|| Regex::new(r#"^(?<int>[\d,]*)\.(?<frac>\d+)$"#).unwrap());
+    let fraction = FRACTION
+        .get_or_init(|| Regex::new(r#"^((?<whole>\d+) )?(?<num>\d+)/(?<den>\d+)$"#).unwrap());
+
+    let is_long_digits = |x: &str| {
+        options
+            .read_digits_over
+            .is_some_and(|max| x.len() > max && x.bytes().all(|b| b.is_ascii_digit()))
+    };
+
+    if is_long_digits(x) {
+        Ok(read_digits(x))
+    } else if let Some(cap) = decimal.captures(x) {
+        // The digits after the point are read one at a time, so 3.14 is "THREE POINT ONE FOUR"
+        // not "THREE POINT FOURTEEN"
+        let int = cap["int"].replace(',', "");
+        let int = if int.is_empty() {
+            0
+        } else {
+            int.parse::<i64>()?
+        };
+        Ok(format!(
+            "{} POINT {}",
+            cardinal_words(int)?,
+            read_digits(&cap["frac"])
+        ))
+    } else if just_number.is_match(x) {
+        // num2words doesn't understand thousands separators
+        let text = Num2Words::parse(&x.replace(',', ""))
+            .and_then(|x| x.to_words().ok())
+            .ok_or_else(|| anyhow::anyhow!("Invalid number '{}'", x))?
+            .replace('-', " ")
+            .to_ascii_uppercase();
+
+        Ok(text)
+    } else if let Some(cap) = fraction.captures(x) {
+        let fraction = fraction_words(cap["num"].parse()?, cap["den"].parse()?)?;
+        match cap.name("whole") {
+            Some(whole) => {
+                // "one and a half" sounds more natural than "one and one half", but only for the
+                // named fractions. "one and one over eight" can't be shortened
+                let fraction = match fraction
+                    .strip_prefix("ONE ")
+                    .filter(|x| !x.starts_with("OVER "))
+                {
+                    Some(x) => format!("A {}", x),
+                    None => fraction,
+                };
+                Ok(format!(
+                    "{} AND {}",
+                    cardinal_words(whole.as_str().parse()?)?,
+                    fraction
+                ))
+            }
+            None => Ok(fraction),
+        }
+    } else if let Some(id) = read_alphanumeric_id(x, options) {
+        id
+    } else if let Some(glued) = process_glued(x, options) {
+        glued
+    } else {
+        anyhow::bail!("No number found in '{}'", x)
+    }
+}
+
+/// Units read after a number with `UnitExpansion::measurements`, as the abbreviation and the
+/// singular and plural names. Single letters like the "m" in "5m" are left out as they're too
+/// ambiguous.
+const MEASUREMENT_UNITS: &[(&str, &str, &str)] = &[
+    ("mm", "MILLIMETRE", "MILLIMETRES"),
+    ("cm", "CENTIMETRE", "CENTIMETRES"),
+    ("km", "KILOMETRE", "KILOMETRES"),
+    ("mg", "MILLIGRAM", "MILLIGRAMS"),
+    ("kg", "KILOGRAM", "KILOGRAMS"),
+    ("ml", "MILLILITRE", "MILLILITRES"),
+    ("kb", "KILOBYTE", "KILOBYTES"),
+    ("mb", "MEGABYTE", "MEGABYTES"),
+    ("gb", "GIGABYTE", "GIGABYTES"),
+    ("tb", "TERABYTE", "TERABYTES"),
+    ("ms", "MILLISECOND", "MILLISECONDS"),
+    ("hz", "HERTZ", "HERTZ"),
+    ("khz", "KILOHERTZ", "KILOHERTZ"),
+    ("mhz", "MEGAHERTZ", "MEGAHERTZ"),
+    ("ghz", "GIGAHERTZ", "GIGAHERTZ"),
+    ("mph", "MILE PER HOUR", "MILES PER HOUR"),
+];
+
+/// Reads a number with something glued to it like "3rd", "5pm" or "£20". The readings are tried
+/// in order: ordinal, time and currency. If none of them apply the number is read with the text
+/// either side of it normalised as normal, so "A4" is "A FOUR". Units like "5kg" are read before
+/// this by `expand_units`.
+/// Returns `None` if there's no number in the token.
+fn process_glued(x: &str, options: &NormaliserOptions) -> Option<anyhow::Result<String>> {
+    static GLUED: OnceCell<Regex> = OnceCell::new();
+    static NUM_SPLITTER: OnceCell<Regex> = OnceCell::new();
+
+    let glued = GLUED.get_or_init(|| {
+        Regex::new(r#"^(?<head>\D*)(?<number>\d[\d,]*(\.\d+)?)(?<tail>\D*)$"#).unwrap()
+    });
+    let num_splitter = NUM_SPLITTER
+        .get_or_init(|| Regex::new(r#"(?<head>\D*)(?<digit>[[:digit:]]+)(?<tail>\D*)"#).unwrap());
+
+    if let Some(cap) = glued.captures(x) {
+        let head = &cap["head"];
+        let number = &cap["number"];
+        let tail = &cap["tail"];
+        let is_integer = number.bytes().all(|b| b.is_ascii_digit());
+
+        if head.is_empty() && is_integer {
+            if ["st", "nd", "rd", "th"]
+                .iter()
+                .any(|suffix| tail.eq_ignore_ascii_case(suffix))
+            {
+                let text = Num2Words::parse(number)
+                    .and_then(|x| x.ordinal().to_words().ok())
+                    .map(|x| x.replace('-', " ").to_ascii_uppercase())
+                    .ok_or_else(|| anyhow::anyhow!("Invalid ordinal: '{}'", x));
+                return Some(text);
+            }
+            if tail.eq_ignore_ascii_case("am") || tail.eq_ignore_ascii_case("pm") {
+                if let Some(time) = time_words(number, "0", Some(tail)) {
+                    return Some(Ok(time));
+                }
+            }
+        }
+
+        // Both "£20" and "20€" are seen in the wild
+        let currency = match (head, tail) {
+            (symbol, "") | ("", symbol) if currency_names(symbol).is_some() => Some(symbol),
+            _ => None,
+        };
+        if let Some(symbol) = currency {
+            return Some(process_currency(symbol, number));
+        }
+    }
+
+    let cap = num_splitter.captures(x)?;
+    Some(read_split_number(
+        &cap["head"],
+        &cap["digit"],
+        &cap["tail"],
+        options,
+    ))
+}
+
+/// Reads a number with the text before and after it normalised on its own, the fallback for
+/// `process_glued`.
+fn read_split_number(
+    head: &str,
+    digit: &str,
+    tail: &str,
+    options: &NormaliserOptions,
+) -> anyhow::Result<String> {
+    // We can to_string the normalise text stuff here because we know that this is isolated to
+    // a single word and punctuation has already been stripped.
+    let head = normalise_text_with_options(head, options).to_string()?;
+
+    let digit = process_number(digit, options)?;
+
+    let tail = normalise_text_with_options(tail, options).to_string()?;
+
+    let mut res = String::new();
+
+    let head_t = head.trim();
+    let tail_t = tail.trim();
+
+    if !head_t.is_empty() {
+        res.push_str(head_t);
+        res.push(' ');
+    }
+
+    res.push_str(&digit);
+
+    if !tail_t.is_empty() {
+        res.push(' ');
+        res.push_str(tail_t);
+    }
+
+    Ok(res)
+}
+
+/// Reads a token made of several groups of letters and digits i.e. "R2D2" as "R TWO D TWO". Returns
+/// `None` if the token isn't an alphanumeric ID or they're turned off, see `AlphanumericIds`.
+fn read_alphanumeric_id(x: &str, options: &NormaliserOptions) -> Option<anyhow::Result<String>> {
+    static ID_GROUPS: OnceCell<Regex> = OnceCell::new();
+    // Longest group of letters we'll spell out, anything longer is probably a word
+    const MAX_SPELLED_LETTERS: usize = 3;
+
+    if options.alphanumeric_ids == AlphanumericIds::Off
+        || !x.bytes().all(|b| b.is_ascii_alphanumeric())
+    {
+        return None;
+    }
+    let id_groups = ID_GROUPS.get_or_init(|| Regex::new("[[:alpha:]]+|[[:digit:]]+").unwrap());
+    let groups = id_groups
+        .find_iter(x)
+        .map(|x| x.as_str())
+        .collect::<Vec<_>>();
+    let is_digits = |x: &str| x.starts_with(|c: char| c.is_ascii_digit());
+    let digit_groups = groups.iter().filter(|x| is_digits(x)).count();
+    let letter_groups = groups.len() - digit_groups;
+    if digit_groups == 0 || (digit_groups < 2 && letter_groups < 2) {
+        return None;
+    }
+
+    let read = |group: &str| -> anyhow::Result<String> {
+        if is_digits(group) {
+            if options.alphanumeric_ids == AlphanumericIds::SpellDigits {
+                Ok(read_digits(group))
+            } else {
+                process_number(group, options)
+            }
+        } else if group.len() <= MAX_SPELLED_LETTERS {
+            let letters = group
+                .chars()
+                .map(|c| c.to_ascii_uppercase().to_string())
+                .collect::<Vec<_>>();
+            Ok(letters.join(" "))
+        } else {
+            Ok(group.to_ascii_uppercase())
+        }
+    };
+    Some(
+        groups
+            .into_iter()
+            .map(read)
+            .collect::<anyhow::Result<Vec<_>>>()
+            .map(|x| x.join(" ")),
+    )
+}
+
+/// Full month names, used to recognise dates.
+const MONTHS: [&str; 12] = [
+    "january",
+    "february",
+    "march",
+    "april",
+    "may",
+    "june",
+    "july",
+    "august",
+    "september",
+    "october",
+    "november",
+    "december",
+];
+
+/// Splits a word into the word and any punctuation on the end of it.
+fn split_trailing_punct(word: &str) -> (&str, &str) {
+    let end = word.trim_end_matches(|c: char| c.is_ascii_punctuation());
+    (end, &word[end.len()..])
+}
+
+/// Gets the full month name from a month or abbreviated month i.e. "Sept" or "Dec".
+fn month_name(word: &str) -> Option<&'static str> {
+    let word = word.to_ascii_lowercase();
+    if word == "sept" {
+        return Some("september");
+    }
+    MONTHS
+        .iter()
+        .copied()
+        .find(|month| word == *month || (word.len() == 3 && month.starts_with(&word)))
+}
+
+/// Like `month_name` but for use in dates. "may" and "march" are also everyday words, so they're
+/// only taken as months when capitalised otherwise "step 2 may fail" would be a date.
+fn date_month(word: &str) -> Option<&'static str> {
+    let month = month_name(word)?;
+    let capitalised = word.starts_with(|c: char| c.is_ascii_uppercase());
+    (capitalised || !matches!(month, "may" | "march")).then_some(month)
+}
+
+/// Parses a day of the month like "3", "03" or "3rd".
+fn day_of_month(word: &str) -> Option<u32> {
+    let digits = word.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let suffix = &word[digits.len()..];
+    if !matches!(
+        suffix.to_ascii_lowercase().as_str(),
+        "" | "st" | "nd" | "rd" | "th"
+    ) {
+        return None;
+    }
+    match digits.parse() {
+        Ok(day @ 1..=31) if digits.len() <= 2 => Some(day),
+        _ => None,
+    }
+}
+
+/// Adds num2words output or other expansions to `output` as separate words, with `punct` on the
+/// end of the last one.
+fn push_words(output: &mut Vec<String>, words: &str, punct: &str) {
+    output.extend(
+        words
+            .replace('-', " ")
+            .split_ascii_whitespace()
+            .map(String::from),
+    );
+    if let Some(last) = output.last_mut() {
+        last.push_str(punct);
+    }
+}
+
+/// Looks for dates written in prose and rewrites them as words so they're read properly. This
+/// handles "June 3rd" ("June third"), "the 3rd of June" and "3 June" ("the third of June") and a
+/// four digit year can follow any of them. The month can be abbreviated, but has to be a month
+/// name - "3/6/2024" is too ambiguous to guess at. May and March have to be capitalised, see
+/// `date_month`.
+fn expand_dates(words: Vec<String>) -> Vec<String> {
+    let mut res = vec![];
+    let mut i = 0;
+    let word_at = |i: usize| words.get(i).map(|x| split_trailing_punct(x));
+    while i < words.len() {
+        let (word, punct) = split_trailing_punct(&words[i]);
+        // Where the date ended and the punctuation at the end of it
+        let mut date_end = None;
+        if let (Some(month), true) = (date_month(word), punct.is_empty()) {
+            // June 3rd
+            if let Some((day, day_punct)) =
+                word_at(i + 1).and_then(|(w, p)| Some((day_of_month(w)?, p)))
+            {
+                res.push(month.to_string());
+                if let Ok(day) = Num2Words::new(day).ordinal().to_words() {
+                    push_words(&mut res, &day, day_punct);
+                }
+                date_end = Some((i + 2, day_punct));
+            }
+        } else if let (Some(day), true) = (day_of_month(word), punct.is_empty()) {
+            // 3rd of June or 3 June
+            let (month_index, has_of) = match word_at(i + 1) {
+                Some((of, "")) if of.eq_ignore_ascii_case("of") => (i + 2, true),
+                _ => (i + 1, false),
+            };
+            if let Some((month, month_punct)) =
+                word_at(month_index).and_then(|(w, p)| Some((date_month(w)?, p)))
+            {
+                let after_the = res
+                    .last()
+                    .is_some_and(|x: &String| x.eq_ignore_ascii_case("the"));
+                if !after_the && !has_of {
+                    res.push("the".to_string());
+                }
+                if let Ok(day) = Num2Words::new(day).ordinal().to_words() {
+                    push_words(&mut res, &day, "");
+                }
+                res.push("of".to_string());
+                res.push(format!("{}{}", month, month_punct));
+                date_end = Some((month_index + 1, month_punct));
+            }
+        }
+        match date_end {
+            Some((end, end_punct)) => {
+                i = end;
+                // A year can follow after a comma i.e. "June 3rd, 2024"
+                if let (Some((year, year_punct)), "" | ",") = (word_at(i), end_punct) {
+                    if year.len() == 4 && year.bytes().all(|b| b.is_ascii_digit()) {
+                        if let Some(year) = year
+                            .parse::<u32>()
+                            .ok()
+                            .and_then(|x| Num2Words::new(x).year().to_words().ok())
+                        {
+                            push_words(&mut res, &year, year_punct);
+                            i += 1;
+                        }
+                    }
+                }
+            }
+            None => {
+                res.push(words[i].clone());
+                i += 1;
+            }
+        }
+    }
+    res
+}
+
+/// Expands the abbreviations in `abbreviations` (see `NormaliserOptions::abbreviations`). The full
+/// stop after an abbreviation is removed so "Dr. Smith" isn't read as two sentences, unless it's
+/// the last word where it's also the end of the sentence. Like `drop_abbreviation_stops` the full
+/// stop is also kept before a capitalised word, "etc. Then" is two sentences, but not after a
+/// title as a name always follows.
+fn expand_abbreviations(words: Vec<String>, abbreviations: &[Abbreviation]) -> Vec<String> {
+    let mut res = Vec::with_capacity(words.len());
+    for (i, word) in words.iter().enumerate() {
+        let (stem, punct) = split_trailing_punct(word);
+        match abbreviations
+            .iter()
+            .find(|x| x.short.eq_ignore_ascii_case(stem))
+        {
+            Some(abbreviation) => {
+                let continues = words.get(i + 1).is_some_and(|next| {
+                    abbreviation.title || !next.starts_with(char::is_uppercase)
+                });
+                let punct = match punct.strip_prefix('.') {
+                    Some(rest) if continues => rest,
+                    _ => punct,
+                };
+                push_words(&mut res, abbreviation.expansion, punct);
+            }
+            None => res.push(word.clone()),
+        }
+    }
+    res
+}
+
+/// Short forms that are left as they are but are usually followed by a number, like "fig. 3", so
+/// the full stop after them isn't the end of a sentence. See `drop_abbreviation_stops`.
+const SHORT_FORMS: [&str; 9] = ["approx", "cf", "ch", "eq", "fig", "figs", "no", "pp", "vol"];
+
+/// Removes the full stop after abbreviations that are read as they are, a dotted one like "p.m."
+/// or "e.g." or one of `SHORT_FORMS`, so the text isn't split or read as two sentences there. If
+/// the next word is capitalised the full stop is kept as it's likely the end of the sentence too.
+/// So "5 p.m. tomorrow" and "see fig. 3" are one sentence but "It ends at 5 p.m. Then we go" is
+/// two.
+fn drop_abbreviation_stops(mut words: Vec<String>) -> Vec<String> {
+    for i in 1..words.len() {
+        if words[i].starts_with(char::is_uppercase) {
+            continue;
+        }
+        let (stem, punct) = split_trailing_punct(&words[i - 1]);
+        let abbreviated = stem.contains('.')
+            || SHORT_FORMS
+                .iter()
+                .any(|short| short.eq_ignore_ascii_case(stem));
+        if punct == "." && abbreviated {
+            words[i - 1].pop();
+        }
+    }
+    words
+}
+
+/// Reads a Roman numeral straight after one of the names as a regnal number, "Henry VIII" is
+/// "Henry the eighth". A numeral after punctuation like "Henry, IV" is left alone. As "I" is
+/// usually the pronoun it's only read as "the first" when it ends a clause, i.e. "Elizabeth I."
+/// but not "James I went". Numerals above `MAX_REGNAL_NUMBER` are left alone as they're much more
+/// likely to be an acronym, "Robert MD" or "Mary CV".
+fn expand_regnal_names(words: Vec<String>, names: &[&str]) -> Vec<String> {
+    let is_name = |word: &str| {
+        word.starts_with(|c: char| c.is_ascii_uppercase())
+            && names.iter().any(|name| name.eq_ignore_ascii_case(word))
+    };
+    let n_words = words.len();
+    let mut res = Vec::with_capacity(n_words);
+    let mut after_name = false;
+    for (i, word) in words.into_iter().enumerate() {
+        let (stem, punct) = split_trailing_punct(&word);
+        let regnal = after_name
+            .then(|| roman_to_int(stem))
+            .flatten()
+            .filter(|num| *num <= MAX_REGNAL_NUMBER)
+            .filter(|_| stem != "I" || !punct.is_empty() || i + 1 == n_words)
+            .and_then(|num| Num2Words::new(num).ordinal().to_words().ok());
+        after_name = punct.is_empty() && is_name(stem);
+        match regnal {
+            Some(ordinal) => push_words(&mut res, &format!("the {}", ordinal), punct),
+            None => res.push(word),
+        }
+    }
+    res
+}
+
+/// Highest regnal number read by `expand_regnal_names`, the highest in use is John XXIII.
+const MAX_REGNAL_NUMBER: u32 = 30;
+
+/// Checks if the word is a plain number that could be the hour in a time like "5 o'clock".
+fn is_hour(word: &str) -> bool {
+    matches!(word.parse::<u8>(), Ok(1..=24)) && word.len() <= 2
+}
+
+/// "o'clock" gets written a few different ways, and if it's split over two words i.e. "o' clock"
+/// the apostrophe gets treated as punctuation and we get a weird pause. If the words after an hour
+/// spell out "o'clock" this joins them into one word keeping any punctuation from the end. Times
+/// like "half past three" or "quarter to 9" don't need anything special, they're read fine word by
+/// word.
+fn join_oclock(words: &mut Vec<String>) {
+    let is_o = |x: &str| matches!(x.to_ascii_lowercase().as_str(), "o" | "o'");
+    if words.len() < 2 || !is_o(&words[0]) {
+        return;
+    }
+    if let Some(end) = words[1].to_ascii_lowercase().strip_prefix("clock") {
+        if end.chars().all(|c| c.is_ascii_punctuation()) {
+            let joined = format!("oclock{}", end);
+            words.splice(0..2, [joined]);
+        }
+    }
+}
+
+/// If a whole number is followed by a fraction like "1 1/2" this joins them into one word so
+/// `process_number` can read them as a mixed number, "ONE AND A HALF".
+fn join_mixed_fraction(word: &mut String, words: &mut Vec<String>) {
+    let Some(next) = words.first() else {
+        return;
+    };
+    let fraction = next.trim_end_matches(|c: char| c.is_ascii_punctuation());
+    let is_fraction = fraction.split_once('/').is_some_and(|(num, den)| {
+        [num, den]
+            .iter()
+            .all(|x| !x.is_empty() && x.bytes().all(|b| b.is_ascii_digit()))
+    });
+    if !word.is_empty() && is_fraction {
+        let next = words.remove(0);
+        word.push(' ');
+        word.push_str(&next);
+    }
+}
+
+/// Roman numerals from largest to smallest including the subtractive pairs like "IV".
+const ROMAN_NUMERALS: [(u32, &str); 13] = [
+    (1000, "M"),
+    (900, "CM"),
+    (500, "D"),
+    (400, "CD"),
+    (100, "C"),
+    (90, "XC"),
+    (50, "L"),
+    (40, "XL"),
+    (10, "X"),
+    (9, "IX"),
+    (5, "V"),
+    (4, "IV"),
+    (1, "I"),
+];
+
+/// Parses an uppercase Roman numeral, returning `None` if it's not a valid one. Only the standard
+/// form is accepted, so "IV" is 4 but "IIII", "IIV" and "VX" aren't numerals. Numbers over 3999
+/// need an overline so can't be written.
+pub fn roman_to_int(x: &str) -> Option<u32> {
+    let digits = x
+        .chars()
+        .map(|c| {
+            ROMAN_NUMERALS
+                .iter()
+                .find(|(_, numeral)| numeral.len() == 1 && numeral.starts_with(c))
+                .map(|(value, _)| *value as i64)
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let mut total = 0;
+    for (i, digit) in digits.iter().enumerate() {
+        // A smaller numeral before a bigger one is subtracted
+        if digits.get(i + 1).is_some_and(|next| next > digit) {
+            total -= digit;
+        } else {
+            total += digit;
+        }
+    }
+    // "IIII" or "IC" still add up to something, so check it's how the number would be written
+    let total = u32::try_from(total)
+        .ok()
+        .filter(|x| (1..4000).contains(x))?;
+    (to_roman(total) == x).then_some(total)
+}
+
+/// Writes a number as a Roman numeral, see `roman_to_int`.
+fn to_roman(mut x: u32) -> String {
+    let mut res = String::new();
+    for (value, numeral) in ROMAN_NUMERALS {
+        while x >= value {
+            res.push_str(numeral);
+            x -= value;
+        }
+    }
+    res
+}
+
+/// Reads each digit in the string individually, letters are kept and anything else is dropped. So
+/// "1.2.3" becomes "ONE TWO THREE". This is the fallback for when we can't make sense of a number.
+fn read_digits(x: &str) -> String {
+    const DIGITS: [&str; 10] = [
+        "ZERO", "ONE", "TWO", "THREE", "FOUR", "FIVE", "SIX", "SEVEN", "EIGHT", "NINE",
+    ];
+    let mut words = vec![];
+    let mut current = String::new();
+    for c in x.chars() {
+        if c.is_ascii_alphabetic() {
+            current.push(c.to_ascii_uppercase());
+            continue;
+        }
+        if !current.is_empty() {
+            words.push(std::mem::take(&mut current));
+        }
+        if let Some(digit) = c.to_digit(10) {
+            words.push(DIGITS[digit as usize].to_string());
+        }
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words.join(" ")
 }
 
 /// Normalise non-SSML text, this splits by words and then attempts to normalise each word in
 /// isolation as well as gathering the punctuation information.
 pub fn normalise_text(x: &str) -> NormalisedText {
+    normalise_text_with_options(x, &NormaliserOptions::default())
+}
+
+/// Normalise non-SSML text with the given options, see `normalise_text`.
+pub fn normalise_text_with_options(x: &str, options: &NormaliserOptions) -> NormalisedText {
     static IS_NUM: OnceCell<Regex> = OnceCell::new();
     static IS_PUNCT: OnceCell<Regex> = OnceCell::new();
+    static LEADING_PUNCT: OnceCell<Regex> = OnceCell::new();
     static PROBLEM_CHARS: OnceCell<Regex> = OnceCell::new();
 
     let is_num = IS_NUM.get_or_init(|| Regex::new(r#"\d"#).unwrap());
-    let is_punct = IS_PUNCT.get_or_init(|| Regex::new(r#"[[:punct:]]$"#).unwrap());
+    let is_punct = IS_PUNCT.get_or_init(|| Regex::new(r#"[[:punct:]]+$"#).unwrap());
+    let leading_punct = LEADING_PUNCT.get_or_init(|| Regex::new(r#"^[[:punct:]]+"#).unwrap());
     let problem_chars = PROBLEM_CHARS.get_or_init(|| Regex::new(r#"[\[\(\)\]\-:]"#).unwrap());
 
     let mut text_buffer = String::new();
     let mut result = NormalisedText::default();
-    let s = deunicode(x);
+
+    // Symbols are read on the raw unicode first as deunicode loses information, it will turn `£`
+    // into "PS" and `≠` into "=". Once they're expanded deunicode transliterates whatever's left.
+    // See `CharClass` for which characters are handled where.
+    let s = if options.split_identifiers {
+        split_identifiers(x)
+    } else {
+        Cow::Borrowed(x)
+    };
+    let s = expand_list_markers(&s, options.list_markers);
+    let s = if options.number_hints {
+        expand_number_hints(&s)
+    } else {
+        s
+    };
+    let s = expand_currency(&s);
+    let s = expand_units(&s, options);
+    let s = expand_times(&s);
+    let s = expand_fractions(&s, options);
+    let s = expand_superscript_ordinals(&s);
+    let s = expand_arithmetic(&s);
+    let s = expand_symbols(&s);
+    let s = deunicode(&s);
 
     // Lets initially clean away some problem characters! This is a bit of a hack. And also ones
-    // like `-` may be spoken or not.
+    // like `-` may be spoken or not. Times have already been read by `expand_times`, so the colons
+    // left aren't part of a time.
     let s = problem_chars.replace_all(&s, " ");
 
-    let mut words: Vec<String> = s
+    let words: Vec<String> = s
         .split_ascii_whitespace()
         .map(|x| x.to_string())
         .collect::<Vec<_>>();
+    let words = expand_dates(words);
+    let words = expand_abbreviations(words, options.abbreviations);
+    let words = drop_abbreviation_stops(words);
+    let mut words = expand_regnal_names(words, options.regnal_names);
 
     while !words.is_empty() {
         let mut word = words.remove(0);
@@ -486,24 +2389,63 @@ pub fn normalise_text(x: &str) -> NormalisedText {
         if word.trim() == "&" {
             word = word.replace('&', "and");
         }
+        if is_hour(&word) {
+            join_oclock(&mut words);
+        }
+        if word.bytes().all(|b| b.is_ascii_digit()) {
+            join_mixed_fraction(&mut word, &mut words);
+        }
 
         // So NAN is a number... Be careful! https://github.com/Ballasi/num2words/issues/12
-        let mut end_punct = None;
-        let word = if let Some(punct) = is_punct.find(&word) {
-            if let Ok(punct) = Punctuation::from_str(punct.as_str()) {
-                end_punct = Some(punct);
-            } else if !matches!(punct.as_str(), "'" | "\"") {
-                // We can ignore apostrophes!
-                warn!("Unhandled punctuation: '{}'", punct.as_str());
-            }
-            &word[0..punct.start()]
-        } else {
-            &word
+        let (word, end_punct) = match is_punct.find(&word) {
+            Some(punct) => (
+                &word[0..punct.start()],
+                collapse_punctuation(punct.as_str()),
+            ),
+            None => (word.as_str(), None),
+        };
+        // Leading punctuation like ",world" belongs to the word before it, at the start of the
+        // text i.e. ".Hello" there's nothing to attach it to so it's dropped. A decimal point
+        // like ".5" is part of the number though.
+        let word = match leading_punct.find(word) {
+            Some(punct)
+                if !(punct.as_str().ends_with('.')
+                    && word[punct.end()..].starts_with(|c: char| c.is_ascii_digit())) =>
+            {
+                if let Some(leading) = collapse_punctuation(punct.as_str()) {
+                    if result.chunks.is_empty() && text_buffer.trim().is_empty() {
+                        debug!("Dropping leading punctuation: '{}'", punct.as_str());
+                    } else {
+                        push_punctuation(&mut result, &mut text_buffer, leading);
+                    }
+                }
+                &word[punct.end()..]
+            }
+            _ => word,
         };
 
-        if is_num.is_match(word) {
+        let roman = options
+            .roman_numerals
+            .then_some(word)
+            .filter(|x| *x != "I")
+            .and_then(roman_to_int);
+        if let Some(num) = roman {
+            match cardinal_words(num.into()) {
+                Ok(number) => text_buffer.push_str(&number),
+                Err(e) => {
+                    warn!("Couldn't read roman numeral '{}': {}", word, e);
+                    text_buffer.push_str(word);
+                }
+            }
+        } else if is_num.is_match(word) {
             // We don't want to remove spaces after punctuation!
-            text_buffer.push_str(&process_number(word).unwrap());
+            match process_number(word, options) {
+                Ok(number) => text_buffer.push_str(&number),
+                Err(e) => {
+                    warn!("Couldn't read number '{}', reading digits: {}", word, e);
+                    text_buffer.push_str(&read_digits(word));
+                }
+            }
         } else {
             let mut word = word.to_string();
             word.retain(valid_char);
@@ -512,16 +2454,7 @@ pub fn normalise_text(x: &str) -> NormalisedText {
             text_buffer.push_str(&word);
         }
         if let Some(end_punct) = end_punct {
-            // Push the punctuation back on
-            if !text_buffer.is_empty() {
-                result
-                    .chunks
-                    .push(NormaliserChunk::Text(text_buffer.clone()));
-                text_buffer.clear();
-            }
-            result.chunks.push(NormaliserChunk::Punct(end_punct));
-            // Keeps space after punct
-            text_buffer.push(' ');
+            push_punctuation(&mut result, &mut text_buffer, end_punct);
         } else {
             text_buffer.push(' ');
         }
@@ -536,52 +2469,1828 @@ pub fn normalise_text(x: &str) -> NormalisedText {
     result
 }
 
-/// Used to remove characters we can't synthesise from words. Any punctuation in here should be
-/// picked up and added to the normaliser output before we strip it!
-fn valid_char(x: char) -> bool {
-    !r#"!"£$%^&*()-_=+[{]};:'@#~,<.>/?|\`¬"#.contains(x)
+/// How a character is handled by the normaliser before it's split into words. Currency, fractions,
+/// maths symbols and emoji are expanded into words on the raw unicode text, then everything else
+/// that isn't ASCII is transliterated to ASCII by deunicode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CharClass {
+    /// ASCII characters are kept as they are
+    Ascii,
+    /// Accented letters and letters from other scripts are transliterated by deunicode i.e. "é"
+    /// becomes "e"
+    Letter,
+    /// Currency symbols we can read, an amount after them is read as money i.e. "£5" is "five
+    /// pounds"
+    Currency,
+    /// Unicode fractions i.e. "½" is "one half", deunicode would turn "1½" into "11/2"
+    Fraction,
+    /// Maths symbols that are read as words i.e. "×" is "times"
+    Math,
+    /// Emoji and pictographs are read by name i.e. "❤" is "heart"
+    Emoji,
+    /// Everything else is left to deunicode, which may turn it into punctuation or remove it
+    Other,
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Maths symbols and how they're read
+const MATH_SYMBOLS: &[(char, &str)] = &[
+    ('×', "TIMES"),
+    ('÷', "DIVIDED BY"),
+    ('±', "PLUS OR MINUS"),
+    ('−', "MINUS"),
+    ('≠', "NOT EQUAL TO"),
+    ('≤', "LESS THAN OR EQUAL TO"),
+    ('≥', "GREATER THAN OR EQUAL TO"),
+    ('≈', "APPROXIMATELY"),
+    ('∞', "INFINITY"),
+    ('√', "SQUARE ROOT OF"),
+];
 
-    #[test]
-    fn basic_text_norm() {
-        assert_eq!(normalise_text("2nd").to_string_unchecked(), "SECOND");
-        assert_eq!(normalise_text("3d").to_string_unchecked(), "THREE D");
-        assert_eq!(normalise_text("k8s").to_string_unchecked(), "K EIGHT S");
-    }
+/// Unicode fractions and how they're read
+const VULGAR_FRACTIONS: &[(char, &str)] = &[
+    ('½', "ONE HALF"),
+    ('⅓', "ONE THIRD"),
+    ('⅔', "TWO THIRDS"),
+    ('¼', "ONE QUARTER"),
+    ('¾', "THREE QUARTERS"),
+    ('⅕', "ONE FIFTH"),
+    ('⅖', "TWO FIFTHS"),
+    ('⅗', "THREE FIFTHS"),
+    ('⅘', "FOUR FIFTHS"),
+    ('⅙', "ONE SIXTH"),
+    ('⅚', "FIVE SIXTHS"),
+    ('⅐', "ONE SEVENTH"),
+    ('⅛', "ONE EIGHTH"),
+    ('⅜', "THREE EIGHTHS"),
+    ('⅝', "FIVE EIGHTHS"),
+    ('⅞', "SEVEN EIGHTHS"),
+    ('⅑', "ONE NINTH"),
+    ('⅒', "ONE TENTH"),
+];
 
-    #[test]
-    fn duplicate_removal() {
-        assert_eq!(dict_normalise("BATH(2)"), "BATH");
-        assert_eq!(dict_normalise("HELLO(45)"), "HELLO");
-        assert_eq!(dict_normalise("(3)d"), "THREE D");
+/// Works out which class a character is in, see `CharClass`.
+pub fn char_class(c: char) -> CharClass {
+    if c.is_ascii() && c != '$' {
+        CharClass::Ascii
+    } else if currency_names(c.encode_utf8(&mut [0; 4])).is_some() {
+        CharClass::Currency
+    } else if VULGAR_FRACTIONS.iter().any(|(fraction, _)| *fraction == c) {
+        CharClass::Fraction
+    } else if MATH_SYMBOLS.iter().any(|(symbol, _)| *symbol == c) {
+        CharClass::Math
+    } else if matches!(c as u32, 0x1F300..=0x1FAFF | 0x2600..=0x27BF | 0x2B50 | 0x2B55) {
+        CharClass::Emoji
+    } else if c.is_alphabetic() {
+        CharClass::Letter
+    } else {
+        CharClass::Other
     }
+}
 
-    #[test]
-    fn hyphened_numbers() {
-        assert_eq!(
-            normalise_text("sixty-four ninety-three").to_string_unchecked(),
-            "SIXTY FOUR NINETY THREE"
-        )
+/// Reads currency amounts like "£1,000.50". If we fail to read the amount it's left for the rest
+/// of the normaliser.
+fn expand_currency(x: &str) -> Cow<'_, str> {
+    static CURRENCY: OnceCell<Regex> = OnceCell::new();
+    let currency = CURRENCY.get_or_init(|| {
+        Regex::new(r#"(?<symbol>[$£€¥])(?<amount>\d{1,3}(,\d{3})+(\.\d+)?|\d*\.\d+|\d+(\.\d+)?)"#)
+            .unwrap()
+    });
+    currency.replace_all(x, |caps: &regex::Captures| {
+        match process_currency(&caps["symbol"], &caps["amount"]) {
+            Ok(s) => format!(" {}", s),
+            Err(e) => {
+                warn!("Couldn't read currency '{}': {}", &caps[0], e);
+                caps[0].to_string()
+            }
+        }
+    })
+}
+
+/// Reads amounts with units that are enabled in `UnitExpansion`, the unit can be glued to the
+/// number or after a space so "5kg" and "5 kg" are both read. This is done on the raw text as
+/// deunicode would turn `‰` into "%0".
+fn expand_units<'a>(x: &'a str, options: &NormaliserOptions) -> Cow<'a, str> {
+    static UNITS: OnceCell<Regex> = OnceCell::new();
+    let units = UNITS.get_or_init(|| {
+        let measurements = MEASUREMENT_UNITS
+            .iter()
+            .map(|(unit, _, _)| *unit)
+            .collect::<Vec<_>>()
+            .join("|");
+        Regex::new(&format!(
+            r#"\b(?<amount>\d[\d,]*(\.\d+)?) ?(?<unit>‰|(?i:bps|{})\b)"#,
+            measurements
+        ))
+        .unwrap()
+    });
+    units.replace_all(x, |caps: &regex::Captures| {
+        let amount = &caps["amount"];
+        let measurement = MEASUREMENT_UNITS
+            .iter()
+            .find(|(unit, _, _)| caps["unit"].eq_ignore_ascii_case(unit));
+        let unit = match (&caps["unit"], measurement) {
+            ("‰", _) if options.units.per_mille => "PER MILLE",
+            ("‰", _) => return caps[0].to_string(),
+            (_, Some(_)) if !options.units.measurements => return caps[0].to_string(),
+            (_, Some((_, singular, _))) if amount == "1" => singular,
+            (_, Some((_, _, plural))) => plural,
+            _ if !options.units.basis_points => return caps[0].to_string(),
+            _ if amount == "1" => "BASIS POINT",
+            _ => "BASIS POINTS",
+        };
+        match process_number(amount, options) {
+            Ok(number) => format!(" {} {} ", number, unit),
+            Err(e) => {
+                warn!("Couldn't read amount '{}': {}", &caps[0], e);
+                caps[0].to_string()
+            }
+        }
+    })
+}
+
+/// Splits identifiers from code into separate words. Underscores become spaces and words are split
+/// where the case changes from lower to upper, so "snake_case", "camelCase" and "SCREAMING_SNAKE"
+/// are all two words. A run of capitals is kept together as an acronym, "HTTPServer" is "HTTP
+/// Server". A single lowercase letter at the start isn't split off so "iPhone" and "eBay" stay as
+/// they are.
+pub fn split_identifiers(x: &str) -> Cow<'_, str> {
+    let is_split = |chars: &[char], i: usize| {
+        let (previous, current) = (chars[i - 1], chars[i]);
+        if !current.is_uppercase() || !previous.is_alphabetic() {
+            return false;
+        }
+        let word_start = i == 1 || !chars[i - 2].is_alphanumeric();
+        let acronym_end = chars.get(i + 1).is_some_and(|x| x.is_lowercase());
+        (previous.is_lowercase() && !word_start) || (previous.is_uppercase() && acronym_end)
+    };
+    let chars = x.chars().collect::<Vec<_>>();
+    if !chars.contains(&'_') && !(1..chars.len()).any(|i| is_split(&chars, i)) {
+        return Cow::Borrowed(x);
+    }
+    let mut res = String::with_capacity(x.len() + 4);
+    for (i, c) in chars.iter().enumerate() {
+        if *c == '_' {
+            res.push(' ');
+            continue;
+        }
+        if i > 0 && is_split(&chars, i) {
+            res.push(' ');
+        }
+        res.push(*c);
     }
+    Cow::Owned(res)
+}
 
-    #[test]
-    fn extract_punctuation() {
-        let actual = normalise_text("Is this my 1st talk? You tell me!");
+/// Reads numbers with a hint after them like "1980{year}", see the module docs. The hints only
+/// cover whole numbers, so after a decimal, sign or currency symbol like "3.5{cardinal}",
+/// "-5{cardinal}" or "£5{cardinal}" the hint is dropped and the number is read as if it wasn't
+/// there. Unknown hints are left alone.
+fn expand_number_hints(x: &str) -> Cow<'_, str> {
+    static HINT: OnceCell<Regex> = OnceCell::new();
+    let hint =
+        HINT.get_or_init(|| Regex::new(r#"(?<number>\d[\d,]*)\{(?<hint>[a-z]+)\}"#).unwrap());
+    if !x.contains('{') {
+        return Cow::Borrowed(x);
+    }
+    hint.replace_all(x, |caps: &regex::Captures| {
+        let all = caps.get(0).unwrap();
+        let mut before = x[..all.start()].chars().rev();
+        let has_prefix = match before.next() {
+            Some('.') => true,
+            Some('-' | '+') => !before.next().is_some_and(|c| c.is_alphanumeric()),
+            Some(c) => c.is_ascii_digit() || char_class(c) == CharClass::Currency,
+            None => false,
+        };
+        let number = caps["number"].replace(',', "");
+        let words = match (&caps["hint"], number.parse::<i64>()) {
+            ("cardinal" | "ordinal" | "year" | "digits", _) if has_prefix => {
+                Some(caps["number"].to_string())
+            }
+            ("cardinal", Ok(n)) => cardinal_words(n).ok(),
+            ("ordinal", Ok(n)) => Num2Words::new(n)
+                .ordinal()
+                .to_words()
+                .ok()
+                .map(|x| x.replace('-', " ").to_ascii_uppercase()),
+            ("year", Ok(n)) => year_words(n).ok(),
+            ("digits", _) => Some(read_digits(&number)),
+            _ => None,
+        };
+        match words {
+            Some(words) => words,
+            None => {
+                warn!("Ignoring number hint '{}'", all.as_str());
+                all.as_str().to_string()
+            }
+        }
+    })
+}
 
-        let expected = NormalisedText {
-            chunks: vec![
-                NormaliserChunk::Text("IS THIS MY FIRST TALK".to_string()),
-                NormaliserChunk::Punct(Punctuation::QuestionMark),
-                NormaliserChunk::Text(" YOU TELL ME".to_string()),
-                NormaliserChunk::Punct(Punctuation::ExclamationMark),
-            ],
+/// Reads times like "3:30pm" and "09:05" before the colon is stripped, otherwise they'd be read as
+/// "THREE THIRTY PM" and "NINE FIVE". An "am" or "pm" after the time is spelled out, without one
+/// the time can be in the 24 hour clock. Anything that isn't a valid time is left alone.
+fn expand_times(x: &str) -> Cow<'_, str> {
+    static TIME: OnceCell<Regex> = OnceCell::new();
+    let time = TIME.get_or_init(|| {
+        Regex::new(r#"\b(?<hour>\d{1,2}):(?<minute>\d{2})(?<period>\s*(?i:am|pm))?"#).unwrap()
+    });
+    if !x.contains(':') {
+        return Cow::Borrowed(x);
+    }
+    // The regex crate doesn't do lookahead, so check the time isn't part of a longer word
+    let ends_word = |end: usize| !x[end..].starts_with(|c: char| c.is_alphanumeric() || c == ':');
+    let mut res = String::with_capacity(x.len());
+    let mut last = 0;
+    for caps in time.captures_iter(x) {
+        let minute = caps.name("minute").unwrap();
+        let (period, end) = match caps.name("period") {
+            Some(period) if ends_word(period.end()) => (Some(period.as_str().trim()), period.end()),
+            _ => (None, minute.end()),
+        };
+        if !ends_word(end) {
+            continue;
+        }
+        let Some(words) = time_words(&caps["hour"], minute.as_str(), period) else {
+            continue;
         };
+        let start = caps.get(0).unwrap().start();
+        res.push_str(&x[last..start]);
+        res.push_str(&words);
+        last = end;
+    }
+    res.push_str(&x[last..]);
+    Cow::Owned(res)
+}
 
-        assert_eq!(actual, expected);
+/// Reads the hours and minutes of a time, see `expand_times`. On the hour it's "O'CLOCK" for the 12
+/// hour clock, "HUNDRED" for the 24 hour clock and nothing if there's an am or pm. Minutes under
+/// ten are read like "OH FIVE". Returns `None` if the time isn't valid.
+fn time_words(hour: &str, minute: &str, period: Option<&str>) -> Option<String> {
+    let hour = hour.parse::<i64>().ok()?;
+    let minute = minute.parse::<i64>().ok()?;
+    let valid_hour = match period {
+        Some(_) => (1..=12).contains(&hour),
+        None => (0..24).contains(&hour),
+    };
+    if !valid_hour || minute >= 60 {
+        return None;
+    }
+    let mut res = cardinal_words(hour).ok()?;
+    match minute {
+        0 if period.is_some() => {}
+        0 if (1..=12).contains(&hour) => res.push_str(" OCLOCK"),
+        0 => res.push_str(" HUNDRED"),
+        1..=9 => {
+            res.push_str(" OH ");
+            res.push_str(&cardinal_words(minute).ok()?);
+        }
+        _ => {
+            res.push(' ');
+            res.push_str(&cardinal_words(minute).ok()?);
+        }
+    }
+    // Spelled out so "AM" isn't read as the word "am"
+    for c in period.unwrap_or_default().chars() {
+        res.push(' ');
+        res.push(c.to_ascii_uppercase());
+    }
+    Some(res)
+}
+
+/// Reads unicode fractions, a whole number before the fraction makes it a mixed number so "1½" is
+/// "ONE AND A HALF".
+fn expand_fractions<'a>(x: &'a str, options: &NormaliserOptions) -> Cow<'a, str> {
+    static FRACTION: OnceCell<Regex> = OnceCell::new();
+    let fraction =
+        FRACTION.get_or_init(|| Regex::new(r#"((?<whole>\d+) ?)?(?<fraction>[½¼¾⅐-⅞])"#).unwrap());
+    fraction.replace_all(x, |caps: &regex::Captures| {
+        let c = caps["fraction"].chars().next().unwrap_or_default();
+        let Some((_, name)) = VULGAR_FRACTIONS.iter().find(|(fraction, _)| *fraction == c) else {
+            return caps[0].to_string();
+        };
+        let Some(whole) = caps.name("whole") else {
+            return format!(" {} ", name);
+        };
+        match process_number(whole.as_str(), options) {
+            Ok(whole) => {
+                // "one and a half" sounds more natural than "one and one half"
+                let fraction = match name.strip_prefix("ONE ") {
+                    Some(x) => format!("A {}", x),
+                    None => name.to_string(),
+                };
+                format!(" {} AND {} ", whole, fraction)
+            }
+            Err(e) => {
+                warn!("Couldn't read number '{}': {}", &caps[0], e);
+                caps[0].to_string()
+            }
+        }
+    })
+}
+
+/// Replaces the markers at the start of list items with a pause, and with `ListMarkers::Read` the
+/// number or letter of the item. The pause before an item is left out if it's the start of the text
+/// or the last line already ended with a pause.
+fn expand_list_markers(x: &str, list_markers: ListMarkers) -> Cow<'_, str> {
+    static MARKER: OnceCell<Regex> = OnceCell::new();
+    if list_markers == ListMarkers::Off {
+        return Cow::Borrowed(x);
+    }
+    let marker = MARKER.get_or_init(|| {
+        Regex::new(
+            r#"(?m)^[\t ]*(?:(?<number>\d{1,3}|[a-z])[.)]|(?<letter>[A-Z])\)|[•◦▪*-])[\t ]+"#,
+        )
+        .unwrap()
+    });
+    let mut res = String::with_capacity(x.len());
+    let mut last = 0;
+    for caps in marker.captures_iter(x) {
+        let start = caps.get(0).unwrap().start();
+        res.push_str(&x[last..start]);
+        let after_punct = res.trim_end().ends_with(['.', ',', ';', '!', '?']);
+        if !res.trim().is_empty() && !after_punct {
+            res.push_str(", ");
+        }
+        if list_markers == ListMarkers::Read {
+            if let Some(item) = caps.name("number").or_else(|| caps.name("letter")) {
+                res.push_str(item.as_str());
+                res.push_str(", ");
+            }
+        }
+        last = caps.get(0).unwrap().end();
+    }
+    res.push_str(&x[last..]);
+    Cow::Owned(res)
+}
+
+/// Replaces superscript ordinal suffixes like "1ˢᵗ" with the plain ones, "1st", so they're read as
+/// ordinals.
+fn expand_superscript_ordinals(x: &str) -> Cow<'_, str> {
+    static ORDINAL: OnceCell<Regex> = OnceCell::new();
+    let ordinal =
+        ORDINAL.get_or_init(|| Regex::new(r#"(?<number>\d)(?<suffix>ˢᵗ|ⁿᵈ|ʳᵈ|ᵗʰ)"#).unwrap());
+    ordinal.replace_all(x, |caps: &regex::Captures| {
+        let suffix = match &caps["suffix"] {
+            "ˢᵗ" => "st",
+            "ⁿᵈ" => "nd",
+            "ʳᵈ" => "rd",
+            _ => "th",
+        };
+        format!("{}{}", &caps["number"], suffix)
+    })
+}
+
+/// Reads the ASCII maths symbols that are next to numbers, these would otherwise be dropped so
+/// "2+2" would be "TWO TWO". "%" after a number is "PERCENT", "+" and "=" next to a number are
+/// "PLUS" and "EQUALS". Hyphens are only "MINUS" with a space either side between two numbers
+/// ("5 - 3") or directly before a number at the start of a word ("-5"), so hyphenated words,
+/// ranges like "10-20" and dates are left alone.
+fn expand_arithmetic(x: &str) -> Cow<'_, str> {
+    if !x.contains(['%', '+', '=', '-']) {
+        return Cow::Borrowed(x);
+    }
+    let chars = x.chars().collect::<Vec<_>>();
+    let is_digit = |c: Option<&char>| c.is_some_and(|c| c.is_ascii_digit());
+    let is_space = |c: Option<&char>| c.is_none() || c.is_some_and(|c| c.is_whitespace());
+    let mut res = String::with_capacity(x.len());
+    for (i, c) in chars.iter().enumerate() {
+        let previous = chars[..i].iter().rev().find(|x| !x.is_whitespace());
+        let next = chars[i + 1..].iter().find(|x| !x.is_whitespace());
+        let before = i.checked_sub(1).and_then(|x| chars.get(x));
+        let after = chars.get(i + 1);
+        let word = match c {
+            '%' if is_digit(previous) => "PERCENT",
+            '+' if is_digit(previous) || is_digit(next) => "PLUS",
+            '=' if is_digit(previous) || is_digit(next) => "EQUALS",
+            '-' if is_space(before)
+                && ((is_space(after) && is_digit(previous) && is_digit(next))
+                    || is_digit(after)) =>
+            {
+                "MINUS"
+            }
+            c => {
+                res.push(*c);
+                continue;
+            }
+        };
+        res.push(' ');
+        res.push_str(word);
+        res.push(' ');
+    }
+    Cow::Owned(res)
+}
+
+/// Replaces maths symbols and emoji with words, with spaces either side so they're separate from
+/// the words around them i.e. "3×4" is "3 TIMES 4".
+fn expand_symbols(x: &str) -> Cow<'_, str> {
+    if !x
+        .chars()
+        .any(|c| matches!(char_class(c), CharClass::Math | CharClass::Emoji))
+    {
+        return Cow::Borrowed(x);
+    }
+    let mut res = String::with_capacity(x.len());
+    for c in x.chars() {
+        match char_class(c) {
+            CharClass::Math => {
+                if let Some((_, name)) = MATH_SYMBOLS.iter().find(|(symbol, _)| *symbol == c) {
+                    res.push(' ');
+                    res.push_str(name);
+                    res.push(' ');
+                }
+            }
+            CharClass::Emoji => {
+                if let Some(name) = deunicode::deunicode_char(c) {
+                    res.push(' ');
+                    res.push_str(&name.trim().replace('_', " "));
+                    res.push(' ');
+                }
+            }
+            _ => res.push(c),
+        }
+    }
+    Cow::Owned(res)
+}
+
+/// Lists the characters in the text that the normaliser can't say, in the order they first
+/// appear. These are either removed outright (i.e. `@`, `~` or quotes) or are non-ASCII characters
+/// that deunicode can't turn into letters, like `©` which becomes "(c)" and is read as "C" or `→`
+/// which becomes a hyphen. Accented letters, emoji (which are read by name) and scripts deunicode
+/// can romanise are fine, as are the characters we split words on (brackets, hyphens and colons),
+/// the symbols we read like `%`, `+`, `=` and `/` and the braces around number hints. This
+/// doesn't check whether words are in the dictionary.
+pub fn unsupported_characters(text: &str) -> Vec<char> {
+    let mut res = vec![];
+    for c in text.chars() {
+        if !is_supported_char(c) && !res.contains(&c) {
+            res.push(c);
+        }
+    }
+    res
+}
+
+fn is_supported_char(c: char) -> bool {
+    match char_class(c) {
+        CharClass::Ascii => {
+            c.is_ascii_alphanumeric()
+                || c.is_ascii_whitespace()
+                || Punctuation::from_str(c.encode_utf8(&mut [0; 4])).is_ok()
+                || "[]&%+=/{}".contains(c)
+        }
+        CharClass::Currency | CharClass::Fraction | CharClass::Math | CharClass::Emoji => true,
+        _ if c.is_whitespace() => true,
+        // Romanised text may include spaces i.e. CJK characters are transliterated syllable by
+        // syllable
+        _ => deunicode::deunicode_char(c).is_some_and(|x| {
+            let x = x.trim();
+            !x.is_empty() && x.chars().all(|c| c.is_ascii_alphanumeric() || c == ' ')
+        }),
+    }
+}
+
+/// Collapses a run of punctuation like "??", "?!" or `."` into the single mark that should be
+/// spoken, see `merge_punctuation`.
+fn collapse_punctuation(run: &str) -> Option<Punctuation> {
+    let mut res = None;
+    for c in run.chars() {
+        if let Ok(punct) = Punctuation::from_str(c.encode_utf8(&mut [0; 4])) {
+            res = Some(match res {
+                Some(prev) => merge_punctuation(prev, punct),
+                None => punct,
+            });
+        } else if c != '"' {
+            // We can ignore quotes!
+            warn!("Unhandled punctuation: '{}'", c);
+        }
+    }
+    res
+}
+
+/// Flushes the text buffer and pushes the punctuation after it. If there's no text since the last
+/// punctuation i.e. "Hello . . ." then the marks are merged so the pauses don't stack up.
+fn push_punctuation(result: &mut NormalisedText, text_buffer: &mut String, punct: Punctuation) {
+    let has_text = !text_buffer.trim().is_empty();
+    if has_text {
+        result
+            .chunks
+            .push(NormaliserChunk::Text(text_buffer.trim_end().to_string()));
+    }
+    text_buffer.clear();
+    match result.chunks.last_mut() {
+        Some(NormaliserChunk::Punct(prev)) if !has_text => {
+            *prev = merge_punctuation(*prev, punct);
+        }
+        _ => result.chunks.push(NormaliserChunk::Punct(punct)),
+    }
+    // Keeps space after punct
+    text_buffer.push(' ');
+}
+
+/// Combines two adjacent punctuation marks into the one that should be spoken. A question mark
+/// always wins so "?!" keeps its question intonation, then sentence ends win over other marks and
+/// apostrophes lose to everything. Otherwise the later mark is kept.
+fn merge_punctuation(prev: Punctuation, next: Punctuation) -> Punctuation {
+    let strength = |x: Punctuation| match x {
+        Punctuation::QuestionMark => 3,
+        x if x.is_sentence_end() => 2,
+        Punctuation::Apostrophe => 0,
+        _ => 1,
+    };
+    if strength(prev) > strength(next) {
+        prev
+    } else {
+        next
+    }
+}
+
+/// Used to remove characters we can't synthesise from words. Any punctuation in here should be
+/// picked up and added to the normaliser output before we strip it!
+fn valid_char(x: char) -> bool {
+    !r#"!"£$%^&*()-_=+[{]};:'@#~,<.>/?|\`¬"#.contains(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basic_text_norm() {
+        assert_eq!(normalise_text("2nd").to_string_unchecked(), "SECOND");
+        assert_eq!(normalise_text("3d").to_string_unchecked(), "THREE D");
+        assert_eq!(normalise_text("k8s").to_string_unchecked(), "K EIGHT S");
+    }
+
+    #[test]
+    fn duplicate_removal() {
+        assert_eq!(dict_normalise("BATH(2)"), "BATH");
+        assert_eq!(dict_normalise("HELLO(45)"), "HELLO");
+        assert_eq!(dict_normalise("(3)d"), "THREE D");
+    }
+
+    #[test]
+    fn hyphened_numbers() {
+        assert_eq!(
+            normalise_text("sixty-four ninety-three").to_string_unchecked(),
+            "SIXTY FOUR NINETY THREE"
+        )
+    }
+
+    #[test]
+    fn extract_punctuation() {
+        let actual = normalise_text("Is this my 1st talk? You tell me!");
+
+        let expected = NormalisedText {
+            chunks: vec![
+                NormaliserChunk::Text("IS THIS MY FIRST TALK".to_string()),
+                NormaliserChunk::Punct(Punctuation::QuestionMark),
+                NormaliserChunk::Text(" YOU TELL ME".to_string()),
+                NormaliserChunk::Punct(Punctuation::ExclamationMark),
+            ],
+        };
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn repeated_punctuation() {
+        let expected = NormalisedText {
+            chunks: vec![
+                NormaliserChunk::Text("WAIT".to_string()),
+                NormaliserChunk::Punct(Punctuation::QuestionMark),
+            ],
+        };
+        assert_eq!(normalise_text("Wait??"), expected);
+        assert_eq!(normalise_text("Wait?!"), expected);
+        assert_eq!(normalise_text("Wait ? ? ?"), expected);
+
+        let expected = NormalisedText {
+            chunks: vec![NormaliserChunk::Punct(Punctuation::ExclamationMark)],
+        };
+        assert_eq!(normalise_text("!!!"), expected);
+        assert_eq!(normalise_text("! ! !"), expected);
+
+        let expected = NormalisedText {
+            chunks: vec![
+                NormaliserChunk::Text("HELLO".to_string()),
+                NormaliserChunk::Punct(Punctuation::FullStop),
+                NormaliserChunk::Text(" HE SAID".to_string()),
+            ],
+        };
+        assert_eq!(normalise_text("Hello..\" he said"), expected);
+        assert_eq!(normalise_text("Hello... . he said"), expected);
+
+        assert_eq!(normalise_text(".Hello").to_string_unchecked(), "HELLO");
+        assert_eq!(normalise_text("...Hello").to_string_unchecked(), "HELLO");
+        let expected = NormalisedText {
+            chunks: vec![
+                NormaliserChunk::Text("HELLO".to_string()),
+                NormaliserChunk::Punct(Punctuation::Comma),
+                NormaliserChunk::Text(" WORLD".to_string()),
+            ],
+        };
+        assert_eq!(normalise_text("Hello ,world"), expected);
+        // Decimal points aren't leading punctuation
+        assert_eq!(
+            normalise_text(".5").to_string_unchecked(),
+            "ZERO POINT FIVE"
+        );
+    }
+
+    #[test]
+    fn arithmetic_symbols() {
+        let norm = |x: &str| {
+            normalise_text(x)
+                .to_string_unchecked()
+                .split_ascii_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        assert_eq!(norm("50%"), "FIFTY PERCENT");
+        assert_eq!(norm("up 7.5 %"), "UP SEVEN POINT FIVE PERCENT");
+        assert_eq!(norm("2 + 2 = 4"), "TWO PLUS TWO EQUALS FOUR");
+        assert_eq!(norm("2+2=4"), "TWO PLUS TWO EQUALS FOUR");
+        assert_eq!(norm("1+2+3"), "ONE PLUS TWO PLUS THREE");
+        assert_eq!(norm("5 - 3 = 2"), "FIVE MINUS THREE EQUALS TWO");
+        assert_eq!(norm("it was -5 outside"), "IT WAS MINUS FIVE OUTSIDE");
+        assert_eq!(norm("3×4"), "THREE TIMES FOUR");
+        // Hyphens in words and ranges aren't subtraction
+        assert_eq!(norm("well-being"), "WELL BEING");
+        assert_eq!(norm("pages 10-20"), "PAGES TEN TWENTY");
+        assert_eq!(norm("COVID-19"), "COVID NINETEEN");
+        // Not next to a number so they're dropped as before
+        assert_eq!(norm("C++ a=b 100 percent"), "C AB ONE HUNDRED PERCENT");
+    }
+
+    #[test]
+    fn number_hints() {
+        let norm = |x: &str| {
+            normalise_text(x)
+                .to_string_unchecked()
+                .split_ascii_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        // Without a hint a month followed by a number is a date
+        assert_eq!(norm("June 3"), "JUNE THIRD");
+        assert_eq!(norm("June 3{cardinal}"), "JUNE THREE");
+        assert_eq!(
+            norm("the 1,980{cardinal} entries"),
+            format!("THE {} ENTRIES", cardinal_words(1980).unwrap())
+        );
+        assert_eq!(norm("in 1980{year}."), "IN NINETEEN EIGHTY.");
+        assert_ne!(norm("in 1980."), "IN NINETEEN EIGHTY.");
+        assert_eq!(norm("finished 2{ordinal}"), "FINISHED SECOND");
+        assert_eq!(norm("room 101{digits}"), "ROOM ONE ZERO ONE");
+
+        // Unknown hints aren't read as hints
+        assert_eq!(expand_number_hints("5{bananas}"), "5{bananas}");
+
+        // Hints after a decimal point, sign or currency are dropped
+        assert_eq!(expand_number_hints("3.5{cardinal}"), "3.5");
+        assert_eq!(norm("3.5{cardinal}"), norm("3.5"));
+        assert_eq!(norm("-5{cardinal}"), norm("-5"));
+        assert!(norm("-5{cardinal}").contains("MINUS"));
+        assert_eq!(norm("£5{cardinal}"), "FIVE POUNDS");
+        assert_eq!(norm("$20{ordinal}"), norm("$20"));
+
+        let options = NormaliserOptions {
+            number_hints: false,
+            ..Default::default()
+        };
+        assert_ne!(
+            normalise_text_with_options("in 1980{year}", &options).to_string_unchecked(),
+            "IN NINETEEN EIGHTY"
+        );
+    }
+
+    #[test]
+    fn regnal_names() {
+        let norm = |x: &str| {
+            normalise_text(x)
+                .to_string_unchecked()
+                .split_ascii_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        assert_eq!(norm("Henry VIII"), "HENRY THE EIGHTH");
+        assert_eq!(
+            norm("Elizabeth II was crowned."),
+            "ELIZABETH THE SECOND WAS CROWNED."
+        );
+        assert_eq!(
+            norm("Pope John Paul II visited."),
+            "POPE JOHN PAUL THE SECOND VISITED."
+        );
+        assert_eq!(
+            norm("Louis XIV, the sun king"),
+            "LOUIS THE FOURTEENTH, THE SUN KING"
+        );
+        assert_eq!(norm("Elizabeth I."), "ELIZABETH THE FIRST.");
+        // Only straight after a capitalised name and "I" has to end the clause
+        assert_eq!(norm("James I went"), "JAMES I WENT");
+        assert_eq!(norm("henry VIII"), "HENRY VIII");
+        assert_eq!(norm("Henry, IV"), "HENRY, IV");
+        assert_eq!(norm("World War II"), "WORLD WAR II");
+        assert_eq!(norm("Pope John XXIII"), "POPE JOHN THE TWENTY THIRD");
+        // Acronyms that happen to be valid numerals
+        assert!(!norm("Ask Robert MD about it").contains("THE"));
+        assert!(!norm("Send Mary CV updates").contains("THE"));
+        assert!(!norm("Charles DC office").contains("THE"));
+
+        let options = NormaliserOptions {
+            regnal_names: &[],
+            ..Default::default()
+        };
+        assert_eq!(
+            normalise_text_with_options("Henry VIII", &options).to_string_unchecked(),
+            "HENRY VIII"
+        );
+    }
+
+    #[test]
+    fn roman_numerals() {
+        assert_eq!(roman_to_int("IV"), Some(4));
+        assert_eq!(roman_to_int("IX"), Some(9));
+        assert_eq!(roman_to_int("VIII"), Some(8));
+        assert_eq!(roman_to_int("XL"), Some(40));
+        assert_eq!(roman_to_int("MCMLXX"), Some(1970));
+        assert_eq!(roman_to_int("MMMCMXCIX"), Some(3999));
+        for invalid in ["", "IIII", "IIV", "VX", "IC", "MMMM", "iv", "HELLO", "XIIV"] {
+            assert_eq!(roman_to_int(invalid), None, "{}", invalid);
+        }
+
+        let norm = |x: &str, options: &NormaliserOptions| {
+            normalise_text_with_options(x, options)
+                .to_string_unchecked()
+                .split_ascii_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let options = NormaliserOptions {
+            roman_numerals: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            norm("World War II, I think", &options),
+            "WORLD WAR TWO, I THINK"
+        );
+        assert_eq!(norm("Chapter XIV.", &options), "CHAPTER FOURTEEN.");
+        // Lowercase and invalid numerals are left alone
+        assert_eq!(norm("mix it", &options), "MIX IT");
+        assert_eq!(norm("XIIV", &options), "XIIV");
+        assert_eq!(
+            norm("World War II", &NormaliserOptions::default()),
+            "WORLD WAR II"
+        );
+
+        let ssml = |x: &str| {
+            normalise_ssml(x)
+                .unwrap()
+                .to_string_unchecked()
+                .split_ascii_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        assert_eq!(
+            ssml(r#"<speak>Louis <say-as interpret-as="roman">xiv</say-as></speak>"#),
+            "LOUIS FOURTEEN"
+        );
+        assert_eq!(
+            ssml(r#"<speak>the <say-as interpret-as="roman" format="ordinal">IX</say-as></speak>"#),
+            "THE NINTH"
+        );
+        assert!(
+            normalise_ssml(r#"<speak><say-as interpret-as="roman">IIII</say-as></speak>"#).is_err()
+        );
+    }
+
+    #[test]
+    fn times() {
+        let norm = |x: &str| {
+            normalise_text(x)
+                .to_string_unchecked()
+                .split_ascii_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        assert_eq!(norm("3:30pm"), "THREE THIRTY P M");
+        assert_eq!(norm("09:05"), "NINE OH FIVE");
+        assert_eq!(norm("12:00"), "TWELVE OCLOCK");
+        assert_eq!(norm("Meet at 3:00 PM."), "MEET AT THREE P M.");
+        assert_eq!(
+            norm("10:15 am, or 11:45am"),
+            "TEN FIFTEEN A M, OR ELEVEN FORTY FIVE A M"
+        );
+        // 24 hour clock
+        assert_eq!(norm("15:00"), "FIFTEEN HUNDRED");
+        assert_eq!(norm("23:59"), "TWENTY THREE FIFTY NINE");
+        assert_eq!(norm("00:30"), "ZERO THIRTY");
+        // Not times so the colons are stripped like before
+        assert_eq!(norm("25:61"), "TWENTY FIVE SIXTY ONE");
+        assert_eq!(norm("13:30pm"), "THIRTEEN THIRTY PM");
+        assert_eq!(norm("Note: 3:30 amazing"), "NOTE THREE THIRTY AMAZING");
+        assert_eq!(norm("12:30:45"), "TWELVE THIRTY FORTY FIVE");
+    }
+
+    #[test]
+    fn list_markers() {
+        let norm = |x: &str, list_markers: ListMarkers| {
+            let options = NormaliserOptions {
+                list_markers,
+                ..Default::default()
+            };
+            normalise_text_with_options(x, &options)
+                .to_string_unchecked()
+                .split_ascii_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let numbered = "Shopping list:\n1. Eggs\n2. Milk\n3. Bread";
+        assert_eq!(
+            norm(numbered, ListMarkers::Off),
+            "SHOPPING LIST ONE. EGGS TWO. MILK THREE. BREAD"
+        );
+        assert_eq!(
+            norm(numbered, ListMarkers::Read),
+            "SHOPPING LIST, ONE, EGGS, TWO, MILK, THREE, BREAD"
+        );
+        assert_eq!(
+            norm(numbered, ListMarkers::Skip),
+            "SHOPPING LIST, EGGS, MILK, BREAD"
+        );
+        assert_eq!(
+            norm("a) first\nb) second\n  c. third", ListMarkers::Read),
+            "A, FIRST, B, SECOND, C, THIRD"
+        );
+        assert_eq!(
+            norm("1) Apples\r\n2) Pears", ListMarkers::Skip),
+            "APPLES, PEARS"
+        );
+
+        // Bullets are a pause either way, and there's no extra pause after a full stop
+        let bulleted = "Things to pack\n• Passport\n• Tickets.\n- Socks";
+        for list_markers in [ListMarkers::Read, ListMarkers::Skip] {
+            assert_eq!(
+                norm(bulleted, list_markers),
+                "THINGS TO PACK, PASSPORT, TICKETS. SOCKS"
+            );
+        }
+
+        // Only at the start of a line
+        assert_eq!(
+            norm("Call me at 5. Or don't", ListMarkers::Read),
+            "CALL ME AT FIVE. OR DONT"
+        );
+        assert_eq!(
+            norm("J. R. R. Tolkien", ListMarkers::Read),
+            "J. R. R. TOLKIEN"
+        );
+    }
+
+    #[test]
+    fn glued_tokens() {
+        let options = NormaliserOptions::default();
+        let glued = |x: &str| process_glued(x, &options).unwrap().unwrap();
+
+        assert_eq!(glued("3rd"), "THIRD");
+        assert_eq!(glued("21ST"), "TWENTY FIRST");
+        assert_eq!(glued("5pm"), "FIVE P M");
+        assert_eq!(glued("11AM"), "ELEVEN A M");
+        assert_eq!(glued("£20"), "TWENTY POUNDS");
+        assert_eq!(glued("20€"), "TWENTY EUROS");
+        // Not a valid time so it falls back to reading the suffix as text
+        assert_eq!(glued("13pm"), "THIRTEEN PM");
+        assert_eq!(glued("5th"), "FIFTH");
+        assert_eq!(glued("A4"), "A FOUR");
+        assert!(process_glued("pm", &options).is_none());
+
+        let norm = |x: &str| {
+            normalise_text(x)
+                .to_string_unchecked()
+                .split_ascii_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        assert_eq!(
+            norm("The 3rd bag weighs 5kg, see you at 5pm."),
+            "THE THIRD BAG WEIGHS FIVE KILOGRAMS, SEE YOU AT FIVE P M."
+        );
+        // Units are read by `expand_units` with or without a space
+        assert_eq!(norm("20%"), "TWENTY PERCENT");
+        assert_eq!(norm("5kg or 5 kg"), "FIVE KILOGRAMS OR FIVE KILOGRAMS");
+        assert_eq!(norm("1km"), "ONE KILOMETRE");
+        assert_eq!(norm("2.5GB"), "TWO POINT FIVE GIGABYTES");
+        assert_eq!(norm("1,000 mph"), "ONE THOUSAND MILES PER HOUR");
+        assert_eq!(norm("5 kgs"), "FIVE KGS");
+    }
+
+    #[test]
+    fn ascii_fractions() {
+        let norm = |x: &str| {
+            normalise_text(x)
+                .to_string_unchecked()
+                .split_ascii_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        assert_eq!(norm("1/2"), "ONE HALF");
+        assert_eq!(norm("3/4"), "THREE QUARTERS");
+        assert_eq!(norm("2/3"), "TWO THIRDS");
+        assert_eq!(norm("5/8"), "FIVE OVER EIGHT");
+        assert_eq!(norm("1 1/2 cups"), "ONE AND A HALF CUPS");
+        assert_eq!(norm("2 3/4."), "TWO AND THREE QUARTERS.");
+        assert_eq!(norm("1 1/8 cups"), "ONE AND ONE OVER EIGHT CUPS");
+        assert_eq!(norm("2 1/5"), "TWO AND ONE OVER FIVE");
+        assert_eq!(norm("1/8"), "ONE OVER EIGHT");
+        // The comma means these aren't a mixed number
+        assert_eq!(norm("1, 1/2"), "ONE, ONE HALF");
+    }
+
+    #[test]
+    fn merged_text_chunks() {
+        let dict = "NEW YORK  N UW1 Y AO1 R K\nNEW  N UW1\nYORK  Y AO1 R K\nHELLO  HH AH0 L OW1\n\
+                    IS  IH1 Z\nBIG  B IH1 G\n";
+        let dict = CmuDictionary::from_reader(std::io::Cursor::new(dict)).unwrap();
+        let pronounce = |text: &NormalisedText| {
+            let mut text = text.clone();
+            text.words_to_pronunciation(&dict);
+            text
+        };
+
+        // Merging doesn't change anything when each chunk is pronounced on its own
+        let text = normalise_text("Hello, xyzzy. York is big! Is it?");
+        let mut merged = text.clone();
+        merged.merge_text_chunks();
+        assert_eq!(
+            merged.chunks(),
+            [NormaliserChunk::Text(
+                "HELLO, XYZZY. YORK IS BIG! IS IT?".to_string()
+            )]
+        );
+        let merged = pronounce(&merged);
+        assert_eq!(merged, pronounce(&text));
+        assert_eq!(
+            merged
+                .chunks()
+                .iter()
+                .filter_map(|x| match x {
+                    NormaliserChunk::Punct(p) => Some(*p),
+                    _ => None,
+                })
+                .collect::<Vec<_>>(),
+            [
+                Punctuation::Comma,
+                Punctuation::FullStop,
+                Punctuation::ExclamationMark,
+                Punctuation::QuestionMark
+            ]
+        );
+
+        // But phrases can now be found over chunk boundaries
+        let text = NormalisedText::from(vec![
+            NormaliserChunk::Text("NEW".to_string()),
+            NormaliserChunk::Text(" YORK".to_string()),
+            NormaliserChunk::Punct(Punctuation::Comma),
+            NormaliserChunk::Text(" HELLO".to_string()),
+        ]);
+        assert_eq!(pronounce(&text).chunks().len(), 4);
+        let mut merged = text.clone();
+        merged.merge_text_chunks();
+        let merged = pronounce(&merged);
+        let phones = |x: &str| {
+            x.split_ascii_whitespace()
+                .map(|x| TtsUnit::Phone(x.parse().unwrap()))
+                .chain(std::iter::once(TtsUnit::Space))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            merged.chunks(),
+            [
+                NormaliserChunk::Pronunciation(phones("N UW1 Y AO1 R K")),
+                NormaliserChunk::Punct(Punctuation::Comma),
+                NormaliserChunk::Pronunciation(phones("HH AH0 L OW1")),
+            ]
+        );
+    }
+
+    #[test]
+    fn sentence_types() {
+        use SentenceType::*;
+
+        let text = normalise_text("Hello there. Are you well? Great! Really?! And you");
+        assert_eq!(
+            text.sentence_types(),
+            vec![Statement, Question, Exclamation, Question, Statement]
+        );
+
+        assert_eq!(
+            normalise_text("Is it 5 o'clock?").sentence_types(),
+            vec![Question]
+        );
+        assert_eq!(normalise_text("").sentence_types(), vec![]);
+        assert_eq!(normalise_text("...").sentence_types(), vec![]);
+
+        let dict = "HELLO  HH AH0 L OW1\nARE  AA1 R\nYOU  Y UW1\nWELL  W EH1 L\n";
+        let dict = CmuDictionary::from_reader(std::io::Cursor::new(dict)).unwrap();
+        let mut text = normalise_text("Hello. Are you well?");
+        text.words_to_pronunciation(&dict);
+        assert_eq!(text.sentence_types(), vec![Statement, Question]);
+    }
+
+    #[test]
+    fn abbreviations() {
+        let norm = |x: &str| {
+            normalise_text(x)
+                .to_string_unchecked()
+                .split_ascii_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        assert_eq!(
+            norm("Dr. Smith went to St. Paul's"),
+            "DOCTOR SMITH WENT TO SAINT PAULS"
+        );
+        // No sentence break after the abbreviations
+        assert!(!normalise_text("Mr. and Mrs. Jones")
+            .chunks()
+            .iter()
+            .any(|x| matches!(x, NormaliserChunk::Punct(_))));
+        assert_eq!(norm("Mr and mrs Jones"), "MISTER AND MISSUS JONES");
+        // Dictionary entries aren't expanded
+        assert_eq!(dict_normalise("ST"), "ST");
+        assert_eq!(
+            norm("Apples, pears, etc. are fruit"),
+            "APPLES, PEARS, ET CETERA ARE FRUIT"
+        );
+        // At the end of the text the full stop is kept
+        assert_eq!(norm("Apples, pears etc."), "APPLES, PEARS ET CETERA.");
+        assert_eq!(norm("Ask the Dr., then"), "ASK THE DOCTOR, THEN");
+        // Before a capital it's the end of the sentence too, unless it's a title
+        assert_eq!(
+            norm("I bought apples etc. Then I left."),
+            "I BOUGHT APPLES ET CETERA. THEN I LEFT."
+        );
+        assert_eq!(norm("Ask Dr. Smith"), "ASK DOCTOR SMITH");
+
+        // Abbreviations that are read as they are only end a sentence before a capital
+        assert_eq!(
+            norm("It ends at 5 p.m. tomorrow."),
+            "IT ENDS AT FIVE PM TOMORROW."
+        );
+        assert_eq!(
+            norm("The U.S. army, e.g. this one"),
+            "THE US ARMY, EG THIS ONE"
+        );
+        assert_eq!(norm("See fig. 3 for more."), "SEE FIG THREE FOR MORE.");
+        assert_eq!(
+            norm("It ends at 5 p.m. Then we go"),
+            "IT ENDS AT FIVE PM. THEN WE GO"
+        );
+        assert_eq!(
+            norm("No. 5 is good. 5 is fine"),
+            "NO FIVE IS GOOD. FIVE IS FINE"
+        );
+
+        const STREET: &[Abbreviation] = &[Abbreviation::new("ST", "STREET")];
+        let options = NormaliserOptions {
+            abbreviations: STREET,
+            ..Default::default()
+        };
+        assert_eq!(
+            normalise_text_with_options("Baker St. is near Dr. Who", &options).chunks[0],
+            NormaliserChunk::Text("BAKER STREET IS NEAR DR".to_string())
+        );
+        let off = NormaliserOptions {
+            abbreviations: &[],
+            ..Default::default()
+        };
+        assert_eq!(
+            normalise_text_with_options("Dr. Smith", &off).chunks[1],
+            NormaliserChunk::Punct(Punctuation::FullStop)
+        );
+    }
+
+    #[test]
+    fn decimal_numbers() {
+        let norm = |x: &str| normalise_text(x).to_string_unchecked();
+        assert_eq!(norm("3.14"), "THREE POINT ONE FOUR");
+        assert_eq!(norm("0.5"), "ZERO POINT FIVE");
+        assert_eq!(norm("3.14159"), "THREE POINT ONE FOUR ONE FIVE NINE");
+        assert_eq!(norm("2.05"), "TWO POINT ZERO FIVE");
+        assert_eq!(
+            norm("1,234.5"),
+            format!("{} POINT FIVE", cardinal_words(1234).unwrap())
+        );
+        assert_eq!(norm("1,000"), "ONE THOUSAND");
+        assert_eq!(norm("It's 3.5."), "ITS THREE POINT FIVE.");
+    }
+
+    #[test]
+    fn long_digit_runs() {
+        assert_eq!(
+            normalise_text("1234567890").to_string_unchecked(),
+            "ONE TWO THREE FOUR FIVE SIX SEVEN EIGHT NINE ZERO"
+        );
+        assert_eq!(
+            normalise_text("1000000").to_string_unchecked(),
+            "ONE MILLION"
+        );
+        assert_eq!(
+            normalise_text("ID12345678").to_string_unchecked(),
+            "ID ONE TWO THREE FOUR FIVE SIX SEVEN EIGHT"
+        );
+
+        let never = NormaliserOptions {
+            read_digits_over: None,
+            ..Default::default()
+        };
+        assert!(normalise_text_with_options("1234567890", &never)
+            .to_string_unchecked()
+            .starts_with("ONE BILLION"));
+
+        let short = NormaliserOptions {
+            read_digits_over: Some(6),
+            ..Default::default()
+        };
+        assert_eq!(
+            normalise_text_with_options("1000000", &short).to_string_unchecked(),
+            "ONE ZERO ZERO ZERO ZERO ZERO ZERO"
+        );
+        // Thousands separators mean it's definitely a quantity
+        assert_eq!(
+            normalise_text_with_options("1,000,000", &short).to_string_unchecked(),
+            "ONE MILLION"
+        );
+    }
+
+    #[test]
+    fn alphanumeric_ids() {
+        assert_eq!(normalise_text("R2D2").to_string_unchecked(), "R TWO D TWO");
+        assert_eq!(normalise_text("C3PO").to_string_unchecked(), "C THREE P O");
+        assert_eq!(normalise_text("A1").to_string_unchecked(), "A ONE");
+        assert_eq!(
+            normalise_text("A1B2C3").to_string_unchecked(),
+            "A ONE B TWO C THREE"
+        );
+        assert_eq!(
+            normalise_text("w3schools").to_string_unchecked(),
+            "W THREE SCHOOLS"
+        );
+        assert_eq!(normalise_text("A12B").to_string_unchecked(), "A TWELVE B");
+
+        let spell = NormaliserOptions {
+            alphanumeric_ids: AlphanumericIds::SpellDigits,
+            ..Default::default()
+        };
+        assert_eq!(
+            normalise_text_with_options("A12B", &spell).to_string_unchecked(),
+            "A ONE TWO B"
+        );
+        // Single groups are unaffected
+        assert_eq!(
+            normalise_text_with_options("A12", &spell).to_string_unchecked(),
+            "A TWELVE"
+        );
+
+        let off = NormaliserOptions {
+            alphanumeric_ids: AlphanumericIds::Off,
+            ..Default::default()
+        };
+        assert_eq!(
+            normalise_text_with_options("C3PO", &off).to_string_unchecked(),
+            "C THREE PO"
+        );
+    }
+
+    #[test]
+    fn time_phrases() {
+        for oclock in [
+            "5 o'clock",
+            "5 oclock",
+            "5 o' clock",
+            "5 o clock",
+            "5 O'Clock",
+            "5 o’clock",
+        ] {
+            assert_eq!(normalise_text(oclock).to_string_unchecked(), "FIVE OCLOCK");
+        }
+        assert_eq!(
+            normalise_text("at 12 o' clock.").to_string_unchecked(),
+            "AT TWELVE OCLOCK."
+        );
+        // Not after an hour so left alone
+        assert_eq!(
+            normalise_text("the o clock").to_string_unchecked(),
+            "THE O CLOCK"
+        );
+        assert_eq!(
+            normalise_text("half past three").to_string_unchecked(),
+            "HALF PAST THREE"
+        );
+        assert_eq!(
+            normalise_text("quarter to 9").to_string_unchecked(),
+            "QUARTER TO NINE"
+        );
+    }
+
+    #[test]
+    fn ssml_word_tokens() {
+        let ssml = "<speak>Connect to <w>Wi-Fi</w> in <token>New York</token></speak>";
+        assert_eq!(
+            normalise(ssml).unwrap().to_string_unchecked(),
+            "CONNECT TO WIFI IN NEW YORK"
+        );
+        assert_eq!(
+            normalise("<speak><token> Wi-Fi\n  hot-spot </token></speak>")
+                .unwrap()
+                .to_string_unchecked(),
+            "WIFI HOTSPOT"
+        );
+        // Without the tags they're separate words
+        assert_eq!(
+            normalise("<speak>Connect to Wi-Fi in New York</speak>")
+                .unwrap()
+                .to_string_unchecked(),
+            "CONNECT TO WI FI IN NEW YORK"
+        );
+        assert_eq!(
+            normalise("<speak>Use <w>Node.js</w>.</speak>")
+                .unwrap()
+                .to_string_unchecked(),
+            "USE NODEJS."
+        );
+    }
+
+    #[test]
+    fn ssml_dates() {
+        let date = |text: &str, format: Option<&str>| {
+            let format = format
+                .map(|x| format!(r#" format="{}""#, x))
+                .unwrap_or_default();
+            normalise(&format!(
+                r#"<speak><say-as interpret-as="date"{}>{}</say-as></speak>"#,
+                format, text
+            ))
+            .map(|x| x.to_string_unchecked())
+        };
+        let expected = "THE FIFTH OF JANUARY TWENTY TWENTY FOUR";
+        assert_eq!(date("2024-01-05", Some("ymd")).unwrap(), expected);
+        assert_eq!(date("05/01/2024", Some("dmy")).unwrap(), expected);
+        assert_eq!(date("01.05.2024", Some("mdy")).unwrap(), expected);
+        // Without a format it's day first unless the year is first
+        assert_eq!(date("5/1/24", None).unwrap(), expected);
+        assert_eq!(date("2024-01-05", None).unwrap(), expected);
+        assert_eq!(
+            date("12/25/99", Some("mdy")).unwrap(),
+            "THE TWENTY FIFTH OF DECEMBER NINETEEN NINETY NINE"
+        );
+        // Missing day
+        assert_eq!(
+            date("01/2024", Some("my")).unwrap(),
+            "JANUARY TWENTY TWENTY FOUR"
+        );
+        assert_eq!(date("2024-01", None).unwrap(), "JANUARY TWENTY TWENTY FOUR");
+        assert_eq!(date("Jan-1970", None).unwrap(), "JANUARY NINETEEN SEVENTY");
+        assert_eq!(date("5/1", Some("dm")).unwrap(), "THE FIFTH OF JANUARY");
+
+        assert!(date("2024-13-01", Some("ymd")).is_err());
+        assert!(date("2024-01-05", Some("dm")).is_err());
+    }
+
+    #[test]
+    fn ssml_telephone() {
+        let telephone = |number: &str, options: &NormaliserOptions| {
+            normalise_ssml_with_options(
+                &format!(
+                    r#"<speak><say-as interpret-as="telephone">{}</say-as></speak>"#,
+                    number
+                ),
+                options,
+            )
+            .unwrap()
+            .chunks
+        };
+        let pause = NormaliserChunk::Break(TELEPHONE_GROUP_PAUSE);
+        let text = |x: &str| NormaliserChunk::Text(x.to_string());
+
+        let options = NormaliserOptions::default();
+        assert_eq!(
+            telephone("0800 001 066", &options),
+            [
+                text("OH EIGHT DOUBLE OH"),
+                pause.clone(),
+                text("DOUBLE OH ONE"),
+                pause.clone(),
+                text("OH DOUBLE SIX"),
+            ]
+        );
+        assert_eq!(
+            telephone("0800001066", &options),
+            [text("OH EIGHT DOUBLE OH DOUBLE OH ONE OH DOUBLE SIX")]
+        );
+        assert_eq!(
+            telephone("+44 (20) 7946-0000", &options),
+            [
+                text("PLUS DOUBLE FOUR"),
+                pause.clone(),
+                text("TWO OH"),
+                pause.clone(),
+                text("SEVEN NINE FOUR SIX"),
+                pause.clone(),
+                text("DOUBLE OH DOUBLE OH"),
+            ]
+        );
+
+        let options = NormaliserOptions {
+            repeated_digits: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            telephone("0800 001 066", &options),
+            [
+                text("OH EIGHT OH OH"),
+                pause.clone(),
+                text("OH OH ONE"),
+                pause,
+                text("OH SIX SIX"),
+            ]
+        );
+    }
+
+    #[test]
+    fn ssml_years() {
+        let year = |text: &str, interpret_as: &str| {
+            normalise(&format!(
+                r#"<speak><say-as interpret-as="{}">{}</say-as></speak>"#,
+                interpret_as, text
+            ))
+            .unwrap()
+            .to_string_unchecked()
+        };
+        for interpret_as in ["year", "date"] {
+            assert_eq!(year("1905", interpret_as), "NINETEEN OH FIVE");
+            assert_eq!(year("1970", interpret_as), "NINETEEN SEVENTY");
+            assert_eq!(year("2000", interpret_as), "TWO THOUSAND");
+            assert_eq!(year("2005", interpret_as), "TWO THOUSAND AND FIVE");
+            assert_eq!(year("2019", interpret_as), "TWENTY NINETEEN");
+        }
+        assert_eq!(year("1900", "year"), "NINETEEN HUNDRED");
+        assert_eq!(year("1066", "year"), cardinal_words(1066).unwrap());
+        // Without the say-as it's just a number
+        assert_eq!(
+            normalise("<speak>1970</speak>")
+                .unwrap()
+                .to_string_unchecked()
+                .trim(),
+            cardinal_words(1970).unwrap()
+        );
+    }
+
+    #[test]
+    fn ssml_phrase_suppresses_pauses() {
+        let breaths = BreathPauses {
+            threshold: 12,
+            duration: Duration::from_millis(250),
+        };
+        let ssml = "<speak>Our motto, <phrase>eat, sleep, code, and repeat.</phrase> Then we rest, and, after a while, carry on</speak>";
+        let mut text = normalise(ssml).unwrap();
+        // Only the punctuation at the end of the phrase is kept
+        assert_eq!(
+            text.to_string_unchecked()
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" "),
+            "OUR MOTTO, EAT SLEEP CODE AND REPEAT. THEN WE REST, AND, AFTER A WHILE, CARRY ON"
+        );
+        text.insert_breaths(&breaths);
+        let chunks = text.chunks();
+        let start = chunks
+            .iter()
+            .position(|x| *x == NormaliserChunk::NoBreaks(true))
+            .unwrap();
+        let end = chunks
+            .iter()
+            .position(|x| *x == NormaliserChunk::NoBreaks(false))
+            .unwrap();
+        assert!(start < end);
+        assert!(!chunks[start..end].iter().any(|x| matches!(
+            x,
+            NormaliserChunk::Break(_) | NormaliserChunk::Punct(Punctuation::Comma)
+        )));
+        // Outside of the phrase pauses are still added
+        assert!(chunks[end..]
+            .iter()
+            .any(|x| matches!(x, NormaliserChunk::Break(_))));
+
+        // Nested phrases only mark the outermost one
+        let text =
+            normalise("<speak><phrase>a, <phrase>b, c</phrase>, d</phrase></speak>").unwrap();
+        assert_eq!(
+            text.to_string_unchecked()
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" "),
+            "A B C D"
+        );
+        assert_eq!(
+            text.chunks()
+                .iter()
+                .filter(|x| matches!(x, NormaliserChunk::NoBreaks(_)))
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn ssml_chunks_streamed() {
+        let ssml = r#"<speak>Hello world.<break time="1s"/><phrase>eat, sleep, code</phrase> and <w>repeat</w><break time="1s"/>Goodbye</speak>"#;
+        let options = NormaliserOptions::default();
+        let parsed = parse_ssml(ssml).unwrap();
+        let total = parsed.event_iter().count();
+
+        let streamed = SsmlChunks::new(parsed.event_iter(), &options)
+            .collect::<anyhow::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(streamed, normalise_ssml(ssml).unwrap().chunks());
+
+        // Chunks are yielded before the rest of the document is read
+        let consumed = std::cell::Cell::new(0);
+        let events = parsed
+            .event_iter()
+            .inspect(|_| consumed.set(consumed.get() + 1));
+        let mut chunks = SsmlChunks::new(events, &options);
+        let first = chunks.next().unwrap().unwrap();
+        assert_eq!(first, streamed[0]);
+        assert!(consumed.get() < total);
+
+        // Nothing in a phrase comes out until it's closed
+        let phrase = streamed
+            .iter()
+            .position(|x| *x == NormaliserChunk::NoBreaks(true))
+            .unwrap();
+        for chunk in &streamed[1..phrase] {
+            assert_eq!(chunks.next().unwrap().unwrap(), *chunk);
+        }
+        let before_phrase = consumed.get();
+        assert_eq!(
+            chunks.next().unwrap().unwrap(),
+            NormaliserChunk::NoBreaks(true)
+        );
+        assert!(consumed.get() > before_phrase + 1);
+        assert_eq!(chunks.count(), streamed.len() - phrase - 1);
+    }
+
+    #[test]
+    fn ssml_prosody_pitch() {
+        let ssml = r#"<speak>Say <prosody pitch="+20%">hello <prosody pitch="-2st">there</prosody></prosody> friend</speak>"#;
+        let text = normalise(ssml).unwrap();
+        assert_eq!(text.to_string_unchecked(), "SAY HELLO THERE FRIEND");
+        let pitches = text
+            .chunks()
+            .iter()
+            .filter_map(|x| match x {
+                NormaliserChunk::Pitch(cents) => Some(*cents),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        // +20% is ~3.16 semitones and the nested tag is relative to it
+        assert_eq!(pitches, [316, 116, 316, 0]);
+
+        let text = normalise(r#"<speak><prosody pitch="high">Hi</prosody></speak>"#).unwrap();
+        assert_eq!(
+            text.chunks(),
+            [
+                NormaliserChunk::Pitch(300),
+                NormaliserChunk::Text("HI".to_string()),
+                NormaliserChunk::Pitch(0)
+            ]
+        );
+        // Tags without a pitch we understand don't change anything
+        let text = normalise(r#"<speak><prosody pitch="200Hz">Hi</prosody></speak>"#).unwrap();
+        assert_eq!(text.chunks(), [NormaliserChunk::Text("HI".to_string())]);
+    }
+
+    #[test]
+    fn unsupported_character_listing() {
+        assert!(unsupported_characters("Hello, world! It's 3:30 (roughly) - £5 & $6?").is_empty());
+        assert!(unsupported_characters("Café naïve Straße").is_empty());
+        assert_eq!(
+            unsupported_characters("Email me @ \"home\" ~ @ © → ok 😀"),
+            ['@', '"', '~', '©', '→']
+        );
+        assert_eq!(unsupported_characters("a\u{301}"), ['\u{301}']);
+        assert!(unsupported_characters("50% of 2+2=4, 3/4 and 1980{year} km/h").is_empty());
+    }
+
+    #[test]
+    fn symbols_before_deunicode() {
+        assert_eq!(char_class('a'), CharClass::Ascii);
+        assert_eq!(char_class('é'), CharClass::Letter);
+        assert_eq!(char_class('$'), CharClass::Currency);
+        assert_eq!(char_class('£'), CharClass::Currency);
+        assert_eq!(char_class('×'), CharClass::Math);
+        assert_eq!(char_class('½'), CharClass::Fraction);
+        assert_eq!(char_class('❤'), CharClass::Emoji);
+        assert_eq!(char_class('😀'), CharClass::Emoji);
+        assert_eq!(char_class('©'), CharClass::Other);
+
+        // Letters are still transliterated
+        assert_eq!(
+            normalise_text("Café naïve Straße").to_string_unchecked(),
+            "CAFE NAIVE STRASSE"
+        );
+        assert_eq!(normalise_text("£5").to_string_unchecked(), "FIVE POUNDS");
+        assert_eq!(
+            normalise_text("3×4 ≠ 11").to_string_unchecked(),
+            "THREE TIMES FOUR NOT EQUAL TO ELEVEN"
+        );
+        assert_eq!(
+            normalise_text("I❤️rust").to_string_unchecked(),
+            "I HEART RUST"
+        );
+        assert_eq!(
+            normalise_text("a 🦄 in a café").to_string_unchecked(),
+            "A UNICORN IN A CAFE"
+        );
+    }
+
+    #[test]
+    fn unicode_fractions_and_ordinals() {
+        let norm = |x: &str| normalise_text(x).to_string_unchecked();
+        assert_eq!(norm("½"), "ONE HALF");
+        assert_eq!(norm("¾ of the way"), "THREE QUARTERS OF THE WAY");
+        assert_eq!(norm("add ⅛ cup"), "ADD ONE EIGHTH CUP");
+        assert_eq!(norm("1½ cups"), "ONE AND A HALF CUPS");
+        assert_eq!(norm("2 ¾ miles"), "TWO AND THREE QUARTERS MILES");
+        assert_eq!(norm("about 10⅔"), "ABOUT TEN AND TWO THIRDS");
+
+        assert_eq!(norm("1ˢᵗ"), "FIRST");
+        assert_eq!(norm("the 2ⁿᵈ and 3ʳᵈ"), "THE SECOND AND THIRD");
+        assert_eq!(norm("on the 24ᵗʰ"), "ON THE TWENTY FOURTH");
+        assert!(unsupported_characters("1½ and 2ⁿᵈ").is_empty());
+    }
+
+    #[test]
+    fn identifier_splitting() {
+        assert_eq!(
+            split_identifiers("get_pronunciations"),
+            "get pronunciations"
+        );
+        assert_eq!(split_identifiers("camelCase"), "camel Case");
+        assert_eq!(split_identifiers("XdTts"), "Xd Tts");
+        assert_eq!(split_identifiers("SCREAMING_SNAKE"), "SCREAMING SNAKE");
+        assert_eq!(split_identifiers("HTTPServer"), "HTTP Server");
+        assert_eq!(
+            split_identifiers("parseHTTPResponse"),
+            "parse HTTP Response"
+        );
+        assert_eq!(split_identifiers("iPhone and eBay"), "iPhone and eBay");
+        assert_eq!(split_identifiers("Hello world"), "Hello world");
+
+        let options = NormaliserOptions {
+            split_identifiers: true,
+            ..Default::default()
+        };
+        let norm = |x: &str| normalise_text_with_options(x, &options).to_string_unchecked();
+        assert_eq!(
+            norm("Call get_pronunciations on XdTts."),
+            "CALL GET PRONUNCIATIONS ON XD TTS."
+        );
+        assert_eq!(norm("camelCase"), "CAMEL CASE");
+        assert_eq!(
+            norm("MAX_DECODER_STEPS is 1000"),
+            "MAX DECODER STEPS IS ONE THOUSAND"
+        );
+        // Off by default
+        assert_eq!(
+            normalise_text("get_pronunciations camelCase").to_string_unchecked(),
+            "GETPRONUNCIATIONS CAMELCASE"
+        );
+    }
+
+    #[test]
+    fn financial_units() {
+        assert_eq!(
+            normalise_text("5‰ of the total").to_string_unchecked(),
+            "FIVE PER MILLE OF THE TOTAL"
+        );
+        assert_eq!(
+            normalise_text("Rates rose 25bps then 1 BPS").to_string_unchecked(),
+            "RATES ROSE TWENTY FIVE BASIS POINTS THEN ONE BASIS POINT"
+        );
+        assert_eq!(
+            normalise_text("a 50 bps cut").to_string_unchecked(),
+            "A FIFTY BASIS POINTS CUT"
+        );
+        // Only after a number
+        assert_eq!(normalise_text("bps").to_string_unchecked(), "BPS");
+
+        let options = NormaliserOptions {
+            units: UnitExpansion {
+                per_mille: false,
+                basis_points: false,
+                measurements: false,
+            },
+            ..Default::default()
+        };
+        assert_eq!(
+            normalise_text_with_options("5‰ or 25bps", &options).to_string_unchecked(),
+            "FIVE OR TWENTY FIVE BPS"
+        );
+        assert_eq!(
+            normalise_text_with_options("5kg or 5 kg", &options).to_string_unchecked(),
+            "FIVE KG OR FIVE KG"
+        );
+    }
+
+    #[test]
+    fn prose_dates() {
+        let norm = |x: &str| normalise_text(x).to_string_unchecked();
+        assert_eq!(norm("June 3rd"), "JUNE THIRD");
+        assert_eq!(norm("on Dec 25"), "ON DECEMBER TWENTY FIFTH");
+        assert_eq!(norm("the 3rd of June"), "THE THIRD OF JUNE");
+        assert_eq!(norm("21st of March"), "TWENTY FIRST OF MARCH");
+        assert_eq!(norm("3 June"), "THE THIRD OF JUNE");
+        assert_eq!(norm("3 June 2024"), "THE THIRD OF JUNE TWENTY TWENTY FOUR");
+        assert_eq!(
+            norm("On the 3rd June 2024."),
+            "ON THE THIRD OF JUNE TWENTY TWENTY FOUR."
+        );
+        assert_eq!(norm("Sept 1 1939"), "SEPTEMBER FIRST NINETEEN THIRTY NINE");
+
+        let expected = NormalisedText {
+            chunks: vec![
+                NormaliserChunk::Text("JUNE THIRD".to_string()),
+                NormaliserChunk::Punct(Punctuation::Comma),
+                NormaliserChunk::Text(" TWENTY TWENTY FOUR".to_string()),
+                NormaliserChunk::Punct(Punctuation::FullStop),
+            ],
+        };
+        assert_eq!(normalise_text("June 3rd, 2024."), expected);
+
+        // Not dates
+        assert_eq!(norm("May 40"), "MAY FORTY");
+        assert_eq!(norm("March on"), "MARCH ON");
+        assert_eq!(norm("the 23rd"), "THE TWENTY THIRD");
+        assert_eq!(norm("the 3 of us"), "THE THREE OF US");
+        assert_eq!(norm("Step 2 may fail"), "STEP TWO MAY FAIL");
+        assert_eq!(norm("I march 10 miles"), "I MARCH TEN MILES");
+        assert_eq!(norm("it may 3 times"), "IT MAY THREE TIMES");
+        assert_eq!(norm("10 mar"), "TEN MAR");
+        assert_eq!(norm("2 May"), "THE SECOND OF MAY");
+    }
+
+    #[test]
+    fn currency_normalisation() {
+        assert_eq!(normalise_text("£1").to_string_unchecked(), "ONE POUND");
+        assert_eq!(
+            normalise_text("$5.00").to_string_unchecked(),
+            "FIVE DOLLARS"
+        );
+        assert_eq!(normalise_text("$0.01").to_string_unchecked(), "ONE CENT");
+        assert_eq!(
+            normalise_text("It cost €2.5 today").to_string_unchecked(),
+            "IT COST TWO EUROS AND FIFTY CENTS TODAY"
+        );
+
+        let large = normalise_text("$1,234,567.89").to_string_unchecked();
+        assert!(large.starts_with("ONE MILLION TWO HUNDRED"));
+        assert!(large.contains("THIRTY FOUR THOUSAND"));
+        assert!(large.ends_with("SIXTY SEVEN DOLLARS AND EIGHTY NINE CENTS"));
+
+        let large = normalise_text("£2,000,000.00.").to_string_unchecked();
+        assert_eq!(large, "TWO MILLION POUNDS.");
+
+        // Each symbol with and without decimals
+        let norm = |x: &str| normalise_text(x).to_string_unchecked();
+        assert_eq!(norm("£5"), "FIVE POUNDS");
+        assert_eq!(norm("£5.20"), "FIVE POUNDS AND TWENTY PENCE");
+        assert_eq!(norm("$3"), "THREE DOLLARS");
+        assert_eq!(norm("$3.50"), "THREE DOLLARS AND FIFTY CENTS");
+        assert_eq!(norm("€10"), "TEN EUROS");
+        assert_eq!(norm("€10.01"), "TEN EUROS AND ONE CENT");
+        assert_eq!(
+            norm("In 1970 £1970"),
+            "IN ONE THOUSAND NINE HUNDRED AND SEVENTY ONE THOUSAND NINE HUNDRED AND SEVENTY POUNDS"
+        );
+        // Numbers mixed with letters still go through the number splitter
+        assert_eq!(norm("k8s"), "K EIGHT S");
+    }
+
+    #[test]
+    fn breath_insertion() {
+        let breaths = BreathPauses {
+            threshold: 12,
+            duration: Duration::from_millis(250),
+        };
+
+        let mut short = normalise_text("Hello, world. Goodbye, world.");
+        let expected = short.clone();
+        short.insert_breaths(&breaths);
+        assert_eq!(short, expected);
+
+        let mut long = normalise_text(
+            "We walked along the river, over the bridge, past the mill and into town",
+        );
+        long.insert_breaths(&breaths);
+
+        let breath = NormaliserChunk::Break(breaths.duration);
+        let expected = vec![
+            NormaliserChunk::Text("WE WALKED ALONG THE RIVER".to_string()),
+            NormaliserChunk::Punct(Punctuation::Comma),
+            breath.clone(),
+            NormaliserChunk::Text(" OVER THE BRIDGE".to_string()),
+            NormaliserChunk::Punct(Punctuation::Comma),
+            breath.clone(),
+            NormaliserChunk::Text(" PAST THE MILL ".to_string()),
+            breath,
+            NormaliserChunk::Text("AND INTO TOWN".to_string()),
+        ];
+        assert_eq!(long.chunks, expected);
+    }
+
+    #[test]
+    fn english_frontend() {
+        let text = "Is this my 1st talk? You tell me!";
+        assert_eq!(
+            EnglishFrontend::default().normalise(text).unwrap(),
+            normalise(text).unwrap()
+        );
+    }
+
+    #[test]
+    fn adversarial_inputs_dont_panic() {
+        let corpus = [
+            "",
+            " ",
+            "\0",
+            "\u{feff}",
+            "1.2.3",
+            "1..2",
+            ",,,1",
+            "1,,,",
+            ".5.",
+            "99999999999999999999999999999999999999999",
+            "-99999999999999999999999999999999999999999",
+            "$99999999999999999999999999",
+            "£.",
+            "$.",
+            "€,",
+            "1st2nd3rd",
+            "0th",
+            "1e400",
+            "NaN inf -inf",
+            "0x1F",
+            "12:30:45",
+            "١٢٣",
+            "𝟙𝟚𝟛",
+            "🎉🎉🎉 2🎉",
+            "e\u{301}\u{301}\u{301}",
+            "مرحبا 123",
+            "((((()))))",
+            "----",
+            "!?!?.,;:",
+            "'\"'\"'",
+            "&&& & &",
+            "<speak>",
+            "<speak></speak></speak>",
+            "<speak><say-as interpret-as=\"cardinal\">abc</say-as></speak>",
+            "<speak><say-as interpret-as=\"ordinal\">-1</say-as></speak>",
+            "<speak><say-as interpret-as=\"characters\"><p>hi</p></say-as></speak>",
+            "<speak><phoneme ph=\"\">x</phoneme></speak>",
+            "<speak><phoneme ph=\"ˈˈˈ\"><s>x</s></phoneme></speak>",
+        ];
+        for input in corpus {
+            let _ = normalise(input);
+        }
+        let long = "9".repeat(10_000);
+        let _ = normalise(&long);
+
+        // Then some random strings built from characters known to be troublesome. This uses a
+        // simple xorshift so the test is repeatable without pulling in a dependency.
+        let pool = "0123456789.,$£€¥:;-()[]!?'\"&% aZé£¹½\u{301}<>/=speak";
+        let pool = pool.chars().collect::<Vec<_>>();
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for _ in 0..2000 {
+            let len = (next() % 24) as usize;
+            let input = (0..len)
+                .map(|_| pool[(next() % pool.len() as u64) as usize])
+                .collect::<String>();
+            let _ = normalise(&input);
+        }
+    }
+
+    #[test]
+    fn unreadable_numbers() {
+        assert_eq!(read_digits("1.2.3"), "ONE TWO THREE");
+        assert_eq!(read_digits("a1b22"), "A ONE B TWO TWO");
+        assert_eq!(normalise_text("1..2").to_string_unchecked(), "ONE TWO");
     }
 
     #[test]
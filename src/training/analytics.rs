@@ -7,6 +7,7 @@ use crate::text_normaliser::*;
 use crate::CmuDictionary;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use std::io;
 use tracing::info;
 
 /// A diphone is a neighbouring pair of phones, and a phone is a distinct speech sound. A phone
@@ -47,6 +48,35 @@ pub struct Analytics {
     pub sentence_lengths: BTreeMap<usize, usize>,
 }
 
+impl Analytics {
+    /// Writes the report as a CSV so it can be sorted and filtered in a spreadsheet. Everything is
+    /// in one table with the columns `type`, `key` and `count`, the type is one of `diphone`,
+    /// `phoneme`, `oov` or `sentence_length`. Diphones are written as the two phones separated by
+    /// a space and for sentence lengths the key is the length.
+    pub fn write_csv(&self, writer: impl io::Write) -> anyhow::Result<()> {
+        let mut writer = csv::Writer::from_writer(writer);
+        writer.write_record(["type", "key", "count"])?;
+        for diphone in &self.diphones {
+            writer.write_record([
+                "diphone",
+                &diphone.phones.join(" "),
+                &diphone.count.to_string(),
+            ])?;
+        }
+        for (phoneme, count) in &self.phonemes {
+            writer.write_record(["phoneme", phoneme, &count.to_string()])?;
+        }
+        for (word, count) in &self.oov {
+            writer.write_record(["oov", word, &count.to_string()])?;
+        }
+        for (length, count) in &self.sentence_lengths {
+            writer.write_record(["sentence_length", &length.to_string(), &count.to_string()])?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
 /// Used to generate analytics, this is because some of the running state may not want to be
 /// serialized or may otherwise be unserialisable (taking json as a target format).
 #[derive(Debug, Default)]
@@ -104,7 +134,10 @@ impl AnalyticsGenerator {
                 NormaliserChunk::Text(t) => {
                     unreachable!("'{}' Should have been converted to pronunciation", t)
                 }
-                NormaliserChunk::Break(_duration) => {}
+                NormaliserChunk::Break(_)
+                | NormaliserChunk::Pitch(_)
+                | NormaliserChunk::Language(_)
+                | NormaliserChunk::NoBreaks(_) => {}
                 NormaliserChunk::Punct(p) => {
                     sentence_len += 1;
                     if p.is_sentence_end() {
@@ -153,3 +186,32 @@ impl AnalyticsGenerator {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_report() {
+        let report = Analytics {
+            diphones: vec![DiphoneStat {
+                phones: ["HH".to_string(), "AH0".to_string()],
+                count: 2,
+            }],
+            phonemes: BTreeMap::from([("HH".to_string(), 3), ("AH0".to_string(), 2)]),
+            oov: BTreeMap::from([("XYZZY, INC".to_string(), 1)]),
+            sentence_lengths: BTreeMap::from([(12, 4)]),
+        };
+        let mut output = vec![];
+        report.write_csv(&mut output).unwrap();
+
+        let expected = "type,key,count
+diphone,HH AH0,2
+phoneme,AH0,2
+phoneme,HH,3
+oov,\"XYZZY, INC\",1
+sentence_length,12,4
+";
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+    }
+}
@@ -0,0 +1,134 @@
+//! Looking at a spectrogram is one of the quickest ways to see what's gone wrong with a model. If
+//! the attention failed you'll see the same pattern repeating or the speech trailing off into
+//! noise, if the gate fired too early the spectrogram will be cut short. Normally you'd load the
+//! `.npy` file into python and plot it with matplotlib, this module lets you skip that step by
+//! writing a PNG directly. It's only available with the `png-export` feature.
+//!
+//! The image is laid out the same way as a typical spectrogram plot:
+//!
+//! * The x axis is time, each column is one frame. For tacotron2 a frame is a hop of 256 samples
+//!   or ~11.6ms of audio.
+//! * The y axis is the row of the array, for a mel spectrogram that's the mel channel with the
+//!   lowest frequency at the bottom of the image. Each row is drawn `row_height` pixels tall as
+//!   there's a lot more frames than mel channels.
+//!
+//! The colours use an approximation of the viridis colormap, the smallest value in the array is
+//! dark purple and the largest is yellow. Values are scaled between the min and max of the array
+//! so colours aren't comparable between images. This can also be used for attention alignments
+//! where the x axis is the decoder step and the y axis the input unit.
+use ndarray::Array2;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Control points for the colormap, these are evenly spaced samples from viridis.
+const VIRIDIS: [[f32; 3]; 5] = [
+    [68.0, 1.0, 84.0],
+    [59.0, 82.0, 139.0],
+    [33.0, 145.0, 140.0],
+    [94.0, 201.0, 98.0],
+    [253.0, 231.0, 37.0],
+];
+
+/// Row height used for mel spectrograms so 80 channels comes out as a readable 320 pixels tall.
+pub const MEL_ROW_HEIGHT: u32 = 4;
+
+/// Maps a value in [0, 1] to an RGB colour.
+fn colormap(x: f32) -> [u8; 3] {
+    let x = if x.is_finite() {
+        x.clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let scaled = x * (VIRIDIS.len() - 1) as f32;
+    let index = (scaled.floor() as usize).min(VIRIDIS.len() - 2);
+    let frac = scaled - index as f32;
+    let (low, high) = (VIRIDIS[index], VIRIDIS[index + 1]);
+    let mut res = [0; 3];
+    for i in 0..3 {
+        res[i] = (low[i] + (high[i] - low[i]) * frac).round() as u8;
+    }
+    res
+}
+
+/// Writes the array as a PNG heatmap to the given path. See the module docs for the layout.
+pub fn write_png(
+    path: impl AsRef<Path>,
+    data: &Array2<f32>,
+    row_height: u32,
+) -> anyhow::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    encode_png(data, row_height, &mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Encodes the array as a PNG heatmap into `output`. See the module docs for the layout.
+pub fn encode_png(data: &Array2<f32>, row_height: u32, output: impl Write) -> anyhow::Result<()> {
+    if data.is_empty() {
+        anyhow::bail!("Can't draw an empty array");
+    }
+    let row_height = row_height.max(1);
+    let (min, max) = data
+        .iter()
+        .filter(|x| x.is_finite())
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), x| {
+            (min.min(*x), max.max(*x))
+        });
+    let range = if max > min { max - min } else { 1.0 };
+
+    let width = data.ncols() as u32;
+    let height = data.nrows() as u32 * row_height;
+    let mut image = Vec::with_capacity((width * height * 3) as usize);
+    // Rows are drawn top to bottom so go through the array in reverse to put row 0 at the bottom
+    for row in (0..data.nrows()).rev() {
+        let pixels = data
+            .row(row)
+            .iter()
+            .flat_map(|x| colormap((x - min) / range))
+            .collect::<Vec<u8>>();
+        for _ in 0..row_height {
+            image.extend_from_slice(&pixels);
+        }
+    }
+
+    let mut encoder = png::Encoder::new(output, width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&image)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::arr2;
+
+    #[test]
+    fn colormap_ends() {
+        assert_eq!(colormap(0.0), [68, 1, 84]);
+        assert_eq!(colormap(1.0), [253, 231, 37]);
+        assert_eq!(colormap(f32::NAN), [68, 1, 84]);
+        assert_eq!(colormap(2.0), colormap(1.0));
+    }
+
+    #[test]
+    fn heatmap_dimensions() {
+        let data = arr2(&[[0.0, 1.0, 2.0], [3.0, 4.0, 5.0]]);
+        let mut output = vec![];
+        encode_png(&data, 4, &mut output).unwrap();
+
+        let decoder = png::Decoder::new(output.as_slice());
+        let mut reader = decoder.read_info().unwrap();
+        let mut image = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut image).unwrap();
+        assert_eq!(info.width, 3);
+        assert_eq!(info.height, 8);
+        // Largest value is in the top right as row 1 is drawn at the top
+        assert_eq!(&image[6..9], &[253, 231, 37]);
+        // Smallest value is in the bottom left
+        let bottom_row = (info.height as usize - 1) * info.line_size;
+        assert_eq!(&image[bottom_row..(bottom_row + 3)], &[68, 1, 84]);
+    }
+}
@@ -0,0 +1,197 @@
+//! Neural vocoders to use instead of Griffin-Lim. Griffin-Lim has to estimate the phase of the
+//! audio from the spectrogram which is where the robotic, slightly metallic sound comes from. A
+//! neural vocoder is trained to generate the waveform directly so it sounds much more natural, at
+//! the cost of another network to run.
+//!
+//! [HiFi-GAN](https://arxiv.org/abs/2010.05646) is a GAN where the generator upsamples the
+//! spectrogram with transposed convolutions until there's a sample for every point in the audio.
+//! Only the generator is needed for inference, and it's small and fast enough to run on the CPU.
+//! The generator has to be trained (or fine-tuned) on spectrograms with the same parameters as the
+//! acoustic model outputs, for tacotron2 that's 80 mels of natural log magnitudes at 22050Hz with a
+//! hop length of 256. The nvidia HiFi-GAN checkpoints for tacotron2 match this.
+use crate::tacotron2::{execution_providers, session_builder, ExecutionProvider, HOP_LENGTH};
+use crate::Vocoder;
+use anyhow::Context;
+use ndarray::prelude::*;
+use ort::{inputs, Session};
+use std::path::Path;
+
+/// How the spectrogram is scaled before it's given to the vocoder. Tacotron2 outputs the natural
+/// log of the mel magnitudes, if the vocoder was trained on a different scale the spectrogram has
+/// to be converted or the output will be noise.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum MelNormalisation {
+    /// Natural log magnitudes, the same as tacotron2 outputs. This is the default.
+    #[default]
+    NaturalLog,
+    /// Log base 10 magnitudes
+    Log10,
+    /// Natural log magnitudes normalised with the mean and standard deviation from training
+    Standardised { mean: f32, std: f32 },
+}
+
+impl MelNormalisation {
+    /// Converts a natural log spectrogram to this scale.
+    fn apply(self, mel: ArrayView2<f32>) -> Array2<f32> {
+        match self {
+            Self::NaturalLog => mel.to_owned(),
+            Self::Log10 => mel.mapv(|x| x / std::f32::consts::LN_10),
+            Self::Standardised { mean, std } => mel.mapv(|x| (x - mean) / std),
+        }
+    }
+}
+
+/// Handle to a HiFi-GAN generator ONNX graph. The graph should take a spectrogram of shape
+/// [1, mels, frames] and output the audio with a shape of [1, samples] or [1, 1, samples].
+pub struct HifiGan {
+    generator: Session,
+    normalisation: MelNormalisation,
+}
+
+impl HifiGan {
+    /// Load the generator from an ONNX file, or a folder containing a `generator.onnx`.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Self::load_with_providers(path, &[])
+    }
+
+    /// Load the generator to run on the first of the execution providers that's available, see
+    /// `Tacotron2::load_with_providers`.
+    pub fn load_with_providers(
+        path: impl AsRef<Path>,
+        providers: &[ExecutionProvider],
+    ) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let path = if path.is_dir() {
+            path.join("generator.onnx")
+        } else {
+            path.to_path_buf()
+        };
+        let generator = session_builder(&execution_providers(providers))?
+            .commit_from_file(&path)
+            .with_context(|| format!("converting '{}' to runnable model", path.display()))?;
+        Ok(Self {
+            generator,
+            normalisation: MelNormalisation::default(),
+        })
+    }
+
+    /// Sets how the spectrogram is scaled for the generator, see `MelNormalisation`.
+    pub fn with_mel_normalisation(mut self, normalisation: MelNormalisation) -> Self {
+        self.normalisation = normalisation;
+        self
+    }
+
+    /// Generates the audio for a spectrogram of shape [80, frames], returning `HOP_LENGTH` samples
+    /// for every frame.
+    pub fn infer(&self, mel: &Array2<f32>) -> anyhow::Result<Vec<f32>> {
+        run_generator(mel, self.normalisation, |input| {
+            let outputs = self.generator.run(inputs![input]?)?;
+            let audio = outputs[0].try_extract_tensor::<f32>()?;
+            Ok(audio.into_owned())
+        })
+    }
+}
+
+/// Runs the generator on the spectrogram, `generator` is given the input for the ONNX graph and
+/// returns its output. This is everything in `HifiGan::infer` apart from the session so it can be
+/// tested without a model.
+fn run_generator(
+    mel: &Array2<f32>,
+    normalisation: MelNormalisation,
+    generator: impl FnOnce(Array3<f32>) -> anyhow::Result<ArrayD<f32>>,
+) -> anyhow::Result<Vec<f32>> {
+    if mel.is_empty() {
+        return Ok(vec![]);
+    }
+    let audio = generator(generator_input(mel.view(), normalisation))?;
+    generator_samples(audio.view(), mel.ncols())
+}
+
+impl Vocoder for HifiGan {
+    fn infer(&self, mel: &Array2<f32>) -> anyhow::Result<Vec<f32>> {
+        HifiGan::infer(self, mel)
+    }
+}
+
+/// Scales the spectrogram and adds the batch dimension.
+fn generator_input(mel: ArrayView2<f32>, normalisation: MelNormalisation) -> Array3<f32> {
+    normalisation.apply(mel).insert_axis(Axis(0))
+}
+
+/// Flattens the generator output into samples, it can be [1, samples] or [1, 1, samples]. The
+/// transposed convolutions can leave a few extra samples at the end so the audio is trimmed to
+/// `HOP_LENGTH` samples per frame.
+fn generator_samples(audio: ArrayViewD<f32>, frames: usize) -> anyhow::Result<Vec<f32>> {
+    let shape = audio.shape();
+    anyhow::ensure!(
+        shape.len() > 1 && shape[..(shape.len() - 1)].iter().all(|x| *x == 1),
+        "Vocoder output has shape {:?}, expected [1, samples] or [1, 1, samples]",
+        shape
+    );
+    let expected = frames * HOP_LENGTH;
+    anyhow::ensure!(
+        audio.len() >= expected,
+        "Vocoder output {} samples for {} frames, expected at least {}",
+        audio.len(),
+        frames,
+        expected
+    );
+    Ok(audio.iter().copied().take(expected).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generator_shapes() {
+        let frames = 7;
+        let mel = Array2::from_shape_fn((80, frames), |(i, j)| {
+            ((i * 31 + j * 17) as f32).sin() - 5.0
+        });
+
+        // Stands in for the generator, upsamples each frame with a couple of samples left over
+        let stub = |input: Array3<f32>| {
+            assert_eq!(input.dim(), (1, 80, frames));
+            assert_eq!(input.index_axis(Axis(0), 0), mel);
+            let audio = Array3::from_shape_fn((1, 1, frames * HOP_LENGTH + 2), |(_, _, i)| {
+                input[[0, 0, (i / HOP_LENGTH).min(frames - 1)]].tanh()
+            });
+            Ok(audio.into_dyn())
+        };
+        let audio = run_generator(&mel, MelNormalisation::default(), stub).unwrap();
+        assert_eq!(audio.len(), frames * HOP_LENGTH);
+        assert_eq!(audio[HOP_LENGTH], mel[[0, 1]].tanh());
+
+        // Generators exported without the channel dimension output [1, samples]
+        let flat = |_| Ok(Array2::from_elem((1, frames * HOP_LENGTH), 0.5).into_dyn());
+        let audio = run_generator(&mel, MelNormalisation::default(), flat).unwrap();
+        assert_eq!(audio, vec![0.5; frames * HOP_LENGTH]);
+
+        let short = |_| Ok(Array2::<f32>::zeros((1, 10)).into_dyn());
+        assert!(run_generator(&mel, MelNormalisation::default(), short).is_err());
+        let batched = |_| Ok(Array2::<f32>::zeros((2, frames * HOP_LENGTH)).into_dyn());
+        assert!(run_generator(&mel, MelNormalisation::default(), batched).is_err());
+        let empty = Array2::zeros((80, 0));
+        let unused = |_| -> anyhow::Result<ArrayD<f32>> { panic!("Nothing to vocode") };
+        assert!(run_generator(&empty, MelNormalisation::default(), unused)
+            .unwrap()
+            .is_empty());
+
+        let log10 = generator_input(mel.view(), MelNormalisation::Log10);
+        assert!((log10[[0, 3, 2]] - mel[[3, 2]].exp().log10()).abs() < 1e-5);
+    }
+
+    /// The generator isn't in the repo, export the nvidia HiFi-GAN checkpoint for tacotron2 (from
+    /// their DeepLearningExamples repo) to ONNX and put it in `models/hifigan/generator.onnx` to
+    /// run this with `cargo test -- --ignored`.
+    #[test]
+    #[ignore = "needs a HiFi-GAN generator in models/hifigan"]
+    fn hifigan_sanity() {
+        let vocoder = HifiGan::load("./models/hifigan").unwrap();
+        let mel = Array2::from_elem((80, 20), -5.0);
+        let audio = vocoder.infer(&mel).unwrap();
+        assert_eq!(audio.len(), 20 * HOP_LENGTH);
+        assert!(audio.iter().all(|x| x.is_finite()));
+    }
+}